@@ -101,6 +101,14 @@ pub struct R1csOpt {
     )]
     /// linear combination constraints up to this size will be eliminated
     pub lc_elim_thresh: usize,
+
+    #[arg(
+        long = "r1cs-lookup-width",
+        env = "R1CS_LOOKUP_WIDTH",
+        default_value = "0"
+    )]
+    /// Lower range checks and bitwise ops to lookups into tables of this many (even) bits, instead of bit-splits. 0 disables lookups
+    pub lookup_width: usize,
 }
 
 impl Default for R1csOpt {
@@ -110,6 +118,7 @@ impl Default for R1csOpt {
             profile: false,
             div_by_zero: FieldDivByZero::Incomplete,
             lc_elim_thresh: 50,
+            lookup_width: 0,
         }
     }
 }
@@ -359,6 +368,37 @@ pub struct ZsharpOpt {
     /// [ZoKrates](https://zokrates.github.io/language/control_flow.html).
     #[arg(long = "zsharp-isolate-asserts", env = "ZSHARP_ISOLATE_ASSERTS", action = ArgAction::Set, default_value = "true")]
     pub isolate_asserts: bool,
+    /// In Z#, assert that every index of a multi-dimensional array declared through `array_decl`
+    /// is smaller than the length of its dimension.
+    #[arg(long = "zsharp-array-bound-check", env = "ZSHARP_ARRAY_BOUND_CHECK", action = ArgAction::Set, default_value = "false")]
+    pub array_bound_check: bool,
+    /// In Z#, export the block control-flow graph after every optimization stage
+    #[arg(
+        long = "zsharp-cfg-export",
+        env = "ZSHARP_CFG_EXPORT",
+        value_enum,
+        default_value = "none"
+    )]
+    pub cfg_export: CfgExportFormat,
+    /// In Z#, the directory to export the control-flow graphs to (default: current directory)
+    #[arg(
+        long = "zsharp-cfg-export-dir",
+        env = "ZSHARP_CFG_EXPORT_DIR",
+        default_value = ""
+    )]
+    pub cfg_export_dir: String,
+}
+
+#[derive(ValueEnum, Debug, Default, PartialEq, Eq, Clone, Copy)]
+/// Format of an exported control-flow graph
+pub enum CfgExportFormat {
+    /// Do not export
+    #[default]
+    None,
+    /// Graphviz DOT
+    Dot,
+    /// JSON
+    Json,
 }
 
 /// Options for the datalog frontend
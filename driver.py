@@ -128,6 +128,7 @@ def test(features, extra_args):
         if "lp" in features:
             log_run_check(["./scripts/test_zok_to_ilp.zsh"])
         if "r1cs" in features:
+            log_run_check(["./scripts/zx_diff_tests/run_tests.sh"])
             if "spartan" in features:  # spartan field
                 log_run_check(["./scripts/spartan_zok_test.zsh"])
             else:  # bellman field
@@ -1,34 +1,27 @@
 // TODO: Might want to simplify Liveness Analysis & PMR now that scope changes are handled in optimization
 
-/*
-use bellman::gadgets::test::TestConstraintSystem;
-use bellman::groth16::{
-    create_random_proof, generate_parameters, generate_random_parameters, prepare_verifying_key,
-    verify_proof, Parameters, Proof, VerifyingKey,
-};
-use bellman::Circuit;
-use bls12_381::{Bls12, Scalar};
-*/
-const PRINT_PROOF: bool = false;
-const TOTAL_NUM_VARS_BOUND: usize = 10000000000;
-
 use core::cmp::min;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::hash::BuildHasherDefault;
 use rug::Integer;
-use circ::front::zsharp::{self, ZSharpFE};
+use circ::front::zsharp::{self, zx, ZSharpFE, MemTraceEntry, MemTraceAlloc, BlockExecution, BlockError, CostProfile};
 use circ::front::{FrontEnd, Mode};
-use circ::ir::opt::{opt, Opt};
+use circ::ir::opt::opt_with_times;
+use circ::ir::term::Value;
 /*
 use circ::target::r1cs::bellman::parse_instance;
 */
 use circ::target::r1cs::{R1cs, VarType, Lc};
-use circ::target::r1cs::opt::reduce_linearities;
+use circ::target::r1cs::opt::reduce_linearities_keeping;
 use circ::target::r1cs::trans::to_r1cs;
-use circ::target::r1cs::wit_comp::StagedWitCompEvaluator;
+use circ::target::r1cs::wit_comp::{StagedWitComp, StagedWitCompEvaluator};
 use circ::target::r1cs::ProverData;
+use circ::target::r1cs::blocks::{knowledge::*, proof::*, spartan::BlockSpartan, VARS_PER_ST_ACCESS, VARS_PER_VM_ACCESS};
+#[cfg(feature = "bellman")]
+use circ::target::r1cs::blocks::{folding::BlockFolding, groth16::BlockGroth16};
 
-use std::fs::File;
-use std::io::{BufReader, BufRead, Write};
+use std::fs::{File, create_dir_all};
+use std::io::{BufReader, BufWriter, Write};
 
 use circ::cfg::{
     cfg,
@@ -36,12 +29,13 @@ use circ::cfg::{
     CircOpt,
 };
 use std::path::PathBuf;
-use core::cmp::Ordering;
 
 use std::time::*;
-use serde::{Serialize, Deserialize};
-use libspartan::{instance::Instance, SNARKGens, Assignment, VarsAssignment, SNARK, InputsAssignment, MemsAssignment};
-use merlin::Transcript;
+use serde::{Serialize, de::DeserializeOwned};
+use rayon::prelude::*;
+use std::collections::BinaryHeap;
+use std::cmp::Reverse;
+use libspartan::Assignment;
 
 // How many reserved variables (EXCLUDING V) are in front of the actual input / output?
 // %BN, %RET, %TS, %AS, %SP, %BP
@@ -51,9 +45,6 @@ const OUTPUT_OFFSET: usize = 2;
 // What is the maximum width (# of bits) of %TS?
 const MAX_TS_WIDTH: usize = 20;
 
-const VARS_PER_ST_ACCESS: usize = 2;
-const VARS_PER_VM_ACCESS: usize = 4;
-
 #[derive(Debug, Parser)]
 #[command(name = "zxc", about = "CirC: the circuit compiler")]
 struct Options {
@@ -61,19 +52,22 @@ struct Options {
     #[arg(name = "PATH")]
     path: PathBuf,
 
-    /*
-    #[arg(long, default_value = "P", parse(from_os_str))]
+    #[arg(long, default_value = "P")]
+    /// proving key, written by --action setup and read by --action prove
     prover_key: PathBuf,
 
-    #[arg(long, default_value = "V", parse(from_os_str))]
+    #[arg(long, default_value = "V")]
+    /// verifying key, written by --action setup and read by --action verify
     verifier_key: PathBuf,
 
-    #[arg(long, default_value = "pi", parse(from_os_str))]
+    #[arg(long, default_value = "pi")]
+    /// proof, written by --action prove and read by --action verify
     proof: PathBuf,
 
-    #[arg(long, default_value = "x", parse(from_os_str))]
+    #[arg(long, default_value = "x")]
+    /// instance with the public inputs and the output, written by --action prove and read by --action verify
     instance: PathBuf,
-    */
+
     #[arg(short = 'L')]
     /// skip linearity reduction entirely
     skip_linred: bool,
@@ -98,16 +92,103 @@ struct Options {
     verbose_opt: bool,
 
     #[arg(long = "inline_spartan")]
-    /// automatically execute Spartan
+    /// automatically setup, prove and verify with the block backend
     inline_spartan: bool,
+
+    #[arg(long, default_value = "spartan")]
+    /// block backend, the RTK is written for it, and setup, prove and verify use it
+    backend: BlockBackend,
+
+    #[arg(long = "proof_mode", default_value = "non-zk")]
+    /// whether block proofs hide the witnesses and the control flow, see --zk_exec_bound;
+    /// zk is only supported by the groth16 backend, as the Spartan commitments are not hiding
+    proof_mode: ProofMode,
+
+    #[arg(long = "zk_exec_bound")]
+    /// number of executions every block is padded to in zk mode, revealed instead of the actual numbers
+    zk_exec_bound: Option<usize>,
+
+    #[arg(long = "parallel_witness")]
+    /// evaluate the witnesses of block executions on a thread pool (ignored by --stream_witness)
+    parallel_witness: bool,
+
+    #[arg(long = "stream_witness")]
+    /// stream block executions and memory accesses to disk while generating the witnesses
+    stream_witness: bool,
+
+    #[arg(long = "stream_chunk_size", default_value_t = 1 << 20)]
+    /// number of memory accesses sorted in memory at a time when streaming witnesses
+    stream_chunk_size: usize,
+
+    #[arg(long = "trace_format", default_value = "csv")]
+    /// output format of --action mem-trace
+    trace_format: TraceFormat,
+
+    #[arg(long = "stats_json")]
+    /// write compile statistics and per-pass timings as JSON to this file
+    stats_json: Option<PathBuf>,
+
+    #[arg(long = "check_witness")]
+    /// check the block witnesses against the constraints and the memory lists without Spartan (ignored by --stream_witness)
+    check_witness: bool,
+
+    #[arg(long = "write_knowledge")]
+    /// also write CTK and RTK in the versioned format, next to the files read by Spartan
+    write_knowledge: Option<KnowledgeEncoding>,
+
+    #[arg(long = "ctk")]
+    /// CTK loaded by --action inspect, export, setup and prove, defaults to the file read by Spartan
+    ctk: Option<PathBuf>,
+
+    #[arg(long = "rtk")]
+    /// RTK loaded by --action inspect, export, setup and prove, defaults to the file read by Spartan
+    rtk: Option<PathBuf>,
+
+    #[arg(long = "shape")]
+    /// execution shape (CIRC_SHAPE file) that --action setup generates the keys for, instead of the shape of the RTK;
+    /// --action prove pads RTKs with fewer executions of a block to it
+    shape: Option<PathBuf>,
+
+    #[arg(long = "export_dir")]
+    /// directory written by --action export, defaults to ../zok_tests/r1cs/<benchmark>
+    export_dir: Option<PathBuf>,
 }
 
 #[derive(PartialEq, Eq, Debug, Clone, ValueEnum)]
 enum ProofAction {
+    /// compile the program, generate its witnesses and write the CTK and RTK
     Count,
+    /// only generate the keys from a CTK and the execution shape given by --shape, or else by the RTK
     Setup,
+    /// only prove an RTK with the proving key, padded to the execution shape of the key
     Prove,
+    /// only verify a proof with the verifying key and the public inputs and output
     Verify,
+    /// only interpret the program and dump its memory trace
+    MemTrace,
+    /// only interpret the program and report the constraint cost of every source line and function
+    Profile,
+    /// only load the CTK and RTK of the program, and check the block witnesses against the constraints
+    Inspect,
+    /// only load the CTK and RTK of the program, and export the block and glue instances with their witnesses in the circom R1CS format
+    Export,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, ValueEnum)]
+enum BlockBackend {
+    /// spartan_parallel, with one instance per block
+    Spartan,
+    /// Groth16 over BLS12-381, with the whole execution in one circuit (requires the bellman feature and --field-builtin bls12381)
+    Groth16,
+    /// non-succinct folding demo: folds block executions into a relaxed R1CS instance over BLS12-381, and the verifier
+    /// refolds every step, so proofs and verification grow with the execution; not zero-knowledge (requires the bellman feature and --field-builtin bls12381)
+    Folding,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, ValueEnum)]
+enum TraceFormat {
+    Csv,
+    Json,
 }
 
 #[derive(PartialEq, Debug, Clone, ValueEnum)]
@@ -174,38 +255,6 @@ fn get_sparse_cons_with_v_check(
     return Some(SparseMatEntry { args_a, args_b, args_c });
 }
 
-// Convert an integer into a little-endian byte array
-fn integer_to_bytes(mut raw: Integer) -> [u8; 32] {
-    let mut res = [0; 32];
-    let width = Integer::from(256);
-    let field = Integer::from_str_radix("7237005577332262213973186563042994240857116359379907606001950938285454250989", 10).unwrap();
-    // Cast negative number to the other side of the field
-    if raw < 0 {
-        raw += field;
-    }
-    let mut i = 0;
-    while raw != 0 {
-        if i >= 32 {
-            panic!("Failed to convert integer to byte array: integer is too large! Remainder is: {:?}", raw)
-        }
-        res[i] = (raw.clone() % width.clone()).to_u8().unwrap();
-        raw /= width.clone();
-        i += 1;
-    }
-    res
-}
-
-// Convert a little-endian byte array to integer
-fn bytes_to_integer(bytes: &[u8; 32]) -> Integer {
-    let mut i = Integer::from(0);
-    let mut factor = Integer::from(1);
-    for b in bytes {
-        i += Integer::from(*b as usize) * factor.clone();
-        factor *= 256;
-    }
-    i
-}
-
 fn write_bytes(mut f: &File, bytes: &[u8; 32]) -> std::io::Result<()> {
     // Disregard the trailing zeros
     let mut size = 32;
@@ -221,308 +270,658 @@ fn write_bytes(mut f: &File, bytes: &[u8; 32]) -> std::io::Result<()> {
 }
 
 // --
-// Structures to match Spartan
+// CTK / RTK files of a benchmark
 // --
-#[derive(Serialize, Deserialize)]
-struct CompileTimeKnowledge {
-    block_num_instances: usize,
-    num_vars: usize,
-    num_inputs_unpadded: usize,
-    num_vars_per_block: Vec<usize>,
-    block_num_phy_ops: Vec<usize>,
-    block_num_vir_ops: Vec<usize>,
-    max_ts_width: usize,
-  
-    args: Vec<Vec<(Vec<(usize, [u8; 32])>, Vec<(usize, [u8; 32])>, Vec<(usize, [u8; 32])>)>>,
-  
-    input_liveness: Vec<bool>,
-    func_input_width: usize,
-    input_offset: usize,
-    input_block_num: usize,
-    output_offset: usize,
-    output_block_num: usize
-}
-
-impl CompileTimeKnowledge {
+// The headerless files read by Spartan and the versioned files are written under ../zok_tests
+trait KnowledgeFiles: Knowledge {
+    // Directory under ../zok_tests
+    const DIR: &'static str;
+    // Extension of the files, see KnowledgeEncoding::extension
+    const EXT: &'static str;
+
+    fn file_name(benchmark_name: &str, suffix: &str) -> PathBuf {
+        PathBuf::from(format!("../zok_tests/{}/{}{}", Self::DIR, benchmark_name, suffix))
+    }
+
     fn serialize_to_file(&self, benchmark_name: String) -> std::io::Result<()> {
-        let file_name = format!("../zok_tests/constraints/{}_bin.ctk", benchmark_name);
+        let file_name = Self::file_name(&benchmark_name, &format!("_bin.{}", Self::EXT));
         let mut f = File::create(file_name)?;
         let content = bincode::serialize(&self).unwrap();
         f.write(&content)?;
         Ok(())
     }
 
-    fn write_to_file(&self, benchmark_name: String) -> std::io::Result<()> {
-        let file_name = format!("../zok_tests/constraints/{}.ctk", benchmark_name);
-        let mut f = File::create(file_name)?;
-        writeln!(&mut f, "Num Blocks: {}", self.block_num_instances)?;
-        writeln!(&mut f, "Max Num Vars: {}", self.num_vars)?;
-        writeln!(&mut f, "Num Inputs: {}", self.num_inputs_unpadded)?;
-        write!(&mut f, "{:>11}: ", "Block")?;
-        for i in 0..self.block_num_instances {
-            write!(&mut f, "{:>6}", i)?;
-        }
-        writeln!(&mut f, "")?;
-        write!(&mut f, "{:>11}: ", "Num Vars")?;
-        for i in &self.num_vars_per_block {
-            write!(&mut f, "{:>6}", i)?;
-        }
-        writeln!(&mut f, "")?;
-        write!(&mut f, "{:>11}: ", "Num Phy Ops")?;
-        for i in &self.block_num_phy_ops {
-            write!(&mut f, "{:>6}", i)?;
-        }
-        writeln!(&mut f, "")?;
-        write!(&mut f, "{:>11}: ", "Num Vir Ops")?;
-        for i in &self.block_num_vir_ops {
-            write!(&mut f, "{:>6}", i)?;
-        }
-        writeln!(&mut f, "")?;
-        writeln!(&mut f, "Max TS Width: {}", self.max_ts_width)?;
-
-        // Instances
-        let mut counter = 0;
-        for inst in &self.args {
-            writeln!(&mut f, "--\nINST {}, {} x {}", counter, inst.len(), self.num_vars_per_block[counter])?;
-            for cons in inst {
-                write!(&mut f, "  A ")?;
-                let mut pad = false;
-                for (var, val) in &cons.0 {
-                    if !pad {
-                        write!(&mut f, "{} ", var)?;
-                    } else {
-                        write!(&mut f, "    {} ", var)?;
-                    }
-                    writeln!(&mut f, "{}", bytes_to_integer(&val))?;
-                    pad = true;
-                }
-                if !pad { writeln!(&mut f, "")?; }
-                write!(&mut f, "  B ")?;
-                let mut pad = false;
-                for (var, val) in &cons.1 {
-                    if !pad {
-                        write!(&mut f, "{} ", var)?;
-                    } else {
-                        write!(&mut f, "    {} ", var)?;
-                    }
-                    writeln!(&mut f, "{}", bytes_to_integer(&val))?;
-                    pad = true;
-                }
-                if !pad { writeln!(&mut f, "")?; }
-                write!(&mut f, "  C ")?;
-                let mut pad = false;
-                for (var, val) in &cons.2 {
-                    if !pad {
-                        write!(&mut f, "{} ", var)?;
-                    } else {
-                        write!(&mut f, "    {} ", var)?;
-                    }
-                    writeln!(&mut f, "{}", bytes_to_integer(&val))?;
-                    pad = true;
-                }
-                if !pad { writeln!(&mut f, "")?; }
-                writeln!(&mut f, "")?;
-            }
-            counter += 1;
-        }
-        writeln!(&mut f, "INST_END")?;
-        writeln!(&mut f, "")?;
+    fn write_to_file(&self, benchmark_name: &str, encoding: &KnowledgeEncoding) -> std::io::Result<()> {
+        save_knowledge(self, &Self::file_name(benchmark_name, &format!(".{}", encoding.extension(Self::EXT))), encoding)
+    }
+}
+
+impl KnowledgeFiles for CompileTimeKnowledge {
+    const DIR: &'static str = "constraints";
+    const EXT: &'static str = "ctk";
+}
+
+impl<A: RtkAssignment> KnowledgeFiles for RunTimeKnowledge<A> {
+    const DIR: &'static str = "inputs";
+    const EXT: &'static str = "rtk";
+}
+
+// --
+// Check block witnesses without Spartan
+// --
+// Only the first MAX_REPORTED_FAILURES failures are described
+const MAX_REPORTED_FAILURES: usize = 20;
+
+struct WitnessCheck {
+    num_cons_checked: usize,
+    num_consis_checked: usize,
+    num_mem_checked: usize,
+    num_failures: usize,
+    failures: Vec<String>,
+}
 
-        write!(&mut f, "Input Liveness: ")?;
-        for b in &self.input_liveness {
-            write!(&mut f, "{} ", if *b { 1 } else { 0 })?;
+impl WitnessCheck {
+    fn fail(&mut self, msg: impl FnOnce() -> String) {
+        if self.failures.len() < MAX_REPORTED_FAILURES {
+            self.failures.push(msg());
         }
-        writeln!(&mut f, "")?;
-        writeln!(&mut f, "Prog Input Width: {}", self.func_input_width)?;
-        writeln!(&mut f, "Input Offset: {}", self.input_offset)?;
-        writeln!(&mut f, "Input Block Num: {}", self.input_block_num)?;
-        writeln!(&mut f, "Output Offset: {}", self.output_offset)?;
-        writeln!(&mut f, "Output Block Num: {}", self.output_block_num)?;
-        Ok(())
+        self.num_failures += 1;
     }
 }
 
-#[derive(Serialize, Deserialize)]
-struct RunTimeKnowledge {
-    block_max_num_proofs: usize,
-    block_num_proofs: Vec<usize>,
-    consis_num_proofs: usize,
-    total_num_init_phy_mem_accesses: usize,
-    total_num_init_vir_mem_accesses: usize,
-    total_num_phy_mem_accesses: usize,
-    total_num_vir_mem_accesses: usize,
-  
-    block_vars_matrix: Vec<Vec<VarsAssignment>>,
-    exec_inputs: Vec<InputsAssignment>,
-    // Initial memory state, in (addr, val, ls = STORE, ts = 0) pair, sorted by appearance in program input (the same as address order)
-    init_phy_mems_list: Vec<MemsAssignment>,
-    init_vir_mems_list: Vec<MemsAssignment>,
-    addr_phy_mems_list: Vec<MemsAssignment>,
-    addr_vir_mems_list: Vec<MemsAssignment>,
-    addr_ts_bits_list: Vec<MemsAssignment>,
-  
-    input: Vec<[u8; 32]>,
-    input_stack: Vec<[u8; 32]>,
-    input_mem: Vec<[u8; 32]>,
-    output: [u8; 32],
-    output_exec_num: usize
-}
-
-impl RunTimeKnowledge {
-    fn serialize_to_file(&self, benchmark_name: String) -> std::io::Result<()> {
-        let file_name = format!("../zok_tests/inputs/{}_bin.rtk", benchmark_name);
-        let mut f = File::create(file_name)?;
-        let content = bincode::serialize(&self).unwrap();
-        f.write(&content)?;
-        Ok(())
+// Registers 0 - 6 are reserved for _, %BN, %RET, %TS, %AS, %SP, and %BP, see new_io_map
+const RESERVED_REGS: [&str; 7] = ["_", "%BN", "%RET", "%TS", "%AS", "%SP", "%BP"];
+
+// Name register j of the inputs (prefix i) or the outputs (prefix o) of a block
+fn describe_reg(prefix: &str, j: usize) -> String {
+    match RESERVED_REGS.get(j) {
+        Some(r) => format!("%{}{:06} = {}", prefix, j, r),
+        None => format!("%{}{:06}", prefix, j),
     }
+}
 
-    fn write_to_file(&self, benchmark_name: String) -> std::io::Result<()> {
-        let file_name = format!("../zok_tests/inputs/{}.rtk", benchmark_name);
-        let mut f = File::create(file_name)?;
-        writeln!(&mut f, "Block Max Num Proofs: {}", self.block_max_num_proofs)?;
-        write!(&mut f, "{:>11}: ", "Block")?;
-        for i in 0..self.block_num_proofs.len() {
-            write!(&mut f, "{:>6}", i)?;
-        }
-        writeln!(&mut f, "")?;
-        writeln!(&mut f, "{:>11}: ", "Num Proofs")?;
-        for i in &self.block_num_proofs {
-            write!(&mut f, "{:>6}", i)?;
-        }
-        writeln!(&mut f, "")?;
-        writeln!(&mut f, "Total Num Proofs: {}", self.consis_num_proofs)?;
-        writeln!(&mut f, "Total Num Init Phy Mem Acc: {}", self.total_num_init_phy_mem_accesses)?;
-        writeln!(&mut f, "Total Num Init Vir Mem Acc: {}", self.total_num_init_vir_mem_accesses)?;
-        writeln!(&mut f, "Total Num Phy Mem Acc: {}", self.total_num_phy_mem_accesses)?;
-        writeln!(&mut f, "Total Num Vir Mem Acc: {}", self.total_num_vir_mem_accesses)?;
-
-        writeln!(&mut f, "BLOCK_VARS")?;
-        let mut block_counter = 0;
-        for block in &self.block_vars_matrix {
-            writeln!(&mut f, "BLOCK {}", block_counter)?;
-            let mut exec_counter = 0;
-            for exec in block {
-                writeln!(&mut f, "EXEC {}", exec_counter)?;
-                for assg in &exec.assignment {
-                    write!(&mut f, "{} ", bytes_to_integer(&assg.to_bytes()))?;
+// Name a variable of an execution of block b: valid bit, inputs, outputs, physical accesses and lookups,
+// virtual accesses, then witnesses
+// Input reg j is at z[j + 1], output reg j is at z[n + j], so z[1] is the unused input _ and %BN is in z[2]
+fn describe_var(var: usize, b: usize, ctk: &CompileTimeKnowledge) -> String {
+    let n = ctk.num_inputs_unpadded;
+    let phy_base = 2 * n;
+    let vir_base = phy_base + VARS_PER_ST_ACCESS * ctk.block_num_phy_ops[b];
+    let wit_base = vir_base + VARS_PER_VM_ACCESS * ctk.block_num_vir_ops[b];
+    if var == 0 {
+        "z[0] (valid)".to_string()
+    } else if var < n {
+        format!("z[{}] (input {})", var, describe_reg("i", var - 1))
+    } else if var < phy_base {
+        format!("z[{}] (output {})", var, describe_reg("o", var - n))
+    } else if var < vir_base {
+        let field = ["addr", "data"][(var - phy_base) % VARS_PER_ST_ACCESS];
+        format!("z[{}] ({} of physical access {})", var, field, (var - phy_base) / VARS_PER_ST_ACCESS)
+    } else if var < wit_base {
+        let field = ["addr", "data", "ls", "ts"][(var - vir_base) % VARS_PER_VM_ACCESS];
+        format!("z[{}] ({} of virtual access {})", var, field, (var - vir_base) / VARS_PER_VM_ACCESS)
+    } else {
+        format!("z[{}] (witness {})", var, var - wit_base)
+    }
+}
+
+// Evaluates A * z o B * z = C * z for every execution of every block, checks that the inputs of every execution
+// are the outputs of the previous one, checks the sorted physical and virtual memory accesses, and checks that
+// they are the memory accesses of the executions
+// The CTK and the RTK should have passed inspect_knowledge
+fn check_block_witnesses<A: RtkAssignment>(ctk: &CompileTimeKnowledge, rtk: &RunTimeKnowledge<A>) -> WitnessCheck {
+    let field = cfg().field().modulus().clone();
+    // Witnesses are computed over the integers, and cast into the field when converted to bytes
+    let modp = |i: Integer| -> Integer { Integer::from(i.rem_euc_ref(&field)) };
+    let to_integers = |a: &A| -> Vec<Integer> { a.to_bytes().iter().map(bytes_to_integer).collect() };
+    let n = ctk.num_inputs_unpadded;
+    let mut check = WitnessCheck { num_cons_checked: 0, num_consis_checked: 0, num_mem_checked: 0, num_failures: 0, failures: Vec::new() };
+
+    // Constraints
+    let eval_row = |row: &Vec<(usize, [u8; 32])>, vars: &Vec<Integer>| -> Integer {
+        let mut res = Integer::from(0);
+        for (var, coeff) in row {
+            res += bytes_to_integer(coeff) * vars[*var].clone();
+        }
+        modp(res)
+    };
+    // exec_list[b][k] is the vars of the k-th execution of block b
+    let mut exec_list: Vec<Vec<Vec<Integer>>> = vec![Vec::new(); ctk.block_num_instances];
+    for (slot, b) in live_blocks_by_slot(&rtk.block_num_proofs).into_iter().enumerate() {
+        exec_list[b] = rtk.block_vars_matrix[slot].iter().map(to_integers).collect();
+        for (e, vars) in exec_list[b].iter().enumerate() {
+            for (i, (a, b_, c)) in ctk.args[b].iter().enumerate() {
+                check.num_cons_checked += 1;
+                let (az, bz, cz) = (eval_row(a, vars), eval_row(b_, vars), eval_row(c, vars));
+                if modp(az.clone() * bz.clone()) != cz {
+                    check.fail(|| {
+                        let mut row_vars: Vec<usize> = a.iter().chain(b_).chain(c).map(|(var, _)| *var).collect();
+                        row_vars.sort();
+                        row_vars.dedup();
+                        let vals: Vec<String> = row_vars.iter().map(|var| format!("{} = {}", describe_var(*var, b, ctk), vars[*var])).collect();
+                        format!("execution {} of block {} does not satisfy constraint {}: A * z = {}, B * z = {}, C * z = {}\n    {}", e, b, i, az, bz, cz, vals.join("\n    "))
+                    });
                 }
-                writeln!(&mut f, "")?;
-                exec_counter += 1;
             }
-            block_counter += 1;
-        }
-        writeln!(&mut f, "EXEC_INPUTS")?;
-        let mut exec_counter = 0;
-        for exec in &self.exec_inputs {
-            writeln!(&mut f, "EXEC {}", exec_counter)?;
-            for assg in &exec.assignment {
-                write!(&mut f, "{} ", bytes_to_integer(&assg.to_bytes()))?;
+        }
+    }
+
+    // Consistency between executions: exec_inputs are in execution order, with input %BN in z[2]
+    // Invalid executions, padded by pad_run_time_knowledge, have no predecessor or successor
+    let mut next_exec = vec![0; ctk.block_num_instances];
+    let mut last_outputs: Option<Vec<Integer>> = None;
+    for (i, exec) in rtk.exec_inputs.iter().enumerate() {
+        let io = to_integers(exec);
+        if io[0] == 0 {
+            last_outputs = None;
+            continue;
+        }
+        let b = match io[2].to_usize() {
+            Some(b) if b < ctk.block_num_instances && next_exec[b] < exec_list[b].len() => b,
+            _ => {
+                check.fail(|| format!("block execution {} has block number {}, which has no recorded execution left", i, io[2]));
+                last_outputs = None;
+                continue;
+            }
+        };
+        let vars = &exec_list[b][next_exec[b]];
+        if let Some(j) = (0..2 * n).find(|j| io[*j] != vars[*j]) {
+            check.fail(|| format!("block execution {} ({}th execution of block {}) has {} = {} in exec_inputs, but {} in block_vars_matrix", i, next_exec[b], b, describe_var(j, b, ctk), io[j], vars[j]));
+        }
+        next_exec[b] += 1;
+        if let Some(outputs) = &last_outputs {
+            check.num_consis_checked += 1;
+            // Input reg j is at z[j + 1], output reg j is at z[n + j]
+            if let Some(j) = (1..n - 1).find(|j| io[j + 1] != outputs[n + j]) {
+                check.fail(|| format!("block execution {} (block {}) has input {} = {}, but the previous execution outputs {}", i, b, describe_reg("i", j), io[j + 1], outputs[n + j]));
             }
-            writeln!(&mut f, "")?;
-            exec_counter += 1;
-        }
-        writeln!(&mut f, "INIT_PHY_MEMS")?;
-        let mut addr_counter = 0;
-        for addr in &self.init_phy_mems_list {
-            writeln!(&mut f, "ACCESS {}", addr_counter)?;
-            for assg in &addr.assignment {
-                write!(&mut f, "{} ", bytes_to_integer(&assg.to_bytes()))?;
+        }
+        last_outputs = Some(io);
+    }
+
+    // Physical memory: valid, D, addr, data
+    // Accesses to the same address must have the same data, and addresses must be consecutive
+    // Invalid accesses are only checked through D
+    let phy_mems: Vec<Vec<Integer>> = rtk.addr_phy_mems_list.iter().map(to_integers).collect();
+    for k in 1..phy_mems.len() {
+        check.num_mem_checked += 1;
+        let (last, next) = (&phy_mems[k - 1], &phy_mems[k]);
+        if last[1] != modp(next[0].clone() * (Integer::from(1) - next[2].clone() + last[2].clone())) {
+            check.fail(|| format!("physical memory access {} has D = {}, expected v[k + 1] * (1 - addr[k + 1] + addr[k])", k - 1, last[1]));
+        }
+        if next[0] == 0 {
+            continue;
+        }
+        if next[2] == last[2] {
+            if next[3] != last[3] {
+                check.fail(|| format!("physical memory access {} has data {} at address {}, but access {} has {}", k, next[3], next[2], k - 1, last[3]));
             }
-            writeln!(&mut f, "")?;
-            addr_counter += 1;
-        }
-        writeln!(&mut f, "INIT_VIR_MEMS")?;
-        let mut addr_counter = 0;
-        for addr in &self.init_vir_mems_list {
-            writeln!(&mut f, "ACCESS {}", addr_counter)?;
-            for assg in &addr.assignment {
-                write!(&mut f, "{} ", bytes_to_integer(&assg.to_bytes()))?;
+        } else if next[2] != last[2].clone() + 1 {
+            check.fail(|| format!("physical memory access {} at address {} does not follow address {}", k, next[2], last[2]));
+        }
+    }
+
+    // Virtual memory: valid, D1, addr, data, ls, ts, _, _
+    // Accesses to the same address are sorted by timestamp, and a LOAD reads the data of the previous access
+    let vir_mems: Vec<Vec<Integer>> = rtk.addr_vir_mems_list.iter().map(to_integers).collect();
+    for k in 1..vir_mems.len() {
+        check.num_mem_checked += 1;
+        let (last, next) = (&vir_mems[k - 1], &vir_mems[k]);
+        if last[1] != modp(next[0].clone() * (Integer::from(1) - next[2].clone() + last[2].clone())) {
+            check.fail(|| format!("virtual memory access {} has D1 = {}, expected v[k + 1] * (1 - addr[k + 1] + addr[k])", k - 1, last[1]));
+        }
+        if next[0] == 0 {
+            continue;
+        }
+        if next[2] < last[2] {
+            check.fail(|| format!("virtual memory access {} at address {} is not sorted after address {}", k, next[2], last[2]));
+        } else if next[2] == last[2] {
+            if next[5] < last[5] {
+                check.fail(|| format!("virtual memory access {} at address {} has timestamp {} before {}", k, next[2], next[5], last[5]));
             }
-            writeln!(&mut f, "")?;
-            addr_counter += 1;
-        }
-        writeln!(&mut f, "ADDR_PHY_MEMS")?;
-        let mut addr_counter = 0;
-        for addr in &self.addr_phy_mems_list {
-            writeln!(&mut f, "ACCESS {}", addr_counter)?;
-            for assg in &addr.assignment {
-                write!(&mut f, "{} ", bytes_to_integer(&assg.to_bytes()))?;
+            // LOAD = 1
+            if next[4] == 1 && next[3] != last[3] {
+                check.fail(|| format!("virtual memory access {} loads {} at address {}, but access {} has {}", k, next[3], next[2], k - 1, last[3]));
             }
-            writeln!(&mut f, "")?;
-            addr_counter += 1;
-        }
-        writeln!(&mut f, "ADDR_VIR_MEMS")?;
-        let mut addr_counter = 0;
-        for addr in &self.addr_vir_mems_list {
-            writeln!(&mut f, "ACCESS {}", addr_counter)?;
-            for assg in &addr.assignment {
-                write!(&mut f, "{} ", bytes_to_integer(&assg.to_bytes()))?;
+        }
+    }
+
+    // The sorted lists must be a permutation of the initial memory and the accesses of every valid execution
+    // phy_count[m] and vir_count[m] are the number of times m is accessed by the initial memory and the executions,
+    // minus the number of times it appears in the sorted list
+    let mut phy_count: HashMap<Vec<Integer>, isize> = HashMap::new();
+    let mut vir_count: HashMap<Vec<Integer>, isize> = HashMap::new();
+    for m in rtk.init_phy_mems_list.iter().map(to_integers) {
+        *phy_count.entry(m[2..4].to_vec()).or_default() += 1;
+    }
+    // Initial virtual memory is written with ls = STORE = 0 and ts = 0
+    for m in rtk.init_vir_mems_list.iter().map(to_integers) {
+        *vir_count.entry(vec![m[2].clone(), m[3].clone(), Integer::from(0), Integer::from(0)]).or_default() += 1;
+    }
+    for b in 0..ctk.block_num_instances {
+        let phy_base = 2 * n;
+        let vir_base = phy_base + VARS_PER_ST_ACCESS * ctk.block_num_phy_ops[b];
+        for vars in exec_list[b].iter().filter(|vars| vars[0] == 1) {
+            for k in 0..ctk.block_num_phy_ops[b] {
+                let j = phy_base + VARS_PER_ST_ACCESS * k;
+                *phy_count.entry(vars[j..j + VARS_PER_ST_ACCESS].to_vec()).or_default() += 1;
             }
-            writeln!(&mut f, "")?;
-            addr_counter += 1;
-        }
-        writeln!(&mut f, "ADDR_VM_BITS")?;
-        let mut addr_counter = 0;
-        for addr in &self.addr_ts_bits_list {
-            writeln!(&mut f, "ACCESS {}", addr_counter)?;
-            for assg in &addr.assignment {
-                write!(&mut f, "{} ", bytes_to_integer(&assg.to_bytes()))?;
+            for k in 0..ctk.block_num_vir_ops[b] {
+                let j = vir_base + VARS_PER_VM_ACCESS * k;
+                *vir_count.entry(vars[j..j + VARS_PER_VM_ACCESS].to_vec()).or_default() += 1;
             }
-            writeln!(&mut f, "")?;
-            addr_counter += 1;
         }
-        write!(&mut f, "Inputs: ")?;
-        for assg in &self.input {
-            write!(&mut f, "{} ", bytes_to_integer(&assg))?;
+    }
+    for m in phy_mems.iter().filter(|m| m[0] == 1) {
+        check.num_mem_checked += 1;
+        *phy_count.entry(m[2..4].to_vec()).or_default() -= 1;
+    }
+    for m in vir_mems.iter().filter(|m| m[0] == 1) {
+        check.num_mem_checked += 1;
+        *vir_count.entry(m[2..6].to_vec()).or_default() -= 1;
+    }
+    for (kind, list_name, fields, count) in [
+        ("physical", "addr_phy_mems_list", "(addr, data)", phy_count),
+        ("virtual", "addr_vir_mems_list", "(addr, data, ls, ts)", vir_count),
+    ] {
+        let mut mismatches: Vec<(Vec<Integer>, isize)> = count.into_iter().filter(|(_, c)| *c != 0).collect();
+        mismatches.sort();
+        for (m, c) in mismatches {
+            let m: Vec<String> = m.iter().map(|x| x.to_string()).collect();
+            if c > 0 {
+                check.fail(|| format!("{} memory access {} = ({}) is made {} more times by the executions than it appears in {}", kind, fields, m.join(", "), c, list_name));
+            } else {
+                check.fail(|| format!("{} memory access {} = ({}) appears {} more times in {} than it is made by the executions", kind, fields, m.join(", "), -c, list_name));
+            }
         }
-        writeln!(&mut f, "")?;
-        write!(&mut f, "Input Mems: ")?;
-        for assg in &self.input_mem {
-            write!(&mut f, "{} ", bytes_to_integer(&assg))?;
+    }
+    check
+}
+
+fn print_witness_check(check: &WitnessCheck) {
+    println!("Constraints checked: {}", check.num_cons_checked);
+    println!("Consecutive executions checked: {}", check.num_consis_checked);
+    println!("Memory accesses checked: {}", check.num_mem_checked);
+    if check.num_failures == 0 {
+        println!("All checks passed.");
+        return;
+    }
+    println!("{} checks failed:", check.num_failures);
+    for f in &check.failures {
+        println!("  {}", f);
+    }
+    if check.num_failures > check.failures.len() {
+        println!("  ...");
+    }
+}
+
+// --
+// Export to the circom R1CS format
+// --
+// Every block is written as <dir>/block_<b>.r1cs, and every execution of it in the RTK as <dir>/block_<b>_<e>.wtns
+// The valid bit is the constant of the block constraints, so it is wire 0, the constant 1 of circom, and the I/O are the public inputs
+// The glue instances check a whole RTK list at once, with wire 0 followed by every entry of the list:
+// * consis.r1cs: exec_inputs, the inputs of every execution are the outputs of the previous one
+// * phy_mem.r1cs: addr_phy_mems_list, addresses are consecutive and physical memory is read-only
+// * vir_mem.r1cs: addr_vir_mems_list, every access followed by its TS bits,
+//   accesses to the same address are sorted by timestamp and a LOAD reads the data of the previous access
+// with the witness <dir>/<instance>.wtns. The permutations between the block executions and the lists, which Spartan
+// checks with challenges of the verifier, are written as <dir>/<instance>.perm, with one line `K SOURCE` for every
+// entry K of the list, where SOURCE is `block B E` for execution E of block B, `block B E OP` for its memory
+// access OP, `init J` for entry J of the initial memory, or `invalid` for padding.
+const CIRCOM_R1CS_VERSION: u32 = 1;
+const CIRCOM_WTNS_VERSION: u32 = 2;
+
+type SparseRow = Vec<(usize, [u8; 32])>;
+
+struct CircomR1cs {
+    num_wires: usize,
+    num_pub_inputs: usize,
+    cons: Vec<(SparseRow, SparseRow, SparseRow)>,
+}
+
+// Sparse row from (wire, coefficient) pairs, negative coefficients are cast into the field
+fn sparse_row(terms: &[(usize, i64)]) -> SparseRow {
+    terms.iter().map(|(w, c)| (*w, integer_to_bytes(Integer::from(*c)))).collect()
+}
+
+// Bytes per field element, a multiple of 8
+fn circom_n8() -> usize {
+    let n8 = (cfg().field().modulus().significant_bits() as usize + 63) / 64 * 8;
+    assert!(n8 <= 32, "Field elements do not fit in 32 bytes");
+    n8
+}
+
+fn write_circom_header(f: &mut impl Write, magic: &[u8; 4], version: u32, num_sections: u32) -> std::io::Result<()> {
+    f.write_all(magic)?;
+    f.write_all(&version.to_le_bytes())?;
+    f.write_all(&num_sections.to_le_bytes())
+}
+
+fn write_circom_section(f: &mut impl Write, ty: u32, size: usize) -> std::io::Result<()> {
+    f.write_all(&ty.to_le_bytes())?;
+    f.write_all(&(size as u64).to_le_bytes())
+}
+
+// n8, followed by the modulus
+fn write_circom_field(f: &mut impl Write, n8: usize) -> std::io::Result<()> {
+    f.write_all(&(n8 as u32).to_le_bytes())?;
+    f.write_all(&integer_to_le_bytes(cfg().field().modulus(), n8))
+}
+
+fn integer_to_le_bytes(i: &Integer, n8: usize) -> Vec<u8> {
+    let mut bytes = i.to_digits::<u8>(rug::integer::Order::Lsf);
+    bytes.resize(n8, 0);
+    bytes
+}
+
+fn write_circom_r1cs(path: &PathBuf, r1cs: &CircomR1cs) -> std::io::Result<()> {
+    let n8 = circom_n8();
+    let mut f = BufWriter::new(File::create(path)?);
+    write_circom_header(&mut f, b"r1cs", CIRCOM_R1CS_VERSION, 3)?;
+    // Header: wire 0 and the public inputs, every other wire is an intermediate
+    write_circom_section(&mut f, 1, 4 + n8 + 4 * 4 + 8 + 4)?;
+    write_circom_field(&mut f, n8)?;
+    f.write_all(&(r1cs.num_wires as u32).to_le_bytes())?;
+    // Public outputs, public inputs, private inputs
+    f.write_all(&0u32.to_le_bytes())?;
+    f.write_all(&(r1cs.num_pub_inputs as u32).to_le_bytes())?;
+    f.write_all(&0u32.to_le_bytes())?;
+    f.write_all(&(r1cs.num_wires as u64).to_le_bytes())?;
+    f.write_all(&(r1cs.cons.len() as u32).to_le_bytes())?;
+    // Constraints
+    let row_size = |row: &SparseRow| 4 + row.len() * (4 + n8);
+    write_circom_section(&mut f, 2, r1cs.cons.iter().map(|(a, b, c)| row_size(a) + row_size(b) + row_size(c)).sum())?;
+    for (a, b, c) in &r1cs.cons {
+        for row in [a, b, c].iter() {
+            f.write_all(&(row.len() as u32).to_le_bytes())?;
+            for (w, coeff) in row.iter() {
+                f.write_all(&(*w as u32).to_le_bytes())?;
+                f.write_all(&coeff[..n8])?;
+            }
         }
-        writeln!(&mut f, "")?;
-        write!(&mut f, "Outputs: ")?;
-        for assg in &[self.output] {
-            write!(&mut f, "{} ", bytes_to_integer(&assg))?;
+    }
+    // Every wire is its own label
+    write_circom_section(&mut f, 3, 8 * r1cs.num_wires)?;
+    for w in 0..r1cs.num_wires {
+        f.write_all(&(w as u64).to_le_bytes())?;
+    }
+    f.flush()
+}
+
+fn write_circom_wtns(path: &PathBuf, vals: &[[u8; 32]]) -> std::io::Result<()> {
+    let n8 = circom_n8();
+    let mut f = BufWriter::new(File::create(path)?);
+    write_circom_header(&mut f, b"wtns", CIRCOM_WTNS_VERSION, 2)?;
+    write_circom_section(&mut f, 1, 4 + n8 + 4)?;
+    write_circom_field(&mut f, n8)?;
+    f.write_all(&(vals.len() as u32).to_le_bytes())?;
+    write_circom_section(&mut f, 2, vals.len() * n8)?;
+    for v in vals {
+        f.write_all(&v[..n8])?;
+    }
+    f.flush()
+}
+
+// Wire 0, followed by the concatenation of the entries
+fn glue_witness<A: RtkAssignment>(entries: &[&A]) -> Vec<[u8; 32]> {
+    let mut vals = vec![integer_to_bytes(Integer::from(1))];
+    for e in entries {
+        vals.extend(e.to_bytes());
+    }
+    vals
+}
+
+// exec_inputs: valid, inputs, outputs, padded to a power of 2
+fn consis_r1cs(num_inputs_unpadded: usize, num_execs: usize) -> CircomR1cs {
+    let n = num_inputs_unpadded;
+    let width = (2 * n).next_power_of_two();
+    let mut cons = Vec::new();
+    for k in 1..num_execs {
+        let (last, next) = (1 + (k - 1) * width, 1 + k * width);
+        // v[k] * (input reg j of k - output reg j of k - 1) = 0
+        cons.extend((1..n - 1).map(|j| (
+            sparse_row(&[(next, 1)]),
+            sparse_row(&[(next + j + 1, 1), (last + n + j, -1)]),
+            sparse_row(&[]),
+        )));
+    }
+    CircomR1cs { num_wires: 1 + num_execs * width, num_pub_inputs: 0, cons }
+}
+
+// Physical memory: valid, D, addr, data
+fn phy_mem_r1cs(num_accesses: usize) -> CircomR1cs {
+    let mut cons = Vec::new();
+    for k in 1..num_accesses {
+        let (last, next) = (1 + (k - 1) * 4, 1 + k * 4);
+        // D[k - 1] = v[k] * (1 - addr[k] + addr[k - 1])
+        cons.push((sparse_row(&[(next, 1)]), sparse_row(&[(0, 1), (next + 2, -1), (last + 2, 1)]), sparse_row(&[(last + 1, 1)])));
+        // D[k - 1] * (D[k - 1] - 1) = 0, so addresses are consecutive
+        cons.push((sparse_row(&[(last + 1, 1)]), sparse_row(&[(last + 1, 1), (0, -1)]), sparse_row(&[])));
+        // D[k - 1] * (data[k] - data[k - 1]) = 0
+        cons.push((sparse_row(&[(last + 1, 1)]), sparse_row(&[(next + 3, 1), (last + 3, -1)]), sparse_row(&[])));
+    }
+    CircomR1cs { num_wires: 1 + num_accesses * 4, num_pub_inputs: 0, cons }
+}
+
+// Virtual memory: valid, D1, addr, data, ls, ts, _, _
+// followed by the TS bits: D2, EQ, B0, B1, B2 ...
+fn vir_mem_r1cs(max_ts_width: usize, num_accesses: usize) -> CircomR1cs {
+    let ts_width = (max_ts_width + 2).next_power_of_two();
+    let entry_width = 8 + ts_width;
+    let mut cons = Vec::new();
+    for k in 1..num_accesses {
+        let (last, ts_bits, next) = (1 + (k - 1) * entry_width, 1 + (k - 1) * entry_width + 8, 1 + k * entry_width);
+        let (d1, d2, eq) = (last + 1, ts_bits, ts_bits + 1);
+        // D1[k - 1] = v[k] * (1 - addr[k] + addr[k - 1])
+        cons.push((sparse_row(&[(next, 1)]), sparse_row(&[(0, 1), (next + 2, -1), (last + 2, 1)]), sparse_row(&[(d1, 1)])));
+        // D2[k - 1] = D1[k - 1] * (ls[k] - STORE), where STORE = 0
+        cons.push((sparse_row(&[(d1, 1)]), sparse_row(&[(next + 4, 1)]), sparse_row(&[(d2, 1)])));
+        // D2[k - 1] * (data[k] - data[k - 1]) = 0
+        cons.push((sparse_row(&[(d2, 1)]), sparse_row(&[(next + 3, 1), (last + 3, -1)]), sparse_row(&[])));
+        // EQ is a bit
+        cons.push((sparse_row(&[(eq, 1)]), sparse_row(&[(eq, 1), (0, -1)]), sparse_row(&[])));
+        // B_i are bits, and zero unless EQ
+        for i in 0..max_ts_width {
+            let b = ts_bits + 2 + i;
+            cons.push((sparse_row(&[(b, 1)]), sparse_row(&[(b, 1), (0, -1)]), sparse_row(&[])));
+            cons.push((sparse_row(&[(0, 1), (eq, -1)]), sparse_row(&[(b, 1)]), sparse_row(&[])));
         }
-        writeln!(&mut f, "")?;
-        writeln!(&mut f, "Output Exec Num: {}", self.output_exec_num)?;
-        Ok(())
+        // D1[k - 1] * (ts[k] - ts[k - 1]) = EQ + sum 2^i * B_i, so timestamps increase at the same address
+        let mut ts_diff = vec![(eq, 1)];
+        ts_diff.extend((0..max_ts_width).map(|i| (ts_bits + 2 + i, 1i64 << i)));
+        cons.push((sparse_row(&[(d1, 1)]), sparse_row(&[(next + 5, 1), (last + 5, -1)]), sparse_row(&ts_diff)));
     }
+    CircomR1cs { num_wires: 1 + num_accesses * entry_width, num_pub_inputs: 0, cons }
+}
+
+// Source of every entry of exec_inputs, addr_phy_mems_list and addr_vir_mems_list, see export_circom
+struct GluePermutations {
+    consis: Vec<String>,
+    phy_mem: Vec<String>,
+    vir_mem: Vec<String>,
 }
 
-// Sort block_num_proofs and record where each entry is
-struct InstanceSortHelper {
-    num_exec: usize,
-    index: usize,
-  }
-impl InstanceSortHelper {
-fn new(num_exec: usize, index: usize) -> InstanceSortHelper {
-        InstanceSortHelper {
-            num_exec,
-            index
+impl GluePermutations {
+    fn of<A: RtkAssignment>(ctk: &CompileTimeKnowledge, rtk: &RunTimeKnowledge<A>) -> GluePermutations {
+        let to_integers = |a: &A| -> Vec<Integer> { a.to_bytes().iter().map(bytes_to_integer).collect() };
+        let n = ctk.num_inputs_unpadded;
+        let invalid = || "invalid".to_string();
+
+        // Executions of every block are in execution order, with input %BN in z[2]
+        let mut next_exec = vec![0; ctk.block_num_instances];
+        let consis = rtk.exec_inputs.iter().map(|exec| {
+            let io = to_integers(exec);
+            match io[2].to_usize() {
+                Some(b) if io[0] == 1 && b < ctk.block_num_instances => {
+                    next_exec[b] += 1;
+                    format!("block {} {}", b, next_exec[b] - 1)
+                }
+                _ => invalid(),
+            }
+        }).collect();
+
+        // Equal accesses are interchangeable, so every entry of a sorted list takes the first unused source with its value
+        let mut phy_sources: HashMap<Vec<Integer>, VecDeque<String>> = HashMap::new();
+        let mut vir_sources: HashMap<Vec<Integer>, VecDeque<String>> = HashMap::new();
+        for (j, m) in rtk.init_phy_mems_list.iter().map(to_integers).enumerate() {
+            phy_sources.entry(m[2..4].to_vec()).or_default().push_back(format!("init {}", j));
+        }
+        // Initial virtual memory is written with ls = STORE = 0 and ts = 0
+        for (j, m) in rtk.init_vir_mems_list.iter().map(to_integers).enumerate() {
+            let key = vec![m[2].clone(), m[3].clone(), Integer::from(0), Integer::from(0)];
+            vir_sources.entry(key).or_default().push_back(format!("init {}", j));
+        }
+        for (slot, b) in live_blocks_by_slot(&rtk.block_num_proofs).into_iter().enumerate() {
+            let phy_base = 2 * n;
+            let vir_base = phy_base + VARS_PER_ST_ACCESS * ctk.block_num_phy_ops[b];
+            for (e, vars) in rtk.block_vars_matrix[slot].iter().map(to_integers).enumerate().filter(|(_, vars)| vars[0] == 1) {
+                for k in 0..ctk.block_num_phy_ops[b] {
+                    let j = phy_base + VARS_PER_ST_ACCESS * k;
+                    phy_sources.entry(vars[j..j + VARS_PER_ST_ACCESS].to_vec()).or_default().push_back(format!("block {} {} {}", b, e, k));
+                }
+                for k in 0..ctk.block_num_vir_ops[b] {
+                    let j = vir_base + VARS_PER_VM_ACCESS * k;
+                    vir_sources.entry(vars[j..j + VARS_PER_VM_ACCESS].to_vec()).or_default().push_back(format!("block {} {} {}", b, e, k));
+                }
+            }
         }
+        let phy_mem = rtk.addr_phy_mems_list.iter().map(to_integers).map(|m| {
+            if m[0] == 0 { return invalid(); }
+            phy_sources.get_mut(&m[2..4].to_vec()).and_then(|s| s.pop_front()).unwrap_or_else(invalid)
+        }).collect();
+        let vir_mem = rtk.addr_vir_mems_list.iter().map(to_integers).map(|m| {
+            if m[0] == 0 { return invalid(); }
+            vir_sources.get_mut(&m[2..6].to_vec()).and_then(|s| s.pop_front()).unwrap_or_else(invalid)
+        }).collect();
+        GluePermutations { consis, phy_mem, vir_mem }
+    }
+}
+
+fn write_permutation(path: &PathBuf, sources: &Vec<String>) -> std::io::Result<()> {
+    let mut f = BufWriter::new(File::create(path)?);
+    for (k, source) in sources.iter().enumerate() {
+        writeln!(f, "{} {}", k, source)?;
     }
+    f.flush()
 }
-// Ordering of InstanceSortHelper solely by num_exec
-impl Ord for InstanceSortHelper {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.num_exec.cmp(&other.num_exec)
+
+fn export_circom<A: RtkAssignment>(ctk: &CompileTimeKnowledge, rtk: &RunTimeKnowledge<A>, dir: &PathBuf) -> std::io::Result<()> {
+    create_dir_all(dir)?;
+    let io_width = 2 * ctk.num_inputs_unpadded;
+    for b in 0..ctk.block_num_instances {
+        let r1cs = CircomR1cs {
+            num_wires: ctk.num_vars_per_block[b],
+            num_pub_inputs: io_width - 1,
+            cons: ctk.args[b].clone(),
+        };
+        write_circom_r1cs(&dir.join(format!("block_{}.r1cs", b)), &r1cs)?;
     }
-  }
-  impl PartialOrd for InstanceSortHelper {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+    for (slot, b) in live_blocks_by_slot(&rtk.block_num_proofs).into_iter().enumerate() {
+        for (e, exec) in rtk.block_vars_matrix[slot].iter().enumerate() {
+            write_circom_wtns(&dir.join(format!("block_{}_{}.wtns", b, e)), &exec.to_bytes())?;
+        }
+    }
+
+    let perms = GluePermutations::of(ctk, rtk);
+    write_circom_r1cs(&dir.join("consis.r1cs"), &consis_r1cs(ctk.num_inputs_unpadded, rtk.exec_inputs.len()))?;
+    write_circom_wtns(&dir.join("consis.wtns"), &glue_witness(&rtk.exec_inputs.iter().collect::<Vec<_>>()))?;
+    write_permutation(&dir.join("consis.perm"), &perms.consis)?;
+    write_circom_r1cs(&dir.join("phy_mem.r1cs"), &phy_mem_r1cs(rtk.addr_phy_mems_list.len()))?;
+    write_circom_wtns(&dir.join("phy_mem.wtns"), &glue_witness(&rtk.addr_phy_mems_list.iter().collect::<Vec<_>>()))?;
+    write_permutation(&dir.join("phy_mem.perm"), &perms.phy_mem)?;
+    write_circom_r1cs(&dir.join("vir_mem.r1cs"), &vir_mem_r1cs(ctk.max_ts_width, rtk.addr_vir_mems_list.len()))?;
+    let entries: Vec<&A> = rtk.addr_vir_mems_list.iter().zip(&rtk.addr_ts_bits_list).flat_map(|(m, ts_bits)| [m, ts_bits]).collect();
+    write_circom_wtns(&dir.join("vir_mem.wtns"), &glue_witness(&entries))?;
+    write_permutation(&dir.join("vir_mem.perm"), &perms.vir_mem)?;
+    Ok(())
+}
+
+// --
+// Compile statistics, written by --stats_json
+// --
+#[derive(Serialize, Default)]
+struct BlockStats {
+    // Includes V * V = V
+    num_cons: usize,
+    num_cons_padded: usize,
+    num_vars: usize,
+    num_vars_padded: usize,
+    num_phy_ops: usize,
+    num_vir_ops: usize,
+    // Number of executions, only known after witness generation
+    num_proofs: usize,
+    num_proofs_padded: usize,
+}
+
+#[derive(Serialize, Default)]
+struct CompileStats {
+    benchmark: String,
+    opt_level: usize,
+    num_blocks: usize,
+    num_inputs_unpadded: usize,
+    io_width: usize,
+    // Maximum number of variables and constraints over all blocks, padded
+    num_vars: usize,
+    max_num_cons: usize,
+    blocks: Vec<BlockStats>,
+    // Bound on the number of executions of any block, padded, against the actual maximum
+    block_max_num_proofs: usize,
+    block_max_num_proofs_padded: usize,
+    consis_num_proofs: usize,
+    total_num_init_phy_mem_accesses: usize,
+    total_num_init_vir_mem_accesses: usize,
+    total_num_phy_mem_accesses: usize,
+    total_num_vir_mem_accesses: usize,
+    compiler_ms: f64,
+    witness_ms: f64,
+    // (pass, ms), in order of execution
+    block_pass_ms: Vec<(String, f64)>,
+    ir_pass_ms: Vec<(String, f64)>,
+}
+
+impl CompileStats {
+    fn record_run_time(
+        &mut self,
+        block_num_proofs: &Vec<usize>,
+        block_max_num_proofs: usize,
+        consis_num_proofs: usize,
+        total_num_init_phy_mem_accesses: usize,
+        total_num_init_vir_mem_accesses: usize,
+        total_num_phy_mem_accesses: usize,
+        total_num_vir_mem_accesses: usize,
+    ) {
+        for (b, num_proofs) in self.blocks.iter_mut().zip(block_num_proofs) {
+            b.num_proofs = *num_proofs;
+            b.num_proofs_padded = num_proofs.next_power_of_two();
+        }
+        self.block_max_num_proofs = block_max_num_proofs;
+        self.block_max_num_proofs_padded = block_max_num_proofs.next_power_of_two();
+        self.consis_num_proofs = consis_num_proofs;
+        self.total_num_init_phy_mem_accesses = total_num_init_phy_mem_accesses;
+        self.total_num_init_vir_mem_accesses = total_num_init_vir_mem_accesses;
+        self.total_num_phy_mem_accesses = total_num_phy_mem_accesses;
+        self.total_num_vir_mem_accesses = total_num_vir_mem_accesses;
     }
-  }
-  impl PartialEq for InstanceSortHelper {
-    fn eq(&self, other: &Self) -> bool {
-        self.num_exec == other.num_exec
+
+    fn write_to_file(&self, path: &PathBuf) -> std::io::Result<()> {
+        let f = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(f, self)?;
+        Ok(())
     }
-  }
-  impl Eq for InstanceSortHelper {}
-  
+}
+
+fn to_ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+// Report an error in the source program or in the files of an action and exit, instead of panicking
+fn exit_on_error<R, E: std::fmt::Display>(r: Result<R, E>) -> R {
+    r.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1)
+    })
+}
 
 // --
 // Generate constraints and others
@@ -530,10 +929,11 @@ impl Ord for InstanceSortHelper {
 fn get_compile_time_knowledge<const VERBOSE: bool>(
     path: PathBuf,
     options: &Options,
+    stats: &mut CompileStats,
 ) -> (CompileTimeKnowledge, Vec<usize>, Vec<usize>, Vec<ProverData>) {
     println!("Generating Compiler Time Data...");
 
-    let (
+    let ((
         cs, 
         func_input_width, 
         num_inputs_unpadded, 
@@ -541,41 +941,20 @@ fn get_compile_time_knowledge<const VERBOSE: bool>(
         block_num_mem_accesses, 
         live_vm_list,
         input_liveness,
-    ) = {
+    ), block_pass_times, num_challenges, input_regs) = {
         let inputs = zsharp::Inputs {
             file: path.clone(),
             mode: Mode::Proof,
             opt_level: options.opt_level,
             verbose_opt: options.verbose_opt
         };
-        ZSharpFE::gen(inputs)
+        exit_on_error(ZSharpFE::gen_with_pass_times(inputs))
     };
+    stats.block_pass_ms = block_pass_times.into_iter().map(|(pass, d)| (pass, to_ms(d))).collect();
 
     println!("Optimizing IR... ");
-    let cs = opt(
-        cs,
-        vec![
-            Opt::ScalarizeVars,
-            Opt::Flatten,
-            Opt::Sha,
-            Opt::ConstantFold(Box::new([])),
-            Opt::Flatten,
-            Opt::Inline,
-            // Tuples must be eliminated before oblivious array elim
-            Opt::Tuple,
-            Opt::ConstantFold(Box::new([])),
-            Opt::Obliv,
-            // The obliv elim pass produces more tuples, that must be eliminated
-            Opt::Tuple,
-            Opt::LinearScan,
-            // The linear scan pass produces more tuples, that must be eliminated
-            Opt::Tuple,
-            Opt::Flatten,
-            Opt::ConstantFold(Box::new([])),
-            Opt::Inline,
-            Opt::SkolemizeChallenges
-        ],
-    );
+    let (cs, ir_pass_times) = opt_with_times(cs, zx::block_ir_opts());
+    stats.ir_pass_ms = ir_pass_times.into_iter().map(|(pass, d)| (format!("{:?}", pass), to_ms(d))).collect();
     println!("done.");
 
     if VERBOSE {
@@ -609,33 +988,66 @@ fn get_compile_time_knowledge<const VERBOSE: bool>(
     let mut prover_data_list = Vec::new();
     // Obtain the actual number of witnesse per block, round to the next power of 2
     let mut num_vars_per_block = Vec::new();
+    // wit_ranks[b] maps the number of every witness of block b left after linearity reduction to its rank, if it was reduced
+    let mut wit_ranks = Vec::new();
+    // Lookups of each block are proven as extra physical memory accesses, after the ones of the block itself
+    let mut block_num_lookups = Vec::new();
+    // lookup_start[b] is the rank of the first lookup var of block b
+    let mut lookup_start = Vec::new();
     while let Some(c) = cs.comps.get(&block_name) {
         let mut r1cs = to_r1cs(c, cfg());
 
         // Remove the last constraint because it is about the return value
         r1cs.constraints.pop();
 
-        // Add prover data
-        let (prover_data, _) = r1cs.clone().finalize(c);
-        prover_data_list.push(prover_data);
-
-        /*
-        let r1cs = {
+        // Inputs and outputs (instance vars), memory accesses (%pm and %vm) and lookups have fixed positions in the block vars,
+        // so they are kept: only other witnesses are eliminated
+        let num_mem_wits = VARS_PER_ST_ACCESS * block_num_mem_accesses[block_num].0 + live_vm_list[block_num].len();
+        let (r1cs, num_eliminated) = if options.skip_linred {
+            wit_ranks.push(None);
+            (r1cs, 0)
+        } else {
             let old_size = r1cs.constraints().len();
-            let r1cs = reduce_linearities(r1cs, cfg());
+            let old_wits = used_final_wits(&r1cs, num_mem_wits);
+            let r1cs = reduce_linearities_keeping(r1cs, cfg(), |var, name| {
+                matches!(var.ty(), VarType::Inst) || name.starts_with("%pm") || name.starts_with("%vm")
+            });
             let new_size = r1cs.constraints().len();
             if VERBOSE {
                 println!("{} linear reduction: {} -> {}", block_name, old_size, new_size);
             }
-            r1cs
+            // Dead witnesses are removed, so the witnesses left are the first num_mem_wits (memory accesses), the lookup vars
+            // and the other witnesses still used by a constraint, which wit_ranks numbers consecutively in the block vars
+            let new_wits = used_final_wits(&r1cs, num_mem_wits);
+            // Witnesses that were never used are not counted as eliminated, which can only overestimate the block size
+            let num_eliminated = old_wits.len() - new_wits.len();
+            wit_ranks.push(Some(new_wits.into_iter().enumerate().map(|(rank, i)| (i, rank)).collect::<HashMap<usize, usize>>()));
+            (r1cs, num_eliminated)
         };
-        */
+        // Lookup vars are the last witnesses, in pairs of (addr, data)
+        block_num_lookups.push(r1cs.lookups().len());
+        lookup_start.push(r1cs.lookups().first().map_or(usize::MAX, |l| {
+            let i = l.addr.number();
+            wit_ranks[block_num].as_ref().map_or(i, |ranks| ranks[&i])
+        }));
+
+        // Add prover data, which only evaluates the remaining witnesses
+        let (prover_data, _) = r1cs.clone().finalize(c);
+        prover_data_list.push(prover_data);
+
         let num_witnesses = 
             io_width // input + output
-            + r1cs.num_vars()
+            + r1cs.num_vars() - num_eliminated
             + VARS_PER_VM_ACCESS * block_num_mem_accesses[block_num].1 - live_vm_list[block_num].len() // remove live vm vars, add all vm vars
             - live_io_list[block_num].0.len() - live_io_list[block_num].1.len(); // remove all inputs / outputs
         num_vars_per_block.push(num_witnesses.next_power_of_two());
+        stats.blocks.push(BlockStats {
+            num_vars: num_witnesses,
+            num_vars_padded: num_witnesses.next_power_of_two(),
+            num_phy_ops: block_num_mem_accesses[block_num].0 + block_num_lookups[block_num],
+            num_vir_ops: block_num_mem_accesses[block_num].1,
+            ..Default::default()
+        });
         // Include V * V = V
         let num_cons = r1cs.constraints().len() + 1;
         if num_witnesses > max_num_witnesses { max_num_witnesses = num_witnesses };
@@ -671,7 +1083,9 @@ fn get_compile_time_knowledge<const VERBOSE: bool>(
     };
     // Add all IOs and WV in front
     let witness_relabel = |b: usize, i: usize|  -> usize {
+        let i = wit_ranks[b].as_ref().map_or(i, |ranks| ranks[&i]);
         let num_pm_vars = VARS_PER_ST_ACCESS * block_num_mem_accesses[b].0;
+        let num_lookup_vars = VARS_PER_ST_ACCESS * block_num_lookups[b];
         let num_live_vm_vars = live_vm_list[b].len();
         let num_vm_vars = VARS_PER_VM_ACCESS * block_num_mem_accesses[b].1;
         let new_i = {
@@ -679,13 +1093,17 @@ fn get_compile_time_knowledge<const VERBOSE: bool>(
             if i < num_pm_vars {
                 io_width + i
             }
+            // lookups, as physical memory accesses
+            else if i >= lookup_start[b] {
+                io_width + num_pm_vars + (i - lookup_start[b])
+            }
             // virtual memory accesses
             else if i < num_pm_vars + num_live_vm_vars {
-                io_width + num_pm_vars + live_vm_list[b][i - num_pm_vars]
+                io_width + num_pm_vars + num_lookup_vars + live_vm_list[b][i - num_pm_vars]
             }
             // other witneses
             else {
-                io_width + num_vm_vars + (i - num_live_vm_vars)
+                io_width + num_lookup_vars + num_vm_vars + (i - num_live_vm_vars)
             }
         };
         new_i
@@ -738,6 +1156,17 @@ fn get_compile_time_knowledge<const VERBOSE: bool>(
     let input_block_num = 0;
     let output_block_num = block_num_instances;
 
+    // Constraints involving "return" are discarded while converting to the sparse format
+    for b in 0..block_num_instances {
+        stats.blocks[b].num_cons = args[b].len();
+        stats.blocks[b].num_cons_padded = args[b].len().next_power_of_two();
+    }
+    stats.num_blocks = block_num_instances;
+    stats.num_inputs_unpadded = num_inputs_unpadded;
+    stats.io_width = io_width;
+    stats.num_vars = num_vars;
+    stats.max_num_cons = max_num_cons;
+
     let live_io_size = live_io_list.iter().map(|i| i.0.len() + i.1.len()).collect();
     let live_mem_size = (0..live_vm_list.len()).map(|i| VARS_PER_ST_ACCESS * block_num_mem_accesses[i].0 + live_vm_list[i].len()).collect();
     
@@ -746,7 +1175,7 @@ fn get_compile_time_knowledge<const VERBOSE: bool>(
         num_vars,
         num_inputs_unpadded,
         num_vars_per_block,
-        block_num_phy_ops: block_num_mem_accesses.iter().map(|i| i.0).collect(),
+        block_num_phy_ops: block_num_mem_accesses.iter().zip(&block_num_lookups).map(|(i, l)| i.0 + l).collect(),
         block_num_vir_ops: block_num_mem_accesses.iter().map(|i| i.1).collect(),
         max_ts_width: MAX_TS_WIDTH,
         args,
@@ -756,7 +1185,10 @@ fn get_compile_time_knowledge<const VERBOSE: bool>(
         input_offset: NUM_RESERVED_VARS,
         input_block_num,
         output_offset: OUTPUT_OFFSET,
-        output_block_num
+        output_block_num,
+        lookup_width: cfg().r1cs.lookup_width,
+        num_challenges,
+        input_regs,
       },
       live_io_size,
       live_mem_size,
@@ -764,60 +1196,285 @@ fn get_compile_time_knowledge<const VERBOSE: bool>(
     )
 }
 
-// --
-// Generate witnesses and others
-// --
-fn get_run_time_knowledge<const VERBOSE: bool>(
-    path: PathBuf,
-    options: &Options,
-    mut entry_regs: BTreeMap<String, Integer>,
-    entry_stacks: BTreeMap<String, Vec<Integer>>,
-    entry_arrays: BTreeMap<String, Vec<Integer>>,
-    entry_witnesses: Vec<Integer>,
-    entry_regs_concat: Vec<Integer>,
-    entry_stacks_concat: Vec<Integer>,
-    entry_arrays_concat: Vec<Integer>,
-    ctk: &CompileTimeKnowledge,
-    live_io_size: Vec<usize>,
-    live_mem_size: Vec<usize>,
-    prover_data_list: Vec<ProverData>,
-    total_num_init_phy_mem_accesses: usize,
-    total_num_init_vir_mem_accesses: usize,
-) -> RunTimeKnowledge {
-    let interpret_start = Instant::now();
+// Numbers of the witnesses of r1cs used by some constraint or lookup, and of the first num_kept witnesses
+fn used_final_wits(r1cs: &R1cs, num_kept: usize) -> BTreeSet<usize> {
+    let mut wits: BTreeSet<usize> = (0..num_kept).collect();
+    wits.extend(r1cs.lookups().iter().flat_map(|l| [l.addr.number(), l.value.number()]));
+    for (a, b, c) in r1cs.constraints() {
+        for lc in [a, b, c].iter() {
+            wits.extend(lc.monomials.keys().filter(|var| matches!(var.ty(), VarType::FinalWit)).map(|var| var.number()));
+        }
+    }
+    wits
+}
 
-    let num_blocks = ctk.block_num_instances;
-    let num_input_unpadded = ctk.num_inputs_unpadded;
-    let io_width = 2 * num_input_unpadded;
-    // bl_outputs records ios of blocks as lists
-    // bl_io_map maps name of io variables to their values
-    // bl_outputs are used to fill in io part of vars
-    // bl_io_map is used to compute witness part of vars
-    let (
-        _, 
-        block_id_list, 
-        bl_outputs_list, 
-        bl_mems_list, 
-        bl_io_map_list,
-        init_phy_mem_list,
-        init_vir_mem_list,
-        phy_mem_list, 
-        vir_mem_list,
-    ) = {
-        let inputs = zsharp::Inputs {
-            file: path,
-            mode: Mode::Proof,
-            opt_level: options.opt_level,
-            verbose_opt: options.verbose_opt
-        };
+// Number of physical memory vars of the block itself and of its lookups,
+// given the number of memory vars of the block itself (physical and virtual)
+fn num_block_pm_vars(id: usize, ctk: &CompileTimeKnowledge, num_mem_vars: usize) -> (usize, usize) {
+    let num_vm_vars = VARS_PER_VM_ACCESS * ctk.block_num_vir_ops[id];
+    let num_pm_vars = num_mem_vars - num_vm_vars;
+    (num_pm_vars, VARS_PER_ST_ACCESS * ctk.block_num_phy_ops[id] - num_pm_vars)
+}
 
-        ZSharpFE::interpret(inputs, &mut entry_regs, &entry_stacks, &entry_arrays, &entry_witnesses)
-    };
-    let interpret_time = interpret_start.elapsed();
-    println!("\n--\nInterpret time: {}ms", interpret_time.as_millis());
+// Physical memory accesses (addr, data) of the lookups of a block execution, see gen_block_vars
+fn gen_lookup_ops(id: usize, vars: &Vec<Integer>, ctk: &CompileTimeKnowledge, num_mem_vars: usize) -> Vec<(Integer, Integer)> {
+    let io_width = 2 * ctk.num_inputs_unpadded;
+    let (num_pm_vars, num_lookup_vars) = num_block_pm_vars(id, ctk, num_mem_vars);
+    (0..num_lookup_vars).step_by(VARS_PER_ST_ACCESS).map(|j| {
+        let base = io_width + num_pm_vars + j;
+        (vars[base].clone(), vars[base + 1].clone())
+    }).collect()
+}
 
-    let block_start = Instant::now();
-    // Meta info
+// Compute the vars of a single block execution: input + output + memory accesses + witnesses
+// reg_in and reg_out are the register states before and after the execution
+fn gen_block_vars<const VERBOSE: bool>(
+    id: usize,
+    input: HashMap<String, Value, BuildHasherDefault<fxhash::FxHasher>>,
+    reg_in: &Vec<Option<Value>>,
+    reg_out: &Vec<Option<Value>>,
+    reg_mem: &Vec<Option<Value>>,
+    ctk: &CompileTimeKnowledge,
+    live_io_size: &Vec<usize>,
+    live_mem_size: &Vec<usize>,
+    precompute: &StagedWitComp,
+) -> Vec<Integer> {
+    let num_input_unpadded = ctk.num_inputs_unpadded;
+    let io_width = 2 * num_input_unpadded;
+    let zero = Integer::from(0);
+    let one = Integer::from(1);
+    if VERBOSE { println!("ID: {}", id); }
+    let mut evaluator = StagedWitCompEvaluator::new(precompute);
+    let mut eval = Vec::new();
+    eval.extend(evaluator.eval_stage(input).into_iter().cloned());
+    // Drop the last entry of io, which is the dummy return 0
+    eval.pop();
+    eval.extend(evaluator.eval_stage(Default::default()).into_iter().cloned());
+
+    // Vars are described in a length-(num_vars) array, consisted of input + output + witnesses
+    let mut vars: Vec<Integer> = vec![zero.clone(); ctk.num_vars_per_block[id]];
+    // Valid bit should be 1
+    vars[0] = one.clone();
+    // Use bl_outputs_list to assign input
+    // Note that we do not use eval because eval automatically deletes dead registers
+    // (that need to stay for consistency check)
+    for j in 0..reg_in.len() {
+        if let Some(ri) = &reg_in[j] {
+            vars[j + 1] = ri.as_integer().unwrap();
+        }
+        if let Some(ro) = &reg_out[j] {
+            vars[num_input_unpadded + j] = ro.as_integer().unwrap();
+        }
+    }
+    // Use bl_mems_list to assign all memory operations
+    // Lookups are placed between physical and virtual memory operations
+    let (num_pm_vars, num_lookup_vars) = num_block_pm_vars(id, ctk, reg_mem.len());
+    for j in 0..reg_mem.len() {
+        if let Some(rm) = &reg_mem[j] {
+            let k = if j < num_pm_vars { j } else { j + num_lookup_vars };
+            vars[io_width + k] = rm.as_integer().unwrap();
+        }
+    }
+
+    // Use eval to assign witnesses, the last ones are the lookups
+    let wit_offset = live_io_size[id] + live_mem_size[id];
+    let lookup_offset = eval.len() - num_lookup_vars;
+    for j in wit_offset..eval.len() {
+        let k = if j < lookup_offset {
+            j - wit_offset + reg_mem.len() + num_lookup_vars
+        } else {
+            j - lookup_offset + num_pm_vars
+        };
+        vars[io_width + k] = eval[j].as_integer().unwrap();
+    }
+    if VERBOSE {
+        let print_width = min(num_input_unpadded - 1, 32);
+        print!("{:3} ", " ");
+        for i in 0..2 + print_width {
+            print!("{:3} ", i);
+        }
+        println!();
+        print!("{:3} ", "I");
+        for i in 0..2 + print_width {
+            print!("{:3} ", vars[i]);
+        }
+        if num_input_unpadded - 1 > print_width {
+            println!("...");
+        } else {
+            println!();
+        }
+        print!("{:3} {:3} {:3} ", "O", " ", " ");
+        for i in num_input_unpadded + 1..num_input_unpadded + 1 + print_width {
+            print!("{:3} ", vars[i]);
+        }
+        if num_input_unpadded - 1 > print_width {
+            println!("...");
+        } else {
+            println!();
+        }
+        print!("{:3} ", "W");
+        let print_width = min(vars.len() - io_width, 32);
+        for i in 0..print_width {
+            print!("{:3} ", vars[io_width + i]);
+        }
+        if vars.len() > print_width {
+            println!("...");
+        } else {
+            println!();
+        }
+    }
+
+    vars
+}
+
+// Convert the vars of a block execution into its (padded) inputs and vars assignments
+fn gen_block_assignments<A: RtkAssignment>(vars: &Vec<Integer>, io_width: usize) -> (A, A) {
+    let zero = Integer::from(0);
+    let inputs = [vars[..io_width].to_vec(), vec![zero; io_width.next_power_of_two() - io_width]].concat();
+    (integers_to_assignment(&inputs), integers_to_assignment(vars))
+}
+
+// Physical Memory: valid, D, addr, data
+// Every entry depends on the entry after it, so each entry is only emitted once its successor is pushed
+struct PhyMemBuilder {
+    last: Option<Vec<Integer>>,
+}
+
+impl PhyMemBuilder {
+    fn new() -> PhyMemBuilder {
+        PhyMemBuilder { last: None }
+    }
+
+    // Push the next access in address order, returns the previous access if there is one
+    fn push<A: RtkAssignment>(&mut self, addr: Integer, data: Integer) -> Option<A> {
+        let one = Integer::from(1);
+        let mut mem: Vec<Integer> = vec![Integer::from(0); 4];
+        mem[0] = one.clone();
+        mem[2] = addr;
+        mem[3] = data;
+        let res = self.last.take().map(|mut phy_mem_last| {
+            // backend requires the 1st entry to be v[k + 1] * (1 - addr[k + 1] + addr[k])
+            phy_mem_last[1] = mem[0].clone() * (one.clone() - mem[2].clone() + phy_mem_last[2].clone());
+            integers_to_assignment(&phy_mem_last)
+        });
+        self.last = Some(mem);
+        res
+    }
+
+    // The last access has no successor
+    fn finish<A: RtkAssignment>(self) -> Option<A> {
+        self.last.map(|mem| integers_to_assignment(&mem))
+    }
+}
+
+// Virtual Memory: valid, D1, addr, data, ls, ts, _, _
+// TS Bits: D2, EQ, B0, B1, B2 ..., max_ts_width bits of the timestamp difference
+struct VirMemBuilder {
+    max_ts_width: usize,
+    last: Option<(Vec<Integer>, Vec<Integer>)>,
+}
+
+impl VirMemBuilder {
+    fn new(max_ts_width: usize) -> VirMemBuilder {
+        VirMemBuilder { max_ts_width, last: None }
+    }
+
+    // Push the next access in (address, timestamp) order, returns the previous access and its TS bits if there is one
+    fn push<A: RtkAssignment>(&mut self, addr: Integer, data: Integer, ls: Integer, ts: Integer) -> Option<(A, A)> {
+        let one = Integer::from(1);
+        let zero = Integer::from(0);
+        let mut mem: Vec<Integer> = vec![zero.clone(); 8];
+        mem[0] = one.clone();
+        mem[2] = addr;
+        mem[3] = data;
+        mem[4] = ls;
+        mem[5] = ts;
+        
+        let ts_bits: Vec<Integer> = vec![zero.clone(); (self.max_ts_width + 2).next_power_of_two()];
+        // D1, D2, D3, D4
+        let res = self.last.take().map(|(mut vir_mem_last, mut ts_bits_last)| {
+            // D1[k] = v[k + 1] * (1 - addr[k + 1] + addr[k])
+            vir_mem_last[1] = mem[0].clone() * (one.clone() - mem[2].clone() + vir_mem_last[2].clone());
+            // D2[k] = D1[k] * (ls[k + 1] - STORE), where STORE = 0
+            ts_bits_last[0] = vir_mem_last[1].clone() * mem[4].clone();
+            // Bits of D1[k] * (ts[k + 1] - ts[k]) in ts_bits_last[2..]
+            let mut d4 = vir_mem_last[1].clone() * (mem[5].clone() - vir_mem_last[5].clone());
+            if d4 != 0 {
+                // EQ = 1
+                ts_bits_last[1] = Integer::from(1);
+                // Use bits to assemble D4 - 1
+                d4 -= 1;
+                for i in 0..self.max_ts_width {
+                    ts_bits_last[2 + i] = d4.clone() % 2;
+                    d4 /= 2;
+                }
+            }
+            (integers_to_assignment(&vir_mem_last), integers_to_assignment(&ts_bits_last))
+        });
+        self.last = Some((mem, ts_bits));
+        res
+    }
+
+    // The last access has no successor
+    fn finish<A: RtkAssignment>(self) -> Option<(A, A)> {
+        self.last.map(|(mem, ts_bits)| (integers_to_assignment(&mem), integers_to_assignment(&ts_bits)))
+    }
+}
+
+// --
+// Generate witnesses and others
+// --
+fn get_run_time_knowledge<const VERBOSE: bool, A: RtkAssignment>(
+    path: PathBuf,
+    options: &Options,
+    mut entry_regs: BTreeMap<String, Integer>,
+    entry_stacks: BTreeMap<String, Vec<Integer>>,
+    entry_arrays: BTreeMap<String, Vec<Integer>>,
+    entry_witnesses: Vec<Integer>,
+    entry_regs_concat: Vec<Integer>,
+    entry_stacks_concat: Vec<Integer>,
+    entry_arrays_concat: Vec<Integer>,
+    ctk: &CompileTimeKnowledge,
+    live_io_size: Vec<usize>,
+    live_mem_size: Vec<usize>,
+    prover_data_list: Vec<ProverData>,
+    total_num_init_phy_mem_accesses: usize,
+    total_num_init_vir_mem_accesses: usize,
+) -> RunTimeKnowledge<A> {
+    let interpret_start = Instant::now();
+
+    let num_blocks = ctk.block_num_instances;
+    let num_input_unpadded = ctk.num_inputs_unpadded;
+    let io_width = 2 * num_input_unpadded;
+    // bl_outputs records ios of blocks as lists
+    // bl_io_map maps name of io variables to their values
+    // bl_outputs are used to fill in io part of vars
+    // bl_io_map is used to compute witness part of vars
+    let (
+        _, 
+        block_id_list, 
+        bl_outputs_list, 
+        bl_mems_list, 
+        bl_io_map_list,
+        init_phy_mem_list,
+        init_vir_mem_list,
+        phy_mem_list, 
+        vir_mem_list,
+    ) = {
+        let inputs = zsharp::Inputs {
+            file: path,
+            mode: Mode::Proof,
+            opt_level: options.opt_level,
+            verbose_opt: options.verbose_opt
+        };
+
+        exit_on_error(ZSharpFE::interpret(inputs, &mut entry_regs, &entry_stacks, &entry_arrays, &entry_witnesses))
+    };
+    let interpret_time = interpret_start.elapsed();
+    println!("\n--\nInterpret time: {}ms", interpret_time.as_millis());
+
+    let block_start = Instant::now();
+    // Meta info
     // The most time any block is executed
     let mut block_max_num_proofs = 0;
     // Number of times each block is executed
@@ -831,8 +1488,6 @@ fn get_run_time_knowledge<const VERBOSE: bool>(
         }
         consis_num_proofs += 1;
     }
-    let total_num_phy_mem_accesses = phy_mem_list.len();
-    let total_num_vir_mem_accesses = vir_mem_list.len();
     let output_exec_num = block_id_list.len() - 1;
 
     // num_blocks_live is # of non-zero entries in block_num_proofs
@@ -859,95 +1514,59 @@ fn get_run_time_knowledge<const VERBOSE: bool>(
     let mut block_vars_matrix = vec![Vec::new(); num_blocks_live];
     let mut exec_inputs = Vec::new();
 
-    let mut func_outputs = Integer::from(0);
-    for i in 0..block_id_list.len() {
-        let id = block_id_list[i];
-        let input = bl_io_map_list[i].clone();
-        if VERBOSE { println!("ID: {}", id); }
-        let mut evaluator = StagedWitCompEvaluator::new(&prover_data_list[id].precompute);
-        let mut eval = Vec::new();
-        eval.extend(evaluator.eval_stage(input).into_iter().cloned());
-        // Drop the last entry of io, which is the dummy return 0
-        eval.pop();
-        eval.extend(evaluator.eval_stage(Default::default()).into_iter().cloned());
-
-        // Vars are described in a length-(num_vars) array, consisted of input + output + witnesses
-        let mut vars: Vec<Integer> = vec![zero.clone(); ctk.num_vars_per_block[id]];
-        // Valid bit should be 1
-        vars[0] = one.clone();
-        // Use bl_outputs_list to assign input
-        // Note that we do not use eval because eval automatically deletes dead registers
-        // (that need to stay for consistency check)
-        let reg_in = &bl_outputs_list[i];
-        let reg_out = &bl_outputs_list[i + 1];
-        for j in 0..reg_in.len() {
-            if let Some(ri) = &reg_in[j] {
-                vars[j + 1] = ri.as_integer().unwrap();
-            }
-            if let Some(ro) = &reg_out[j] {
-                vars[num_input_unpadded + j] = ro.as_integer().unwrap();
-            }
-        }
-        // Use bl_mems_list to assign all memory operations
-        let reg_mem = &bl_mems_list[i];
-        for j in 0..reg_mem.len() {
-            if let Some(rm) = &reg_mem[j] {
-                vars[io_width + j] = rm.as_integer().unwrap();
+    // Witnesses of each block execution only depend on the execution trace, so they can be evaluated in any order
+    // The results are collected in execution order, so both paths produce the same output
+    let block_vars_list: Vec<Vec<Integer>> = if options.parallel_witness {
+        // StagedWitComp holds terms that cannot be shared across threads,
+        // so every worker deserializes its own copy of the precomputes it needs
+        let precompute_bytes_list: Vec<Vec<u8>> = prover_data_list.iter().map(|p| bincode::serialize(&p.precompute).unwrap()).collect();
+        (0..block_id_list.len()).into_par_iter().map_init(
+            || (0..num_blocks).map(|_| None).collect::<Vec<Option<StagedWitComp>>>(),
+            |precompute_list, i| {
+                let id = block_id_list[i];
+                let precompute = precompute_list[id].get_or_insert_with(|| bincode::deserialize(&precompute_bytes_list[id]).unwrap());
+                gen_block_vars::<VERBOSE>(
+                    id,
+                    bl_io_map_list[i].clone(),
+                    &bl_outputs_list[i],
+                    &bl_outputs_list[i + 1],
+                    &bl_mems_list[i],
+                    ctk,
+                    &live_io_size,
+                    &live_mem_size,
+                    precompute,
+                )
             }
-        }
+        ).collect()
+    } else {
+        (0..block_id_list.len()).map(|i| {
+            let id = block_id_list[i];
+            gen_block_vars::<VERBOSE>(
+                id,
+                bl_io_map_list[i].clone(),
+                &bl_outputs_list[i],
+                &bl_outputs_list[i + 1],
+                &bl_mems_list[i],
+                ctk,
+                &live_io_size,
+                &live_mem_size,
+                &prover_data_list[id].precompute,
+            )
+        }).collect()
+    };
 
-        // Use eval to assign witnesses
-        let wit_offset = live_io_size[id] + live_mem_size[id];
-        for j in wit_offset..eval.len() {
-            let k = j - wit_offset + reg_mem.len();
-            vars[io_width + k] = eval[j].as_integer().unwrap();
-        }
+    let mut func_outputs = Integer::from(0);
+    let mut lookup_ops = Vec::new();
+    for (i, vars) in block_vars_list.into_iter().enumerate() {
+        let id = block_id_list[i];
         if i == block_id_list.len() - 1 {
             func_outputs = vars[num_input_unpadded + OUTPUT_OFFSET].clone();
         }
-        if VERBOSE {
-            let print_width = min(num_input_unpadded - 1, 32);
-            print!("{:3} ", " ");
-            for i in 0..2 + print_width {
-                print!("{:3} ", i);
-            }
-            println!();
-            print!("{:3} ", "I");
-            for i in 0..2 + print_width {
-                print!("{:3} ", vars[i]);
-            }
-            if num_input_unpadded - 1 > print_width {
-                println!("...");
-            } else {
-                println!();
-            }
-            print!("{:3} {:3} {:3} ", "O", " ", " ");
-            for i in num_input_unpadded + 1..num_input_unpadded + 1 + print_width {
-                print!("{:3} ", vars[i]);
-            }
-            if num_input_unpadded - 1 > print_width {
-                println!("...");
-            } else {
-                println!();
-            }
-            print!("{:3} ", "W");
-            let print_width = min(vars.len() - io_width, 32);
-            for i in 0..print_width {
-                print!("{:3} ", vars[io_width + i]);
-            }
-            if vars.len() > print_width {
-                println!("...");
-            } else {
-                println!();
-            }
-        }
-
-        let inputs = [vars[..io_width].to_vec(), vec![zero.clone(); io_width.next_power_of_two() - io_width]].concat();
-        let inputs_assignment = Assignment::new(&inputs.iter().map(|i| integer_to_bytes(i.clone())).collect::<Vec<[u8; 32]>>()).unwrap();
-        let vars_assignment = Assignment::new(&vars.iter().map(|i| integer_to_bytes(i.clone())).collect::<Vec<[u8; 32]>>()).unwrap();
+        lookup_ops.extend(gen_lookup_ops(id, &vars, ctk, bl_mems_list[i].len()));
+        let (inputs_assignment, vars_assignment) = gen_block_assignments(&vars, io_width);
 
         let slot = index_rev[id];
-        exec_inputs.push(inputs_assignment.clone());
+        exec_inputs.push(inputs_assignment);
         block_vars_matrix[slot].push(vars_assignment);
     }
     let block_time = block_start.elapsed();
@@ -964,7 +1583,7 @@ fn get_run_time_knowledge<const VERBOSE: bool>(
         mem[2] = m[0].as_integer().unwrap();
         mem[3] = m[1].as_integer().unwrap();
         
-        init_phy_mems_list.push(Assignment::new(&mem.iter().map(|i| integer_to_bytes(i.clone())).collect::<Vec<[u8; 32]>>()).unwrap())
+        init_phy_mems_list.push(integers_to_assignment(&mem))
     }
     let mut init_vir_mems_list = Vec::new();
     // No need to record TS bits since it is always 0
@@ -977,76 +1596,51 @@ fn get_run_time_knowledge<const VERBOSE: bool>(
         mem[2] = m[0].as_integer().unwrap();
         mem[3] = m[1].as_integer().unwrap();
         
-        init_vir_mems_list.push(Assignment::new(&mem.iter().map(|i| integer_to_bytes(i.clone())).collect::<Vec<[u8; 32]>>()).unwrap())
+        init_vir_mems_list.push(integers_to_assignment(&mem))
     }
 
+    // Lookups are physical memory accesses as well, the sort is stable so initial memory stays first
+    let mut phy_mem_list: Vec<(Integer, Integer)> = phy_mem_list.iter()
+        .map(|m| (m.0.as_integer().unwrap(), m.1.as_integer().unwrap()))
+        .chain(lookup_ops)
+        .collect();
+    phy_mem_list.sort_by(|a, b| a.0.cmp(&b.0));
+    let total_num_phy_mem_accesses = phy_mem_list.len();
+    let total_num_vir_mem_accesses = vir_mem_list.len();
+
     // Physical Memory: valid, D, addr, data
     let mut addr_phy_mems_list = Vec::new();
-    let mut phy_mem_last = vec![one.clone(); 4];
-    for i in 0..phy_mem_list.len() {
-        let m = &phy_mem_list[i];
-        let mut mem: Vec<Integer> = vec![zero.clone(); 4];
-        mem[0] = one.clone();
-        mem[2] = m.0.as_integer().unwrap();
-        mem[3] = m.1.as_integer().unwrap();
-        // backend requires the 1st entry to be v[k + 1] * (1 - addr[k + 1] + addr[k])
-        if i != 0 {
-            phy_mem_last[1] = mem[0].clone() * (one.clone() - mem[2].clone() + phy_mem_last[2].clone());
-            addr_phy_mems_list.push(Assignment::new(&phy_mem_last.iter().map(|i| integer_to_bytes(i.clone())).collect::<Vec<[u8; 32]>>()).unwrap());
-        }
-        if i == phy_mem_list.len() - 1 {
-            addr_phy_mems_list.push(Assignment::new(&mem.iter().map(|i| integer_to_bytes(i.clone())).collect::<Vec<[u8; 32]>>()).unwrap());
-        } else {
-            phy_mem_last = mem;
+    let mut phy_mem_builder = PhyMemBuilder::new();
+    for (addr, data) in phy_mem_list {
+        if let Some(mem) = phy_mem_builder.push(addr, data) {
+            addr_phy_mems_list.push(mem);
         }
     }
+    if let Some(mem) = phy_mem_builder.finish() {
+        addr_phy_mems_list.push(mem);
+    }
 
     // Virtual Memory: valid, D1, addr, data, ls, ts, _, _
     let mut addr_vir_mems_list = Vec::new();
-    let mut vir_mem_last: Vec<Integer> = Vec::new();
     // TS Bits: D2, EQ, B0, B1, B2 ...
     let mut addr_ts_bits_list = Vec::new();
-    let mut ts_bits_last: Vec<Integer> = Vec::new();
-    for i in 0..vir_mem_list.len() {
-        let m = &vir_mem_list[i];
-        
-        let mut mem: Vec<Integer> = vec![zero.clone(); 8];
-        mem[0] = one.clone();
-        mem[2] = m[0].as_integer().unwrap();
-        mem[3] = m[1].as_integer().unwrap();
-        mem[4] = m[2].as_integer().unwrap();
-        mem[5] = m[3].as_integer().unwrap();
-        
-        let ts_bits: Vec<Integer> = vec![zero.clone(); (MAX_TS_WIDTH + 2).next_power_of_two()];
-        // D1, D2, D3, D4
-        if i != 0 {
-            // D1[k] = v[k + 1] * (1 - addr[k + 1] + addr[k])
-            vir_mem_last[1] = mem[0].clone() * (one.clone() - mem[2].clone() + vir_mem_last[2].clone());
-            // D2[k] = D1[k] * (ls[k + 1] - STORE), where STORE = 0
-            ts_bits_last[0] = vir_mem_last[1].clone() * mem[4].clone();
-            // Bits of D1[k] * (ts[k + 1] - ts[k]) in ts_bits_last[2..]
-            let mut d4 = vir_mem_last[1].clone() * (mem[5].clone() - vir_mem_last[5].clone());
-            if d4 != 0 {
-                // EQ = 1
-                ts_bits_last[1] = Integer::from(1);
-                // Use bits to assemble D4 - 1
-                d4 -= 1;
-                for i in 0..MAX_TS_WIDTH {
-                    ts_bits_last[2 + i] = d4.clone() % 2;
-                    d4 /= 2;
-                }
-            }
-            addr_vir_mems_list.push(Assignment::new(&vir_mem_last.iter().map(|i| integer_to_bytes(i.clone())).collect::<Vec<[u8; 32]>>()).unwrap());
-            addr_ts_bits_list.push(Assignment::new(&ts_bits_last.iter().map(|i| integer_to_bytes(i.clone())).collect::<Vec<[u8; 32]>>()).unwrap());
-        }
-        if i == vir_mem_list.len() - 1 {
-            addr_vir_mems_list.push(Assignment::new(&mem.iter().map(|i| integer_to_bytes(i.clone())).collect::<Vec<[u8; 32]>>()).unwrap());
-            addr_ts_bits_list.push(Assignment::new(&ts_bits.iter().map(|i| integer_to_bytes(i.clone())).collect::<Vec<[u8; 32]>>()).unwrap());
-        } else {
-            vir_mem_last = mem;
-            ts_bits_last = ts_bits;
+    let mut vir_mem_builder = VirMemBuilder::new(ctk.max_ts_width);
+    for m in &vir_mem_list {
+        let next = vir_mem_builder.push(
+            m[0].as_integer().unwrap(), 
+            m[1].as_integer().unwrap(), 
+            m[2].as_integer().unwrap(), 
+            m[3].as_integer().unwrap()
+        );
+        if let Some((mem, ts_bits)) = next {
+            addr_vir_mems_list.push(mem);
+            addr_ts_bits_list.push(ts_bits);
         }
     }
+    if let Some((mem, ts_bits)) = vir_mem_builder.finish() {
+        addr_vir_mems_list.push(mem);
+        addr_ts_bits_list.push(ts_bits);
+    }
 
     // Fold entry_arrays
     let mem_time = mem_start.elapsed();
@@ -1106,213 +1700,555 @@ fn get_run_time_knowledge<const VERBOSE: bool>(
     }
 }
 
-fn run_spartan_proof(ctk: CompileTimeKnowledge, rtk: RunTimeKnowledge) {
-  // --
-  // INSTANCE PREPROCESSING
-  // --
-  println!("Preprocessing instances...");
-  let preprocess_start = Instant::now();
-  let block_num_instances_bound = ctk.block_num_instances;
-  let num_vars = ctk.num_vars;
-  // num_inputs_unpadded is the actual size of the input
-  let num_inputs_unpadded = ctk.num_inputs_unpadded;
-  // num_ios is the width used by all input related computations
-  let num_ios = (num_inputs_unpadded * 2).next_power_of_two();
-  let block_num_phy_ops = ctk.block_num_phy_ops;
-  let block_num_vir_ops = ctk.block_num_vir_ops;
-  let max_block_num_phy_ops = *block_num_phy_ops.iter().max().unwrap();
-  let max_block_num_vir_ops = *block_num_vir_ops.iter().max().unwrap();
-
-  let mem_addr_ts_bits_size = (2 + ctk.max_ts_width).next_power_of_two();
-
-  assert_eq!(num_vars, num_vars.next_power_of_two());
-  assert!(ctk.args.len() == block_num_instances_bound);
-  assert!(block_num_phy_ops.len() == block_num_instances_bound);
-  // If output_block_num < block_num_instances, the prover can cheat by executing the program multiple times
-  assert!(ctk.output_block_num >= block_num_instances_bound);
-
-  println!("Generating Circuits...");
-  // --
-  // BLOCK INSTANCES
-  let (block_num_vars, block_num_cons, block_num_non_zero_entries, mut block_inst) = Instance::gen_block_inst::<true>(
-    block_num_instances_bound, 
-    num_vars, 
-    &ctk.args,
-    num_inputs_unpadded,
-    &block_num_phy_ops,
-    &block_num_vir_ops,
-    &ctk.num_vars_per_block,
-    &rtk.block_num_proofs,
-  );
-  println!("Finished Block");
-
-  // Pairwise INSTANCES
-  // CONSIS_CHECK & PHY_MEM_COHERE
-  let (pairwise_check_num_vars, pairwise_check_num_cons, pairwise_check_num_non_zero_entries, mut pairwise_check_inst) = Instance::gen_pairwise_check_inst::<true>(
-    ctk.max_ts_width, 
-    mem_addr_ts_bits_size,
-    rtk.consis_num_proofs,
-    rtk.total_num_phy_mem_accesses,
-    rtk.total_num_vir_mem_accesses,
-  );
-  println!("Finished Pairwise");
-
-  // PERM INSTANCES
-  // PERM_ROOT
-  let (perm_root_num_cons, perm_root_num_non_zero_entries, perm_root_inst) = Instance::gen_perm_root_inst::<true>(
-    num_inputs_unpadded, 
-    num_ios,
-    rtk.consis_num_proofs,
-    rtk.total_num_phy_mem_accesses,
-    rtk.total_num_vir_mem_accesses,
-  );
-  println!("Finished Perm");
-
-  // --
-  // COMMITMENT PREPROCESSING
-  // --
-  println!("Producing Public Parameters...");
-  // produce public parameters
-  let block_gens = SNARKGens::new(block_num_cons, block_num_vars, block_num_instances_bound, block_num_non_zero_entries);
-  let pairwise_check_gens = SNARKGens::new(pairwise_check_num_cons, 4 * pairwise_check_num_vars, 3, pairwise_check_num_non_zero_entries);
-  let perm_root_gens = SNARKGens::new(perm_root_num_cons, 8 * num_ios, 1, perm_root_num_non_zero_entries);
-  // Only use one version of gens_r1cs_sat
-  let vars_gens = SNARKGens::new(block_num_cons, TOTAL_NUM_VARS_BOUND, block_num_instances_bound.next_power_of_two(), block_num_non_zero_entries).gens_r1cs_sat;
-  
-  // create a commitment to the R1CS instance
-  println!("Comitting Circuits...");
-  // block_comm_map records the sparse_polys committed in each commitment
-  // Note that A, B, C are committed separately, so sparse_poly[3*i+2] corresponds to poly C of instance i
-  let (block_comm_map, block_comm_list, block_decomm_list) = SNARK::multi_encode(&block_inst, &block_gens);
-  println!("Finished Block");
-  let (pairwise_check_comm, pairwise_check_decomm) = SNARK::encode(&pairwise_check_inst, &pairwise_check_gens);
-  println!("Finished Pairwise");
-  let (perm_root_comm, perm_root_decomm) = SNARK::encode(&perm_root_inst, &perm_root_gens);
-  println!("Finished Perm");
-
-  // --
-  // WITNESS PREPROCESSING
-  // --
-  let block_num_proofs = rtk.block_num_proofs;
-  let block_vars_matrix = rtk.block_vars_matrix;
-
-  assert!(block_num_proofs.len() <= block_num_instances_bound);
-  assert!(block_vars_matrix.len() <= block_num_instances_bound);
-  let preprocess_time = preprocess_start.elapsed();
-  println!("Preprocess time: {}ms", preprocess_time.as_millis());
-
-  println!("Running the proof...");
-  // produce a proof of satisfiability
-  let mut prover_transcript = Transcript::new(b"snark_example");
-  let proof = SNARK::prove(
-    ctk.input_block_num,
-    ctk.output_block_num,
-    &ctk.input_liveness,
-    ctk.func_input_width,
-    ctk.input_offset,
-    ctk.output_offset,
-    &rtk.input,
-    &rtk.output,
-    rtk.output_exec_num,
-    
-    num_vars,
-    num_ios,
-    max_block_num_phy_ops,
-    &block_num_phy_ops,
-    max_block_num_vir_ops,
-    &block_num_vir_ops,
-    mem_addr_ts_bits_size,
-    num_inputs_unpadded,
-    &ctk.num_vars_per_block,
-
-    block_num_instances_bound,
-    rtk.block_max_num_proofs,
-    &block_num_proofs,
-    &mut block_inst,
-    &block_comm_map,
-    &block_comm_list,
-    &block_decomm_list,
-    &block_gens,
-    
-    rtk.consis_num_proofs,
-    rtk.total_num_init_phy_mem_accesses,
-    rtk.total_num_init_vir_mem_accesses,
-    rtk.total_num_phy_mem_accesses,
-    rtk.total_num_vir_mem_accesses,
-    &mut pairwise_check_inst,
-    &pairwise_check_comm,
-    &pairwise_check_decomm,
-    &pairwise_check_gens,
-
-    block_vars_matrix,
-    rtk.exec_inputs,
-    rtk.init_phy_mems_list,
-    rtk.init_vir_mems_list,
-    rtk.addr_phy_mems_list,
-    rtk.addr_vir_mems_list,
-    rtk.addr_ts_bits_list,
-
-    &perm_root_inst,
-    &perm_root_comm,
-    &perm_root_decomm,
-    &perm_root_gens,
-
-    &vars_gens,
-    &mut prover_transcript,
-  );
-
-  println!("Verifying the proof...");
-  // verify the proof of satisfiability
-  let mut verifier_transcript = Transcript::new(b"snark_example");
-  assert!(proof.verify(
-    ctk.input_block_num,
-    ctk.output_block_num,
-    &ctk.input_liveness,
-    ctk.func_input_width,
-    ctk.input_offset,
-    ctk.output_offset,
-    &rtk.input,
-    &rtk.input_stack,
-    &rtk.input_mem,
-    &rtk.output,
-    rtk.output_exec_num,
-
-    num_vars,
-    num_ios,
-    max_block_num_phy_ops,
-    &block_num_phy_ops,
-    max_block_num_vir_ops,
-    &block_num_vir_ops,
-    mem_addr_ts_bits_size,
-    num_inputs_unpadded,
-    &ctk.num_vars_per_block,
-    
-    block_num_instances_bound, 
-    rtk.block_max_num_proofs, 
-    &block_num_proofs, 
-    block_num_cons,
-    &block_comm_map,
-    &block_comm_list,
-    &block_gens,
-
-    rtk.consis_num_proofs, 
-    rtk.total_num_init_phy_mem_accesses,
-    rtk.total_num_init_vir_mem_accesses,
-    rtk.total_num_phy_mem_accesses,
-    rtk.total_num_vir_mem_accesses,
-    pairwise_check_num_cons,
-    &pairwise_check_comm,
-    &pairwise_check_gens,
-
-    perm_root_num_cons,
-    &perm_root_comm,
-    &perm_root_gens,
-
-    &vars_gens,
-    &mut verifier_transcript
-  ).is_ok());
-  println!("proof verification successful!");
-}  
+// --
+// Spill directory
+// --
+// A directory in the system temp dir that is removed with everything in it when dropped,
+// so spill files do not outlive a failed witness generation
+struct SpillDir(PathBuf);
+
+impl SpillDir {
+    fn create(name: &str) -> std::io::Result<SpillDir> {
+        let dir = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+        create_dir_all(&dir)?;
+        Ok(SpillDir(dir))
+    }
+}
+
+impl Drop for SpillDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+// --
+// External merge sort
+// --
+// Items are sorted in memory in chunks of chunk_size, each sorted chunk is spilled to disk as a run
+// The runs are then merged with a k-way merge
+struct ExtSorter<T> {
+    dir: PathBuf,
+    tag: &'static str,
+    chunk_size: usize,
+    buf: Vec<T>,
+    // (file, # of items) of every run
+    runs: Vec<(PathBuf, usize)>,
+}
+
+impl<T: Ord + Serialize + DeserializeOwned> ExtSorter<T> {
+    fn new(dir: &PathBuf, tag: &'static str, chunk_size: usize) -> ExtSorter<T> {
+        ExtSorter {
+            dir: dir.clone(),
+            tag,
+            chunk_size: chunk_size.max(1),
+            buf: Vec::new(),
+            runs: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, item: T) -> std::io::Result<()> {
+        self.buf.push(item);
+        if self.buf.len() >= self.chunk_size {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    fn spill(&mut self) -> std::io::Result<()> {
+        if self.buf.len() == 0 {
+            return Ok(());
+        }
+        self.buf.sort();
+        let file_name = self.dir.join(format!("{}_{}.run", self.tag, self.runs.len()));
+        let mut f = BufWriter::new(File::create(&file_name)?);
+        for item in &self.buf {
+            bincode::serialize_into(&mut f, item).unwrap();
+        }
+        f.flush()?;
+        self.runs.push((file_name, self.buf.len()));
+        self.buf.clear();
+        Ok(())
+    }
+
+    fn into_sorted(mut self) -> std::io::Result<ExtSortIter<T>> {
+        self.spill()?;
+        let mut readers = Vec::new();
+        let mut heap = BinaryHeap::new();
+        for (file_name, len) in &self.runs {
+            let mut r = BufReader::new(File::open(file_name)?);
+            let item: T = bincode::deserialize_from(&mut r).unwrap();
+            heap.push(Reverse((item, readers.len())));
+            readers.push((r, len - 1));
+        }
+        Ok(ExtSortIter { readers, heap })
+    }
+}
+
+struct ExtSortIter<T> {
+    // (reader, # of items left) of every run
+    readers: Vec<(BufReader<File>, usize)>,
+    heap: BinaryHeap<Reverse<(T, usize)>>,
+}
+
+impl<T: Ord + DeserializeOwned> Iterator for ExtSortIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let Reverse((item, run)) = self.heap.pop()?;
+        let (r, left) = &mut self.readers[run];
+        if *left > 0 {
+            *left -= 1;
+            let next: T = bincode::deserialize_from(r).unwrap();
+            self.heap.push(Reverse((next, run)));
+        }
+        Some(item)
+    }
+}
+
+// Copy a spill file of bincode-encoded items into f as a bincode-encoded Vec
+fn copy_spill_as_vec(f: &mut BufWriter<File>, spill: &PathBuf, len: usize) -> std::io::Result<()> {
+    bincode::serialize_into(&mut *f, &(len as u64)).unwrap();
+    std::io::copy(&mut File::open(spill)?, f)?;
+    Ok(())
+}
+
+// --
+// Generate witnesses and stream them to file
+// --
+// Produces the same RTK file as get_run_time_knowledge + serialize_to_file, without keeping
+// block executions and memory accesses in memory:
+// block executions are spilled per block, memory accesses are sorted with ExtSorter,
+// and RunTimeKnowledge is written to file field by field
+fn stream_run_time_knowledge<const VERBOSE: bool, A: RtkAssignment>(
+    path: PathBuf,
+    options: &Options,
+    rtk_file_name: &PathBuf,
+    mut entry_regs: BTreeMap<String, Integer>,
+    entry_stacks: BTreeMap<String, Vec<Integer>>,
+    entry_arrays: BTreeMap<String, Vec<Integer>>,
+    entry_witnesses: Vec<Integer>,
+    entry_regs_concat: Vec<Integer>,
+    entry_stacks_concat: Vec<Integer>,
+    entry_arrays_concat: Vec<Integer>,
+    ctk: &CompileTimeKnowledge,
+    live_io_size: Vec<usize>,
+    live_mem_size: Vec<usize>,
+    prover_data_list: Vec<ProverData>,
+    total_num_init_phy_mem_accesses: usize,
+    total_num_init_vir_mem_accesses: usize,
+    stats: &mut CompileStats,
+) -> std::io::Result<()> {
+    let interpret_start = Instant::now();
+
+    let num_blocks = ctk.block_num_instances;
+    let num_input_unpadded = ctk.num_inputs_unpadded;
+    let io_width = 2 * num_input_unpadded;
+
+    let spill_guard = SpillDir::create("zxc_witness")?;
+    let spill_dir = &spill_guard.0;
+    let block_spill_name = |id: usize| spill_dir.join(format!("block_{}.vars", id));
+    let exec_inputs_spill_name = spill_dir.join("exec_inputs");
+
+    // Executions of each block are written to its own spill file, in execution order
+    let mut block_spill_list: Vec<Option<BufWriter<File>>> = (0..num_blocks).map(|_| None).collect();
+    let mut exec_inputs_spill = BufWriter::new(File::create(&exec_inputs_spill_name)?);
+    // Physical memory accesses are sorted by address, virtual memory accesses by (address, timestamp)
+    // Ties are broken by origin (initial memory first) and then by order of appearance
+    let mut phy_mem_sorter: ExtSorter<((usize, usize, usize), [[u8; 32]; 2])> = 
+        ExtSorter::new(spill_dir, "phy", options.stream_chunk_size);
+    let mut vir_mem_sorter: ExtSorter<((usize, usize, usize, usize), [[u8; 32]; 4])> = 
+        ExtSorter::new(spill_dir, "vir", options.stream_chunk_size);
+    let mut phy_mem_count = 0;
+    let mut vir_mem_count = 0;
+
+    // Meta info
+    let mut block_max_num_proofs = 0;
+    let mut block_num_proofs = vec![0; num_blocks];
+    let mut consis_num_proofs = 0;
+    let mut func_outputs = Integer::from(0);
+
+    let mut exec_sink = |exec: BlockExecution| {
+        let id = exec.blk_id;
+        let vars = gen_block_vars::<VERBOSE>(
+            id,
+            exec.io_map,
+            &exec.reg_in,
+            &exec.reg_out,
+            &exec.mems,
+            ctk,
+            &live_io_size,
+            &live_mem_size,
+            &prover_data_list[id].precompute,
+        );
+        // The output of the program is the output of the last block execution
+        func_outputs = vars[num_input_unpadded + OUTPUT_OFFSET].clone();
+        let (inputs_assignment, vars_assignment): (A, A) = gen_block_assignments(&vars, io_width);
+        bincode::serialize_into(&mut exec_inputs_spill, &inputs_assignment).unwrap();
+        if block_spill_list[id].is_none() {
+            block_spill_list[id] = Some(BufWriter::new(File::create(block_spill_name(id)).unwrap()));
+        }
+        bincode::serialize_into(block_spill_list[id].as_mut().unwrap(), &vars_assignment).unwrap();
+
+        block_num_proofs[id] += 1;
+        if block_num_proofs[id] > block_max_num_proofs {
+            block_max_num_proofs = block_num_proofs[id];
+        }
+        consis_num_proofs += 1;
+
+        let lookup_ops = gen_lookup_ops(id, &vars, ctk, exec.mems.len());
+        let phy_mem_ops = exec.phy_mem_op.iter().map(|(addr, data)| (addr.as_integer().unwrap(), data.as_integer().unwrap()));
+        for (addr, data) in phy_mem_ops.chain(lookup_ops) {
+            let key = (addr.to_usize().unwrap(), 1, phy_mem_count);
+            phy_mem_sorter.push((key, [integer_to_bytes(addr), integer_to_bytes(data)])).unwrap();
+            phy_mem_count += 1;
+        }
+        for m in exec.vir_mem_op {
+            let key = (m[0].as_integer().unwrap().to_usize().unwrap(), m[3].as_integer().unwrap().to_usize().unwrap(), 1, vir_mem_count);
+            vir_mem_sorter.push((key, m.map(|v| integer_to_bytes(v.as_integer().unwrap())))).unwrap();
+            vir_mem_count += 1;
+        }
+    };
+    let (_, _, init_phy_mem_list, init_vir_mem_list) = {
+        let inputs = zsharp::Inputs {
+            file: path,
+            mode: Mode::Proof,
+            opt_level: options.opt_level,
+            verbose_opt: options.verbose_opt
+        };
+
+        // Returning the error removes the spill directory
+        ZSharpFE::interpret_stream(inputs, &mut entry_regs, &entry_stacks, &entry_arrays, &entry_witnesses, &mut exec_sink)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?
+    };
+    exec_inputs_spill.flush()?;
+    for bs in block_spill_list.iter_mut().flatten() {
+        bs.flush()?;
+    }
+    drop(block_spill_list);
+    // Initial memory accesses precede all accesses to the same address (and timestamp)
+    for (i, m) in init_phy_mem_list.iter().enumerate() {
+        let key = (m[0].as_integer().unwrap().to_usize().unwrap(), 0, i);
+        phy_mem_sorter.push((key, [
+            integer_to_bytes(m[0].as_integer().unwrap()), 
+            integer_to_bytes(m[1].as_integer().unwrap())
+        ]))?;
+    }
+    for (i, m) in init_vir_mem_list.iter().enumerate() {
+        let key = (m[0].as_integer().unwrap().to_usize().unwrap(), m[3].as_integer().unwrap().to_usize().unwrap(), 0, i);
+        vir_mem_sorter.push((key, m.clone().map(|v| integer_to_bytes(v.as_integer().unwrap()))))?;
+    }
+    let interpret_time = interpret_start.elapsed();
+    println!("\n--\nInterpret + block gen time: {}ms", interpret_time.as_millis());
+
+    let mem_start = Instant::now();
+    let total_num_phy_mem_accesses = init_phy_mem_list.len() + phy_mem_count;
+    let total_num_vir_mem_accesses = init_vir_mem_list.len() + vir_mem_count;
+    let output_exec_num = consis_num_proofs - 1;
+
+    // num_blocks_live is # of non-zero entries in block_num_proofs
+    let num_blocks_live = block_num_proofs.iter().fold(0, |i, j| if *j > 0 { i + 1 } else { i });
+    // Sort blocks by number of execution
+    let mut inst_sorter = Vec::new();
+    for i in 0..num_blocks {
+      inst_sorter.push(InstanceSortHelper::new(block_num_proofs[i], i))
+    }
+    // Sort from high -> low
+    inst_sorter.sort_by(|a, b| b.cmp(a));
+
+    // Initial Physical & Virtual Memory: valid, _, addr, data (ts and ls are both 0 and are not recorded)
+    let init_mems_list = |init_mem_list: Vec<Vec<Value>>| -> Vec<A> {
+        init_mem_list.iter().map(|m| {
+            let mut mem: Vec<Integer> = vec![Integer::from(0); 4];
+            mem[0] = Integer::from(1);
+            mem[2] = m[0].as_integer().unwrap();
+            mem[3] = m[1].as_integer().unwrap();
+            integers_to_assignment(&mem)
+        }).collect()
+    };
+    let init_phy_mems_list = init_mems_list(init_phy_mem_list.into_iter().map(|m| m.to_vec()).collect());
+    let init_vir_mems_list = init_mems_list(init_vir_mem_list.into_iter().map(|m| m.to_vec()).collect());
+
+    // Write RTK to file, in the field order of RunTimeKnowledge
+    let mut f = BufWriter::new(File::create(rtk_file_name)?);
+    stats.record_run_time(
+        &block_num_proofs,
+        block_max_num_proofs,
+        consis_num_proofs,
+        total_num_init_phy_mem_accesses,
+        total_num_init_vir_mem_accesses,
+        total_num_phy_mem_accesses,
+        total_num_vir_mem_accesses,
+    );
+    bincode::serialize_into(&mut f, &block_max_num_proofs).unwrap();
+    bincode::serialize_into(&mut f, &block_num_proofs).unwrap();
+    bincode::serialize_into(&mut f, &consis_num_proofs).unwrap();
+    bincode::serialize_into(&mut f, &total_num_init_phy_mem_accesses).unwrap();
+    bincode::serialize_into(&mut f, &total_num_init_vir_mem_accesses).unwrap();
+    bincode::serialize_into(&mut f, &total_num_phy_mem_accesses).unwrap();
+    bincode::serialize_into(&mut f, &total_num_vir_mem_accesses).unwrap();
+    // block_vars_matrix: sorted by block_num_proofs, tie-breaked by block id
+    bincode::serialize_into(&mut f, &(num_blocks_live as u64)).unwrap();
+    for slot in 0..num_blocks_live {
+        let id = inst_sorter[slot].index;
+        copy_spill_as_vec(&mut f, &block_spill_name(id), block_num_proofs[id])?;
+    }
+    copy_spill_as_vec(&mut f, &exec_inputs_spill_name, consis_num_proofs)?;
+    bincode::serialize_into(&mut f, &init_phy_mems_list).unwrap();
+    bincode::serialize_into(&mut f, &init_vir_mems_list).unwrap();
+
+    // Physical Memory: valid, D, addr, data
+    bincode::serialize_into(&mut f, &(total_num_phy_mem_accesses as u64)).unwrap();
+    let mut phy_mem_builder = PhyMemBuilder::new();
+    for (_, m) in phy_mem_sorter.into_sorted()? {
+        if let Some(mem) = phy_mem_builder.push::<A>(bytes_to_integer(&m[0]), bytes_to_integer(&m[1])) {
+            bincode::serialize_into(&mut f, &mem).unwrap();
+        }
+    }
+    if let Some(mem) = phy_mem_builder.finish::<A>() {
+        bincode::serialize_into(&mut f, &mem).unwrap();
+    }
+
+    // Virtual Memory: valid, D1, addr, data, ls, ts, _, _
+    // TS Bits are spilled and copied after all virtual memory accesses
+    let ts_bits_spill_name = spill_dir.join("ts_bits");
+    let mut ts_bits_spill = BufWriter::new(File::create(&ts_bits_spill_name)?);
+    bincode::serialize_into(&mut f, &(total_num_vir_mem_accesses as u64)).unwrap();
+    let mut vir_mem_builder = VirMemBuilder::new(ctk.max_ts_width);
+    for (_, m) in vir_mem_sorter.into_sorted()? {
+        let next = vir_mem_builder.push::<A>(
+            bytes_to_integer(&m[0]), 
+            bytes_to_integer(&m[1]), 
+            bytes_to_integer(&m[2]), 
+            bytes_to_integer(&m[3])
+        );
+        if let Some((mem, ts_bits)) = next {
+            bincode::serialize_into(&mut f, &mem).unwrap();
+            bincode::serialize_into(&mut ts_bits_spill, &ts_bits).unwrap();
+        }
+    }
+    if let Some((mem, ts_bits)) = vir_mem_builder.finish::<A>() {
+        bincode::serialize_into(&mut f, &mem).unwrap();
+        bincode::serialize_into(&mut ts_bits_spill, &ts_bits).unwrap();
+    }
+    ts_bits_spill.flush()?;
+    drop(ts_bits_spill);
+    copy_spill_as_vec(&mut f, &ts_bits_spill_name, total_num_vir_mem_accesses)?;
+    let mem_time = mem_start.elapsed();
+    println!("\n--\nMem gen time: {}ms", mem_time.as_millis());
+
+    println!("\n--\nFUNC");
+    println!("{:3} {:?}", "I", entry_regs_concat);
+    print!("{:3} ", "O");
+    println!("{:3} ", func_outputs);
+
+    let func_inputs: Vec<[u8; 32]> = entry_regs_concat.iter().map(|i| integer_to_bytes(i.clone())).collect();
+    let input_stack: Vec<[u8; 32]> = entry_stacks_concat.iter().map(|i| integer_to_bytes(i.clone())).collect();
+    let input_mem: Vec<[u8; 32]> = entry_arrays_concat.iter().map(|i| integer_to_bytes(i.clone())).collect();
+    bincode::serialize_into(&mut f, &func_inputs).unwrap();
+    bincode::serialize_into(&mut f, &input_stack).unwrap();
+    bincode::serialize_into(&mut f, &input_mem).unwrap();
+    bincode::serialize_into(&mut f, &integer_to_bytes(func_outputs)).unwrap();
+    bincode::serialize_into(&mut f, &output_exec_num).unwrap();
+    f.flush()?;
+    Ok(())
+}
+
+// The number of executions every block is padded to when generating the witnesses, None outside of zk mode
+fn zk_exec_bound<P: BlockProofSystem>(options: &Options) -> Result<Option<usize>, String> {
+    if options.proof_mode == ProofMode::NonZk {
+        return Ok(None);
+    }
+    if !P::ZERO_KNOWLEDGE {
+        return Err("The block backend does not hide the witnesses, and cannot be used with --proof_mode zk".to_string());
+    }
+    if options.stream_witness {
+        return Err("--stream_witness cannot be used with --proof_mode zk".to_string());
+    }
+    match options.zk_exec_bound {
+        Some(bound) => Ok(Some(bound)),
+        None => Err("--proof_mode zk requires --zk_exec_bound".to_string()),
+    }
+}
+
+// The program, inputs and witnesses of a benchmark are <benchmark>.zok, <benchmark>.input and <benchmark>.witness
+fn benchmark_file_name(benchmark_name: &str, extension: &str) -> PathBuf {
+    PathBuf::from(format!("../zok_tests/benchmarks/{}.{}", benchmark_name, extension))
+}
+
+// Draw the seeds of the challenges from the instance, and provide them to the interpreter as the read-only input %CH
+fn fill_challenges(
+    ctk: &CompileTimeKnowledge,
+    entry_stacks: &mut BTreeMap<String, Vec<Integer>>,
+    entry_regs_concat: &Vec<Integer>,
+    entry_stacks_concat: &mut Vec<Integer>,
+    entry_arrays_concat: &Vec<Integer>,
+) {
+    if ctk.num_challenges == 0 {
+        return;
+    }
+    let to_bytes = |v: &Vec<Integer>| -> Vec<[u8; 32]> { v.iter().map(|i| integer_to_bytes(i.clone())).collect() };
+    let reserved = ReservedMemory::of(ctk);
+    let (_, challenges) = reserved.transcript(
+        &public_input(&to_bytes(entry_regs_concat), &ctk.input_liveness),
+        &to_bytes(entry_stacks_concat),
+        &to_bytes(entry_arrays_concat),
+    );
+    entry_stacks_concat.splice(reserved.challenge_addr()..reserved.size(), challenges.iter().cloned());
+    entry_stacks.insert("%CH".to_string(), challenges);
+}
+
+// --
+// Memory trace export
+// --
+fn write_mem_trace(
+    benchmark_name: &str,
+    format: &TraceFormat,
+    trace: &Vec<MemTraceEntry>,
+    alloc_list: &Vec<MemTraceAlloc>,
+) -> std::io::Result<()> {
+    create_dir_all("../zok_tests/traces")?;
+    match format {
+        TraceFormat::Csv => {
+            let file_name = format!("../zok_tests/traces/{}.csv", benchmark_name);
+            let mut f = File::create(&file_name)?;
+            writeln!(&mut f, "exec_num,blk_id,mem,addr,data,op,ts")?;
+            for m in trace {
+                writeln!(&mut f, "{},{},{},{},{},{},{}",
+                    m.exec_num.map_or("init".to_string(), |i| i.to_string()),
+                    m.blk_id.map_or("".to_string(), |i| i.to_string()),
+                    if m.phy { "phy" } else { "vir" },
+                    m.addr,
+                    m.data,
+                    match m.store { Some(true) => "store", Some(false) => "load", None => "" },
+                    m.ts.map_or("".to_string(), |i| i.to_string()),
+                )?;
+            }
+            println!("Memory trace written to {}", file_name);
+        }
+        TraceFormat::Json => {
+            let file_name = format!("../zok_tests/traces/{}.json", benchmark_name);
+            let f = File::create(&file_name)?;
+            serde_json::to_writer_pretty(f, &serde_json::json!({
+                "allocs": alloc_list,
+                "trace": trace,
+            }))?;
+            println!("Memory trace written to {}", file_name);
+        }
+    }
+    Ok(())
+}
+
+// Print the most accessed addresses and the number of accesses to each array
+// Initial memory states are not counted as accesses
+fn print_mem_trace_summary(trace: &Vec<MemTraceEntry>, alloc_list: &Vec<MemTraceAlloc>) {
+    const NUM_HOT_ADDRS: usize = 10;
+
+    // Index allocations by (phy, starting address), addresses are never reused
+    let mut alloc_map: BTreeMap<(bool, usize), usize> = BTreeMap::new();
+    for (i, a) in alloc_list.iter().enumerate() {
+        if a.len > 0 {
+            alloc_map.insert((a.phy, a.addr), i);
+        }
+    }
+    // (num_loads, num_stores) of each allocation, the last entry records physical accesses outside of any array
+    let mut alloc_count = vec![(0, 0); alloc_list.len() + 1];
+    let mut addr_count: BTreeMap<(bool, usize), usize> = BTreeMap::new();
+    let mut num_phy_accesses = 0;
+    let mut num_vir_accesses = 0;
+    for m in trace.iter().filter(|m| m.exec_num.is_some()) {
+        if m.phy { num_phy_accesses += 1; } else { num_vir_accesses += 1; }
+        *addr_count.entry((m.phy, m.addr)).or_insert(0) += 1;
+        let alloc = alloc_map.range(..=(m.phy, m.addr)).next_back()
+            .filter(|((phy, addr), i)| *phy == m.phy && m.addr < addr + alloc_list[**i].len)
+            .map_or(alloc_list.len(), |(_, i)| *i);
+        if m.store == Some(true) {
+            alloc_count[alloc].1 += 1;
+        } else {
+            alloc_count[alloc].0 += 1;
+        }
+    }
+
+    println!("\n--\nMemory Trace Summary:");
+    println!("Physical memory accesses: {}", num_phy_accesses);
+    println!("Virtual memory accesses: {}", num_vir_accesses);
+
+    let mut hot_addrs: Vec<((bool, usize), usize)> = addr_count.into_iter().collect();
+    // Sort from high -> low, tie-broken by address
+    hot_addrs.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    println!("\nHot addresses:");
+    println!("{:>4} {:>10} {:>10}", "MEM", "ADDR", "ACCESSES");
+    for ((phy, addr), count) in hot_addrs.iter().take(NUM_HOT_ADDRS) {
+        println!("{:>4} {:>10} {:>10}", if *phy { "phy" } else { "vir" }, addr, count);
+    }
+
+    println!("\nArray accesses:");
+    println!("{:>4} {:>10} {:>8} {:>10} {:>10} {:>10}  {}", "MEM", "ADDR", "LEN", "LOADS", "STORES", "ALLOC_BL", "ARRAY");
+    for (i, a) in alloc_list.iter().enumerate() {
+        println!("{:>4} {:>10} {:>8} {:>10} {:>10} {:>10}  {}",
+            if a.phy { "phy" } else { "vir" },
+            a.addr,
+            a.len,
+            alloc_count[i].0,
+            alloc_count[i].1,
+            a.blk_id.map_or("input".to_string(), |b| b.to_string()),
+            a.name,
+        );
+    }
+    let (stack_loads, stack_stores) = alloc_count[alloc_list.len()];
+    if stack_loads + stack_stores > 0 {
+        println!("{:>4} {:>10} {:>8} {:>10} {:>10} {:>10}  {}", "phy", "", "", stack_loads, stack_stores, "", "%PHY stack");
+    }
+}
+
+// --
+// Constraint-cost profile
+// --
+fn print_cost_profile(profile: &CostProfile) {
+    const NUM_HOT_LINES: usize = 20;
+
+    println!("\n--\nConstraint Cost Profile:");
+    println!("Blocks executed: {}", profile.num_execs);
+    println!("Constraints: {}", profile.num_cons);
+    println!("Memory operations: {}", profile.num_mem_ops);
+
+    // Percentage of the total number of constraints
+    let pct = |num_cons: usize| if profile.num_cons == 0 { 0.0 } else { 100.0 * num_cons as f64 / profile.num_cons as f64 };
+    println!("\nHot lines:");
+    println!("{:>12} {:>6} {:>10} {:>10}  {}", "CONS", "%", "MEM_OPS", "EXECS", "LINE");
+    for l in profile.lines.iter().take(NUM_HOT_LINES) {
+        let line = match &l.path {
+            Some(path) => format!("{}:{}  {}", path.file_name().unwrap().to_string_lossy(), l.line, l.source),
+            None => "<generated>".to_string(),
+        };
+        println!("{:>12} {:>6.2} {:>10} {:>10}  {}", l.num_cons, pct(l.num_cons), l.num_mem_ops, l.num_execs, line);
+    }
+
+    println!("\nFunctions:");
+    println!("{:>12} {:>6} {:>10} {:>10}  {}", "CONS", "%", "MEM_OPS", "BLOCKS", "FUNCTION");
+    for f in &profile.functions {
+        println!("{:>12} {:>6.2} {:>10} {:>10}  {}", f.num_cons, pct(f.num_cons), f.num_mem_ops, f.num_execs, f.name);
+    }
+}
+
+// Load a CTK and an RTK, then check the block witnesses against the constraints, or export them to export_dir
+fn inspect_files<A: RtkAssignment>(ctk_path: &PathBuf, rtk_path: &PathBuf, export_dir: Option<PathBuf>) -> Result<(), String> {
+    let ctk: CompileTimeKnowledge = load_knowledge(ctk_path)?;
+    let rtk: RunTimeKnowledge<A> = load_knowledge(rtk_path)?;
+    inspect_knowledge(&ctk, &rtk)
+        .map_err(|e| format!("{} and {} are inconsistent: {}", ctk_path.display(), rtk_path.display(), e))?;
+    if let Some(export_dir) = export_dir {
+        export_circom(&ctk, &rtk, &export_dir).map_err(|e| format!("{}: {}", export_dir.display(), e))?;
+        println!("Exported {} blocks to {}", ctk.block_num_instances, export_dir.display());
+        return Ok(());
+    }
+    let check = check_block_witnesses(&ctk, &rtk);
+    print_witness_check(&check);
+    if check.num_failures > 0 {
+        return Err(format!("{} and {} are inconsistent.", ctk_path.display(), rtk_path.display()));
+    }
+    println!("{} and {} are consistent.", ctk_path.display(), rtk_path.display());
+    Ok(())
+}
 
 fn main() {
     env_logger::Builder::from_default_env()
@@ -1322,131 +2258,705 @@ fn main() {
     let options = Options::parse();
     circ::cfg::set(&options.circ);
     println!("{options:?}");
+    match options.backend {
+        BlockBackend::Spartan => run_zxc::<BlockSpartan>(options),
+        #[cfg(feature = "bellman")]
+        BlockBackend::Groth16 => run_zxc::<BlockGroth16>(options),
+        #[cfg(feature = "bellman")]
+        BlockBackend::Folding => run_zxc::<BlockFolding>(options),
+        #[cfg(not(feature = "bellman"))]
+        BlockBackend::Groth16 => panic!("The Groth16 backend requires the bellman feature"),
+        #[cfg(not(feature = "bellman"))]
+        BlockBackend::Folding => panic!("The folding backend requires the bellman feature"),
+    }
+}
 
+// Compile the program and generate its witnesses, or run a single action, with the block proof system P
+fn run_zxc<P: BlockProofSystem>(options: Options) {
     // --
     // Generate Constraints
     // --
     let compiler_start = Instant::now();
     let benchmark_name = options.path.as_os_str().to_str().unwrap();
-    let path = PathBuf::from(format!("../zok_tests/benchmarks/{}.zok", benchmark_name));
+    let path = benchmark_file_name(benchmark_name, "zok");
+
+    if options.action == ProofAction::MemTrace || options.action == ProofAction::Profile {
+        // The interpreter needs the seeds of the challenges, which are drawn from the instance described by the CTK
+        let (ctk, _, _, _) = get_compile_time_knowledge::<false>(path.clone(), &options, &mut CompileStats::default());
+        let (
+            mut entry_regs,
+            mut entry_stacks,
+            entry_arrays,
+            entry_regs_concat,
+            mut entry_stacks_concat,
+            entry_arrays_concat,
+            _,
+            _,
+        ) = zx::read_inputs(&benchmark_file_name(benchmark_name, "input"), ctk.num_challenges);
+        fill_challenges(&ctk, &mut entry_stacks, &entry_regs_concat, &mut entry_stacks_concat, &entry_arrays_concat);
+        let entry_witnesses = zx::read_witnesses(&benchmark_file_name(benchmark_name, "witness"));
+        let inputs = zsharp::Inputs {
+            file: path,
+            mode: Mode::Proof,
+            opt_level: options.opt_level,
+            verbose_opt: options.verbose_opt
+        };
+        if options.action == ProofAction::MemTrace {
+            let (trace, alloc_list) = exit_on_error(ZSharpFE::mem_trace(inputs, &mut entry_regs, &entry_stacks, &entry_arrays, &entry_witnesses));
+            write_mem_trace(benchmark_name, &options.trace_format, &trace, &alloc_list).unwrap();
+            print_mem_trace_summary(&trace, &alloc_list);
+        } else {
+            let profile = exit_on_error(ZSharpFE::profile(inputs, &mut entry_regs, &entry_stacks, &entry_arrays, &entry_witnesses));
+            print_cost_profile(&profile);
+        }
+        return;
+    }
+    let mut stats = CompileStats {
+        benchmark: benchmark_name.to_string(),
+        opt_level: options.opt_level,
+        ..Default::default()
+    };
+    let ctk_path = options.ctk.clone().unwrap_or_else(|| CompileTimeKnowledge::file_name(benchmark_name, "_bin.ctk"));
+    let rtk_path = options.rtk.clone().unwrap_or_else(|| RunTimeKnowledge::<P::Assignment>::file_name(benchmark_name, "_bin.rtk"));
+    if options.action == ProofAction::Setup {
+        exit_on_error(P::setup_fs(&options.proof_mode, &ctk_path, &rtk_path, options.shape.as_ref(), &options.prover_key, &options.verifier_key));
+        return;
+    }
+    if options.action == ProofAction::Prove {
+        exit_on_error(P::prove_fs(&options.proof_mode, &ctk_path, &options.prover_key, &rtk_path, &options.proof, &options.instance));
+        return;
+    }
+    if options.action == ProofAction::Verify {
+        if !exit_on_error(P::verify_fs(&options.verifier_key, &options.instance, &options.proof)) {
+            eprintln!("proof verification failed!");
+            std::process::exit(1);
+        }
+        println!("proof verification successful!");
+        return;
+    }
+    if options.action == ProofAction::Inspect || options.action == ProofAction::Export {
+        let export_dir = options.export_dir.clone().unwrap_or_else(|| PathBuf::from(format!("../zok_tests/r1cs/{}", benchmark_name)));
+        let export_dir = if options.action == ProofAction::Export { Some(export_dir) } else { None };
+        if let Err(e) = inspect_files::<P::Assignment>(&ctk_path, &rtk_path, export_dir) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    let zk_exec_bound = exit_on_error(zk_exec_bound::<P>(&options));
     let (ctk, live_io_size, live_mem_size, prover_data_list) = 
-        get_compile_time_knowledge::<false>(path.clone(), &options);
+        get_compile_time_knowledge::<false>(path.clone(), &options, &mut stats);
     let compiler_time = compiler_start.elapsed();
 
     // --
     // Obtain Inputs
     // --
     let witness_start = Instant::now();
-    // Keep track of %SP and %AS and record initial memory state
-    let mut stack_alloc_counter = 0;
-    let mut mem_alloc_counter = 0;
-    // Inputs as register, stack, or array
-    // Note that stacks and arrays cans be passed as pointers (registers)
-    let mut entry_regs: BTreeMap<String, Integer> = BTreeMap::new();
-    let mut entry_stacks: BTreeMap<String, Vec<Integer>> = BTreeMap::new(); // for read-only
-    let mut entry_arrays: BTreeMap<String, Vec<Integer>> = BTreeMap::new(); // for others
-    // Initial memory setup
-    let mut entry_regs_concat: Vec<Integer> = Vec::new();
-    let mut entry_stacks_concat: Vec<Integer> = Vec::new();
-    let mut entry_arrays_concat: Vec<Integer> = Vec::new();
-
-    let input_file_name = format!("../zok_tests/benchmarks/{}.input", benchmark_name);
-    let f = File::open(input_file_name);
-    if let Ok(f) = f {
-        let mut reader = BufReader::new(f);
-        let mut buffer = String::new();
-        reader.read_line(&mut buffer).unwrap();
-        while buffer.trim() != "END".to_string() {
-        let split: Vec<String> = buffer.split(' ').map(|i| i.to_string().trim().to_string()).collect();
-        let var_name = split[0].split(":").next().unwrap().trim();
-        // split is either of form [VAR, VAL] or [VAR, "[", ENTRY_0, ENTRY_1, ..., "]"] 
-        if let Ok(val) = Integer::from_str_radix(&split[1], 10) {
-            entry_regs.insert(var_name.to_string(), val.clone());
-            entry_regs_concat.push(val);
-        } else if split[1] == "[ro" {
-            assert_eq!(split[split.len() - 1], "]");
-            entry_regs.insert(var_name.to_string(), Integer::from(stack_alloc_counter));
-            entry_regs_concat.push(Integer::from(stack_alloc_counter));
-            // Parse the entries
-            let stack_entries: Vec<Integer> = split[2..split.len() - 1].iter().map(|entry| Integer::from_str_radix(&entry, 10).unwrap()).collect();
-            entry_stacks.insert(var_name.to_string(), stack_entries.clone());
-            entry_stacks_concat.extend(stack_entries);
-            stack_alloc_counter += split.len() - 3; // var, "[", and "]"
-        } else {
-            assert_eq!(split[1], "[");
-            assert_eq!(split[split.len() - 1], "]");
-            entry_regs.insert(var_name.to_string(), Integer::from(mem_alloc_counter));
-            entry_regs_concat.push(Integer::from(mem_alloc_counter));
-            // Parse the entries
-            let array_entries: Vec<Integer> = split[2..split.len() - 1].iter().map(|entry| Integer::from_str_radix(&entry, 10).unwrap()).collect();
-            entry_arrays.insert(var_name.to_string(), array_entries.clone());
-            entry_arrays_concat.extend(array_entries);
-            mem_alloc_counter += split.len() - 3; // var, "[", and "]"
-        }
-        buffer.clear();
-        reader.read_line(&mut buffer).unwrap();
-    }
-    }
-    // Insert [%SP, %AS] to the front of entry_reg
-    entry_regs.insert("%AS".to_string(), Integer::from(mem_alloc_counter));
-    entry_regs_concat.insert(0, Integer::from(mem_alloc_counter));
-    entry_regs.insert("%SP".to_string(), Integer::from(stack_alloc_counter));
-    entry_regs_concat.insert(0, Integer::from(stack_alloc_counter));
-
-    // Obtain Witnesses,
-    let mut entry_witnesses: Vec<Integer> = Vec::new();
-    let witness_file_name = format!("../zok_tests/benchmarks/{}.witness", benchmark_name);
-    let f = File::open(witness_file_name);
-    if let Ok(f) = f {
-        let mut reader = BufReader::new(f);
-        let mut buffer = String::new();
-        reader.read_line(&mut buffer).unwrap();
-        while buffer.trim() != "END".to_string() {
-            let split: Vec<String> = buffer.split(' ').map(|i| i.to_string().trim().to_string()).collect();
-            entry_witnesses.extend(split.iter().map(|entry| Integer::from_str_radix(&entry, 10).unwrap()));
-            buffer.clear();
-            reader.read_line(&mut buffer).unwrap();
-        }
-    }
+    let (
+        entry_regs,
+        mut entry_stacks,
+        entry_arrays,
+        entry_regs_concat,
+        mut entry_stacks_concat,
+        entry_arrays_concat,
+        stack_alloc_counter,
+        mem_alloc_counter,
+    ) = zx::read_inputs(&benchmark_file_name(benchmark_name, "input"), ctk.num_challenges);
+    let entry_witnesses = zx::read_witnesses(&benchmark_file_name(benchmark_name, "witness"));
+    fill_challenges(&ctk, &mut entry_stacks, &entry_regs_concat, &mut entry_stacks_concat, &entry_arrays_concat);
 
     println!("INPUT: {:?}", entry_regs_concat);
 
     // --
     // Generate Witnesses
     // --
-    let rtk = get_run_time_knowledge::<false>(
-        path.clone(), 
-        &options, 
-        entry_regs, 
-        entry_stacks, 
-        entry_arrays, 
-        entry_witnesses,
-        entry_regs_concat,
-        entry_stacks_concat,
-        entry_arrays_concat,
-        &ctk, 
-        live_io_size, 
-        live_mem_size, 
-        prover_data_list,
-        stack_alloc_counter,
-        mem_alloc_counter
-    );
-    let witness_time = witness_start.elapsed();
+    let witness_time;
+    if options.stream_witness {
+        // RTK is written to file while it is generated
+        if options.inline_spartan {
+            panic!("--stream_witness cannot be used with --inline_spartan");
+        }
+        stream_run_time_knowledge::<false, P::Assignment>(
+            path.clone(), 
+            &options, 
+            &RunTimeKnowledge::<P::Assignment>::file_name(benchmark_name, "_bin.rtk"),
+            entry_regs, 
+            entry_stacks, 
+            entry_arrays, 
+            entry_witnesses,
+            entry_regs_concat,
+            entry_stacks_concat,
+            entry_arrays_concat,
+            &ctk, 
+            live_io_size, 
+            live_mem_size, 
+            prover_data_list,
+            stack_alloc_counter,
+            mem_alloc_counter,
+            &mut stats
+        ).unwrap();
+        witness_time = witness_start.elapsed();
 
-    if PRINT_PROOF {
-        ctk.write_to_file(benchmark_name.to_string()).unwrap();
-        rtk.write_to_file(benchmark_name.to_string()).unwrap();
-    }
-    if !options.inline_spartan {
-        // --
-        // Write CTK, RTK to file
-        // --
         ctk.serialize_to_file(benchmark_name.to_string()).unwrap();
-        rtk.serialize_to_file(benchmark_name.to_string()).unwrap();
+        match &options.write_knowledge {
+            Some(KnowledgeEncoding::Text) => {
+                // The text encoding needs the whole RTK, which is loaded back from the streamed file
+                ctk.write_to_file(benchmark_name, &KnowledgeEncoding::Text).unwrap();
+                let rtk: RunTimeKnowledge<P::Assignment> = load_knowledge(&RunTimeKnowledge::<P::Assignment>::file_name(benchmark_name, "_bin.rtk")).unwrap();
+                rtk.write_to_file(benchmark_name, &KnowledgeEncoding::Text).unwrap();
+            }
+            Some(KnowledgeEncoding::Bin) => {
+                ctk.write_to_file(benchmark_name, &KnowledgeEncoding::Bin).unwrap();
+                copy_knowledge_as_bin(
+                    RunTimeKnowledge::<P::Assignment>::KIND,
+                    &RunTimeKnowledge::<P::Assignment>::file_name(benchmark_name, "_bin.rtk"),
+                    &RunTimeKnowledge::<P::Assignment>::file_name(benchmark_name, &format!(".{}", KnowledgeEncoding::Bin.extension("rtk"))),
+                ).unwrap();
+            }
+            None => {}
+        }
     } else {
-        run_spartan_proof(ctk, rtk);
+        let rtk = get_run_time_knowledge::<false, P::Assignment>(
+            path.clone(), 
+            &options, 
+            entry_regs, 
+            entry_stacks, 
+            entry_arrays, 
+            entry_witnesses,
+            entry_regs_concat,
+            entry_stacks_concat,
+            entry_arrays_concat,
+            &ctk, 
+            live_io_size, 
+            live_mem_size, 
+            prover_data_list,
+            stack_alloc_counter,
+            mem_alloc_counter
+        );
+        let rtk = match zk_exec_bound {
+            Some(bound) => {
+                let shape = ExecShape::bounded(&ctk, vec![bound; ctk.block_num_instances], rtk.total_num_init_phy_mem_accesses, rtk.total_num_init_vir_mem_accesses);
+                exit_on_error(pad_run_time_knowledge(&ctk, rtk, &shape))
+            }
+            None => rtk,
+        };
+        witness_time = witness_start.elapsed();
+        stats.record_run_time(
+            &rtk.block_num_proofs,
+            rtk.block_max_num_proofs,
+            rtk.consis_num_proofs,
+            rtk.total_num_init_phy_mem_accesses,
+            rtk.total_num_init_vir_mem_accesses,
+            rtk.total_num_phy_mem_accesses,
+            rtk.total_num_vir_mem_accesses,
+        );
+
+        if options.check_witness {
+            inspect_knowledge(&ctk, &rtk).unwrap_or_else(|e| panic!("Inconsistent CTK and RTK: {}", e));
+            let check = check_block_witnesses(&ctk, &rtk);
+            print_witness_check(&check);
+            if check.num_failures > 0 {
+                panic!("Block witnesses do not satisfy the constraints.");
+            }
+        }
+        if let Some(encoding) = &options.write_knowledge {
+            ctk.write_to_file(benchmark_name, encoding).unwrap();
+            rtk.write_to_file(benchmark_name, encoding).unwrap();
+        }
+        if !options.inline_spartan {
+            // --
+            // Write CTK, RTK to file
+            // --
+            ctk.serialize_to_file(benchmark_name.to_string()).unwrap();
+            rtk.serialize_to_file(benchmark_name.to_string()).unwrap();
+        } else {
+            exit_on_error(run_block_proof::<P>(&options.proof_mode, ctk, rtk));
+        }
     }
 
     println!("Compiler time: {}ms", compiler_time.as_millis());
     println!("\n--\nWitness time: {}ms", witness_time.as_millis());
-}
\ No newline at end of file
+
+    if let Some(stats_path) = &options.stats_json {
+        stats.compiler_ms = to_ms(compiler_time);
+        stats.witness_ms = to_ms(witness_time);
+        stats.write_to_file(stats_path).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::convert::TryInto;
+    use std::sync::Once;
+
+    // Programs of the differential tests, with branches, function calls and both kinds of memory
+    const TEST_PROGRAMS: [&str; 3] = ["cond_store", "entry_array", "func_call"];
+
+    // The field is set once per process, by the first test, to ZXC_TEST_FIELD or curve25519
+    fn test_options(args: &[&str]) -> Options {
+        static SET_CFG: Once = Once::new();
+        let field = std::env::var("ZXC_TEST_FIELD").unwrap_or_else(|_| "curve25519".to_string());
+        let options = Options::try_parse_from(["zxc", "test", "--field-builtin", &field].iter().chain(args)).unwrap();
+        SET_CFG.call_once(|| circ::cfg::set(&options.circ));
+        options
+    }
+
+    // Everything run_zxc obtains before generating the witnesses of a program
+    struct TestProgram {
+        path: PathBuf,
+        ctk: CompileTimeKnowledge,
+        live_io_size: Vec<usize>,
+        live_mem_size: Vec<usize>,
+        prover_data_list: Vec<ProverData>,
+        inputs: zx::EntryInputs,
+        witnesses: Vec<Integer>,
+    }
+
+    fn compile(name: &str, options: &Options) -> TestProgram {
+        compile_file(PathBuf::from(format!("scripts/zx_diff_tests/{}.zok", name)), options)
+    }
+
+    fn compile_file(path: PathBuf, options: &Options) -> TestProgram {
+        let (ctk, live_io_size, live_mem_size, prover_data_list) =
+            get_compile_time_knowledge::<false>(path.clone(), options, &mut CompileStats::default());
+        let mut inputs = zx::read_inputs(&path.with_extension("input"), ctk.num_challenges);
+        fill_challenges(&ctk, &mut inputs.1, &inputs.3, &mut inputs.4, &inputs.5);
+        let witnesses = zx::read_witnesses(&path.with_extension("witness"));
+        TestProgram { path, ctk, live_io_size, live_mem_size, prover_data_list, inputs, witnesses }
+    }
+
+    fn run_time_knowledge(p: TestProgram, options: &Options) -> (CompileTimeKnowledge, RunTimeKnowledge) {
+        let (regs, stacks, arrays, regs_concat, stacks_concat, arrays_concat, sp, r#as) = p.inputs;
+        let rtk = get_run_time_knowledge::<false, Assignment>(
+            p.path, options, regs, stacks, arrays, p.witnesses, regs_concat, stacks_concat, arrays_concat,
+            &p.ctk, p.live_io_size, p.live_mem_size, p.prover_data_list, sp, r#as,
+        );
+        (p.ctk, rtk)
+    }
+
+    fn raw_run_time_knowledge(p: TestProgram, options: &Options) -> (CompileTimeKnowledge, RunTimeKnowledge<RawAssignment>) {
+        let (regs, stacks, arrays, regs_concat, stacks_concat, arrays_concat, sp, r#as) = p.inputs;
+        let rtk = get_run_time_knowledge::<false, RawAssignment>(
+            p.path, options, regs, stacks, arrays, p.witnesses, regs_concat, stacks_concat, arrays_concat,
+            &p.ctk, p.live_io_size, p.live_mem_size, p.prover_data_list, sp, r#as,
+        );
+        (p.ctk, rtk)
+    }
+
+    fn stream_run_time_knowledge_to(p: TestProgram, options: &Options, rtk_file_name: &PathBuf) {
+        let (regs, stacks, arrays, regs_concat, stacks_concat, arrays_concat, sp, r#as) = p.inputs;
+        stream_run_time_knowledge::<false, Assignment>(
+            p.path, options, rtk_file_name, regs, stacks, arrays, p.witnesses, regs_concat, stacks_concat, arrays_concat,
+            &p.ctk, p.live_io_size, p.live_mem_size, p.prover_data_list, sp, r#as, &mut CompileStats::default(),
+        ).unwrap();
+    }
+
+    #[test]
+    fn streamed_rtk_matches_rtk() {
+        // A small chunk size spills several runs of memory accesses, which are then merged
+        let options = test_options(&["--stream_chunk_size", "3"]);
+        let dir = SpillDir::create("zxc_test_streamed_rtk").unwrap();
+        for name in TEST_PROGRAMS {
+            let (_, rtk) = run_time_knowledge(compile(name, &options), &options);
+            let rtk_file_name = dir.0.join(format!("{}_bin.rtk", name));
+            stream_run_time_knowledge_to(compile(name, &options), &options, &rtk_file_name);
+            assert!(std::fs::read(&rtk_file_name).unwrap() == bincode::serialize(&rtk).unwrap(), "{}: streamed RTK differs", name);
+        }
+    }
+
+    #[test]
+    fn stats_json_lists_every_pass() {
+        let dir = SpillDir::create("zxc_test_stats_json").unwrap();
+        let stats_path = dir.0.join("stats.json");
+        let options = test_options(&["--stats_json", stats_path.to_str().unwrap()]);
+        // Collect the statistics as run_zxc does, on the streaming path that records the executions while writing the RTK
+        let path = PathBuf::from("scripts/zx_diff_tests/func_call.zok");
+        let mut stats = CompileStats { benchmark: "func_call".to_string(), opt_level: options.opt_level, ..Default::default() };
+        let (ctk, live_io_size, live_mem_size, prover_data_list) = get_compile_time_knowledge::<false>(path.clone(), &options, &mut stats);
+        let (regs, mut stacks, arrays, regs_concat, mut stacks_concat, arrays_concat, sp, r#as) = zx::read_inputs(&path.with_extension("input"), ctk.num_challenges);
+        fill_challenges(&ctk, &mut stacks, &regs_concat, &mut stacks_concat, &arrays_concat);
+        let witnesses = zx::read_witnesses(&path.with_extension("witness"));
+        stream_run_time_knowledge::<false, Assignment>(
+            path, &options, &dir.0.join("func_call_bin.rtk"), regs, stacks, arrays, witnesses, regs_concat, stacks_concat, arrays_concat,
+            &ctk, live_io_size, live_mem_size, prover_data_list, sp, r#as, &mut stats,
+        ).unwrap();
+        stats.write_to_file(options.stats_json.as_ref().unwrap()).unwrap();
+
+        let json: serde_json::Value = serde_json::from_reader(File::open(&stats_path).unwrap()).unwrap();
+        let keys: Vec<&str> = json.as_object().unwrap().keys().map(|k| k.as_str()).collect();
+        for key in [
+            "benchmark", "opt_level", "num_blocks", "num_inputs_unpadded", "io_width", "num_vars", "max_num_cons", "blocks",
+            "block_max_num_proofs", "block_max_num_proofs_padded", "consis_num_proofs",
+            "total_num_init_phy_mem_accesses", "total_num_init_vir_mem_accesses", "total_num_phy_mem_accesses", "total_num_vir_mem_accesses",
+            "compiler_ms", "witness_ms", "block_pass_ms", "ir_pass_ms",
+        ] {
+            assert!(keys.contains(&key), "missing key {}", key);
+        }
+        assert_eq!(json["benchmark"], "func_call");
+        let blocks = json["blocks"].as_array().unwrap();
+        assert_eq!(blocks.len(), ctk.block_num_instances);
+        assert_eq!(json["num_blocks"], ctk.block_num_instances);
+        for b in blocks {
+            assert!(b["num_cons"].as_u64().unwrap() <= b["num_cons_padded"].as_u64().unwrap());
+            assert!(b["num_proofs"].as_u64().unwrap() <= b["num_proofs_padded"].as_u64().unwrap());
+        }
+        assert_eq!(json["consis_num_proofs"].as_u64().unwrap() as usize, blocks.iter().map(|b| b["num_proofs"].as_u64().unwrap() as usize).sum::<usize>());
+
+        // Passes are listed as [name, ms], in order of execution
+        let passes = |key: &str| -> Vec<String> {
+            json[key].as_array().unwrap().iter().map(|p| {
+                assert!(p[1].as_f64().unwrap() >= 0.0);
+                p[0].as_str().unwrap().to_string()
+            }).collect()
+        };
+        let block_passes = passes("block_pass_ms");
+        assert_eq!(block_passes.first().map(String::as_str), Some("generation"));
+        assert_eq!(block_passes.last().map(String::as_str), Some("to_circ"));
+        for pass in ["read_only_arrays", "liveness", "block_merge", "var_to_reg"] {
+            assert!(block_passes.iter().any(|p| p == pass), "missing block pass {}", pass);
+        }
+        let ir_passes: Vec<String> = zx::block_ir_opts().iter().map(|o| format!("{:?}", o)).collect();
+        assert_eq!(passes("ir_pass_ms"), ir_passes);
+    }
+
+    #[test]
+    fn parallel_witness_matches_sequential() {
+        let options = test_options(&[]);
+        let parallel_options = test_options(&["--parallel_witness"]);
+        for name in TEST_PROGRAMS {
+            let (_, rtk) = run_time_knowledge(compile(name, &options), &options);
+            let (_, parallel_rtk) = run_time_knowledge(compile(name, &parallel_options), &parallel_options);
+            assert!(bincode::serialize(&parallel_rtk).unwrap() == bincode::serialize(&rtk).unwrap(), "{}: parallel witnesses differ", name);
+        }
+    }
+
+    #[test]
+    fn load_knowledge_validates() {
+        let options = test_options(&[]);
+        let dir = SpillDir::create("zxc_test_knowledge").unwrap();
+        let (ctk, rtk) = run_time_knowledge(compile("func_call", &options), &options);
+        // Both encodings and the headerless files read by Spartan load back as the same knowledge
+        for encoding in [KnowledgeEncoding::Text, KnowledgeEncoding::Bin] {
+            let rtk_path = dir.0.join(format!("func_call.{}", encoding.extension("rtk")));
+            save_knowledge(&rtk, &rtk_path, &encoding).unwrap();
+            let loaded: RunTimeKnowledge = load_knowledge(&rtk_path).unwrap();
+            assert!(bincode::serialize(&loaded).unwrap() == bincode::serialize(&rtk).unwrap());
+        }
+        let ctk_path = dir.0.join("func_call_bin.ctk");
+        let rtk_path = dir.0.join("func_call_bin.rtk");
+        serialize_into_file(&ctk, &ctk_path).unwrap();
+        serialize_into_file(&rtk, &rtk_path).unwrap();
+        let loaded: CompileTimeKnowledge = load_knowledge(&ctk_path).unwrap();
+        assert!(bincode::serialize(&loaded).unwrap() == bincode::serialize(&ctk).unwrap());
+        let loaded: RunTimeKnowledge = load_knowledge(&rtk_path).unwrap();
+        assert!(bincode::serialize(&loaded).unwrap() == bincode::serialize(&rtk).unwrap());
+
+        // A headerless file of the wrong kind, a truncated one, or one with trailing bytes is rejected
+        assert!(load_knowledge::<RunTimeKnowledge>(&ctk_path).is_err());
+        let bytes = std::fs::read(&rtk_path).unwrap();
+        std::fs::write(&rtk_path, &bytes[..bytes.len() - 1]).unwrap();
+        assert!(load_knowledge::<RunTimeKnowledge>(&rtk_path).is_err());
+        std::fs::write(&rtk_path, [bytes.clone(), vec![0]].concat()).unwrap();
+        assert!(load_knowledge::<RunTimeKnowledge>(&rtk_path).is_err());
+        // So is one whose lengths disagree
+        let mut bad_rtk = rtk;
+        bad_rtk.consis_num_proofs += 1;
+        serialize_into_file(&bad_rtk, &rtk_path).unwrap();
+        let e = load_knowledge::<RunTimeKnowledge>(&rtk_path).err().unwrap();
+        assert!(e.contains("exec_inputs"), "{}", e);
+    }
+
+    #[test]
+    fn spartan_round_trip_through_files() {
+        let options = test_options(&[]);
+        let dir = SpillDir::create("zxc_test_spartan_files").unwrap();
+        let path = |name: &str| dir.0.join(name);
+        let (ctk, rtk) = run_time_knowledge(compile("func_call", &options), &options);
+        serialize_into_file(&ctk, &path("func_call_bin.ctk")).unwrap();
+        serialize_into_file(&rtk, &path("func_call_bin.rtk")).unwrap();
+
+        // Keys for explicit bounds above the executions of the RTK, which is padded to them
+        let bounds: Vec<usize> = rtk.block_num_proofs.iter().map(|n| n + 1).collect();
+        let shape = ExecShape::bounded(&ctk, bounds, rtk.total_num_init_phy_mem_accesses, rtk.total_num_init_vir_mem_accesses);
+        save_knowledge(&shape, &path("func_call.shape"), &KnowledgeEncoding::Text).unwrap();
+        for shape_path in [None, Some(path("func_call.shape"))] {
+            BlockSpartan::setup_fs(&ProofMode::NonZk, &path("func_call_bin.ctk"), &path("func_call_bin.rtk"), shape_path.as_ref(), &path("P"), &path("V")).unwrap();
+            BlockSpartan::prove_fs(&ProofMode::NonZk, &path("func_call_bin.ctk"), &path("P"), &path("func_call_bin.rtk"), &path("pi"), &path("x")).unwrap();
+            assert!(BlockSpartan::verify_fs(&path("V"), &path("x"), &path("pi")).unwrap());
+        }
+
+        // A proof does not verify against another output
+        let mut io: BlockPublicIo = load_knowledge(&path("x")).unwrap();
+        io.output = integer_to_bytes(bytes_to_integer(&io.output) + 1);
+        save_knowledge(&io, &path("x"), &KnowledgeEncoding::Text).unwrap();
+        assert!(!BlockSpartan::verify_fs(&path("V"), &path("x"), &path("pi")).unwrap());
+
+        // Bounds below the executions of the RTK, or with inconsistent memory accesses, are errors
+        let bounds: Vec<usize> = rtk.block_num_proofs.iter().map(|n| n.saturating_sub(1)).collect();
+        let small_shape = ExecShape::bounded(&ctk, bounds, rtk.total_num_init_phy_mem_accesses, rtk.total_num_init_vir_mem_accesses);
+        save_knowledge(&small_shape, &path("func_call.shape"), &KnowledgeEncoding::Text).unwrap();
+        BlockSpartan::setup_fs(&ProofMode::NonZk, &path("func_call_bin.ctk"), &path("func_call_bin.rtk"), Some(&path("func_call.shape")), &path("P"), &path("V")).unwrap();
+        assert!(BlockSpartan::prove_fs(&ProofMode::NonZk, &path("func_call_bin.ctk"), &path("P"), &path("func_call_bin.rtk"), &path("pi"), &path("x")).is_err());
+        let mut bad_shape = shape;
+        bad_shape.total_num_phy_mem_accesses += 1;
+        save_knowledge(&bad_shape, &path("func_call.shape"), &KnowledgeEncoding::Text).unwrap();
+        assert!(BlockSpartan::setup_fs(&ProofMode::NonZk, &path("func_call_bin.ctk"), &path("func_call_bin.rtk"), Some(&path("func_call.shape")), &path("P"), &path("V")).is_err());
+    }
+
+    #[test]
+    fn spartan_hides_private_inputs() {
+        let options = test_options(&[]);
+        let (ctk, rtk) = run_time_knowledge(compile("private_input", &options), &options);
+        // Inputs of main are %SP, %AS, then the parameters, with the private x last
+        assert!(ctk.input_liveness.last() == Some(&false), "{:?}", ctk.input_liveness);
+        let io = BlockPublicIo::of(&rtk, &ctk.input_liveness);
+        assert!(io.input.last() == Some(&[0; 32]) && rtk.input.last() != Some(&[0; 32]));
+        // x is not live, but still takes the register after n
+        assert_eq!(ctk.input_regs[..2], [5, 4]);
+        assert_eq!(ctk.input_regs[ctk.input_regs.len() - 2..], [7, 8]);
+        run_block_proof::<BlockSpartan>(&ProofMode::NonZk, ctk, rtk).unwrap();
+    }
+
+    #[test]
+    fn challenges_bind_their_argument() {
+        let options = test_options(&[]);
+        let p = compile_file(PathBuf::from("examples/ZoKrates/pf/chall/fingerprint.zok"), &options);
+        assert_eq!(p.ctk.num_challenges, 1);
+        // The program returns the challenge, the hash of a and b under the seed that follows the lookup tables
+        let seed = p.inputs.4[ReservedMemory::of(&p.ctk).challenge_addr()].clone();
+        let (ctk, rtk) = run_time_knowledge(p, &options);
+        let entries: Vec<Integer> = [1, 2, 3, 4, 3, 1, 4, 2].iter().map(|i| Integer::from(*i)).collect();
+        assert!(bytes_to_integer(&rtk.output) == zsharp::challenge_hash(&seed, &entries));
+        let check = check_block_witnesses(&ctk, &rtk);
+        assert!(check.num_failures == 0, "{:?}", check.failures);
+        run_block_proof::<BlockSpartan>(&ProofMode::NonZk, ctk, rtk).unwrap();
+    }
+
+    #[test]
+    fn witness_check_compares_memory_accesses() {
+        let options = test_options(&[]);
+        for name in TEST_PROGRAMS {
+            let (ctk, rtk) = run_time_knowledge(compile(name, &options), &options);
+            let check = check_block_witnesses(&ctk, &rtk);
+            assert!(check.num_failures == 0, "{}: {:?}", name, check.failures);
+        }
+        // Swapping the data of two physical accesses keeps the sorted list consistent, but not a permutation of the executions
+        let (ctk, mut rtk) = run_time_knowledge(compile("func_call", &options), &options);
+        let (mut i, mut j) = (0, 0);
+        let datas: Vec<Vec<[u8; 32]>> = rtk.addr_phy_mems_list.iter().map(|m| m.to_bytes()).collect();
+        for k in 1..datas.len() {
+            if datas[k][2] != datas[k - 1][2] && datas[k][3] != datas[k - 1][3] {
+                (i, j) = (k - 1, k);
+            }
+        }
+        assert!(i != j, "func_call should access several physical addresses");
+        let (mut mi, mut mj) = (datas[i].clone(), datas[j].clone());
+        std::mem::swap(&mut mi[3], &mut mj[3]);
+        rtk.addr_phy_mems_list[i] = Assignment::from_bytes(&mi).unwrap();
+        rtk.addr_phy_mems_list[j] = Assignment::from_bytes(&mj).unwrap();
+        let check = check_block_witnesses(&ctk, &rtk);
+        assert!(check.failures.iter().any(|f| f.contains("addr_phy_mems_list")), "{:?}", check.failures);
+    }
+
+    // Pad every block to one more execution than the most executed one, as --zk_exec_bound does
+    fn pad_to_uniform_bound<A: RtkAssignment>(ctk: &CompileTimeKnowledge, rtk: RunTimeKnowledge<A>) -> RunTimeKnowledge<A> {
+        let bounds = vec![rtk.block_max_num_proofs + 1; ctk.block_num_instances];
+        let shape = ExecShape::bounded(ctk, bounds, rtk.total_num_init_phy_mem_accesses, rtk.total_num_init_vir_mem_accesses);
+        pad_run_time_knowledge(ctk, rtk, &shape).unwrap()
+    }
+
+    fn bincode_clone<T: Serialize + DeserializeOwned>(x: &T) -> T {
+        bincode::deserialize(&bincode::serialize(x).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn spartan_proves_padded_executions_outside_zk_mode() {
+        let options = test_options(&[]);
+        assert!(zk_exec_bound::<BlockSpartan>(&test_options(&["--proof_mode", "zk", "--zk_exec_bound", "4"])).is_err());
+        for name in TEST_PROGRAMS {
+            let (ctk, rtk) = run_time_knowledge(compile(name, &options), &options);
+            let rtk = pad_to_uniform_bound(&ctk, rtk);
+            let shape = ExecShape::of(&rtk);
+            assert!(shape.is_padded());
+            let check = check_block_witnesses(&ctk, &rtk);
+            assert!(check.num_failures == 0, "{}: {:?}", name, check.failures);
+            assert!(check_proof_mode::<BlockSpartan>(&ProofMode::Zk, &shape).is_err());
+            run_block_proof::<BlockSpartan>(&ProofMode::NonZk, ctk, rtk).unwrap();
+        }
+    }
+
+    // Backends over BLS12-381 need another field than the other tests, so the test runs again in a child process
+    #[cfg(feature = "bellman")]
+    #[test]
+    fn groth16_hides_padded_executions() {
+        if std::env::var("ZXC_TEST_FIELD").is_err() {
+            let status = std::process::Command::new(std::env::current_exe().unwrap())
+                .args(["--exact", "test::groth16_hides_padded_executions"])
+                .env("ZXC_TEST_FIELD", "bls12381")
+                .status()
+                .unwrap();
+            assert!(status.success());
+            return;
+        }
+        let options = test_options(&[]);
+        // The bound only applies in zk mode, where it is required
+        assert_eq!(zk_exec_bound::<BlockGroth16>(&test_options(&["--zk_exec_bound", "4"])), Ok(None));
+        assert_eq!(zk_exec_bound::<BlockGroth16>(&test_options(&["--proof_mode", "zk", "--zk_exec_bound", "4"])), Ok(Some(4)));
+        assert!(zk_exec_bound::<BlockGroth16>(&test_options(&["--proof_mode", "zk"])).is_err());
+        for name in TEST_PROGRAMS {
+            let (ctk, rtk) = raw_run_time_knowledge(compile(name, &options), &options);
+            let rtk = pad_to_uniform_bound(&ctk, rtk);
+            let check = check_block_witnesses(&ctk, &rtk);
+            assert!(check.num_failures == 0, "{}: {:?}", name, check.failures);
+            let shape = ExecShape::of(&rtk);
+            check_proof_mode::<BlockGroth16>(&ProofMode::Zk, &shape).unwrap();
+            let (pk, vk) = BlockGroth16::setup(&ctk, &shape).unwrap();
+            // The instance names the last execution of the shape, not the last valid one
+            let io = BlockGroth16::public_io(&pk, &rtk);
+            assert_eq!(io.output_exec_num, shape.consis_num_proofs - 1);
+            assert!(io.output_exec_num != rtk.output_exec_num);
+            // Two proofs of the same execution are blinded differently, and both verify
+            let proof = BlockGroth16::prove(bincode_clone(&pk), bincode_clone(&rtk)).unwrap();
+            let other_proof = BlockGroth16::prove(pk, rtk).unwrap();
+            assert!(bincode::serialize(&proof).unwrap() != bincode::serialize(&other_proof).unwrap(), "{}: proofs are not blinded", name);
+            assert!(BlockGroth16::verify(&vk, &io, &proof), "{}: padded proof does not verify", name);
+            assert!(BlockGroth16::verify(&vk, &io, &other_proof), "{}: padded proof does not verify", name);
+        }
+    }
+
+    // Backends over BLS12-381 need another field than the other tests, so the test runs again in a child process
+    #[cfg(feature = "bellman")]
+    #[test]
+    fn fold_round_trip() {
+        if std::env::var("ZXC_TEST_FIELD").is_err() {
+            let status = std::process::Command::new(std::env::current_exe().unwrap())
+                .args(["--exact", "test::fold_round_trip"])
+                .env("ZXC_TEST_FIELD", "bls12381")
+                .status()
+                .unwrap();
+            assert!(status.success());
+            return;
+        }
+        let options = test_options(&[]);
+        for name in TEST_PROGRAMS {
+            let (ctk, rtk) = raw_run_time_knowledge(compile(name, &options), &options);
+            let (pk, vk) = BlockFolding::setup(&ctk, &ExecShape::of(&rtk)).unwrap();
+            let io = BlockFolding::public_io(&pk, &rtk);
+            let proof = BlockFolding::prove(pk, rtk).unwrap();
+            assert!(BlockFolding::verify(&vk, &io, &proof), "{}: folded proof does not verify", name);
+
+            // Neither another output nor another folded error term verifies
+            let mut wrong_io: BlockPublicIo = bincode::deserialize(&bincode::serialize(&io).unwrap()).unwrap();
+            wrong_io.output = integer_to_bytes(bytes_to_integer(&io.output) + 1);
+            assert!(!BlockFolding::verify(&vk, &wrong_io, &proof), "{}: verifies another output", name);
+            // The error term is serialized last, as 32 bytes per entry
+            let mut proof_bytes = bincode::serialize(&proof).unwrap();
+            let last_entry = proof_bytes.len() - 32;
+            proof_bytes[last_entry] ^= 1;
+            let wrong_proof = bincode::deserialize(&proof_bytes).unwrap();
+            assert!(!BlockFolding::verify(&vk, &io, &wrong_proof), "{}: verifies another error term", name);
+        }
+    }
+
+    // Reads a field element of n8 bytes, little-endian
+    fn read_circom_element(bytes: &[u8], pos: &mut usize, n8: usize) -> Integer {
+        *pos += n8;
+        Integer::from_digits(&bytes[*pos - n8..*pos], rug::integer::Order::Lsf)
+    }
+
+    fn read_circom_u32(bytes: &[u8], pos: &mut usize) -> usize {
+        *pos += 4;
+        u32::from_le_bytes(bytes[*pos - 4..*pos].try_into().unwrap()) as usize
+    }
+
+    // Checks the header of a circom file, returns n8 and the position after the field
+    fn read_circom_header(bytes: &[u8], magic: &[u8; 4], version: u32) -> (usize, usize) {
+        assert!(&bytes[0..4] == magic);
+        let mut pos = 4;
+        assert!(read_circom_u32(bytes, &mut pos) == version as usize);
+        // Number of sections, type and size of the first section
+        pos += 4 + 4 + 8;
+        let n8 = read_circom_u32(bytes, &mut pos);
+        assert!(read_circom_element(bytes, &mut pos, n8) == *cfg().field().modulus());
+        (n8, pos)
+    }
+
+    // Number of wires and constraints of a circom R1CS file
+    fn read_circom_r1cs(path: &PathBuf) -> (usize, Vec<[Vec<(usize, Integer)>; 3]>) {
+        let bytes = std::fs::read(path).unwrap();
+        let (n8, mut pos) = read_circom_header(&bytes, b"r1cs", CIRCOM_R1CS_VERSION);
+        let num_wires = read_circom_u32(&bytes, &mut pos);
+        // Public outputs, public inputs, private inputs, labels
+        pos += 4 * 3 + 8;
+        let num_cons = read_circom_u32(&bytes, &mut pos);
+        // Type and size of the constraint section
+        pos += 4 + 8;
+        let row = |pos: &mut usize| -> Vec<(usize, Integer)> {
+            let len = read_circom_u32(&bytes, pos);
+            (0..len).map(|_| (read_circom_u32(&bytes, pos), read_circom_element(&bytes, pos, n8))).collect()
+        };
+        let cons = (0..num_cons).map(|_| [row(&mut pos), row(&mut pos), row(&mut pos)]).collect();
+        (num_wires, cons)
+    }
+
+    fn read_circom_wtns(path: &PathBuf) -> Vec<Integer> {
+        let bytes = std::fs::read(path).unwrap();
+        let (n8, mut pos) = read_circom_header(&bytes, b"wtns", CIRCOM_WTNS_VERSION);
+        let num_vals = read_circom_u32(&bytes, &mut pos);
+        // Type and size of the value section
+        pos += 4 + 8;
+        let vals: Vec<Integer> = (0..num_vals).map(|_| read_circom_element(&bytes, &mut pos, n8)).collect();
+        assert!(pos == bytes.len());
+        vals
+    }
+
+    fn assert_circom_satisfied(dir: &PathBuf, r1cs: &str, wtns: &str) -> Vec<Integer> {
+        let (num_wires, cons) = read_circom_r1cs(&dir.join(r1cs));
+        let vals = read_circom_wtns(&dir.join(wtns));
+        assert!(vals.len() == num_wires && vals[0] == 1, "{}: {} values for {} wires", wtns, vals.len(), num_wires);
+        let field = cfg().field().modulus();
+        let eval = |row: &Vec<(usize, Integer)>| -> Integer {
+            let res: Integer = row.iter().map(|(w, c)| Integer::from(c * &vals[*w])).sum();
+            Integer::from(res.rem_euc_ref(field))
+        };
+        for (i, [a, b, c]) in cons.iter().enumerate() {
+            assert!(Integer::from((eval(a) * eval(b)).rem_euc_ref(field)) == eval(c), "{}: constraint {} is not satisfied", wtns, i);
+        }
+        vals
+    }
+
+    #[test]
+    fn circom_export_is_satisfied() {
+        let options = test_options(&[]);
+        let dir = SpillDir::create("zxc_test_circom").unwrap();
+        for name in TEST_PROGRAMS {
+            let (ctk, rtk) = run_time_knowledge(compile(name, &options), &options);
+            let out = dir.0.join(name);
+            export_circom(&ctk, &rtk, &out).unwrap();
+            for (slot, b) in live_blocks_by_slot(&rtk.block_num_proofs).into_iter().enumerate() {
+                for e in 0..rtk.block_vars_matrix[slot].len() {
+                    assert_circom_satisfied(&out, &format!("block_{}.r1cs", b), &format!("block_{}_{}.wtns", b, e));
+                }
+            }
+            assert_circom_satisfied(&out, "consis.r1cs", "consis.wtns");
+            assert_circom_satisfied(&out, "vir_mem.r1cs", "vir_mem.wtns");
+            let phy_mem = assert_circom_satisfied(&out, "phy_mem.r1cs", "phy_mem.wtns");
+
+            // The permutation maps every physical access of an execution to an entry of the list with its address and data
+            let perm = std::fs::read_to_string(out.join("phy_mem.perm")).unwrap();
+            assert!(perm.lines().count() == rtk.addr_phy_mems_list.len());
+            let n = ctk.num_inputs_unpadded;
+            for (k, line) in perm.lines().enumerate() {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                assert!(fields[0] == k.to_string(), "{}", line);
+                if fields[1] == "block" {
+                    let (b, e, op): (usize, usize, usize) = (fields[2].parse().unwrap(), fields[3].parse().unwrap(), fields[4].parse().unwrap());
+                    let vars = read_circom_wtns(&out.join(format!("block_{}_{}.wtns", b, e)));
+                    let j = 2 * n + VARS_PER_ST_ACCESS * op;
+                    assert!(vars[j..j + 2] == phy_mem[1 + 4 * k + 2..1 + 4 * k + 4], "{}: {}", name, line);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use circ::front::zsharp::{self, zx, ZSharpFE, BlockExecution, OPT_RO_ARRAYS};
+use circ::front::Mode;
+use circ::ir::term::Value;
+
+use circ::cfg::{
+    clap::{self, Parser},
+    CircOpt,
+};
+use std::collections::BTreeMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+
+use rug::Integer;
+
+#[derive(Debug, Parser)]
+#[command(name = "zxd", about = "Differential tester of the Z# block optimizations")]
+struct Options {
+    /// Input file
+    #[arg()]
+    zsharp_path: PathBuf,
+
+    /// Program inputs, in the format of zxc (default: PATH with extension .input)
+    #[arg()]
+    inputs_path: Option<PathBuf>,
+
+    /// Program witnesses, in the format of zxc (default: PATH with extension .witness)
+    #[arg(long = "witnesses_path")]
+    witnesses_path: Option<PathBuf>,
+
+    #[command(flatten)]
+    /// CirC options
+    circ: CircOpt,
+
+    #[arg(long = "opt_level")]
+    /// optimization level to compare, can be repeated (default: every level)
+    opt_levels: Vec<usize>,
+
+    #[arg(long = "skip_r1cs_check")]
+    /// only compare the interpreter results, without checking the block R1CS against the witnesses
+    skip_r1cs_check: bool,
+
+    #[arg(long = "verbose_opt")]
+    /// print results of every optimization pass
+    verbose_opt: bool,
+}
+
+fn panic_message(e: &Box<dyn std::any::Any + Send>) -> String {
+    if let Some(s) = e.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = e.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+fn value_to_usize(v: &Value) -> usize {
+    v.as_integer().unwrap().to_usize().unwrap()
+}
+
+// Observable results of a program execution under one opt level
+// The memory allocated by the program itself is laid out differently at every opt level
+// (spilling and read-only arrays move allocations between physical and virtual memory),
+// so only the entry arrays, whose addresses are fixed by the input file, are compared
+#[derive(PartialEq)]
+struct DiffResult {
+    ret: String,
+    entry_mem: Vec<Option<Value>>,
+}
+
+// Interpret the program under one opt level and check every block execution against its R1CS
+// Returns the result of the execution and the number of blocks executed,
+// or an error if either the interpreter or the R1CS check fails
+fn run_opt_level(
+    options: &Options,
+    opt_level: usize,
+    entry_regs: &BTreeMap<String, Integer>,
+    entry_stacks: &BTreeMap<String, Vec<Integer>>,
+    entry_arrays: &BTreeMap<String, Vec<Integer>>,
+    entry_witnesses: &Vec<Integer>,
+) -> Result<(DiffResult, usize), String> {
+    let inputs = || zsharp::Inputs {
+        file: options.zsharp_path.clone(),
+        mode: Mode::Proof,
+        opt_level,
+        verbose_opt: options.verbose_opt,
+    };
+    let prover_data_list = if options.skip_r1cs_check {
+        Vec::new()
+    } else {
+        panic::catch_unwind(AssertUnwindSafe(|| zx::gen_block_prover_data(inputs())))
+            .map_err(|e| format!("constraint generation failed: {}", panic_message(&e)))?
+            .map_err(|e| format!("constraint generation failed: {}", e))?
+    };
+
+    // Entry arrays occupy virtual addresses [0, %AS)
+    let mut entry_mem: Vec<Option<Value>> = vec![None; entry_regs.get("%AS").unwrap().to_usize().unwrap()];
+    let mut r1cs_err: Option<String> = None;
+    let mut exec_num = 0;
+    let mut exec_sink = |exec: BlockExecution| {
+        if r1cs_err.is_none() && !options.skip_r1cs_check {
+            let check = panic::catch_unwind(AssertUnwindSafe(|| prover_data_list[exec.blk_id].check_all(&exec.io_map)));
+            if let Err(e) = check {
+                r1cs_err = Some(format!("execution {} of block {} violates its R1CS: {}", exec_num, exec.blk_id, panic_message(&e)));
+            }
+        }
+        for [addr, data, _, _] in &exec.vir_mem_op {
+            let addr = value_to_usize(addr);
+            if addr < entry_mem.len() {
+                entry_mem[addr] = Some(data.clone());
+            }
+        }
+        exec_num += 1;
+    };
+    let mut entry_regs = entry_regs.clone();
+    let (cs, _, _, init_vir_mem_list) = panic::catch_unwind(AssertUnwindSafe(|| {
+        ZSharpFE::interpret_stream(inputs(), &mut entry_regs, entry_stacks, entry_arrays, entry_witnesses, &mut exec_sink)
+    })).map_err(|e| format!("interpreter failed: {}", panic_message(&e)))?
+        .map_err(|e| format!("interpreter failed: {}", e))?;
+    if let Some(e) = r1cs_err {
+        return Err(e);
+    }
+
+    // Entries never touched by the program keep their initial value
+    for [addr, data, _, _] in &init_vir_mem_list {
+        let addr = value_to_usize(addr);
+        if addr < entry_mem.len() && entry_mem[addr].is_none() {
+            entry_mem[addr] = Some(data.clone());
+        }
+    }
+    Ok((DiffResult { ret: cs.to_string(), entry_mem }, exec_num))
+}
+
+fn main() {
+    env_logger::Builder::from_default_env()
+        .format_level(false)
+        .format_timestamp(None)
+        .init();
+    let options = Options::parse();
+    circ::cfg::set(&options.circ);
+
+    let inputs_path = options.inputs_path.clone().unwrap_or(options.zsharp_path.with_extension("input"));
+    let witnesses_path = options.witnesses_path.clone().unwrap_or(options.zsharp_path.with_extension("witness"));
+    if !inputs_path.exists() {
+        println!("Input file {:?} not found, running with no inputs", inputs_path);
+    }
+    let (entry_regs, entry_stacks, entry_arrays, _, _, _, _, _) = zx::read_inputs(&inputs_path, 0);
+    let entry_witnesses = zx::read_witnesses(&witnesses_path);
+    let opt_levels = if options.opt_levels.is_empty() { (0..=OPT_RO_ARRAYS).collect() } else { options.opt_levels.clone() };
+
+    let mut results = Vec::new();
+    for opt_level in &opt_levels {
+        let res = run_opt_level(&options, *opt_level, &entry_regs, &entry_stacks, &entry_arrays, &entry_witnesses);
+        results.push((*opt_level, res));
+    }
+
+    // Every level is compared against the first one
+    println!("\n--\nDifferential test of {}:", options.zsharp_path.display());
+    let mut error = false;
+    let base = results[0].1.as_ref().map(|(r, _)| r).map_err(|e| e.clone());
+    for (opt_level, res) in &results {
+        match res {
+            Ok((r, exec_num)) => {
+                println!("  Opt level {}: return {}, {} blocks executed", opt_level, r.ret, exec_num);
+                match &base {
+                    Ok(b) if b.ret != r.ret => {
+                        println!("    [failure] return value differs from opt level {}: {} vs {}", opt_levels[0], b.ret, r.ret);
+                        error = true;
+                    }
+                    Ok(b) if b.entry_mem != r.entry_mem => {
+                        let addr = (0..r.entry_mem.len()).find(|i| b.entry_mem[*i] != r.entry_mem[*i]).unwrap();
+                        println!("    [failure] final memory differs from opt level {} at address {}: {:?} vs {:?}", opt_levels[0], addr, b.entry_mem[addr], r.entry_mem[addr]);
+                        error = true;
+                    }
+                    Ok(_) => {}
+                    Err(_) => {
+                        println!("    [failure] opt level {} failed but opt level {} succeeded", opt_levels[0], opt_level);
+                        error = true;
+                    }
+                }
+            }
+            Err(e) => {
+                println!("  Opt level {}: [failure] {}", opt_level, e);
+                error = true;
+            }
+        }
+    }
+    if error {
+        println!("FAIL");
+        std::process::exit(1);
+    }
+    println!("PASS");
+}
@@ -1,17 +1,13 @@
-use circ::front::zsharp::{Inputs, ZSharpFE};
-use circ::ir::term::text::parse_value_map;
+use circ::front::zsharp::{zx, Inputs, ZSharpFE, BlockExecution, BlockDebugger};
+use circ::ir::term::Value;
 
 use circ::cfg::{
     clap::{self, Parser},
     CircOpt,
 };
 use circ::front::Mode;
-use rand_chacha::rand_core::block;
 use std::path::PathBuf;
 
-use zokrates_pest_ast::*;
-use rug::Integer;
-
 #[derive(Debug, Parser)]
 #[command(name = "zxi", about = "The Z# interpreter")]
 struct Options {
@@ -19,18 +15,72 @@ struct Options {
     #[arg()]
     zsharp_path: PathBuf,
 
-    /// Scalar input values
+    /// Program inputs, in the format of zxc (default: PATH with extension .input)
     #[arg()]
     inputs_path: Option<PathBuf>,
 
+    /// Program witnesses, in the format of zxc (default: PATH with extension .witness)
+    #[arg(long = "witnesses_path")]
+    witnesses_path: Option<PathBuf>,
+
     #[command(flatten)]
     /// CirC options
     circ: CircOpt,
+
+    #[arg(long = "opt_level", default_value_t = 3)]
+    /// level of optimizations
+    opt_level: usize,
+
+    #[arg(long = "verbose_opt")]
+    /// print results of every optimization pass
+    verbose_opt: bool,
+
+    #[arg(long = "print_trace")]
+    /// print the ID of every block executed
+    print_trace: bool,
+
+    #[arg(long = "print_regs")]
+    /// print the register states of every block executed
+    print_regs: bool,
+
+    #[arg(long = "print_mem")]
+    /// print the memory operations of every block executed
+    print_mem: bool,
+
+    #[arg(long = "debug")]
+    /// run the program in the interactive block debugger
+    debug: bool,
+
+    #[arg(long = "break")]
+    /// debugger breakpoint on a block label or a function name, can be repeated
+    /// (if present, the debugger runs until the first breakpoint instead of stopping at the first block)
+    breakpoints: Vec<String>,
+
+    #[arg(long = "watch_phy")]
+    /// debugger watchpoint on a physical memory address, can be repeated
+    phy_watchpoints: Vec<usize>,
+
+    #[arg(long = "watch_vir")]
+    /// debugger watchpoint on a virtual memory address, can be repeated
+    vir_watchpoints: Vec<usize>,
 }
 
-fn main() {
-    let func_inputs: Vec<usize> = vec![];
+fn value_to_string(v: &Value) -> String {
+    if let Some(i) = v.as_integer() {
+        i.to_string()
+    } else {
+        format!("{:?}", v)
+    }
+}
 
+fn print_regs(label: &str, regs: &Vec<Option<Value>>) {
+    let reg_str: Vec<String> = regs.iter().enumerate()
+        .filter_map(|(i, r)| r.as_ref().map(|r| format!("{}: {}", i, value_to_string(r))))
+        .collect();
+    println!("  {} {}", label, reg_str.join(", "));
+}
+
+fn main() {
     env_logger::Builder::from_default_env()
         .format_level(false)
         .format_timestamp(None)
@@ -38,30 +88,79 @@ fn main() {
     let mut options = Options::parse();
     options.circ.ir.field_to_bv = circ_opt::FieldToBv::Panic;
     circ::cfg::set(&options.circ);
+
+    let inputs_path = options.inputs_path.clone().unwrap_or(options.zsharp_path.with_extension("input"));
+    let witnesses_path = options.witnesses_path.clone().unwrap_or(options.zsharp_path.with_extension("witness"));
+    if !inputs_path.exists() {
+        println!("Input file {:?} not found, running with no inputs", inputs_path);
+    }
+    let (mut entry_regs, entry_stacks, entry_arrays, _, _, _, _, _) = zx::read_inputs(&inputs_path, 0);
+    let entry_witnesses = zx::read_witnesses(&witnesses_path);
+
     let inputs = Inputs {
-        file: options.zsharp_path,
-        mode: Mode::Proof
+        file: options.zsharp_path.clone(),
+        mode: Mode::Proof,
+        opt_level: options.opt_level,
+        verbose_opt: options.verbose_opt,
     };
-    let entry_regs: Vec<Integer> = func_inputs.iter().map(|i| Integer::from(*i)).collect();
-    let (cs, block_id_list, _, block_inputs_list, mem_list) = ZSharpFE::interpret(inputs, &entry_regs);
-    print!("\n\nReturn value: ");
-    cs.pretty(&mut std::io::stdout().lock())
-        .expect("error pretty-printing value");
-    println!();
-    for i in 0..block_id_list.len() {
-        println!("BLOCK ID: {}", block_id_list[i]);
-        let mut str_list = Vec::new();
-        for (name, val) in &block_inputs_list[i] {
-            str_list.push(format!("{}: {:?}", name, val));
+    let mut exec_num = 0;
+    let mut exec_sink = |exec: BlockExecution| {
+        if options.print_trace || options.print_regs || options.print_mem {
+            println!("EXEC {}: BLOCK {}", exec_num, exec.blk_id);
+        }
+        if options.print_regs {
+            print_regs("IN: ", &exec.reg_in);
+            print_regs("OUT:", &exec.reg_out);
+        }
+        if options.print_mem {
+            for (addr, data) in &exec.phy_mem_op {
+                println!("  PHY ADDR: {}, DATA: {}", value_to_string(addr), value_to_string(data));
+            }
+            for [addr, data, ls, ts] in &exec.vir_mem_op {
+                println!("  VIR ADDR: {}, DATA: {}, LS: {}, TS: {}", value_to_string(addr), value_to_string(data), value_to_string(ls), value_to_string(ts));
+            }
+        }
+        exec_num += 1;
+    };
+    let result = if options.debug {
+        let mut debugger = BlockDebugger::new();
+        for bp in &options.breakpoints {
+            debugger.add_breakpoint(bp);
         }
-        str_list.sort();
-        for s in str_list {
-            println!("{}", s);
+        for addr in &options.phy_watchpoints {
+            debugger.add_watchpoint(true, *addr);
         }
+        for addr in &options.vir_watchpoints {
+            debugger.add_watchpoint(false, *addr);
+        }
+        if options.breakpoints.len() > 0 {
+            debugger.run_to_breakpoint();
+        }
+        ZSharpFE::debug(inputs, &mut entry_regs, &entry_stacks, &entry_arrays, &entry_witnesses, debugger, &mut exec_sink)
+    } else {
+        ZSharpFE::interpret_stream(inputs, &mut entry_regs, &entry_stacks, &entry_arrays, &entry_witnesses, &mut exec_sink)
+    };
+    let (cs, prog_reg_in, init_phy_mem_list, init_vir_mem_list) = result.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1)
+    });
+
+    if options.print_regs {
+        println!("PROGRAM INPUT");
+        print_regs("IN: ", &prog_reg_in);
     }
-    println!("MEM");
-    for (addr, data) in mem_list {
-        println!("ADDR: {:?}", addr);
-        println!("DATA: {:?}", data);
+    if options.print_mem {
+        println!("INIT MEM");
+        for [addr, data] in &init_phy_mem_list {
+            println!("  PHY ADDR: {}, DATA: {}", value_to_string(addr), value_to_string(data));
+        }
+        for [addr, data, ls, ts] in &init_vir_mem_list {
+            println!("  VIR ADDR: {}, DATA: {}, LS: {}, TS: {}", value_to_string(addr), value_to_string(data), value_to_string(ls), value_to_string(ts));
+        }
     }
+    println!("\nBlocks executed: {}", exec_num);
+    print!("Return value: ");
+    cs.pretty(&mut std::io::stdout().lock())
+        .expect("error pretty-printing value");
+    println!();
 }
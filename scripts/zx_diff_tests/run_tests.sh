@@ -0,0 +1,38 @@
+#!/bin/bash
+
+# Run every program at every opt level and compare the results
+# Programs in this directory and the example programs in EXAMPLES are always tested,
+# extra programs (e.g. ../zok_tests/benchmarks/*.zok) can be passed as arguments
+# The inputs and witnesses of PROG.zok are read from PROG.input and PROG.witness,
+# except for the inputs of the example programs, which are read from inputs/PROG.input
+
+TESTDIR=$(dirname -- "$0")
+ZXD=${TESTDIR}/../../target/release/examples/zxd
+EXAMPLES="
+examples/ZoKrates/pf/2024_05_24_benny_bug.zok
+examples/ZoKrates/pf/2024_05_31_benny_bug.zok
+"
+error=0
+
+run_zxd() {
+    output=$(${ZXD} "$@" 2>&1)
+    if [ "$?" != "0" ]; then
+        echo "[failure] $1"
+        echo "$output" | tail -n 20
+        error=1
+    fi
+}
+
+echo Running zx differential tests:
+for i in ${TESTDIR}/*.zok; do
+    run_zxd "$i"
+done
+for i in ${EXAMPLES}; do
+    run_zxd "${TESTDIR}/../../$i" "${TESTDIR}/inputs/$(basename "$i" .zok).input"
+done
+for i in "$@"; do
+    run_zxd "$i"
+done
+echo Done.
+
+exit $error
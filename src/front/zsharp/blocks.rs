@@ -13,10 +13,15 @@ use log::debug;
 use zokrates_pest_ast::*;
 use crate::front::field_list::FieldList;
 use crate::front::zsharp::ZGen;
+use crate::front::zsharp::cfg;
 use crate::front::zsharp::Ty;
 use crate::front::zsharp::PathBuf;
 use crate::front::zsharp::pretty::*;
+use crate::target::r1cs::LookupTable;
 use core::cmp::max;
+use merlin::Transcript;
+use rug::Integer;
+use rug::integer::Order;
 use std::collections::{HashMap, BTreeMap, BTreeSet};
 use std::convert::TryInto;
 use crate::front::zsharp::*;
@@ -230,6 +235,52 @@ pub fn flatten_var(var_name: &str, ty: &Ty, var_list: &mut Vec<(String, Ty)>) {
     }
 }
 
+// Parameters of the hash behind sample_challenge, MiMC over the field of the proof:
+// the smallest exponent e for which x^e permutes the field, enough rounds for e^R to exceed the modulus,
+// and round constants drawn from a transcript, the first one being 0
+pub fn challenge_hash_params() -> (Integer, Vec<Integer>) {
+    let modulus = cfg().field().modulus().clone();
+    let order = Integer::from(&modulus - 1);
+    let mut exp = Integer::from(3);
+    while Integer::from(order.gcd_ref(&exp)) != 1 {
+        exp += 2;
+    }
+    let mut num_rounds = 0;
+    let mut degree = Integer::from(1);
+    while degree < modulus {
+        degree *= &exp;
+        num_rounds += 1;
+    }
+    let mut transcript = Transcript::new(b"zx sample_challenge");
+    transcript.append_message(b"modulus", &modulus.to_digits::<u8>(Order::Lsf));
+    let round_consts = (0..num_rounds).map(|r| {
+        if r == 0 {
+            return Integer::from(0);
+        }
+        let mut bytes = [0; 64];
+        transcript.challenge_bytes(b"round constant", &mut bytes);
+        Integer::from_digits(&bytes, Order::Lsf) % &modulus
+    }).collect();
+    (exp, round_consts)
+}
+
+// The challenge that sample_challenge returns for the entries, given the seed of its call site:
+// MiMC in Miyaguchi-Preneel mode, starting from the seed
+pub fn challenge_hash(seed: &Integer, entries: &[Integer]) -> Integer {
+    let modulus = cfg().field().modulus().clone();
+    let (exp, round_consts) = challenge_hash_params();
+    let mut h = seed.clone();
+    for a in entries {
+        let mut x = a.clone();
+        for c in &round_consts {
+            x = Integer::from(&x + &h) + c;
+            x = x.pow_mod(&exp, &modulus).unwrap();
+        }
+        h = (x + Integer::from(&h * 2) + a) % &modulus;
+    }
+    h
+}
+
 #[derive(Clone)]
 pub struct Block<'ast> {
     pub name: usize,
@@ -401,6 +452,13 @@ pub struct VarScopeInfo {
     var_stack: HashMap<(String, String), Vec<(usize, Ty)>>,
     // Set of names of all of the constants
     constants: BTreeSet<(String, Ty)>,
+    // Multi-dimensional arrays declared through array_decl are stored flat in row-major order
+    // Record the number of dimensions of every such array (by extended name)
+    // The length of the k-th dimension is stored in the variable <arr_name>^dim@k
+    flat_arrays: HashMap<String, usize>,
+    // Functions whose return type is declared array_decl, and the number of dimensions of the returned array
+    // The length of the k-th dimension is returned in %RET^dim@k
+    flat_returns: HashMap<String, usize>,
 }
 
 impl VarScopeInfo {
@@ -409,6 +467,8 @@ impl VarScopeInfo {
             var_version: HashMap::new(),
             var_stack: HashMap::new(),
             constants: BTreeSet::new(),
+            flat_arrays: HashMap::new(),
+            flat_returns: HashMap::new(),
         }
     }
 
@@ -580,19 +640,19 @@ impl<'ast> ArrayInitInfo<'ast> {
 
 impl<'ast> ZGen<'ast> {
     // Returns blocks, block_size, and arguments and their types
-    pub fn bl_gen_entry_fn(&'ast self, n: &str) -> (Vec<Block<'ast>>, usize, Vec<(String, Ty)>) {
+    pub fn bl_gen_entry_fn(&'ast self, n: &str) -> Result<(Vec<Block<'ast>>, usize, Vec<(String, Ty)>), BlockError> {
         debug!("Block Gen entry: {}", n);
+        self.bl_err_loc.replace(None);
+        self.bl_private_inputs.borrow_mut().clear();
+        self.bl_num_challenges.set(0);
 
         let (f_file, f_name) = self.deref_import(n);
         if let Some(f) = self.functions.get(&f_file).and_then(|m| m.get(&f_name)) {
             if !f.generics.is_empty() {
-                panic!("const_entry_fn cannot be called on a generic function")
+                return Err(self.bl_error(BlockStage::Generation, format!("Entry function '{}' cannot be generic", f_name), Some(&f.span)));
             }
         } else {
-            panic!(
-                "No function '{:?}//{}' attempting const_entry_fn",
-                &f_file, &f_name
-            );
+            return Err(self.bl_error(BlockStage::Generation, format!("No entry function '{}' in {}", f_name, f_file.display()), None));
         }
 
         // Blocks for main function
@@ -619,11 +679,11 @@ impl<'ast> ZGen<'ast> {
         // constants
         let files = &self.asts.iter().map(|(p, _)| p).collect();
         (blks, blks_len, var_scope_info) = self.bl_gen_constants(blks, blks_len, &files, var_scope_info)
-            .unwrap_or_else(|e| panic!("gen_constants failed: {}", e));
+            .map_err(|e| self.bl_error(BlockStage::Generation, e, None))?;
         // main functions
         let inputs: Vec<(String, Ty)>;
         (blks, blks_len, inputs, var_scope_info) = self.bl_gen_function_init_::<true>(blks, blks_len, f_file.clone(), f_name, var_scope_info)
-            .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e));
+            .map_err(|e| self.bl_error(BlockStage::Generation, e, None))?;
 
         func_blk_map.insert("main".to_string(), (0, blks_len - 1, false));
         // other functions
@@ -632,7 +692,7 @@ impl<'ast> ZGen<'ast> {
                 if f_name != "main" {
                     let entry_bl = blks_len;
                     (blks, blks_len, _, var_scope_info) = self.bl_gen_function_init_::<false>(blks, blks_len, func_file.clone(), f_name.to_string(), var_scope_info)
-                        .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e));
+                        .map_err(|e| self.bl_error(BlockStage::Generation, e, None))?;
                     func_blk_map.insert(f_name.to_string(), (entry_bl, blks_len - 1, f.inline.is_some()));
                 }
             }
@@ -642,9 +702,10 @@ impl<'ast> ZGen<'ast> {
         let mut new_blks = Vec::new();
         let mut next_new_blk_label = blks_len;
         for next_bl in 0..blks_len {
-            (new_blks, next_new_blk_label) = self.bl_gen_func_call_to_bl_label(&blks, &func_blk_map, new_blks, next_new_blk_label, next_bl, 0, &BTreeMap::new());
+            (new_blks, next_new_blk_label) = self.bl_gen_func_call_to_bl_label(&blks, &func_blk_map, new_blks, next_new_blk_label, next_bl, 0, &BTreeMap::new())
+                .map_err(|e| self.bl_error(BlockStage::Generation, e, None))?;
         }
-        (new_blks.into_iter().map(|i| i.unwrap()).collect(), 0, inputs)
+        Ok((new_blks.into_iter().map(|i| i.unwrap()).collect(), 0, inputs))
     }
 
     fn bl_gen_func_call_to_bl_label(
@@ -656,7 +717,7 @@ impl<'ast> ZGen<'ast> {
         cur_bl: usize,
         offset: usize,
         label_map: &BTreeMap<usize, usize>,
-    ) -> (Vec<Option<Block<'ast>>>, usize) {
+    ) -> Result<(Vec<Option<Block<'ast>>>, usize), String> {
         let mut blk = Block::clone(cur_bl + offset, &blks[cur_bl]);
         // If we encounter any rp@ = <counter>, update <counter> to label_map[<counter>]
         for i in 0..blk.instructions.len() {
@@ -679,7 +740,7 @@ impl<'ast> ZGen<'ast> {
                         next_label_map.insert(next_bl, next_bl + offset);
                     }
                     for next_bl in *entry_bl..*exit_bl + 1 {
-                        (new_blks, next_new_blk_label) = self.bl_gen_func_call_to_bl_label(blks, func_blk_map, new_blks, next_new_blk_label, next_bl, offset, &next_label_map);
+                        (new_blks, next_new_blk_label) = self.bl_gen_func_call_to_bl_label(blks, func_blk_map, new_blks, next_new_blk_label, next_bl, offset, &next_label_map)?;
                     }
                 } else {
                     blk.terminator = BlockTerminator::Transition(bl_coda(NextBlock::Label(*entry_bl)));
@@ -687,7 +748,7 @@ impl<'ast> ZGen<'ast> {
             },
             BlockTerminator::Transition(e) => {
                 // Update terminator
-                blk.terminator = BlockTerminator::Transition(bl_trans_map(e, label_map));
+                blk.terminator = BlockTerminator::Transition(bl_trans_map(e, label_map)?);
             },
             BlockTerminator::ProgTerm => {}
         }
@@ -696,7 +757,7 @@ impl<'ast> ZGen<'ast> {
             new_blks.extend(vec![None; cur_bl + offset + 1 - new_blks.len()]);
         }
         new_blks[cur_bl + offset] = Some(blk);
-        (new_blks, next_new_blk_label)
+        Ok((new_blks, next_new_blk_label))
     }
 
     // Treat constants as normal variables and pass all of them along every function call
@@ -729,7 +790,7 @@ impl<'ast> ZGen<'ast> {
                                 span: Span::new("", 0, 0).unwrap()
                             })],
                             expression: c.expression.clone(),
-                            span: Span::new("", 0, 0).unwrap()
+                            span: c.span.clone()
                         };
                         (blks, blks_len, var_scope_info) = self.bl_gen_assign_::<true>(blks, blks_len, &d, "main", 0, var_scope_info)?;
                         var_scope_info.add_constant(&c.id.value, self.type_impl_::<false>(&c.ty)?);
@@ -785,8 +846,15 @@ impl<'ast> ZGen<'ast> {
             let ret_ty = self.type_impl_::<false>(&ret_type)?;
             if let Ty::Struct(..) = ret_ty {
                 if IS_MAIN {
-                    panic!("%RET cannot be a struct!");
+                    return Err(format!("Return value of the entry function cannot be a struct"));
+                }
+            }
+
+            if let Some(num_dims) = Self::bl_flat_dims_(&f.returns_flat, ret_type, &f_name)? {
+                if IS_MAIN {
+                    return Err(format!("Return value of the entry function cannot be a multi-dimensional array_decl array"));
                 }
+                var_scope_info.flat_returns.insert(f_name.to_string(), num_dims);
             }
 
             // Create new Block, initial scope is 0
@@ -798,11 +866,32 @@ impl<'ast> ZGen<'ast> {
                 let p_id = p.id.value.clone();
                 let p_ty = self.type_impl_::<false>(&p.ty)?;
                 var_scope_info.declare_var(&p_id, &f_name, 0, p_ty.clone());
+                // The dimensions of a multi-dimensional parameter are assigned by the caller
+                if let Some(num_dims) = Self::bl_flat_dims_(&p.flat, &p.ty, &p_id)? {
+                    if IS_MAIN {
+                        return self.bl_locate(Err(format!("Parameter {} of the entry function cannot be a multi-dimensional array_decl array", p_id)), &p.span);
+                    }
+                    let p_extended_name = var_scope_info.reference_var(&p_id, &f_name)?.0;
+                    var_scope_info.flat_arrays.insert(p_extended_name, num_dims);
+                    for k in 0..num_dims {
+                        var_scope_info.declare_var(&format!("{}^dim@{}", p_id, k), &f_name, 0, Ty::Uint(32));
+                    }
+                }
+                // Private inputs of main are not revealed to the verifier
+                let p_private = IS_MAIN && matches!(p.visibility, Some(Visibility::Private(_)));
                 // Flatten out inputs
                 let mut flattened_p = Vec::new();
                 flatten_var(&p_id, &p_ty, &mut flattened_p);
-                for (p_entry, _) in flattened_p {
-                    inputs.push(var_scope_info.reference_var(&p_entry, &f_name)?.clone());
+                for (p_entry, p_entry_ty) in flattened_p {
+                    // The contents of input arrays are part of the initial memory, which is always public
+                    if p_private && matches!(p_entry_ty, Ty::Array(..) | Ty::MutArray(..)) {
+                        return self.bl_locate(Err(format!("Private array parameter {} of main is not supported, the contents of input arrays are public", p_id)), &p.span);
+                    }
+                    let input = var_scope_info.reference_var(&p_entry, &f_name)?.clone();
+                    if p_private {
+                        self.bl_private_inputs.borrow_mut().insert(input.0.clone());
+                    }
+                    inputs.push(input);
                 }
             }
             // Declare all constants, if not main
@@ -882,6 +971,25 @@ impl<'ast> ZGen<'ast> {
                 let p_ty = self.type_impl_::<false>(&p.ty)?;
                 var_scope_info.declare_var(&p_id, &f_name, 0, p_ty.clone());
                 (blks, blks_len) = self.bl_gen_def_stmt_(blks, blks_len, &p_id, &a, &p_ty, &f_name, &caller_name, &var_scope_info)?;
+                // Pass the dimensions of a multi-dimensional array along with its pointer
+                let p_dims = Self::bl_flat_dims_(&p.flat, &p.ty, &p_id)?;
+                let (a_name, a_dims) = if let Expression::Identifier(ie) = &a {
+                    (ie.value.clone(), var_scope_info.flat_arrays.get(&ie.value).cloned())
+                } else {
+                    ("".to_string(), None)
+                };
+                match (p_dims, a_dims) {
+                    (None, None) => {}
+                    (Some(p_num_dims), Some(a_num_dims)) if p_num_dims == a_num_dims => {
+                        for k in 0..p_num_dims {
+                            var_scope_info.declare_var(&format!("{}^dim@{}", p_id, k), &f_name, 0, Ty::Uint(32));
+                        }
+                        (blks, blks_len) = self.bl_gen_flat_dims_copy_(blks, blks_len, &p_id, &f_name, &a_name, &caller_name, p_num_dims, &var_scope_info)?;
+                    }
+                    _ => {
+                        return Err(format!("Cannot pass {} to parameter {} of function {}: multi-dimensional arrays can only be passed to array_decl parameters of the same dimension!", a_name, p_id, f_name));
+                    }
+                }
             }
             // Assign all constants from one function to another
             for (c_name, c_ty) in var_scope_info.constants.clone() {
@@ -955,6 +1063,20 @@ impl<'ast> ZGen<'ast> {
                 span: Span::new("", 0, 0).unwrap()
             });
             (blks, blks_len) = self.bl_gen_def_stmt_(blks, blks_len, &ret_name, &ret_expr, &ret_ty, &caller_name, &f_name, &var_scope_info)?;
+            // Receive the dimensions of a multi-dimensional return value
+            if let Some(num_dims) = Self::bl_flat_dims_(&f.returns_flat, ret_type, &f_name)? {
+                let ret_extended_name = var_scope_info.reference_var(&ret_name, &caller_name)?.0;
+                var_scope_info.flat_arrays.insert(ret_extended_name, num_dims);
+                for k in 0..num_dims {
+                    let ret_dim_name = format!("{}^dim@{}", ret_name, k);
+                    var_scope_info.declare_var(&ret_dim_name, &caller_name, caller_scope, Ty::Uint(32));
+                    let ret_dim_expr = Expression::Identifier(IdentifierExpression {
+                        value: format!("%RET^dim@{}.{}", k, f_name),
+                        span: Span::new("", 0, 0).unwrap()
+                    });
+                    (blks, blks_len) = self.bl_gen_def_stmt_(blks, blks_len, &ret_dim_name, &ret_dim_expr, &Ty::Uint(32), &caller_name, &f_name, &var_scope_info)?;
+                }
+            }
         }
 
         Ok((blks, blks_len, 0, var_scope_info, func_count))
@@ -967,6 +1089,20 @@ impl<'ast> ZGen<'ast> {
     // result[1]: length of the generated blocks
     // result[2]: Scoping information of each variable
     fn bl_gen_stmt_<const IS_MAIN: bool>(
+        &'ast self,
+        blks: Vec<Block<'ast>>,
+        blks_len: usize,
+        s: &'ast Statement<'ast>,
+        ret_ty: &Ty,
+        f_name: &str,
+        var_scope_info: VarScopeInfo,
+        num_exec_bound: usize,
+        cur_scope: usize,
+    ) -> Result<(Vec<Block>, usize, VarScopeInfo), String> {
+        self.bl_locate(self.bl_gen_stmt_impl_::<IS_MAIN>(blks, blks_len, s, ret_ty, f_name, var_scope_info, num_exec_bound, cur_scope), s.span())
+    }
+
+    fn bl_gen_stmt_impl_<const IS_MAIN: bool>(
         &'ast self,
         mut blks: Vec<Block<'ast>>,
         mut blks_len: usize,
@@ -989,6 +1125,22 @@ impl<'ast> ZGen<'ast> {
                 let ret_expr: Expression;
                 (blks, blks_len, var_scope_info, ret_expr, _, _, _, _) = 
                     self.bl_gen_expr_::<IS_MAIN>(blks, blks_len, &r.expressions[0], f_name, 0, 0, 0, 0, var_scope_info)?;
+                // Return the dimensions of a multi-dimensional array along with its pointer
+                let ret_dims = var_scope_info.flat_returns.get(f_name).cloned();
+                let (r_name, r_dims) = if let Expression::Identifier(ie) = &ret_expr {
+                    (ie.value.clone(), var_scope_info.flat_arrays.get(&ie.value).cloned())
+                } else {
+                    ("".to_string(), None)
+                };
+                match (ret_dims, r_dims) {
+                    (None, None) => {}
+                    (Some(ret_num_dims), Some(r_num_dims)) if ret_num_dims == r_num_dims => {
+                        (blks, blks_len) = self.bl_gen_flat_dims_copy_(blks, blks_len, "%RET", f_name, &r_name, f_name, ret_num_dims, &var_scope_info)?;
+                    }
+                    _ => {
+                        return Err(format!("Cannot return {} from function {}: multi-dimensional arrays can only be returned as an array_decl return type of the same dimension!", r_name, f_name));
+                    }
+                }
                 // Convert the statement to %RET = ret_expr
                 // Note return variable should be reference as %RET.<f_name> to allow different type for different functions
                 let ret_name = "%RET".to_string();
@@ -1029,6 +1181,9 @@ impl<'ast> ZGen<'ast> {
                 // Initialize the scoped iterator
                 let v_name = it.index.value.clone();
                 let ty = self.type_impl_::<false>(&it.ty)?;
+                if !matches!(ty, Ty::Field | Ty::Uint(_)) {
+                    return Err(format!("Type not supported for loop iterator: {}.", ty));
+                }
                 let new_v_name = var_scope_info.declare_var(&v_name, f_name, cur_scope, ty.clone());
 
                 let from_expr: Expression;
@@ -1237,7 +1392,55 @@ impl<'ast> ZGen<'ast> {
             Statement::Definition(d) => {
                 (blks, blks_len, var_scope_info) = self.bl_gen_assign_::<IS_MAIN>(blks, blks_len, d, f_name, cur_scope, var_scope_info)?;
             }
-            Statement::CondStore(_) => { panic!("Conditional store statements unsupported.") }
+            Statement::CondStore(c) => {
+                // cond_store(A, index, value, condition) is generated as if condition then A[index] = value endif,
+                // so index is only evaluated when the store happens
+                let cond_expr: Expression;
+                (blks, blks_len, var_scope_info, cond_expr, _, _, _, _) = 
+                    self.bl_gen_expr_::<IS_MAIN>(blks, blks_len, &c.condition, f_name, 0, 0, 0, 0, var_scope_info)?;
+
+                let head_state = blks_len - 1;
+
+                // If statement
+                cur_scope = self.bl_gen_enter_scope_(cur_scope)?;
+                blks.push(Block::new(blks_len, num_exec_bound, f_name.to_string(), cur_scope));
+                blks_len += 1;
+                let store = DefinitionStatement {
+                    lhs: vec![TypedIdentifierOrAssignee::Assignee(Assignee {
+                        id: c.array.clone(),
+                        accesses: vec![AssigneeAccess::Select(ArrayAccess {
+                            expression: RangeOrExpression::Expression(c.index.clone()),
+                            span: c.span
+                        })],
+                        span: c.span
+                    })],
+                    expression: c.value.clone(),
+                    span: c.span
+                };
+                (blks, blks_len, var_scope_info) = self.bl_gen_assign_::<IS_MAIN>(blks, blks_len, &store, f_name, cur_scope, var_scope_info)?;
+                (var_scope_info, cur_scope) = self.bl_gen_exit_scope_(var_scope_info, f_name, cur_scope)?;
+                let if_tail_state = blks_len - 1;
+
+                // Empty else statement
+                cur_scope = self.bl_gen_enter_scope_(cur_scope)?;
+                blks.push(Block::new(blks_len, num_exec_bound, f_name.to_string(), cur_scope));
+                blks_len += 1;
+                (var_scope_info, cur_scope) = self.bl_gen_exit_scope_(var_scope_info, f_name, cur_scope)?;
+                let else_tail_state = blks_len - 1;
+
+                // Block Transition, the same as a conditional statement
+                blks[head_state].terminator = BlockTerminator::Transition(
+                    bl_trans(
+                        cond_expr, 
+                        NextBlock::Label(head_state + 1), 
+                        NextBlock::Label(if_tail_state + 1)
+                    )
+                );
+                blks[if_tail_state].terminator = BlockTerminator::Transition(bl_coda(NextBlock::Label(else_tail_state + 1)));
+
+                blks.push(Block::new(blks_len, num_exec_bound, f_name.to_string(), cur_scope));
+                blks_len += 1;
+            }
             Statement::Witness(w, ) => {
                 let wit_ty = self.type_impl_::<false>(&w.ty)?;
                 let wit_name = w.id.value.to_string();
@@ -1251,10 +1454,46 @@ impl<'ast> ZGen<'ast> {
                 let arr_extended_name = var_scope_info.declare_var(&arr_name, f_name, cur_scope, arr_ty.clone());
                 if let Type::Array(aty) = &a.ty {
                     let index_ty = self.bl_gen_type_(&aty.dimensions[0].1, f_name, &var_scope_info)?;
-                    let new_len_expr: Expression;
-                    (blks, blks_len, var_scope_info, new_len_expr, _, _, _, _) = 
-                        self.bl_gen_expr_::<IS_MAIN>(blks, blks_len, &aty.dimensions[0].1, f_name, 0, 0, 0, 0, var_scope_info)?;
-                    let entry_ty = if let Ty::Array(_, _, entry_ty) = arr_ty { *entry_ty.clone() } else { unreachable!() };
+                    let mut new_dim_exprs = Vec::new();
+                    for (_, dim) in &aty.dimensions {
+                        let new_dim_expr: Expression;
+                        (blks, blks_len, var_scope_info, new_dim_expr, _, _, _, _) = 
+                            self.bl_gen_expr_::<IS_MAIN>(blks, blks_len, dim, f_name, 0, 0, 0, 0, var_scope_info)?;
+                        new_dim_exprs.push(new_dim_expr);
+                    }
+                    // Entry type is obtained by stripping out every dimension
+                    let mut entry_ty = arr_ty;
+                    for _ in 0..new_dim_exprs.len() {
+                        entry_ty = if let Ty::Array(_, _, entry_ty) = entry_ty { *entry_ty.clone() } else { unreachable!() };
+                    }
+
+                    let new_len_expr = if new_dim_exprs.len() == 1 {
+                        var_scope_info.flat_arrays.remove(&arr_extended_name);
+                        new_dim_exprs.pop().unwrap()
+                    } else {
+                        // Multi-dimensional array: store every dimension in arr^dim@k and allocate their product
+                        var_scope_info.flat_arrays.insert(arr_extended_name.clone(), new_dim_exprs.len());
+                        let mut len_expr: Option<Expression> = None;
+                        for (k, new_dim_expr) in new_dim_exprs.iter().enumerate() {
+                            let dim_name = format!("{}^dim@{}", arr_name, k);
+                            let dim_extended_name = var_scope_info.declare_var(&dim_name, f_name, cur_scope, index_ty.clone());
+                            (blks, blks_len) = self.bl_gen_def_stmt_(blks, blks_len, &dim_name, new_dim_expr, &index_ty, f_name, f_name, &var_scope_info)?;
+                            let dim_expr = Expression::Identifier(IdentifierExpression {
+                                value: dim_extended_name,
+                                span: Span::new("", 0, 0).unwrap()
+                            });
+                            len_expr = Some(match len_expr {
+                                None => dim_expr,
+                                Some(prev_expr) => Expression::Binary(BinaryExpression {
+                                    op: BinaryOperator::Mul,
+                                    left: Box::new(prev_expr),
+                                    right: Box::new(dim_expr),
+                                    span: Span::new("", 0, 0).unwrap()
+                                })
+                            });
+                        }
+                        len_expr.unwrap()
+                    };
                     
                     // Compute the actual allocated size
                     let new_size_expr = self.bl_gen_pointer_offset_(new_len_expr, &Vec::new(), &index_ty, &entry_ty)?;
@@ -1351,11 +1590,24 @@ impl<'ast> ZGen<'ast> {
                             AssigneeAccess::Select(s) => {
                                 let (new_l, arr_ty) = var_scope_info.reference_var(&l_name, f_name)?;
                                 if let Ty::Array(ro, _, entry_ty) = arr_ty {
-                                    let struct_ty = *entry_ty.clone();
                                     let mut entry_ty = *entry_ty.clone();
-                                    Self::bl_gen_type_check(&entry_ty, &rhs_ty)?;
                                     skip_stmt_gen = true;
                                     if let RangeOrExpression::Expression(e) = &s.expression {
+                                        // A multi-dimensional array consumes one index per dimension
+                                        let mut index_list = vec![e];
+                                        let num_dims = var_scope_info.flat_arrays.get(&new_l).cloned().unwrap_or(1);
+                                        while index_list.len() < num_dims {
+                                            acc_counter += 1;
+                                            if let Some(AssigneeAccess::Select(ArrayAccess { expression: RangeOrExpression::Expression(e), .. })) = l.accesses.get(acc_counter) {
+                                                index_list.push(e);
+                                                entry_ty = if let Ty::Array(_, _, entry_ty) = entry_ty { *entry_ty.clone() } else { unreachable!() };
+                                            } else {
+                                                return Err(format!("Multi-dimensional array {} must be accessed with exactly {} indices!", l_name, num_dims));
+                                            }
+                                        }
+                                        let struct_ty = entry_ty.clone();
+                                        Self::bl_gen_type_check(&entry_ty, &rhs_ty)?;
+
                                         // For all subsequent struct member accesses, compute index and rhs
                                         let mut member_accesses = Vec::new();
                                         acc_counter += 1;
@@ -1376,9 +1628,18 @@ impl<'ast> ZGen<'ast> {
 
                                         // Process the index
                                         let index_ty = self.bl_gen_type_(&e, f_name, &var_scope_info)?;
-                                        let new_index_expr: Expression;
-                                        (blks, blks_len, var_scope_info, new_index_expr, _, _, _, _) = 
-                                            self.bl_gen_expr_::<IS_MAIN>(blks, blks_len, &e, f_name, 0, 0, 0, 0, var_scope_info)?;
+                                        let mut new_index_exprs = Vec::new();
+                                        for e in index_list {
+                                            let new_index_expr: Expression;
+                                            (blks, blks_len, var_scope_info, new_index_expr, _, _, _, _) = 
+                                                self.bl_gen_expr_::<IS_MAIN>(blks, blks_len, e, f_name, 0, 0, 0, 0, var_scope_info)?;
+                                            new_index_exprs.push(new_index_expr);
+                                        }
+                                        let new_index_expr = if num_dims == 1 { new_index_exprs.pop().unwrap() } else {
+                                            let new_index_expr: Expression;
+                                            (blks, new_index_expr) = self.bl_gen_row_major_index_(blks, blks_len, &l_name, new_index_exprs, f_name, &var_scope_info)?;
+                                            new_index_expr
+                                        };
                                         // Perform pointer arithmetics
                                         (blks, blks_len) = self.bl_gen_store_(blks, blks_len, &new_l, &index_ty, &new_index_expr, &rhs_expr, &entry_ty, f_name, &var_scope_info, false, &struct_ty, &member_accesses, ro)?;
                                     } else {
@@ -1396,6 +1657,8 @@ impl<'ast> ZGen<'ast> {
                     if !skip_stmt_gen {
                         (blks, blks_len) = 
                             self.bl_gen_def_stmt_(blks, blks_len, &l_name, &rhs_expr, &rhs_ty, f_name, f_name, &var_scope_info)?;
+                        (blks, blks_len, var_scope_info) = 
+                            self.bl_gen_flat_array_alias_(blks, blks_len, &l_name, &rhs_expr, f_name, None, var_scope_info)?;
                     }
                 }
                 TypedIdentifierOrAssignee::TypedIdentifier(l) => {
@@ -1406,14 +1669,110 @@ impl<'ast> ZGen<'ast> {
                     var_scope_info.declare_var(&l_name, f_name, cur_scope, lhs_ty.clone());
                     (blks, blks_len) = 
                         self.bl_gen_def_stmt_(blks, blks_len, &l_name, &rhs_expr, &rhs_ty, f_name, f_name, &var_scope_info)?;
+                    (blks, blks_len, var_scope_info) = 
+                        self.bl_gen_flat_array_alias_(blks, blks_len, &l_name, &rhs_expr, f_name, Some(cur_scope), var_scope_info)?;
                 }
             }
         } else {
-            panic!("Statement with no LHS!");
+            return Err(format!("Statement with no LHS!"));
+        }
+        Ok((blks, blks_len, var_scope_info))
+    }
+
+    // Given a pointer assignment l = r_expr, if r_expr is a multi-dimensional array, copy over all of its dimensions
+    // If l is newly declared in cur_scope, l also becomes a multi-dimensional array
+    // Otherwise, l must already be a multi-dimensional array with the same number of dimensions
+    fn bl_gen_flat_array_alias_(
+        &'ast self,
+        mut blks: Vec<Block<'ast>>,
+        mut blks_len: usize,
+        l: &str,
+        new_r_expr: &Expression<'ast>,
+        f_name: &str,
+        decl_scope: Option<usize>,
+        mut var_scope_info: VarScopeInfo,
+    ) -> Result<(Vec<Block>, usize, VarScopeInfo), String> {
+        let (new_r, r_dims) = if let Expression::Identifier(ie) = new_r_expr {
+            (ie.value.clone(), var_scope_info.flat_arrays.get(&ie.value).cloned())
+        } else {
+            ("".to_string(), None)
+        };
+        let new_l = var_scope_info.reference_var(l, f_name)?.0;
+        let l_dims = var_scope_info.flat_arrays.get(&new_l).cloned();
+        match (r_dims, l_dims, decl_scope) {
+            (None, None, _) => {}
+            // l shadows a multi-dimensional array
+            (None, Some(_), Some(_)) => {
+                var_scope_info.flat_arrays.remove(&new_l);
+            }
+            (Some(num_dims), _, Some(cur_scope)) => {
+                var_scope_info.flat_arrays.insert(new_l.clone(), num_dims);
+                for k in 0..num_dims {
+                    let (dim_name, dim_ty) = var_scope_info.reference_var(&format!("{}^dim@{}", new_r.split(".").next().unwrap_or(""), k), f_name)?;
+                    let l_dim_name = format!("{}^dim@{}", l, k);
+                    var_scope_info.declare_var(&l_dim_name, f_name, cur_scope, dim_ty.clone());
+                    let dim_expr = Expression::Identifier(IdentifierExpression {
+                        value: dim_name,
+                        span: Span::new("", 0, 0).unwrap()
+                    });
+                    (blks, blks_len) = self.bl_gen_def_stmt_(blks, blks_len, &l_dim_name, &dim_expr, &dim_ty, f_name, f_name, &var_scope_info)?;
+                }
+            }
+            (Some(r_num_dims), Some(l_num_dims), None) if r_num_dims == l_num_dims => {
+                for k in 0..r_num_dims {
+                    let (dim_name, dim_ty) = var_scope_info.reference_var(&format!("{}^dim@{}", new_r.split(".").next().unwrap_or(""), k), f_name)?;
+                    let dim_expr = Expression::Identifier(IdentifierExpression {
+                        value: dim_name,
+                        span: Span::new("", 0, 0).unwrap()
+                    });
+                    (blks, blks_len) = self.bl_gen_def_stmt_(blks, blks_len, &format!("{}^dim@{}", l, k), &dim_expr, &dim_ty, f_name, f_name, &var_scope_info)?;
+                }
+            }
+            _ => {
+                return Err(format!("Cannot assign {} to {}: multi-dimensional arrays can only be assigned to multi-dimensional arrays of the same dimension!", new_r, l));
+            }
         }
         Ok((blks, blks_len, var_scope_info))
     }
 
+    // If a parameter or return type is declared array_decl, return its number of dimensions
+    // An array_decl array of a single dimension is an ordinary array
+    fn bl_flat_dims_(flat: &Option<FlatArray>, ty: &Type, name: &str) -> Result<Option<usize>, String> {
+        match (flat, ty) {
+            (None, _) => Ok(None),
+            (Some(_), Type::Array(aty)) => Ok(if aty.dimensions.len() > 1 { Some(aty.dimensions.len()) } else { None }),
+            (Some(_), _) => Err(format!("{} is declared array_decl but is not an array!", name)),
+        }
+    }
+
+    // Copy the dimensions of multi-dimensional array r_extended_name in r_f_name to l^dim@k in l_f_name
+    // Dimensions passed across functions are always u32
+    fn bl_gen_flat_dims_copy_(
+        &'ast self,
+        mut blks: Vec<Block<'ast>>,
+        mut blks_len: usize,
+        l: &str,
+        l_f_name: &str,
+        r_extended_name: &str,
+        r_f_name: &str,
+        num_dims: usize,
+        var_scope_info: &VarScopeInfo,
+    ) -> Result<(Vec<Block>, usize), String> {
+        let r = r_extended_name.split(".").next().unwrap_or("");
+        for k in 0..num_dims {
+            let (dim_name, dim_ty) = var_scope_info.reference_var(&format!("{}^dim@{}", r, k), r_f_name)?;
+            if dim_ty != Ty::Uint(32) {
+                return Err(format!("Dimensions of multi-dimensional array {} must be u32 to be passed across functions!", r));
+            }
+            let dim_expr = Expression::Identifier(IdentifierExpression {
+                value: dim_name,
+                span: Span::new("", 0, 0).unwrap()
+            });
+            (blks, blks_len) = self.bl_gen_def_stmt_(blks, blks_len, &format!("{}^dim@{}", l, k), &dim_expr, &Ty::Uint(32), l_f_name, r_f_name, var_scope_info)?;
+        }
+        Ok((blks, blks_len))
+    }
+
     // Generate definition statements l = r_expr that might involve structs
     // Assume that r_expr has been processed
     // Allow f_name for LHS and RHS to be different for function calls
@@ -1620,6 +1979,134 @@ impl<'ast> ZGen<'ast> {
         Ok((blks, blks_len))
     }
 
+    // Generate ret^func_count = sample_challenge(a)
+    // Every call site reads its own seed from read-only memory right after the lookup tables. The seeds are drawn
+    // from the transcript of the public instance, so on their own they cannot bind the witnesses. The challenge
+    // is therefore the hash of the entries of a under the seed (see challenge_hash), computed by the block itself:
+    // like a challenge sent after a is committed, it is fixed only once a is, and every invocation with other
+    // entries receives another challenge.
+    fn bl_gen_challenge_(
+        &'ast self,
+        mut blks: Vec<Block<'ast>>,
+        mut blks_len: usize,
+        arg: &Expression<'ast>,
+        arg_ty: &Ty,
+        f_name: &str,
+        func_count: usize,
+        mut var_scope_info: VarScopeInfo
+    ) -> Result<(Vec<Block>, usize, VarScopeInfo), String> {
+        let (arg_ro, arg_len) = match arg_ty {
+            Ty::Array(ro, n, entry_ty) if **entry_ty == Ty::Field && *n > 0 => (*ro, *n),
+            _ => return Err(format!("sample_challenge expects a non-empty field array of known length, got {}", arg_ty)),
+        };
+        let arr_extended_name = if let Expression::Identifier(ie) = arg {
+            ie.value.clone()
+        } else {
+            return Err(format!("sample_challenge expects an array, got {}", arg.span().as_str()));
+        };
+        let num_chall = self.bl_num_challenges.get();
+        self.bl_num_challenges.set(num_chall + 1);
+        debug!("Block Gen Challenge: {}", num_chall);
+
+        let field_lit = |val: &Integer| Expression::Literal(LiteralExpression::DecimalLiteral(DecimalLiteralExpression {
+            value: DecimalNumber {
+                value: val.to_string(),
+                span: Span::new("", 0, 0).unwrap()
+            },
+            suffix: Some(ty_to_dec_suffix(&ty_to_type(&Ty::Field).unwrap())),
+            span: Span::new("", 0, 0).unwrap()
+        }));
+        let binary = |op: BinaryOperator, left: Expression<'ast>, right: Expression<'ast>| Expression::Binary(BinaryExpression {
+            op,
+            left: Box::new(left),
+            right: Box::new(right),
+            span: Span::new("", 0, 0).unwrap()
+        });
+        let cur_scope = blks[blks_len - 1].scope;
+        // Every intermediate value of the hash is a new variable chall^func_count^k
+        let mut num_tmps = 0;
+        let mut declare_tmp = |var_scope_info: &mut VarScopeInfo| {
+            let tmp_name = format!("chall^{}^{}", func_count, num_tmps);
+            num_tmps += 1;
+            let tmp_extended_name = var_scope_info.declare_var(&tmp_name, f_name, cur_scope, Ty::Field);
+            (tmp_name, tmp_extended_name)
+        };
+        let ident = |extended_name: String| Expression::Identifier(IdentifierExpression {
+            value: extended_name,
+            span: Span::new("", 0, 0).unwrap()
+        });
+
+        // Pointer to the seeds
+        let (ptr_name, ptr_extended_name) = declare_tmp(&mut var_scope_info);
+        let chall_addr = LookupTable::memory_size(cfg().r1cs.lookup_width);
+        (blks, blks_len) = self.bl_gen_def_stmt_(blks, blks_len, &ptr_name, &field_lit(&Integer::from(chall_addr)), &Ty::Field, f_name, f_name, &var_scope_info)?;
+        let (seed_name, seed_extended_name) = declare_tmp(&mut var_scope_info);
+        (blks, blks_len) = self.bl_gen_load_(
+            blks,
+            blks_len,
+            &seed_name,
+            &ptr_extended_name,
+            &Ty::Field,
+            &field_lit(&Integer::from(num_chall)),
+            &Ty::Field,
+            f_name,
+            &var_scope_info,
+            &Ty::Field,
+            &Vec::new(),
+            true,
+        )?;
+        let mut h = ident(seed_extended_name);
+
+        // h = E_h(a[i]) + h + a[i] for every entry, where E_k(x) = x_R + k, x_0 = x, x_{r+1} = (x_r + k + c_r)^e
+        let (exp, round_consts) = challenge_hash_params();
+        for i in 0..arg_len {
+            let (entry_name, entry_extended_name) = declare_tmp(&mut var_scope_info);
+            (blks, blks_len) = self.bl_gen_load_(
+                blks,
+                blks_len,
+                &entry_name,
+                &arr_extended_name,
+                &Ty::Field,
+                &field_lit(&Integer::from(i)),
+                &Ty::Field,
+                f_name,
+                &var_scope_info,
+                &Ty::Field,
+                &Vec::new(),
+                arg_ro,
+            )?;
+            let entry = ident(entry_extended_name);
+            let mut x = entry.clone();
+            for c in &round_consts {
+                let (base_name, base_extended_name) = declare_tmp(&mut var_scope_info);
+                let base_expr = binary(BinaryOperator::Add, binary(BinaryOperator::Add, x, h.clone()), field_lit(c));
+                (blks, blks_len) = self.bl_gen_def_stmt_(blks, blks_len, &base_name, &base_expr, &Ty::Field, f_name, f_name, &var_scope_info)?;
+                // Square and multiply, from the most significant bit of e
+                let base = ident(base_extended_name);
+                x = base.clone();
+                for k in (0..exp.significant_bits() - 1).rev() {
+                    let (sq_name, sq_extended_name) = declare_tmp(&mut var_scope_info);
+                    (blks, blks_len) = self.bl_gen_def_stmt_(blks, blks_len, &sq_name, &binary(BinaryOperator::Mul, x.clone(), x), &Ty::Field, f_name, f_name, &var_scope_info)?;
+                    x = ident(sq_extended_name);
+                    if exp.get_bit(k) {
+                        let (mul_name, mul_extended_name) = declare_tmp(&mut var_scope_info);
+                        (blks, blks_len) = self.bl_gen_def_stmt_(blks, blks_len, &mul_name, &binary(BinaryOperator::Mul, x, base.clone()), &Ty::Field, f_name, f_name, &var_scope_info)?;
+                        x = ident(mul_extended_name);
+                    }
+                }
+            }
+            let (next_name, next_extended_name) = declare_tmp(&mut var_scope_info);
+            let next_expr = binary(BinaryOperator::Add, binary(BinaryOperator::Add, binary(BinaryOperator::Add, x, h.clone()), h), entry);
+            (blks, blks_len) = self.bl_gen_def_stmt_(blks, blks_len, &next_name, &next_expr, &Ty::Field, f_name, f_name, &var_scope_info)?;
+            h = ident(next_extended_name);
+        }
+
+        let ret_name = format!("ret^{}", func_count);
+        var_scope_info.declare_var(&ret_name, f_name, cur_scope, Ty::Field);
+        (blks, blks_len) = self.bl_gen_def_stmt_(blks, blks_len, &ret_name, &h, &Ty::Field, f_name, f_name, &var_scope_info)?;
+        Ok((blks, blks_len, var_scope_info))
+    }
+
     // Generate blocks from expressions
     // Return value:
     // result[0 ~ 2] follows bl_gen_stmt
@@ -1631,6 +2118,21 @@ impl<'ast> ZGen<'ast> {
     // Since the return value of all function calls are stored in %RET, we need to differentiate them if
     // multiple function calls occur in the same statement
     fn bl_gen_expr_<const IS_MAIN: bool>(
+        &'ast self,
+        blks: Vec<Block<'ast>>,
+        blks_len: usize,
+        e: &Expression<'ast>,
+        f_name: &str,
+        func_count: usize,
+        array_count: usize,
+        struct_count: usize,
+        load_count: usize,
+        var_scope_info: VarScopeInfo
+    ) -> Result<(Vec<Block>, usize, VarScopeInfo, Expression, usize, usize, usize, usize), String> {
+        self.bl_locate(self.bl_gen_expr_impl_::<IS_MAIN>(blks, blks_len, e, f_name, func_count, array_count, struct_count, load_count, var_scope_info), e.span())
+    }
+
+    fn bl_gen_expr_impl_<const IS_MAIN: bool>(
         &'ast self, 
         mut blks: Vec<Block<'ast>>,
         mut blks_len: usize,
@@ -1708,9 +2210,17 @@ impl<'ast> ZGen<'ast> {
                                 args.push(new_expr);                       
                             }
         
-                            // Do the function call
-                            (blks, blks_len, _, var_scope_info, func_count) =
-                                self.bl_gen_function_call_::<IS_MAIN>(blks, blks_len, args, callee_path.clone(), callee_name.clone(), func_count, var_scope_info)?;
+                            if self.stdlib.is_embed(&callee_path) && callee_name == "sample_challenge" {
+                                if args.len() != 1 {
+                                    return Err(format!("Got {} args to EMBED/sample_challenge, expected 1", args.len()));
+                                }
+                                let arg_ty = self.bl_gen_type_(&c.arguments.expressions[0], f_name, &var_scope_info)?;
+                                (blks, blks_len, var_scope_info) = self.bl_gen_challenge_(blks, blks_len, &args[0], &arg_ty, f_name, func_count, var_scope_info)?;
+                            } else {
+                                // Do the function call
+                                (blks, blks_len, _, var_scope_info, func_count) =
+                                    self.bl_gen_function_call_::<IS_MAIN>(blks, blks_len, args, callee_path.clone(), callee_name.clone(), func_count, var_scope_info)?;
+                            }
         
                             ret_name = format!("ret^{}", func_count);
                             func_count += 1;
@@ -1727,6 +2237,18 @@ impl<'ast> ZGen<'ast> {
                                 } else {
                                     return Err(format!("Loading from a variable {} that is not an array!", arr_extended_name));
                                 };
+                                // A multi-dimensional array consumes one index per dimension
+                                let mut index_list = vec![e];
+                                let num_dims = var_scope_info.flat_arrays.get(&arr_extended_name).cloned().unwrap_or(1);
+                                while index_list.len() < num_dims {
+                                    acc_counter += 1;
+                                    if let Some(Access::Select(ArrayAccess { expression: RangeOrExpression::Expression(e), .. })) = p.accesses.get(acc_counter) {
+                                        index_list.push(e);
+                                        load_ty = if let Ty::Array(_, _, load_ty) = load_ty { *load_ty.clone() } else { unreachable!() };
+                                    } else {
+                                        return Err(format!("Multi-dimensional array {} must be accessed with exactly {} indices!", arr_name, num_dims));
+                                    }
+                                }
                                 let struct_ty = load_ty.clone();
                                 let cur_scope = blks[blks_len - 1].scope;
 
@@ -1751,9 +2273,18 @@ impl<'ast> ZGen<'ast> {
 
                                 // Process the index
                                 let index_ty = self.bl_gen_type_(&e, f_name, &var_scope_info)?;
-                                let new_index_expr: Expression;
-                                (blks, blks_len, var_scope_info, new_index_expr, func_count, array_count, struct_count, load_count) = 
-                                    self.bl_gen_expr_::<IS_MAIN>(blks, blks_len, &e, f_name, func_count, array_count, struct_count, load_count, var_scope_info)?;
+                                let mut new_index_exprs = Vec::new();
+                                for e in index_list {
+                                    let new_index_expr: Expression;
+                                    (blks, blks_len, var_scope_info, new_index_expr, func_count, array_count, struct_count, load_count) = 
+                                        self.bl_gen_expr_::<IS_MAIN>(blks, blks_len, e, f_name, func_count, array_count, struct_count, load_count, var_scope_info)?;
+                                    new_index_exprs.push(new_index_expr);
+                                }
+                                let new_index_expr = if num_dims == 1 { new_index_exprs.pop().unwrap() } else {
+                                    let new_index_expr: Expression;
+                                    (blks, new_index_expr) = self.bl_gen_row_major_index_(blks, blks_len, arr_name, new_index_exprs, f_name, &var_scope_info)?;
+                                    new_index_expr
+                                };
                                 // Perform pointer arithmetics
                                 (blks, blks_len) = self.bl_gen_load_(
                                     blks, 
@@ -1920,6 +2451,56 @@ impl<'ast> ZGen<'ast> {
         }
     }
 
+    // Convert the indices of a multi-dimensional array access into a single row-major index
+    // i.e. A[i0][i1][i2] -> (i0 * d1 + i1) * d2 + i2, where dk is stored in A^dim@k
+    // If array_bound_check is set, also assert that ik < dk for every dimension
+    fn bl_gen_row_major_index_(
+        &'ast self,
+        mut blks: Vec<Block<'ast>>,
+        blks_len: usize,
+        arr_name: &str,
+        new_index_exprs: Vec<Expression<'ast>>,
+        f_name: &str,
+        var_scope_info: &VarScopeInfo,
+    ) -> Result<(Vec<Block>, Expression), String> {
+        let mut row_major_expr: Option<Expression> = None;
+        for (k, new_index_expr) in new_index_exprs.into_iter().enumerate() {
+            let dim_extended_name = var_scope_info.reference_var(&format!("{}^dim@{}", arr_name, k), f_name)?.0;
+            let dim_expr = Expression::Identifier(IdentifierExpression {
+                value: dim_extended_name,
+                span: Span::new("", 0, 0).unwrap()
+            });
+            if cfg().zsharp.array_bound_check {
+                let asst_stmt = Statement::Assertion(AssertionStatement {
+                    expression: Expression::Binary(BinaryExpression {
+                        op: BinaryOperator::Lt,
+                        left: Box::new(new_index_expr.clone()),
+                        right: Box::new(dim_expr.clone()),
+                        span: Span::new("", 0, 0).unwrap()
+                    }),
+                    message: None,
+                    span: Span::new("", 0, 0).unwrap()
+                });
+                blks[blks_len - 1].instructions.push(BlockContent::Stmt(asst_stmt));
+            }
+            row_major_expr = Some(match row_major_expr {
+                None => new_index_expr,
+                Some(prev_expr) => Expression::Binary(BinaryExpression {
+                    op: BinaryOperator::Add,
+                    left: Box::new(Expression::Binary(BinaryExpression {
+                        op: BinaryOperator::Mul,
+                        left: Box::new(prev_expr),
+                        right: Box::new(dim_expr),
+                        span: Span::new("", 0, 0).unwrap()
+                    })),
+                    right: Box::new(new_index_expr),
+                    span: Span::new("", 0, 0).unwrap()
+                })
+            });
+        }
+        Ok((blks, row_major_expr.ok_or(format!("Accessing array {} without an index!", arr_name))?))
+    }
+
     // Generate blocks from an array initialization
     // Assume both the array and all entries are preprocessed
     // Returns the new blocks
@@ -2649,7 +3230,9 @@ impl<'ast> ZGen<'ast> {
     // Convert a block to circ_ir
     // This can be done to either produce the constraints, or to estimate the size of constraints
     // In estimation mode, we rename all output variable from X -> oX, add assertion, and process the terminator
-    pub fn bl_to_circ<const ESTIMATE: bool>(&self, b: &Block, f: &str, no_ro_accesses: bool) {
+    // Returns the number of assertions of the block by the end of each instruction
+    // The assertions form the conjuncts of the output of the block, followed by the check of the return value
+    pub fn bl_to_circ<const ESTIMATE: bool>(&self, b: &Block, f: &str, no_ro_accesses: bool) -> Vec<usize> {
         debug!("Bl to Circ: {}", b.name);
 
         // setup stack frame for entry function
@@ -2774,9 +3357,11 @@ impl<'ast> ZGen<'ast> {
         }
 
         // Iterate over instructions, convert memory accesses into statements and then IR
+        let mut inst_num_asserts = Vec::new();
         for i in &b.instructions {
             (wit_count, phy_mem_op_count, ro_mem_op_count, vir_mem_op_count) = 
                 self.inst_to_circ::<ESTIMATE>(i, f, wit_count, phy_mem_op_count, ro_mem_op_count, vir_mem_op_count, no_ro_accesses);
+            inst_num_asserts.push(self.assertions.borrow().len());
         }
         
         // If in estimation mode, declare and assert all outputs of the block
@@ -2939,6 +3524,7 @@ impl<'ast> ZGen<'ast> {
                 _ => { panic!("Supported Mode!") }
             }
         }
+        inst_num_asserts
     }
 
     pub fn circ_init_block(&self, f: &str) {
@@ -2958,4 +3544,190 @@ impl<'ast> ZGen<'ast> {
             .metadata
             .add_prover_and_verifier();
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::cfg::cfg_or_default;
+    use crate::front::zsharp::{parser, zx, Inputs, ZSharpFE, OPT_RO_ARRAYS};
+    use crate::front::Mode;
+    use crate::ir::term::{Op, Value};
+    use rug::Integer;
+    use std::collections::BTreeMap;
+    use std::path::Path;
+
+    // A Z# source file that is removed once the test is done with it, even if the test fails
+    struct TempZok(PathBuf);
+
+    impl TempZok {
+        fn new(name: &str, src: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("zsharp_blocks_{}_{}.zok", name, std::process::id()));
+            std::fs::write(&path, src).unwrap();
+            TempZok(path)
+        }
+    }
+
+    impl Drop for TempZok {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    // Generate the blocks of main, return the error message if block generation fails
+    fn gen_blocks(name: &str, src: &str) -> Result<usize, String> {
+        let file = TempZok::new(name, src);
+        let loader = parser::ZLoad::new();
+        let asts = loader.load(&file.0);
+        let mut g = ZGen::new(asts, Mode::Proof, loader.stdlib(), cfg_or_default().zsharp.isolate_asserts);
+        g.visit_files();
+        g.file_stack_push(file.0.clone());
+        g.generics_stack_push(HashMap::new());
+        g.bl_gen_entry_fn("main").map(|(bls, _, _)| bls.len()).map_err(|e| e.to_string())
+    }
+
+    // Interpret main of the program at path at every opt level and return the results
+    fn interpret_file_all(
+        path: &Path,
+        entry_regs: &BTreeMap<String, Integer>,
+        entry_stacks: &BTreeMap<String, Vec<Integer>>,
+        entry_arrays: &BTreeMap<String, Vec<Integer>>,
+        entry_witnesses: &Vec<Integer>,
+    ) -> Vec<Value> {
+        (0..=OPT_RO_ARRAYS).map(|opt_level| {
+            let inputs = Inputs {
+                file: path.to_path_buf(),
+                mode: Mode::Proof,
+                opt_level,
+                verbose_opt: false,
+            };
+            let (ret, _, _, _) = ZSharpFE::interpret_stream(inputs, &mut entry_regs.clone(), entry_stacks, entry_arrays, entry_witnesses, &mut |_| {}).unwrap();
+            match ret.term.op() {
+                Op::Const(v) => v.clone(),
+                _ => panic!("return value {} is not a constant", ret),
+            }
+        }).collect()
+    }
+
+    // Interpret main at every opt level and return the results
+    fn interpret_all(name: &str, src: &str, regs: &[(&str, usize)]) -> Vec<Integer> {
+        cfg_or_default();
+        let file = TempZok::new(name, src);
+        let mut entry_regs: BTreeMap<String, Integer> = BTreeMap::new();
+        for (r, v) in regs.iter().chain([("%AS", 0), ("%SP", 0)].iter()) {
+            entry_regs.insert(r.to_string(), Integer::from(*v));
+        }
+        interpret_file_all(&file.0, &entry_regs, &BTreeMap::new(), &BTreeMap::new(), &Vec::new()).into_iter().map(|v| match v {
+            Value::Field(v) => v.i(),
+            _ => panic!("return value {:?} is not a field constant", v),
+        }).collect()
+    }
+
+    // The programs of scripts/zx_diff_tests/run_tests.sh, with their input files
+    fn diff_test_programs() -> Vec<(PathBuf, PathBuf)> {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let test_dir = root.join("scripts/zx_diff_tests");
+        let mut programs: Vec<(PathBuf, PathBuf)> = std::fs::read_dir(&test_dir).unwrap()
+            .map(|f| f.unwrap().path())
+            .filter(|p| p.extension().map_or(false, |e| e == "zok"))
+            .map(|p| { let input = p.with_extension("input"); (p, input) })
+            .collect();
+        for name in ["2024_05_24_benny_bug", "2024_05_31_benny_bug"] {
+            programs.push((
+                root.join(format!("examples/ZoKrates/pf/{}.zok", name)),
+                test_dir.join(format!("inputs/{}.input", name)),
+            ));
+        }
+        programs
+    }
+
+    #[test]
+    fn diff_test_programs_agree_at_every_opt_level() {
+        cfg_or_default();
+        for (path, input_path) in diff_test_programs() {
+            let (entry_regs, entry_stacks, entry_arrays, _, _, _, _, _) = zx::read_inputs(&input_path, 0);
+            let entry_witnesses = zx::read_witnesses(&path.with_extension("witness"));
+            let results = interpret_file_all(&path, &entry_regs, &entry_stacks, &entry_arrays, &entry_witnesses);
+            assert!(results.iter().all(|r| r == &results[0]), "{}: results differ across opt levels: {:?}", path.display(), results);
+        }
+    }
+
+    #[test]
+    fn flat_array_arguments_and_returns() {
+        let src = include_str!("../../../scripts/zx_diff_tests/mm_flat.zok");
+        // A = [[1, 2, 3], [4, 5, 6]], B = [[7, 8], [9, 10], [11, 12]], AB = [[58, 64], [139, 154]]
+        let results = interpret_all("mm_flat", src, &[("n", 2), ("m", 3), ("p", 2), ("x", 1)]);
+        for r in results {
+            assert_eq!(r, Integer::from(415));
+        }
+    }
+
+    #[test]
+    fn flat_array_dimension_mismatch() {
+        let header = "
+def sum(array_decl field[n][m] A, u32 n, u32 m) -> field:
+    field s = 0
+    for u32 i in 0..n do
+        for u32 j in 0..m do
+            s = s + A[i][j]
+        endfor
+    endfor
+    return s
+";
+        // Multi-dimensional arrays can only be passed to array_decl parameters of the same dimension
+        let passes = "
+def main(u32 n) -> field:
+    array_decl field[n][n] A
+    A[0][0] = 1
+    return sum(A, n, n)
+";
+        let not_flat = "
+def main(u32 n) -> field:
+    field[2][2] A = [[1, 2], [3, 4]]
+    return sum(A, 2, 2)
+";
+        let wrong_dims = "
+def main(u32 n) -> field:
+    array_decl field[n][n][n] A
+    A[0][0][0] = 1
+    return sum(A, n, n)
+";
+        assert!(gen_blocks("flat_ok", &format!("{}{}", header, passes)).is_ok());
+        assert!(gen_blocks("flat_nested", &format!("{}{}", header, not_flat)).unwrap_err().contains("array_decl parameters"));
+        assert!(gen_blocks("flat_dims", &format!("{}{}", header, wrong_dims)).unwrap_err().contains("array_decl parameters"));
+    }
+
+    #[test]
+    fn challenge_hash_known_answers() {
+        // Computed by a separate implementation of merlin, which reproduces the test vector of merlin, and of the hash
+        let int = |s: &str| Integer::from_str_radix(s, 10).unwrap();
+        let field = cfg_or_default().field().modulus().clone();
+        let (first_const, num_rounds, vectors) = if field == int("7237005577332262213973186563042994240857116359379907606001950938285454250989") {
+            ("3870017834805779743902182277192556054132208714175802708893601682048804886052", 109, [
+                "2792247345875562918802667443445915188956731198876233333447435084319181546016",
+                "243865409123510596272230224195382821291041623024083055056816321973715946111",
+                "147631201483322393960819527838606173742451419141390800788160319815703240827",
+            ])
+        } else if field == int("52435875175126190479447740508185965837690552500527637822603658699938581184513") {
+            ("9883759943838225945723611705232063676210061277419282528915970094652823831308", 110, [
+                "4244463249984201904217947881951178781663935584485253738728501183220880153175",
+                "26442285475723946082666216036562609539750996616938230847152915772590705284146",
+                "49293894278121032710619352772803967443830471500182068825584208008683439305786",
+            ])
+        } else {
+            // No vectors for other fields
+            return;
+        };
+
+        let (exp, round_consts) = challenge_hash_params();
+        assert_eq!(exp, 5);
+        assert_eq!(round_consts.len(), num_rounds);
+        assert_eq!(round_consts[0], 0);
+        assert_eq!(round_consts[1], int(first_const));
+        let ints = |v: &[u32]| v.iter().map(|i| Integer::from(*i)).collect::<Vec<_>>();
+        assert_eq!(challenge_hash(&Integer::from(0), &[]), 0);
+        assert_eq!(challenge_hash(&Integer::from(0), &ints(&[0])), int(vectors[0]));
+        assert_eq!(challenge_hash(&Integer::from(1), &ints(&[2, 3])), int(vectors[1]));
+        assert_eq!(challenge_hash(&Integer::from(7), &ints(&[1, 2, 3, 4, 3, 1, 4, 2])), int(vectors[2]));
+    }
+}
@@ -13,12 +13,39 @@ use std::cmp::max;
 use std::iter::FromIterator;
 
 use crate::front::zsharp::ZGen;
+use crate::front::zsharp::error::{SpannedError, sp_locate, sp_error};
+use crate::front::zsharp::debugger::RegNameMap;
 use crate::front::Computations;
 use crate::target::r1cs::trans::to_r1cs;
 use crate::front::zsharp::{cfg, ZSharp};
+use circ_opt::CfgExportFormat;
+use std::time::Instant;
 
-const MAX_BLOCK_SIZE: usize = 32768;
 const CFG_VERBOSE: bool = false;
+// Spartan prover cost model, in units of work per padded constraint
+// Every execution of an instance commits to its witnesses and runs through the sumcheck, both linear in the padded size
+const SPARTAN_COST_PER_EXEC: usize = 2;
+// Every instance also proves the evaluation of its own sparse R1CS matrices, which takes a commitment and a sumcheck
+// over every nonzero entry, with about 4 nonzero entries per constraint
+const SPARTAN_COST_PER_INST: usize = 8;
+
+// Estimated Spartan prover time for num_instances instances, all padded to padded_size constraints
+// Spartan pads both the number of instances and the number of executions of each instance to a power of two
+fn spartan_prover_cost(num_instances: usize, padded_size: usize, exec_counts: &Vec<usize>) -> usize {
+    let num_execs_padded: usize = exec_counts.iter().map(|e| e.next_power_of_two()).sum();
+    SPARTAN_COST_PER_EXEC * num_execs_padded * padded_size
+        + SPARTAN_COST_PER_INST * num_instances.next_power_of_two() * padded_size
+}
+
+// Index and estimated prover cost of the cheapest merge outcome, the first one on ties
+// Every outcome is the number of constraints and the execution bound of every block left after the merge
+fn cheapest_merge(outcomes: &Vec<Vec<(usize, usize)>>) -> (usize, usize) {
+    outcomes.iter().map(|bls| {
+        let padded_size = bls.iter().map(|(num_cons, _)| *num_cons).max().unwrap_or(0).next_power_of_two();
+        let exec_counts = bls.iter().map(|(_, num_execs)| *num_execs).collect();
+        spartan_prover_cost(bls.len(), padded_size, &exec_counts)
+    }).enumerate().min_by_key(|(i, cost)| (*cost, *i)).unwrap()
+}
 
 fn type_to_ty(t: Type) -> Result<Ty, String> {
     match t {
@@ -107,32 +134,32 @@ fn is_rp(var: &String) -> Option<String> {
 
 // If bc is a statement of form field rp@ = val,
 // return val
-fn rp_find_val(bc: &BlockContent) -> Option<usize> {
+fn rp_find_val(bc: &BlockContent) -> Result<Option<usize>, String> {
     // We can ignore memory for now
     // The only case currently is rp@ on the left & constant on the right
     if let BlockContent::Stmt(Statement::Definition(d)) = bc {
         if let TypedIdentifierOrAssignee::TypedIdentifier(ty) = &d.lhs[0] {
             if is_rp(&ty.identifier.value).is_some() {
-                if let Expression::Literal(LiteralExpression::DecimalLiteral(dle)) = &d.expression {
-                    let tmp_bl: usize = dle.value.value.trim().parse().expect("Dead Block Elimination failed: rp@ is assigned to a non-constant value");
-                    return Some(tmp_bl);
-                } else {
-                    panic!("Dead Block Elimination failed: rp@ is assigned to a non-constant value")
-                }
+                return rp_literal_val(&d.expression).map(Some);
             }
         }
         if let TypedIdentifierOrAssignee::Assignee(a) = &d.lhs[0] {
             if is_rp(&a.id.value).is_some() {
-                if let Expression::Literal(LiteralExpression::DecimalLiteral(dle)) = &d.expression {
-                    let tmp_bl: usize = dle.value.value.trim().parse().expect("Dead Block Elimination failed: rp@ is assigned to a non-constant value");
-                    return Some(tmp_bl);
-                } else {
-                    panic!("Dead Block Elimination failed: rp@ is assigned to a non-constant value")
-                }
+                return rp_literal_val(&d.expression).map(Some);
             }
         }
     }
-    return None;
+    return Ok(None);
+}
+
+// Value of the literal assigned to rp@
+fn rp_literal_val(e: &Expression) -> Result<usize, String> {
+    if let Expression::Literal(LiteralExpression::DecimalLiteral(dle)) = e {
+        if let Ok(val) = dle.value.value.trim().parse() {
+            return Ok(val);
+        }
+    }
+    Err(format!("Dead Block Elimination failed: rp@ is assigned to a non-constant value"))
 }
 
 // If bc is a statement of form rp@ = old_val and old_val is a key in val_map,
@@ -191,95 +218,97 @@ pub fn rp_replacement_stmt(bc: BlockContent, val_map: BTreeMap<usize, usize>) ->
 
 // Given an expression consisted of only ternary, literals, and identifiers,
 // Find all the literal values and rp@ it mentioned
-fn bl_trans_find_val(e: &Expression) -> Vec<NextBlock> {
+fn bl_trans_find_val(e: &Expression) -> Result<Vec<NextBlock>, String> {
     match e {
         Expression::Ternary(te) => {
-            let mut ret = bl_trans_find_val(&te.second);
-            ret.append(&mut bl_trans_find_val(&te.third));
-            return ret;
+            let mut ret = bl_trans_find_val(&te.second)?;
+            ret.append(&mut bl_trans_find_val(&te.third)?);
+            return Ok(ret);
         }
-        Expression::Literal(le) => {
-            if let LiteralExpression::DecimalLiteral(dle) = le {
-                let val: usize = dle.value.value.trim().parse().expect("Dead Block Elimination failed: rp@ is assigned to a non-constant value");
-                return vec![NextBlock::Label(val)];
-            } else { panic!("Unexpected value in Block Transition") }
+        Expression::Literal(_) => {
+            let val = bl_trans_label(e)?;
+            return Ok(vec![NextBlock::Label(val)]);
         }
         Expression::Identifier(ie) => {
             if let Some(f_name) = is_rp(&ie.value) {
-                return vec![NextBlock::Rp(f_name)]
+                return Ok(vec![NextBlock::Rp(f_name)])
             } else {
-                panic!("Unexpected variable in Block Transition")
+                Err(format!("Unexpected variable in Block Transition"))
             }
         }
-        _ => { panic!("Unexpected expression in Block Transition") }
+        _ => { Err(format!("Unexpected expression in Block Transition")) }
+    }
+}
+
+// Label of a block a transition literal points to
+fn bl_trans_label(e: &Expression) -> Result<usize, String> {
+    if let Expression::Literal(LiteralExpression::DecimalLiteral(dle)) = e {
+        dle.value.value.trim().parse().map_err(|_| format!("Unexpected value in Block Transition"))
+    } else {
+        Err(format!("Unexpected value in Block Transition"))
     }
 }
 
 // Given an expression consisted of only ternary, literals, and identifiers,
 // Replace all literal values according to label_map
 // Skip all rp@ or other references to variables
-pub fn bl_trans_map<'ast>(e: &Expression<'ast>, label_map: &BTreeMap<usize, usize>) -> Expression<'ast> {
+pub fn bl_trans_map<'ast>(e: &Expression<'ast>, label_map: &BTreeMap<usize, usize>) -> Result<Expression<'ast>, String> {
     match e {
         Expression::Ternary(te) => {
-            let new_second = bl_trans_map(&te.second, label_map);
-            let new_third = bl_trans_map(&te.third, label_map);
+            let new_second = bl_trans_map(&te.second, label_map)?;
+            let new_third = bl_trans_map(&te.third, label_map)?;
             if new_second == new_third {
-                return new_second;
+                return Ok(new_second);
             } else {
-                return Expression::Ternary(TernaryExpression {
+                return Ok(Expression::Ternary(TernaryExpression {
                     first: Box::new(*te.first.clone()),
                     second: Box::new(new_second),
                     third: Box::new(new_third),
                     span: e.span().clone()
-                });
+                }));
             }
         }
-        Expression::Literal(le) => {
-            if let LiteralExpression::DecimalLiteral(dle) = le {
-                let val: usize = dle.value.value.trim().parse().expect("Dead Block Elimination failed: rp@ is assigned to a non-constant value");
-                return bl_coda(NextBlock::Label(*label_map.get(&val).or(Some(&val)).unwrap()));
-            } else { panic!("Unexpected value in Block Transition") }
+        Expression::Literal(_) => {
+            let val = bl_trans_label(e)?;
+            return Ok(bl_coda(NextBlock::Label(*label_map.get(&val).or(Some(&val)).unwrap())));
         }
         Expression::Identifier(_) => {
-            return e.clone();
+            return Ok(e.clone());
         }
-        _ => { panic!("Unexpected expression in Block Transition") }
+        _ => { Err(format!("Unexpected expression in Block Transition")) }
     }
 }
 
 // Given an expression consisted of only ternary, literals, and identifiers,
 // Replace all occurrences of old_val to new_val, which is an expression
 // I don't think we can combine bl_trans_map and bl_trans_replace together efficiently.
-fn bl_trans_replace<'ast>(e: &Expression<'ast>, old_val: usize, new_val: &Expression<'ast>) -> Expression<'ast> {
+fn bl_trans_replace<'ast>(e: &Expression<'ast>, old_val: usize, new_val: &Expression<'ast>) -> Result<Expression<'ast>, String> {
     match e {
         Expression::Ternary(te) => {
-            let new_second = bl_trans_replace(&te.second, old_val, new_val);
-            let new_third = bl_trans_replace(&te.third, old_val, new_val);
+            let new_second = bl_trans_replace(&te.second, old_val, new_val)?;
+            let new_third = bl_trans_replace(&te.third, old_val, new_val)?;
             if new_second == new_third {
-                return new_second;
+                return Ok(new_second);
             } else {
-                return Expression::Ternary(TernaryExpression {
+                return Ok(Expression::Ternary(TernaryExpression {
                     first: Box::new(*te.first.clone()),
                     second: Box::new(new_second),
                     third: Box::new(new_third),
                     span: e.span().clone()
-                });
+                }));
             }
         }
-        Expression::Literal(le) => {
-            if let LiteralExpression::DecimalLiteral(dle) = le {
-                let val: usize = dle.value.value.trim().parse().expect("Dead Block Elimination failed: rp@ is assigned to a non-constant value");
-                if val == old_val {
-                    return new_val.clone();
-                } else {
-                    return e.clone();
-                }
-            } else { panic!("Unexpected value in Block Transition") }
+        Expression::Literal(_) => {
+            if bl_trans_label(e)? == old_val {
+                return Ok(new_val.clone());
+            } else {
+                return Ok(e.clone());
+            }
         }
         Expression::Identifier(_) => {
-            return e.clone();
+            return Ok(e.clone());
         }
-        _ => { panic!("Unexpected expression in Block Transition") }
+        _ => { Err(format!("Unexpected expression in Block Transition")) }
     }
 }
 
@@ -335,34 +364,89 @@ fn fn_top_sort(
 */
 
 // Given an expression, find all variables it references
-fn expr_find_val(e: &Expression) -> BTreeSet<String> {
-    match e {
-        Expression::Ternary(t) => {
-            let mut ret: BTreeSet<String> = expr_find_val(&t.first);
-            ret.extend(expr_find_val(&t.second));
-            ret.extend(expr_find_val(&t.third));
-            ret
-        }
-        Expression::Binary(b) => {
-            let mut ret: BTreeSet<String> = expr_find_val(&b.left);
-            ret.extend(expr_find_val(&b.right));
-            ret
-        }
-        Expression::Unary(u) => expr_find_val(&u.expression),
-        Expression::Postfix(p) => {
-            let mut ret: BTreeSet<String> = BTreeSet::new();
-            ret.insert(p.id.value.clone());
-            for aa in &p.accesses {
-                if let Access::Select(a) = aa {
-                    if let RangeOrExpression::Expression(e) = &a.expression {
-                        ret.extend(expr_find_val(e));
-                    } else {
-                        panic!("Range access not supported.")
-                    }
-                } else {
-                    panic!("Unsupported membership access.")
+fn expr_find_val<'ast>(e: &Expression<'ast>) -> Result<BTreeSet<String>, SpannedError<'ast>> {
+    let r = (|| -> Result<_, SpannedError<'ast>> {
+        match e {
+            Expression::Ternary(t) => {
+                let mut ret: BTreeSet<String> = expr_find_val(&t.first)?;
+                ret.extend(expr_find_val(&t.second)?);
+                ret.extend(expr_find_val(&t.third)?);
+                Ok(ret)
+            }
+            Expression::Binary(b) => {
+                let mut ret: BTreeSet<String> = expr_find_val(&b.left)?;
+                ret.extend(expr_find_val(&b.right)?);
+                Ok(ret)
+            }
+            Expression::Unary(u) => expr_find_val(&u.expression),
+            Expression::Postfix(p) => {
+                let mut ret: BTreeSet<String> = BTreeSet::new();
+                ret.insert(p.id.value.clone());
+                for aa in &p.accesses {
+                    ret.extend(expr_find_val(select_expr(aa, &p.span)?.0)?);
                 }
+                Ok(ret)
             }
+            Expression::Identifier(i) => {
+                let mut ret: BTreeSet<String> = BTreeSet::new();
+                ret.insert(i.value.clone());
+                Ok(ret)
+            }
+            Expression::Literal(_) => Ok(BTreeSet::new()),
+            _ => Err(format!("Unsupported Expression.").into()),
+        }
+    })();
+    sp_locate(r, e.span())
+}
+
+// Index expression and span of an array access, the only kind of access blocks support
+fn select_expr<'a, 'ast>(aa: &'a Access<'ast>, span: &Span<'ast>) -> Result<(&'a Expression<'ast>, &'a Span<'ast>), SpannedError<'ast>> {
+    if let Access::Select(a) = aa {
+        if let RangeOrExpression::Expression(e) = &a.expression {
+            Ok((e, &a.span))
+        } else {
+            sp_error(format!("Range access not supported."), &a.span)
+        }
+    } else {
+        sp_error(format!("Unsupported membership access."), span)
+    }
+}
+
+// Index expression and span of an assignee access, the only kind of access blocks support
+fn assignee_select_expr<'a, 'ast>(aa: &'a AssigneeAccess<'ast>, span: &Span<'ast>) -> Result<(&'a Expression<'ast>, &'a Span<'ast>), SpannedError<'ast>> {
+    if let AssigneeAccess::Select(a) = aa {
+        if let RangeOrExpression::Expression(e) = &a.expression {
+            Ok((e, &a.span))
+        } else {
+            sp_error(format!("Range access not supported."), &a.span)
+        }
+    } else {
+        sp_error(format!("Unsupported membership access."), span)
+    }
+}
+
+// Error for statements that block generation never leaves in a block
+fn stmt_unsupported<'ast, R>(s: &Statement<'ast>) -> Result<R, SpannedError<'ast>> {
+    let msg = match s {
+        Statement::Return(_) => "Blocks should not contain return statements.",
+        Statement::Conditional(_) => "Blocks should not contain conditional statements.",
+        Statement::Iteration(_) => "Blocks should not contain iteration statements.",
+        Statement::WhileLoop(_) => "Blocks should not contain while loop statements.",
+        Statement::CondStore(_) => "Blocks should not contain conditional store statements.",
+        Statement::Witness(_) => "Witness statements unsupported.",
+        Statement::ArrayDecl(_) => "Blocks should not contain array declaration statements.",
+        Statement::Definition(_) | Statement::Assertion(_) => "Unsupported statement.",
+    };
+    sp_error(msg.to_string(), s.span())
+}
+
+// Given an expression, find all variables whose value the expression might evaluate to
+// Array pointers are only passed around through identifiers and ternaries
+fn expr_find_ptr(e: &Expression) -> BTreeSet<String> {
+    match e {
+        Expression::Ternary(t) => {
+            let mut ret: BTreeSet<String> = expr_find_ptr(&t.second);
+            ret.extend(expr_find_ptr(&t.third));
             ret
         }
         Expression::Identifier(i) => {
@@ -370,10 +454,7 @@ fn expr_find_val(e: &Expression) -> BTreeSet<String> {
             ret.insert(i.value.clone());
             ret
         }
-        Expression::Literal(_) => BTreeSet::new(),
-        _ => {
-            panic!("Unsupported Expression.");
-        }
+        _ => BTreeSet::new()
     }
 }
 
@@ -381,216 +462,159 @@ fn expr_find_val(e: &Expression) -> BTreeSet<String> {
 // Return value:
 // ret[0]: all variables that S defines (KILL)
 // ret[1]: all variables that S references (GEN)
-fn stmt_find_val(s: &Statement) -> (BTreeSet<String>, BTreeSet<String>) {
-    match s {
-        Statement::Return(_) => {
-            panic!("Blocks should not contain return statements.")
-        }
-        Statement::Definition(d) => {
-            let mut kill_set = BTreeSet::new();
-            for l in &d.lhs {
-                match l {
-                    TypedIdentifierOrAssignee::Assignee(p) => {
-                        kill_set.insert(p.id.value.clone());
-                        for aa in &p.accesses {
-                            if let AssigneeAccess::Select(a) = aa {
-                                if let RangeOrExpression::Expression(e) = &a.expression {
-                                    kill_set.extend(expr_find_val(e));
-                                } else {
-                                    panic!("Range access not supported.")
-                                }
-                            } else {
-                                panic!("Unsupported membership access.")
+fn stmt_find_val<'ast>(s: &Statement<'ast>) -> Result<(BTreeSet<String>, BTreeSet<String>), SpannedError<'ast>> {
+    let r = (|| -> Result<_, SpannedError<'ast>> {
+        match s {
+            Statement::Definition(d) => {
+                let mut kill_set = BTreeSet::new();
+                for l in &d.lhs {
+                    match l {
+                        TypedIdentifierOrAssignee::Assignee(p) => {
+                            kill_set.insert(p.id.value.clone());
+                            for aa in &p.accesses {
+                                kill_set.extend(expr_find_val(assignee_select_expr(aa, &p.span)?.0)?);
                             }
                         }
-                    }
-                    TypedIdentifierOrAssignee::TypedIdentifier(ti) => {
-                        kill_set.insert(ti.identifier.value.clone());
+                        TypedIdentifierOrAssignee::TypedIdentifier(ti) => {
+                            kill_set.insert(ti.identifier.value.clone());
+                        }
                     }
                 }
+                Ok((kill_set, expr_find_val(&d.expression)?))
             }
-            (kill_set, expr_find_val(&d.expression))
+            Statement::Assertion(a) => Ok((BTreeSet::new(), expr_find_val(&a.expression)?)),
+            _ => stmt_unsupported(s),
         }
-        Statement::Assertion(a) => (BTreeSet::new(), expr_find_val(&a.expression)),
-        Statement::Conditional(_c) => {
-            panic!("Blocks should not contain conditional statements.")
-            /*
-            // KILL is empty
-            // GEN is the union of the two branches
-            // Iterate through if branch
-            let mut if_gen_set = BTreeSet::new();
-            for s in c.ifbranch.iter().rev() {
-                let (kill, gen) = stmt_find_val(&s);
-                if_gen_set = la_kill(if_gen_set, &kill);
-                if_gen_set = la_gen(if_gen_set, &gen);
-            }
-            let mut else_gen_set = BTreeSet::new();
-            for s in c.elsebranch.iter().rev() {
-                let (kill, gen) = stmt_find_val(&s);
-                else_gen_set = la_kill(else_gen_set, &kill);
-                else_gen_set = la_gen(else_gen_set, &gen);
-            }
-            let mut gen_set = BTreeSet::new();
-            gen_set.extend(if_gen_set);
-            gen_set.extend(else_gen_set);
-            gen_set.extend(expr_find_val(&c.condition));
-            (BTreeSet::new(), gen_set)
-            */
-        }
-        Statement::Iteration(_) => { panic!("Blocks should not contain iteration statements.") }
-        Statement::WhileLoop(_) => { panic!("Blocks should not contain while loop statements.") }
-        Statement::CondStore(_) => { panic!("Blocks should not contain conditional store statements.") }
-        Statement::Witness(_) => { panic!("Witness statements unsupported.") }
-        Statement::ArrayDecl(_) => { panic!("Blocks should not contain array declaration statements.") }
-    }
+    })();
+    sp_locate(r, s.span())
 }
 
 // Given an expression, replace all variables of old_f_name with new_f_name, plus scope_diff offset in scope
-fn expr_replace_fn<'ast>(e: &Expression<'ast>, old_f_name: &String, new_f_name: &String, scope_diff: usize) -> Expression<'ast> {
-    match e {
-        Expression::Ternary(t) => {
-            let new_first = expr_replace_fn(&t.first, old_f_name, new_f_name, scope_diff);
-            let new_second = expr_replace_fn(&t.second, old_f_name, new_f_name, scope_diff);
-            let new_third = expr_replace_fn(&t.third, old_f_name, new_f_name, scope_diff);
-            Expression::Ternary(TernaryExpression {
-                first: Box::new(new_first),
-                second: Box::new(new_second),
-                third: Box::new(new_third),
-                span: Span::new("", 0, 0).unwrap()
-            })
-        }
-        Expression::Binary(b) => {
-            let new_left = expr_replace_fn(&b.left, old_f_name, new_f_name, scope_diff);
-            let new_right = expr_replace_fn(&b.right, old_f_name, new_f_name, scope_diff);
-            Expression::Binary(BinaryExpression {
-                op: b.op.clone(),
-                left: Box::new(new_left),
-                right: Box::new(new_right),
-                span: Span::new("", 0, 0).unwrap()
-            })
-        }
-        Expression::Unary(u) => {
-            let new_expr = expr_replace_fn(&u.expression, old_f_name, new_f_name, scope_diff);
-            Expression::Unary(UnaryExpression {
-                op: u.op.clone(),
-                expression: Box::new(new_expr),
-                span: Span::new("", 0, 0).unwrap()
-            })
-        },
-        Expression::Postfix(p) => {
-            // Identifier
-            let new_id = var_fn_merge(&p.id.value, old_f_name, new_f_name, scope_diff);
-            // Accesses
-            let mut new_accesses = Vec::new();
-            for aa in &p.accesses {
-                if let Access::Select(a) = aa {
-                    if let RangeOrExpression::Expression(e) = &a.expression {
-                        let new_expr = expr_replace_fn(e, old_f_name, new_f_name, scope_diff);
-                        new_accesses.push(Access::Select(ArrayAccess {
-                            expression: RangeOrExpression::Expression(new_expr),
-                            span: Span::new("", 0, 0).unwrap()
-                        }))
-                    } else {
-                        panic!("Range access not supported.")
-                    }
-                } else {
-                    panic!("Unsupported membership access.")
+fn expr_replace_fn<'ast>(e: &Expression<'ast>, old_f_name: &String, new_f_name: &String, scope_diff: usize) -> Result<Expression<'ast>, SpannedError<'ast>> {
+    let r = (|| -> Result<_, SpannedError<'ast>> {
+        match e {
+            Expression::Ternary(t) => {
+                let new_first = expr_replace_fn(&t.first, old_f_name, new_f_name, scope_diff)?;
+                let new_second = expr_replace_fn(&t.second, old_f_name, new_f_name, scope_diff)?;
+                let new_third = expr_replace_fn(&t.third, old_f_name, new_f_name, scope_diff)?;
+                Ok(Expression::Ternary(TernaryExpression {
+                    first: Box::new(new_first),
+                    second: Box::new(new_second),
+                    third: Box::new(new_third),
+                    span: Span::new("", 0, 0).unwrap()
+                }))
+            }
+            Expression::Binary(b) => {
+                let new_left = expr_replace_fn(&b.left, old_f_name, new_f_name, scope_diff)?;
+                let new_right = expr_replace_fn(&b.right, old_f_name, new_f_name, scope_diff)?;
+                Ok(Expression::Binary(BinaryExpression {
+                    op: b.op.clone(),
+                    left: Box::new(new_left),
+                    right: Box::new(new_right),
+                    span: Span::new("", 0, 0).unwrap()
+                }))
+            }
+            Expression::Unary(u) => {
+                let new_expr = expr_replace_fn(&u.expression, old_f_name, new_f_name, scope_diff)?;
+                Ok(Expression::Unary(UnaryExpression {
+                    op: u.op.clone(),
+                    expression: Box::new(new_expr),
+                    span: Span::new("", 0, 0).unwrap()
+                }))
+            },
+            Expression::Postfix(p) => {
+                // Identifier
+                let new_id = var_fn_merge(&p.id.value, old_f_name, new_f_name, scope_diff)?;
+                // Accesses
+                let mut new_accesses = Vec::new();
+                for aa in &p.accesses {
+                    let new_expr = expr_replace_fn(select_expr(aa, &p.span)?.0, old_f_name, new_f_name, scope_diff)?;
+                    new_accesses.push(Access::Select(ArrayAccess {
+                        expression: RangeOrExpression::Expression(new_expr),
+                        span: Span::new("", 0, 0).unwrap()
+                    }))
                 }
+                Ok(Expression::Postfix(PostfixExpression {
+                    id: IdentifierExpression {
+                        value: new_id,
+                        span: Span::new("", 0, 0).unwrap()
+                    },
+                    accesses: new_accesses,
+                    span: Span::new("", 0, 0).unwrap()
+                }))
             }
-            Expression::Postfix(PostfixExpression {
-                id: IdentifierExpression {
-                    value: new_id,
+            Expression::Identifier(i) => {
+                Ok(Expression::Identifier(IdentifierExpression {
+                    value: var_fn_merge(&i.value, old_f_name, new_f_name, scope_diff)?,
                     span: Span::new("", 0, 0).unwrap()
-                },
-                accesses: new_accesses,
-                span: Span::new("", 0, 0).unwrap()
-            })
-        }
-        Expression::Identifier(i) => {
-            Expression::Identifier(IdentifierExpression {
-                value: var_fn_merge(&i.value, old_f_name, new_f_name, scope_diff),
-                span: Span::new("", 0, 0).unwrap()
-            })
-        }
-        Expression::Literal(_) => e.clone(),
-        _ => {
-            panic!("Unsupported Expression.");
+                }))
+            }
+            Expression::Literal(_) => Ok(e.clone()),
+            _ => Err(format!("Unsupported Expression.").into()),
         }
-    }
+    })();
+    sp_locate(r, e.span())
 }
 
 // Given a statement, replace all variables of old_f_name with new_f_name, plus scope_diff offset in scope
-fn stmt_replace_fn<'ast>(s: &Statement<'ast>, old_f_name: &String, new_f_name: &String, scope_diff: usize) -> Statement<'ast> {
-    match s {
-        Statement::Return(_) => {
-            panic!("Blocks should not contain return statements.")
-        }
-        Statement::Definition(d) => {
-            let mut new_lhs = Vec::new();
-            for l in &d.lhs {
-                match l {
-                    TypedIdentifierOrAssignee::Assignee(p) => {
-                        let new_id = var_fn_merge(&p.id.value, old_f_name, new_f_name, scope_diff);
-                        let mut new_accesses = Vec::new();
-                        for aa in &p.accesses {
-                            if let AssigneeAccess::Select(a) = aa {
-                                if let RangeOrExpression::Expression(e) = &a.expression {
-                                    let new_expr = expr_replace_fn(e, old_f_name, new_f_name, scope_diff);
-                                    new_accesses.push(AssigneeAccess::Select(ArrayAccess {
-                                        expression: RangeOrExpression::Expression(new_expr),
-                                        span: Span::new("", 0, 0).unwrap()
-                                    }))
-                                } else {
-                                    panic!("Range access not supported.")
-                                }
-                            } else {
-                                panic!("Unsupported membership access.")
+fn stmt_replace_fn<'ast>(s: &Statement<'ast>, old_f_name: &String, new_f_name: &String, scope_diff: usize) -> Result<Statement<'ast>, SpannedError<'ast>> {
+    let r = (|| -> Result<_, SpannedError<'ast>> {
+        match s {
+            Statement::Definition(d) => {
+                let mut new_lhs = Vec::new();
+                for l in &d.lhs {
+                    match l {
+                        TypedIdentifierOrAssignee::Assignee(p) => {
+                            let new_id = var_fn_merge(&p.id.value, old_f_name, new_f_name, scope_diff)?;
+                            let mut new_accesses = Vec::new();
+                            for aa in &p.accesses {
+                                let new_expr = expr_replace_fn(assignee_select_expr(aa, &p.span)?.0, old_f_name, new_f_name, scope_diff)?;
+                                new_accesses.push(AssigneeAccess::Select(ArrayAccess {
+                                    expression: RangeOrExpression::Expression(new_expr),
+                                    span: Span::new("", 0, 0).unwrap()
+                                }))
                             }
-                        }
-                        new_lhs.push(TypedIdentifierOrAssignee::Assignee(Assignee {
-                            id: IdentifierExpression {
-                                value: new_id,
+                            new_lhs.push(TypedIdentifierOrAssignee::Assignee(Assignee {
+                                id: IdentifierExpression {
+                                    value: new_id,
+                                    span: Span::new("", 0, 0).unwrap()
+                                },
+                                accesses: new_accesses,
                                 span: Span::new("", 0, 0).unwrap()
-                            },
-                            accesses: new_accesses,
-                            span: Span::new("", 0, 0).unwrap()
-                        }));
-                    }
-                    TypedIdentifierOrAssignee::TypedIdentifier(ti) => {
-                        new_lhs.push(TypedIdentifierOrAssignee::TypedIdentifier(TypedIdentifier {
-                            array_metadata: ti.array_metadata.clone(),
-                            ty: ti.ty.clone(),
-                            identifier: IdentifierExpression {
-                                value: var_fn_merge(&ti.identifier.value, old_f_name, new_f_name, scope_diff),
+                            }));
+                        }
+                        TypedIdentifierOrAssignee::TypedIdentifier(ti) => {
+                            new_lhs.push(TypedIdentifierOrAssignee::TypedIdentifier(TypedIdentifier {
+                                array_metadata: ti.array_metadata.clone(),
+                                ty: ti.ty.clone(),
+                                identifier: IdentifierExpression {
+                                    value: var_fn_merge(&ti.identifier.value, old_f_name, new_f_name, scope_diff)?,
+                                    span: Span::new("", 0, 0).unwrap()
+                                },
                                 span: Span::new("", 0, 0).unwrap()
-                            },
-                            span: Span::new("", 0, 0).unwrap()
-                        }))
+                            }))
+                        }
                     }
                 }
+                let new_expr = expr_replace_fn(&d.expression, old_f_name, new_f_name, scope_diff)?;
+                Ok(Statement::Definition(DefinitionStatement {
+                    lhs: new_lhs,
+                    expression: new_expr,
+                    span: Span::new("", 0, 0).unwrap()
+                }))
             }
-            let new_expr = expr_replace_fn(&d.expression, old_f_name, new_f_name, scope_diff);
-            Statement::Definition(DefinitionStatement {
-                lhs: new_lhs,
-                expression: new_expr,
-                span: Span::new("", 0, 0).unwrap()
-            })
+            Statement::Assertion(a) => {
+                let new_expr = expr_replace_fn(&a.expression, old_f_name, new_f_name, scope_diff)?;
+                Ok(Statement::Assertion(AssertionStatement {
+                    expression: new_expr,
+                    message: a.message.clone(),
+                    span: Span::new("", 0, 0).unwrap()
+                }))
+            },
+            _ => stmt_unsupported(s),
         }
-        Statement::Assertion(a) => {
-            let new_expr = expr_replace_fn(&a.expression, old_f_name, new_f_name, scope_diff);
-            Statement::Assertion(AssertionStatement {
-                expression: new_expr,
-                message: a.message.clone(),
-                span: Span::new("", 0, 0).unwrap()
-            })
-        },
-        Statement::Conditional(_c) => { panic!("Blocks should not contain conditional statements.") }
-        Statement::Iteration(_) => { panic!("Blocks should not contain iteration statements.") }
-        Statement::WhileLoop(_) => { panic!("Blocks should not contain while loop statements.") }
-        Statement::CondStore(_) => { panic!("Blocks should not contain conditional store statements.") }
-        Statement::Witness(_) => { panic!("Witness statements unsupported.") }
-        Statement::ArrayDecl(_) => { panic!("Blocks should not contain array declaration statements.") }
-    }
+    })();
+    sp_locate(r, s.span())
 }
 
 // Given a statement, decide if it is of form %SP = %SP + x
@@ -691,20 +715,20 @@ fn var_fn_merge(
     old_f_name: &String,
     new_f_name: &String,
     scope_diff: usize,
-) -> String {
+) -> Result<String, String> {
     let parts = var.split(".").collect::<Vec<&str>>();
     // Parts should either have length 1 (reserved), 2 (%RET), or 4 (others)
     match parts.len() {
-        1 => { return var.to_string(); },
+        1 => { return Ok(var.to_string()); },
         2 => {
             if parts[1] == old_f_name {
                 // %RET is still %RET, no need to change function name
                 let mut new_var_name = parts[0].to_string();
                 new_var_name.push_str(".");
                 new_var_name.push_str(old_f_name);
-                return new_var_name;
+                return Ok(new_var_name);
             } else {
-                return var.to_string();
+                return Ok(var.to_string());
             }
         }
         4 => {
@@ -715,17 +739,17 @@ fn var_fn_merge(
                 new_var_name.push_str(".");
                 new_var_name.push_str(new_f_name);
                 new_var_name.push_str(".");
-                let mut scope = parts[2].parse::<usize>().unwrap();
+                let mut scope = parts[2].parse::<usize>().map_err(|_| format!("Cannot parse variable: {}", var))?;
                 scope += scope_diff;
                 new_var_name.push_str(&scope.to_string());
                 new_var_name.push_str(".");
                 new_var_name.push_str(&parts[3].to_string());
-                return new_var_name;
+                return Ok(new_var_name);
             } else {
-                return var.to_string();
+                return Ok(var.to_string());
             }
         }
-        _ => { panic!("Cannot parse variable: {}", var) }
+        _ => { Err(format!("Cannot parse variable: {}", var)) }
     }
 }
 
@@ -750,7 +774,7 @@ fn term_to_instr<'ast>(
     ro_count_list: &Vec<usize>,
     vm_count_list: &Vec<usize>,
     cur_bl: usize,
-) -> (Vec<BlockContent<'ast>>, usize, usize, usize, usize) {
+) -> Result<(Vec<BlockContent<'ast>>, usize, usize, usize, usize), String> {
     // There are three cases for the terminator
     // A ternary should be converted to a branching instruction
     // A constant literal should be converted to the corresponding instruction list x looping
@@ -758,9 +782,9 @@ fn term_to_instr<'ast>(
     match term {
         Expression::Ternary(t) => {
             let (mut left_instr, left_cons_count, left_ro_count, left_vm_count, left_repeat) = 
-                term_to_instr(bls, &t.second, instr_list, cons_count_list, ro_count_list, vm_count_list, cur_bl);
+                term_to_instr(bls, &t.second, instr_list, cons_count_list, ro_count_list, vm_count_list, cur_bl)?;
             let (mut right_instr, right_cons_count, right_ro_count, right_vm_count, right_repeat) = 
-                term_to_instr(bls, &t.third, instr_list, cons_count_list, ro_count_list, vm_count_list, cur_bl);
+                term_to_instr(bls, &t.third, instr_list, cons_count_list, ro_count_list, vm_count_list, cur_bl)?;
 
             // Assert that no witness statements are within branches as that would be confusing
             for i in left_instr.iter().chain(right_instr.iter()) {
@@ -772,7 +796,7 @@ fn term_to_instr<'ast>(
 
             // If both left and right are empty, don't construct if / else
             if left_instr.len() == 0 && right_instr.len() == 0 {
-                return (Vec::new(), 0, 0, 0, 1);
+                return Ok((Vec::new(), 0, 0, 0, 1));
             }
 
             // Restrictions on loop structure
@@ -798,34 +822,34 @@ fn term_to_instr<'ast>(
                 right_instr
             ));
 
-            (
+            Ok((
                 vec![branch_inst; left_repeat], 
                 max(left_cons_count, right_cons_count) * left_repeat, 
                 max(left_ro_count, right_ro_count) * left_repeat, 
                 max(left_vm_count, right_vm_count) * left_repeat, 
                 1
-            )
+            ))
         }
         Expression::Literal(le) => {
-            if let LiteralExpression::DecimalLiteral(dle) = le {
+            if let LiteralExpression::DecimalLiteral(_) = le {
                 let cur_scope = bls[cur_bl].scope;
-                let next_bl: usize = dle.value.value.parse().unwrap();
+                let next_bl = bl_trans_label(term)?;
                 let next_scope = bls[next_bl].scope;
                 if next_scope > cur_scope {
                     // Copy from instr_list only if scope of next_bl is higher than cur_scope
                     // DO NOT unroll the loops here. We need to unroll with the condition
-                    (instr_list[next_bl].clone(), cons_count_list[next_bl], ro_count_list[next_bl], vm_count_list[next_bl], bls[next_bl].fn_num_exec_bound / bls[cur_bl].fn_num_exec_bound)
+                    Ok((instr_list[next_bl].clone(), cons_count_list[next_bl], ro_count_list[next_bl], vm_count_list[next_bl], bls[next_bl].fn_num_exec_bound / bls[cur_bl].fn_num_exec_bound))
                 } else {
-                    (Vec::new(), 0, 0, 0, 1)
+                    Ok((Vec::new(), 0, 0, 0, 1))
                 }
             } else {
-                panic!("Terminator to instruction failed: terminator cannot contain boolean or hex")
+                Err(format!("Terminator to instruction failed: terminator cannot contain boolean or hex"))
             }
         }
         Expression::Identifier(_) => {
-            panic!("Terminator to instruction failed: cannot merge blocks terminating in rp@")
+            Err(format!("Terminator to instruction failed: cannot merge blocks terminating in rp@"))
         }
-        _ => { panic!("Terminator to instruction failed: terminator must be ternary, literal, or rp@") }
+        _ => { Err(format!("Terminator to instruction failed: terminator must be ternary, literal, or rp@")) }
     }
 }
 
@@ -864,105 +888,67 @@ fn new_witness_map() -> BTreeMap<String, usize> {
 fn var_to_reg_stmt<'ast>(
     s: &Statement<'ast>, 
     mut reg_map: BTreeMap<String, usize>, 
-) -> (Statement<'ast>, BTreeMap<String, usize>) {
-    match s {
-        Statement::Return(_) => {
-            panic!("Blocks should not contain return statements.");
-        }
-        Statement::Assertion(a) => {
-            let new_expr: Expression;
-            (new_expr, reg_map) = var_to_reg_expr(&a.expression, reg_map);
-            let new_stmt = AssertionStatement {
-                expression: new_expr,
-                message: a.message.clone(),
-                span: a.span
-            };
-            (Statement::Assertion(new_stmt), reg_map)
-        }
-        Statement::Conditional(_c) => {
-            panic!("Blocks should not contain conditional statements.")
-            /*
-            let new_cond: Expression;
-            (new_cond, reg_map) = var_to_reg_expr(&c.condition, reg_map);
-            let mut new_ifbranch = Vec::new();
-            for s in &c.ifbranch {
-                let new_stmt: Statement;
-                (new_stmt, reg_map) = var_to_reg_stmt(s, reg_map);
-                new_ifbranch.push(new_stmt);
-            }
-            let mut new_elsebranch = Vec::new();
-            for s in &c.elsebranch {
-                let new_stmt: Statement;
-                (new_stmt, reg_map) = var_to_reg_stmt(s, reg_map);
-                new_elsebranch.push(new_stmt);
-            }
-            let new_stmt = ConditionalStatement {
-                condition: new_cond,
-                ifbranch: new_ifbranch,
-                dummy: Vec::new(),
-                elsebranch: new_elsebranch,
-                span: Span::new("", 0, 0).unwrap()
-            };
-            (Statement::Conditional(new_stmt), reg_map)
-            */
-        }
-        Statement::Iteration(_) => { panic!("Blocks should not contain iteration statements.") }
-        Statement::WhileLoop(_) => { panic!("Blocks should not contain while loop statements.") }
-        Statement::Definition(d) => {
-            let mut new_lhs: Vec<TypedIdentifierOrAssignee> = Vec::new();
-            for l in &d.lhs {
-                match l {
-                    TypedIdentifierOrAssignee::TypedIdentifier(tid) => {
-                        let new_id_expr: IdentifierExpression;
-                        (new_id_expr, reg_map) = var_to_reg_id_expr(&tid.identifier, reg_map);
-                        new_lhs.push(TypedIdentifierOrAssignee::TypedIdentifier(TypedIdentifier{
-                            array_metadata: None,
-                            ty: tid.ty.clone(),
-                            identifier: new_id_expr,
-                            span: tid.span
-                        }));
-                    }
-                    TypedIdentifierOrAssignee::Assignee(p) => {
-                        let new_id_expr: IdentifierExpression;
-                        (new_id_expr, reg_map) = var_to_reg_id_expr(&p.id, reg_map);
-                        let mut new_accesses: Vec<AssigneeAccess> = Vec::new();
-                        for aa in &p.accesses {
-                            if let AssigneeAccess::Select(a) = aa {
-                                if let RangeOrExpression::Expression(e) = &a.expression {
-                                    let new_expr: Expression;
-                                    (new_expr, reg_map) = var_to_reg_expr(&e, reg_map);
-                                    new_accesses.push(AssigneeAccess::Select(ArrayAccess {
-                                        expression: RangeOrExpression::Expression(new_expr),
-                                        span: a.span
-                                    }))
-                                } else {
-                                    panic!("Range access not supported.")
-                                }
-                            } else {
-                                panic!("Unsupported membership access.")
+) -> Result<(Statement<'ast>, BTreeMap<String, usize>), SpannedError<'ast>> {
+    let r = (|| -> Result<_, SpannedError<'ast>> {
+        match s {
+            Statement::Assertion(a) => {
+                let new_expr: Expression;
+                (new_expr, reg_map) = var_to_reg_expr(&a.expression, reg_map)?;
+                let new_stmt = AssertionStatement {
+                    expression: new_expr,
+                    message: a.message.clone(),
+                    span: a.span
+                };
+                Ok((Statement::Assertion(new_stmt), reg_map))
+            }
+            Statement::Definition(d) => {
+                let mut new_lhs: Vec<TypedIdentifierOrAssignee> = Vec::new();
+                for l in &d.lhs {
+                    match l {
+                        TypedIdentifierOrAssignee::TypedIdentifier(tid) => {
+                            let new_id_expr: IdentifierExpression;
+                            (new_id_expr, reg_map) = var_to_reg_id_expr(&tid.identifier, reg_map);
+                            new_lhs.push(TypedIdentifierOrAssignee::TypedIdentifier(TypedIdentifier{
+                                array_metadata: None,
+                                ty: tid.ty.clone(),
+                                identifier: new_id_expr,
+                                span: tid.span
+                            }));
+                        }
+                        TypedIdentifierOrAssignee::Assignee(p) => {
+                            let new_id_expr: IdentifierExpression;
+                            (new_id_expr, reg_map) = var_to_reg_id_expr(&p.id, reg_map);
+                            let mut new_accesses: Vec<AssigneeAccess> = Vec::new();
+                            for aa in &p.accesses {
+                                let (e, a_span) = assignee_select_expr(aa, &p.span)?;
+                                let new_expr: Expression;
+                                (new_expr, reg_map) = var_to_reg_expr(e, reg_map)?;
+                                new_accesses.push(AssigneeAccess::Select(ArrayAccess {
+                                    expression: RangeOrExpression::Expression(new_expr),
+                                    span: *a_span
+                                }))
                             }
+                            new_lhs.push(TypedIdentifierOrAssignee::Assignee(Assignee{
+                                id: new_id_expr,
+                                accesses: new_accesses,
+                                span: p.span
+                            }));
                         }
-                        new_lhs.push(TypedIdentifierOrAssignee::Assignee(Assignee{
-                            id: new_id_expr,
-                            accesses: new_accesses,
-                            span: p.span
-                        }));
                     }
                 }
+                let new_expr: Expression;
+                (new_expr, reg_map) = var_to_reg_expr(&d.expression, reg_map)?;
+                let new_stmt = DefinitionStatement {
+                    lhs: new_lhs,
+                    expression: new_expr,
+                    span: d.span
+                };
+                Ok((Statement::Definition(new_stmt), reg_map))
             }
-            let new_expr: Expression;
-            (new_expr, reg_map) = var_to_reg_expr(&d.expression, reg_map);
-            let new_stmt = DefinitionStatement {
-                lhs: new_lhs,
-                expression: new_expr,
-                span: d.span
-            };
-            (Statement::Definition(new_stmt), reg_map)
+            _ => stmt_unsupported(s),
         }
-        Statement::CondStore(_) => { panic!("Blocks should not contain conditional store statements.") }
-        Statement::Witness(_) => { panic!("Witness statements unsupported.") }
-        Statement::ArrayDecl(_) => { panic!("Blocks should not contain array declaration statements.") }
-    }
+    })();
+    sp_locate(r, s.span())
 }
 
 // Turn all variables in an expression to a register reference
@@ -972,81 +958,73 @@ fn var_to_reg_stmt<'ast>(
 fn var_to_reg_expr<'ast>(
     e: &Expression<'ast>, 
     mut reg_map: BTreeMap<String, usize>, 
-) -> (Expression<'ast>, BTreeMap<String, usize>) {
-    match e {
-        Expression::Ternary(t) => {
-            let new_first: Expression;
-            let new_second: Expression;
-            let new_third: Expression;
-            (new_first, reg_map) = var_to_reg_expr(&t.first, reg_map);
-            (new_second, reg_map) = var_to_reg_expr(&t.second, reg_map);
-            (new_third, reg_map) = var_to_reg_expr(&t.third, reg_map);
-            return (Expression::Ternary(TernaryExpression {
-                first: Box::new(new_first),
-                second: Box::new(new_second),
-                third: Box::new(new_third),
-                span: t.span
-            }), reg_map);
-        }
-        Expression::Binary(b) => {
-            let new_left: Expression;
-            let new_right: Expression;
-            (new_left, reg_map) = var_to_reg_expr(&b.left, reg_map);
-            (new_right, reg_map) = var_to_reg_expr(&b.right, reg_map);
-            return (Expression::Binary(BinaryExpression {
-                op: b.op.clone(),
-                left: Box::new(new_left),
-                right: Box::new(new_right),
-                span: b.span
-            }), reg_map);
-        }
-        Expression::Unary(u) => {
-            let new_expr: Expression;
-            (new_expr, reg_map) = var_to_reg_expr(&u.expression, reg_map);
-            return (Expression::Unary(UnaryExpression {
-                op: u.op.clone(),
-                expression: Box::new(new_expr),
-                span: u.span
-            }), reg_map);
-        }
-        Expression::Postfix(p) => {
-            let mut new_accesses = Vec::new();
-            for aa in &p.accesses {
-                if let Access::Select(a) = aa {
-                    if let RangeOrExpression::Expression(e) = &a.expression {
-                        let new_expr: Expression;
-                        (new_expr, reg_map) = var_to_reg_expr(&e, reg_map);
-                        new_accesses.push(Access::Select(ArrayAccess {
-                            expression: RangeOrExpression::Expression(new_expr),
-                            span: a.span
-                        }));
-                    } else {
-                        panic!("Range access not supported.")
-                    }
-                } else {
-                    panic!("Unsupported membership access.")
+) -> Result<(Expression<'ast>, BTreeMap<String, usize>), SpannedError<'ast>> {
+    let r = (|| -> Result<_, SpannedError<'ast>> {
+        match e {
+            Expression::Ternary(t) => {
+                let new_first: Expression;
+                let new_second: Expression;
+                let new_third: Expression;
+                (new_first, reg_map) = var_to_reg_expr(&t.first, reg_map)?;
+                (new_second, reg_map) = var_to_reg_expr(&t.second, reg_map)?;
+                (new_third, reg_map) = var_to_reg_expr(&t.third, reg_map)?;
+                Ok((Expression::Ternary(TernaryExpression {
+                    first: Box::new(new_first),
+                    second: Box::new(new_second),
+                    third: Box::new(new_third),
+                    span: t.span
+                }), reg_map))
+            }
+            Expression::Binary(b) => {
+                let new_left: Expression;
+                let new_right: Expression;
+                (new_left, reg_map) = var_to_reg_expr(&b.left, reg_map)?;
+                (new_right, reg_map) = var_to_reg_expr(&b.right, reg_map)?;
+                Ok((Expression::Binary(BinaryExpression {
+                    op: b.op.clone(),
+                    left: Box::new(new_left),
+                    right: Box::new(new_right),
+                    span: b.span
+                }), reg_map))
+            }
+            Expression::Unary(u) => {
+                let new_expr: Expression;
+                (new_expr, reg_map) = var_to_reg_expr(&u.expression, reg_map)?;
+                Ok((Expression::Unary(UnaryExpression {
+                    op: u.op.clone(),
+                    expression: Box::new(new_expr),
+                    span: u.span
+                }), reg_map))
+            }
+            Expression::Postfix(p) => {
+                let mut new_accesses = Vec::new();
+                for aa in &p.accesses {
+                    let (e, a_span) = select_expr(aa, &p.span)?;
+                    let new_expr: Expression;
+                    (new_expr, reg_map) = var_to_reg_expr(e, reg_map)?;
+                    new_accesses.push(Access::Select(ArrayAccess {
+                        expression: RangeOrExpression::Expression(new_expr),
+                        span: *a_span
+                    }));
                 }
-            }
-            let new_id_expr: IdentifierExpression;
-            (new_id_expr, reg_map) = var_to_reg_id_expr(&p.id, reg_map);
-            return (Expression::Postfix(PostfixExpression {
-                id: new_id_expr,
-                accesses: new_accesses,
-                span: p.span
-            }), reg_map);
-        }
-        Expression::Identifier(i) => {
-            let new_id_expr: IdentifierExpression;
-            (new_id_expr, reg_map) = var_to_reg_id_expr(&i, reg_map);
-            return (Expression::Identifier(new_id_expr), reg_map);
-        }
-        Expression::Literal(_) => {
-            return (e.clone(), reg_map);
-        }
-        _ => {
-            panic!("Unsupported Expression.");
+                let new_id_expr: IdentifierExpression;
+                (new_id_expr, reg_map) = var_to_reg_id_expr(&p.id, reg_map);
+                Ok((Expression::Postfix(PostfixExpression {
+                    id: new_id_expr,
+                    accesses: new_accesses,
+                    span: p.span
+                }), reg_map))
+            }
+            Expression::Identifier(i) => {
+                let new_id_expr: IdentifierExpression;
+                (new_id_expr, reg_map) = var_to_reg_id_expr(&i, reg_map);
+                Ok((Expression::Identifier(new_id_expr), reg_map))
+            }
+            Expression::Literal(_) => Ok((e.clone(), reg_map)),
+            _ => Err(format!("Unsupported Expression.").into()),
         }
-    };
+    })();
+    sp_locate(r, e.span())
 }
 
 fn var_to_reg_id_expr<'ast>(
@@ -1086,23 +1064,17 @@ fn var_name_to_reg_id_expr<const MODE: usize>(
 fn tydef_to_assignee_stmt<'ast, const IN_BRANCH: bool>(
     s: &Statement<'ast>,
     mut gen_set: BTreeSet<String>, 
-) -> (Statement<'ast>, BTreeSet<String>, BTreeMap<String, Ty>) {
+) -> Result<(Statement<'ast>, BTreeSet<String>, BTreeMap<String, Ty>), SpannedError<'ast>> {
     let mut gen_map_branch = BTreeMap::new();
     match s {
-        Statement::Return(_) => {
-            panic!("Blocks should not contain return statements.");
-        }
-        Statement::Assertion(_) => (s.clone(), gen_set, gen_map_branch),
-        Statement::Conditional(_c) => { panic!("Blocks should not contain conditional statements.") }
-        Statement::Iteration(_) => { panic!("Blocks should not contain iteration statements.") }
-        Statement::WhileLoop(_) => { panic!("Blocks should not contain while loop statements.") }
+        Statement::Assertion(_) => Ok((s.clone(), gen_set, gen_map_branch)),
         Statement::Definition(d) => {
             let mut new_lhs: Vec<TypedIdentifierOrAssignee> = Vec::new();
             for l in &d.lhs {
                 match l {
                     TypedIdentifierOrAssignee::TypedIdentifier(tid) => {
                         let v = tid.identifier.value.to_string();
-                        let ty = type_to_ty(tid.ty.clone()).unwrap();
+                        let ty = sp_locate(type_to_ty(tid.ty.clone()).map_err(SpannedError::from), &tid.span)?;
                         // Variable has been declared
                         if gen_set.contains(&v) {
                             new_lhs.push(TypedIdentifierOrAssignee::Assignee(Assignee {
@@ -1142,11 +1114,9 @@ fn tydef_to_assignee_stmt<'ast, const IN_BRANCH: bool>(
                 expression: d.expression.clone(),
                 span: Span::new("", 0, 0).unwrap()
             });
-            (s, gen_set, gen_map_branch)
+            Ok((s, gen_set, gen_map_branch))
         }
-        Statement::CondStore(_) => { panic!("Blocks should not contain conditional store statements.") }
-        Statement::Witness(_) => { panic!("Witness statements unsupported.") }
-        Statement::ArrayDecl(_) => { panic!("Blocks should not contain array declaration statements.") }
+        _ => stmt_unsupported(s),
     }
 }
 
@@ -1163,10 +1133,10 @@ fn la_inst<'ast, const ELIM: bool>(
     mut state_per_call_trace: BTreeMap<Vec<usize>, BTreeSet<String>>,
     inst: &Vec<BlockContent<'ast>>,
     no_ro_accesses: bool,
-) -> (
+) -> Result<(
     BTreeSet<String>, BTreeMap<Vec<usize>, BTreeSet<String>>, Vec<BlockContent<'ast>>, 
     usize, usize // Number of RO and VM accesses
-) {
+), SpannedError<'ast>> {
     let mut new_instructions = Vec::new();
     let mut num_ro_ops = 0;
     let mut num_vm_ops = 0;
@@ -1211,7 +1181,7 @@ fn la_inst<'ast, const ELIM: bool>(
                 let read_only = if no_ro_accesses { &false } else { &read_only };
                 // if is_alive(&state, arr) {
                     new_instructions.insert(0, i.clone());
-                    let gen = expr_find_val(&len);
+                    let gen = expr_find_val(&len)?;
                     la_gen(&mut state, &gen);
                     state.remove(arr);
                     if *read_only {
@@ -1241,9 +1211,9 @@ fn la_inst<'ast, const ELIM: bool>(
                         num_vm_ops += 1;
                     }
                     state.insert(arr.to_string());
-                    let val_gen = expr_find_val(val_expr);
+                    let val_gen = expr_find_val(val_expr)?;
                     la_gen(&mut state, &val_gen);
-                    let id_gen = expr_find_val(id_expr);
+                    let id_gen = expr_find_val(id_expr)?;
                     la_gen(&mut state, &id_gen);
                     if !*read_only {
                         state.insert("%TS".to_string());
@@ -1270,7 +1240,7 @@ fn la_inst<'ast, const ELIM: bool>(
                     }
                     state.remove(val);
                     state.insert(arr.to_string());
-                    let gen = expr_find_val(id_expr);
+                    let gen = expr_find_val(id_expr)?;
                     la_gen(&mut state, &gen);
                     if !*read_only {
                         state.insert("%TS".to_string());
@@ -1306,9 +1276,9 @@ fn la_inst<'ast, const ELIM: bool>(
             BlockContent::Branch((cond, if_inst, else_inst)) => {
                 // Liveness of branches
                 let (mut new_if_state, mut new_if_state_per_call_trace, new_if_inst, left_ro_ops, left_vm_ops) = 
-                    la_inst::<ELIM>(state.clone(), state_per_call_trace.clone(), if_inst, no_ro_accesses);
+                    la_inst::<ELIM>(state.clone(), state_per_call_trace.clone(), if_inst, no_ro_accesses)?;
                 let (new_else_state, new_else_state_per_call_trace, new_else_inst, right_ro_ops, right_vm_ops) = 
-                    la_inst::<ELIM>(state.clone(), state_per_call_trace.clone(), else_inst, no_ro_accesses);
+                    la_inst::<ELIM>(state.clone(), state_per_call_trace.clone(), else_inst, no_ro_accesses)?;
                 new_if_state.extend(new_else_state);
                 assert_eq!(new_if_state_per_call_trace.len(), new_else_state_per_call_trace.len());
                 for (entry_point, _) in new_if_state_per_call_trace.clone() {
@@ -1317,7 +1287,7 @@ fn la_inst<'ast, const ELIM: bool>(
                 state = new_if_state;
                 state_per_call_trace = new_if_state_per_call_trace;
                 // Liveness of condition
-                let gen = expr_find_val(&cond);
+                let gen = expr_find_val(&cond)?;
                 la_gen(&mut state, &gen);
                 for (_, s) in state_per_call_trace.iter_mut() {
                     la_gen(s, &gen);
@@ -1336,7 +1306,7 @@ fn la_inst<'ast, const ELIM: bool>(
                 }
             }
             BlockContent::Stmt(s) => {
-                let (kill, gen) = stmt_find_val(s);
+                let (kill, gen) = stmt_find_val(s)?;
                 // If it's not a definition or the defined variable is alive,
                 // mark the variable dead and append gen to state
                 // Otherwise remove the statement
@@ -1352,14 +1322,14 @@ fn la_inst<'ast, const ELIM: bool>(
             }
         }
     }
-    (state, state_per_call_trace, new_instructions, num_ro_ops, num_vm_ops)
+    Ok((state, state_per_call_trace, new_instructions, num_ro_ops, num_vm_ops))
 }
 
 // Typing
 fn ty_inst<'ast>(
     mut state: BTreeMap<String, Ty>,
     inst: &Vec<BlockContent<'ast>>
-) -> BTreeMap<String, Ty> {
+) -> Result<BTreeMap<String, Ty>, SpannedError<'ast>> {
     for i in inst.iter().rev() {
         match i {
             BlockContent::Witness((var, ty, _)) => {
@@ -1378,23 +1348,23 @@ fn ty_inst<'ast>(
             }
             BlockContent::DummyLoad(_) => {}
             BlockContent::Branch((_, if_inst, else_inst)) => {
-                state = ty_inst(state, &if_inst);
-                state = ty_inst(state, &else_inst);
+                state = ty_inst(state, &if_inst)?;
+                state = ty_inst(state, &else_inst)?;
             }
             BlockContent::Stmt(s) => {
                 if let Statement::Definition(ds) = s {
                     for d in &ds.lhs {
                         if let TypedIdentifierOrAssignee::TypedIdentifier(p) = d {
                             let name = p.identifier.value.to_string();
-                            let ty = type_to_ty(p.ty.clone());
-                            state.insert(name, ty.unwrap());
+                            let ty = sp_locate(type_to_ty(p.ty.clone()).map_err(SpannedError::from), &p.span)?;
+                            state.insert(name, ty);
                         }
                     }
                 }
             }
         }
     }
-    state
+    Ok(state)
 }
 
 // Function Merge
@@ -1405,54 +1375,54 @@ fn fm_inst<'ast, const IS_CALLER: bool>(
     old_f_name: &String,
     new_f_name: &String,
     scope_diff: usize,
-) -> Vec<BlockContent<'ast>> {
+) -> Result<Vec<BlockContent<'ast>>, SpannedError<'ast>> {
     let mut new_instr = Vec::new();
     for i in inst.iter().rev() {
         match i {
             BlockContent::Witness((var, ty, alive)) => {
-                let new_var = var_fn_merge(var, old_f_name, new_f_name, scope_diff);
+                let new_var = var_fn_merge(var, old_f_name, new_f_name, scope_diff)?;
                 new_instr.insert(0, BlockContent::Witness((new_var, ty.clone(), alive.clone())));
             }
             BlockContent::MemPush((name, ty, offset)) => {
-                new_instr.insert(0, BlockContent::MemPush((var_fn_merge(name, old_f_name, new_f_name, scope_diff), ty.clone(), *offset)));
+                new_instr.insert(0, BlockContent::MemPush((var_fn_merge(name, old_f_name, new_f_name, scope_diff)?, ty.clone(), *offset)));
             }
             BlockContent::MemPop((id, ty, offset)) => {
-                new_instr.insert(0, BlockContent::MemPop((var_fn_merge(id, old_f_name, new_f_name, scope_diff), ty.clone(), *offset)));
+                new_instr.insert(0, BlockContent::MemPop((var_fn_merge(id, old_f_name, new_f_name, scope_diff)?, ty.clone(), *offset)));
             }
             BlockContent::ArrayInit((arr, ty, expr, ro)) => {
-                let new_arr = var_fn_merge(arr, old_f_name, new_f_name, scope_diff);
-                let new_expr = expr_replace_fn(expr, old_f_name, new_f_name, scope_diff);
+                let new_arr = var_fn_merge(arr, old_f_name, new_f_name, scope_diff)?;
+                let new_expr = expr_replace_fn(expr, old_f_name, new_f_name, scope_diff)?;
                 new_instr.insert(0, BlockContent::ArrayInit((new_arr, ty.clone(), new_expr, *ro)));
             }
             BlockContent::Store((val_expr, ty, arr, id_expr, init, ro)) => {
-                let new_val_expr = expr_replace_fn(val_expr, old_f_name, new_f_name, scope_diff);
-                let new_arr = var_fn_merge(arr, old_f_name, new_f_name, scope_diff);
-                let new_id_expr = expr_replace_fn(id_expr, old_f_name, new_f_name, scope_diff);
+                let new_val_expr = expr_replace_fn(val_expr, old_f_name, new_f_name, scope_diff)?;
+                let new_arr = var_fn_merge(arr, old_f_name, new_f_name, scope_diff)?;
+                let new_id_expr = expr_replace_fn(id_expr, old_f_name, new_f_name, scope_diff)?;
                 new_instr.insert(0, BlockContent::Store((new_val_expr, ty.clone(), new_arr, new_id_expr, *init, *ro)));
             }
             BlockContent::Load((val, ty, arr, id_expr, ro)) => {
-                let new_val = var_fn_merge(val, old_f_name, new_f_name, scope_diff);
-                let new_arr = var_fn_merge(arr, old_f_name, new_f_name, scope_diff);
-                let new_id_expr = expr_replace_fn(id_expr, old_f_name, new_f_name, scope_diff);
+                let new_val = var_fn_merge(val, old_f_name, new_f_name, scope_diff)?;
+                let new_arr = var_fn_merge(arr, old_f_name, new_f_name, scope_diff)?;
+                let new_id_expr = expr_replace_fn(id_expr, old_f_name, new_f_name, scope_diff)?;
                 new_instr.insert(0, BlockContent::Load((new_val, ty.clone(), new_arr, new_id_expr, *ro)));
             }
             BlockContent::DummyLoad(ro) => {
                 new_instr.insert(0, BlockContent::DummyLoad(*ro));
             }
             BlockContent::Branch((cond, if_inst, else_inst)) => {
-                let new_cond = expr_replace_fn(cond, old_f_name, new_f_name, scope_diff);
-                let new_if_inst = fm_inst::<IS_CALLER>(if_inst, old_f_name, new_f_name, scope_diff);
-                let new_else_inst = fm_inst::<IS_CALLER>(else_inst, old_f_name, new_f_name, scope_diff);
+                let new_cond = expr_replace_fn(cond, old_f_name, new_f_name, scope_diff)?;
+                let new_if_inst = fm_inst::<IS_CALLER>(if_inst, old_f_name, new_f_name, scope_diff)?;
+                let new_else_inst = fm_inst::<IS_CALLER>(else_inst, old_f_name, new_f_name, scope_diff)?;
                 new_instr.insert(0, BlockContent::Branch((new_cond, new_if_inst, new_else_inst)));
             }
             BlockContent::Stmt(s) => {
-                if !IS_CALLER || rp_find_val(i).is_none() {
-                    new_instr.insert(0, BlockContent::Stmt(stmt_replace_fn(s, old_f_name, new_f_name, scope_diff)));
+                if !IS_CALLER || rp_find_val(i)?.is_none() {
+                    new_instr.insert(0, BlockContent::Stmt(stmt_replace_fn(s, old_f_name, new_f_name, scope_diff)?));
                 }
             }
         }
     }
-    new_instr
+    Ok(new_instr)
 }
 
 // Var -> Reg
@@ -1460,7 +1430,7 @@ fn vtr_inst<'ast>(
     mut witness_map: BTreeMap<String, usize>,
     inst: &Vec<BlockContent<'ast>>,
     mut new_instr: Vec<BlockContent<'ast>>,
-) -> (BTreeMap<String, usize>, Vec<BlockContent<'ast>>) {
+) -> Result<(BTreeMap<String, usize>, Vec<BlockContent<'ast>>), SpannedError<'ast>> {
     for s in inst {
         match s {
             BlockContent::Witness((var, ty, alive)) => {
@@ -1482,15 +1452,15 @@ fn vtr_inst<'ast>(
                 let new_arr_name: String;
                 (new_arr_name, witness_map, _) = var_name_to_reg_id_expr::<0>(arr.to_string(), witness_map);
                 let new_size_expr: Expression;
-                (new_size_expr, witness_map) = var_to_reg_expr(&size_expr, witness_map);
+                (new_size_expr, witness_map) = var_to_reg_expr(&size_expr, witness_map)?;
                 new_instr.push(BlockContent::ArrayInit((new_arr_name, ty.clone(), new_size_expr, *ro)));
             }
             BlockContent::Store((val_expr, ty, arr, id_expr, init, ro)) => {
                 let new_val_expr: Expression;
                 let new_id_expr: Expression;
                 let new_arr_name: String;
-                (new_val_expr, witness_map) = var_to_reg_expr(&val_expr, witness_map);
-                (new_id_expr, witness_map) = var_to_reg_expr(&id_expr, witness_map);
+                (new_val_expr, witness_map) = var_to_reg_expr(&val_expr, witness_map)?;
+                (new_id_expr, witness_map) = var_to_reg_expr(&id_expr, witness_map)?;
                 (new_arr_name, witness_map, _) = var_name_to_reg_id_expr::<0>(arr.to_string(), witness_map);
                 new_instr.push(BlockContent::Store((new_val_expr, ty.clone(), new_arr_name, new_id_expr, *init, *ro)))
             }
@@ -1499,7 +1469,7 @@ fn vtr_inst<'ast>(
                 let new_id_expr: Expression;
                 let new_arr_name: String;
                 (new_val, witness_map, _) = var_name_to_reg_id_expr::<0>(val.to_string(), witness_map);
-                (new_id_expr, witness_map) = var_to_reg_expr(&id_expr, witness_map);
+                (new_id_expr, witness_map) = var_to_reg_expr(&id_expr, witness_map)?;
                 (new_arr_name, witness_map, _) = var_name_to_reg_id_expr::<0>(arr.to_string(), witness_map);
                 new_instr.push(BlockContent::Load((new_val, ty.clone(), new_arr_name, new_id_expr, *ro)))
             }
@@ -1510,19 +1480,19 @@ fn vtr_inst<'ast>(
                 let new_cond: Expression;
                 let new_if_inst: Vec<BlockContent<'ast>>;
                 let new_else_inst: Vec<BlockContent<'ast>>;
-                (new_cond, witness_map) = var_to_reg_expr(&cond, witness_map);
-                (witness_map, new_if_inst) = vtr_inst(witness_map, &if_inst, Vec::new());
-                (witness_map, new_else_inst) = vtr_inst(witness_map, &else_inst, Vec::new());
+                (new_cond, witness_map) = var_to_reg_expr(&cond, witness_map)?;
+                (witness_map, new_if_inst) = vtr_inst(witness_map, &if_inst, Vec::new())?;
+                (witness_map, new_else_inst) = vtr_inst(witness_map, &else_inst, Vec::new())?;
                 new_instr.push(BlockContent::Branch((new_cond, new_if_inst, new_else_inst)));
             }
             BlockContent::Stmt(s) => {
                 let new_stmt: Statement;
-                (new_stmt, witness_map) = var_to_reg_stmt(&s, witness_map);
+                (new_stmt, witness_map) = var_to_reg_stmt(&s, witness_map)?;
                 new_instr.push(BlockContent::Stmt(new_stmt));
             }
         }
     }
-    (witness_map, new_instr)
+    Ok((witness_map, new_instr))
 }
 
 // TyDef -> Assignee
@@ -1530,7 +1500,7 @@ fn vtr_inst<'ast>(
 fn tta_inst<'ast, const IN_BRANCH: bool>(
     mut gen_set: BTreeSet<String>,
     inst: &Vec<BlockContent<'ast>>,
-) -> (BTreeSet<String>, BTreeMap<String, Ty>, Vec<BlockContent<'ast>>) {
+) -> Result<(BTreeSet<String>, BTreeMap<String, Ty>, Vec<BlockContent<'ast>>), SpannedError<'ast>> {
     let mut new_instr = Vec::new();
     let mut gen_map_branch = BTreeMap::new();
     // Process instructions
@@ -1539,13 +1509,13 @@ fn tta_inst<'ast, const IN_BRANCH: bool>(
             BlockContent::Stmt(s) => {
                 let new_s: Statement;
                 let new_map: BTreeMap<String, Ty>;
-                (new_s, gen_set, new_map) = tydef_to_assignee_stmt::<IN_BRANCH>(s, gen_set);
+                (new_s, gen_set, new_map) = tydef_to_assignee_stmt::<IN_BRANCH>(s, gen_set)?;
                 new_instr.push(BlockContent::Stmt(new_s));
                 gen_map_branch.extend(new_map);
             }
             BlockContent::Branch((cond, if_inst, else_inst)) => {
-                let (mut gen_if_set, new_if_map, new_if_inst) = tta_inst::<true>(gen_set.clone(), &if_inst);
-                let (gen_else_set, new_else_map, new_else_inst) = tta_inst::<true>(gen_set, &else_inst);
+                let (mut gen_if_set, new_if_map, new_if_inst) = tta_inst::<true>(gen_set.clone(), &if_inst)?;
+                let (gen_else_set, new_else_map, new_else_inst) = tta_inst::<true>(gen_set, &else_inst)?;
                 gen_if_set.extend(gen_else_set);
                 gen_set = gen_if_set;
 
@@ -1571,7 +1541,119 @@ fn tta_inst<'ast, const IN_BRANCH: bool>(
             _ => { new_instr.push(i.clone()); }
         }
     }
-    (gen_set, gen_map_branch, new_instr)
+    Ok((gen_set, gen_map_branch, new_instr))
+}
+
+// Read-only Array Inference
+// Union-find over array pointers, path compression is not needed for the size of our programs
+fn ptr_find(alias_map: &BTreeMap<String, String>, var: &String) -> String {
+    let mut root = var.clone();
+    while let Some(parent) = alias_map.get(&root) {
+        if parent == &root { break; }
+        root = parent.clone();
+    }
+    root
+}
+
+fn ptr_union(alias_map: &mut BTreeMap<String, String>, var1: &String, var2: &String) {
+    let root1 = ptr_find(alias_map, var1);
+    let root2 = ptr_find(alias_map, var2);
+    if root1 != root2 {
+        alias_map.insert(root1, root2);
+    }
+}
+
+// Collect all pointer aliasing and memory information of a list of instructions
+// Returns the updated alias_map, as well as:
+//   alloc_list: all arrays initialized by ArrayInit and whether they are already read-only
+//   store_set: all pointers that are targets of a non-initializing STORE
+//   escape_set: all pointers that are themselves stored into memory
+//   external_set: all pointers that are supplied by the prover as witnesses
+fn roi_inst<'ast>(
+    inst: &Vec<BlockContent<'ast>>,
+    alias_map: &mut BTreeMap<String, String>,
+    alloc_list: &mut Vec<(String, bool)>,
+    store_set: &mut BTreeSet<String>,
+    escape_set: &mut BTreeSet<String>,
+    external_set: &mut BTreeSet<String>,
+) {
+    for i in inst {
+        match i {
+            BlockContent::Witness((var, ty, _)) => {
+                if let Ty::Array(..) = ty {
+                    external_set.insert(var.clone());
+                }
+            }
+            BlockContent::MemPush(_) => {}
+            BlockContent::MemPop(_) => {}
+            BlockContent::ArrayInit((arr, _, _, ro)) => {
+                alloc_list.push((arr.clone(), *ro));
+            }
+            BlockContent::Store((val_expr, _, arr, _, init, _)) => {
+                if !init {
+                    store_set.insert(arr.clone());
+                }
+                escape_set.extend(expr_find_ptr(val_expr));
+            }
+            BlockContent::Load(_) => {}
+            BlockContent::DummyLoad(_) => {}
+            BlockContent::Branch((_, if_inst, else_inst)) => {
+                roi_inst(if_inst, alias_map, alloc_list, store_set, escape_set, external_set);
+                roi_inst(else_inst, alias_map, alloc_list, store_set, escape_set, external_set);
+            }
+            BlockContent::Stmt(Statement::Definition(d)) => {
+                let ptr_set = expr_find_ptr(&d.expression);
+                for l in &d.lhs {
+                    let l_name = match l {
+                        TypedIdentifierOrAssignee::Assignee(a) => a.id.value.clone(),
+                        TypedIdentifierOrAssignee::TypedIdentifier(ti) => ti.identifier.value.clone(),
+                    };
+                    for p in &ptr_set {
+                        ptr_union(alias_map, &l_name, p);
+                    }
+                }
+            }
+            BlockContent::Stmt(_) => {}
+        }
+    }
+}
+
+// Mark every memory operation on a pointer in ro_set as read-only
+// Returns the new instructions and the number of memory operations converted
+// Following bmc_inst, operations inside a branch are only counted once
+fn roi_mark_inst<'ast>(
+    inst: &Vec<BlockContent<'ast>>,
+    alias_map: &BTreeMap<String, String>,
+    ro_set: &BTreeSet<String>,
+) -> (Vec<BlockContent<'ast>>, usize) {
+    let mut new_instr = Vec::new();
+    let mut num_converted = 0;
+    for i in inst {
+        match i {
+            BlockContent::ArrayInit((arr, ty, len_expr, ro)) => {
+                let new_ro = *ro || ro_set.contains(&ptr_find(alias_map, arr));
+                new_instr.push(BlockContent::ArrayInit((arr.clone(), ty.clone(), len_expr.clone(), new_ro)));
+            }
+            BlockContent::Store((val_expr, ty, arr, id_expr, init, ro)) => {
+                let new_ro = *ro || ro_set.contains(&ptr_find(alias_map, arr));
+                if new_ro != *ro { num_converted += 1; }
+                new_instr.push(BlockContent::Store((val_expr.clone(), ty.clone(), arr.clone(), id_expr.clone(), *init, new_ro)));
+            }
+            BlockContent::Load((val, ty, arr, id_expr, ro)) => {
+                let new_ro = *ro || ro_set.contains(&ptr_find(alias_map, arr));
+                if new_ro != *ro { num_converted += 1; }
+                new_instr.push(BlockContent::Load((val.clone(), ty.clone(), arr.clone(), id_expr.clone(), new_ro)));
+            }
+            BlockContent::Branch((cond, if_inst, else_inst)) => {
+                let (new_if_inst, if_converted) = roi_mark_inst(if_inst, alias_map, ro_set);
+                let (new_else_inst, _) = roi_mark_inst(else_inst, alias_map, ro_set);
+                num_converted += if_converted;
+                new_instr.push(BlockContent::Branch((cond.clone(), new_if_inst, new_else_inst)));
+            }
+            _ => { new_instr.push(i.clone()); }
+        }
+    }
+    (new_instr, num_converted)
 }
 
 // Block Memory Counter
@@ -1697,6 +1779,7 @@ use crate::front::zsharp::NO_OPT;
 use crate::front::zsharp::OPT_BLOCK_MERGE;
 use crate::front::zsharp::OPT_SPILLING;
 use crate::front::zsharp::OPT_RO_ARRAYS;
+use crate::front::zsharp::{BlockError, BlockStage};
 
 impl<'ast> ZGen<'ast> {
     // --
@@ -1717,13 +1800,37 @@ impl<'ast> ZGen<'ast> {
         // 3 - + Read-only Arrays
         opt_level: usize,
         VERBOSE: bool,
-    ) -> (Vec<Block<'ast>>, usize, BTreeSet<String>) {
+    ) -> Result<(Vec<Block<'ast>>, usize, BTreeSet<String>), BlockError> {
         println!("\n\n--\nOptimization:");
+        let mut pass_start = Instant::now();
+        let err = |e: String| self.bl_error(BlockStage::Optimization, e, None);
         // Add %SP and %AS to program input
         inputs.insert(0, ("%AS".to_string(), Ty::Field));
         inputs.insert(0, ("%SP".to_string(), Ty::Field));
 
+        self.export_cfg(&bls, entry_bl, "0_generation", &mut pass_start).map_err(&err)?;
         let no_ro_accesses = opt_level < OPT_RO_ARRAYS;
+        // Challenges are stored in read-only memory
+        if no_ro_accesses && self.bl_num_challenges.get() > 0 {
+            return Err(err(format!("sample_challenge requires opt_level of at least {}", OPT_RO_ARRAYS)));
+        }
+        if !no_ro_accesses {
+            // Read-only array inference
+            let (new_bls, promoted) = self.infer_read_only_arrays(bls, &inputs);
+            bls = new_bls;
+            if VERBOSE {
+                println!("Read-only arrays inferred: {}", promoted.len());
+                for arr in &promoted {
+                    println!("    {}", arr);
+                }
+                println!("\n\n--\nRead-only Array Inference:");
+                print_bls(&bls, &entry_bl);
+            }
+            self.record_pass_time("read_only_arrays", &mut pass_start);
+        }
+        // Largest merged block chosen by the cost model in block merge, also respected by EBE
+        // Blocks only count their constraints from block merge on, so there is no limit below OPT_BLOCK_MERGE
+        let mut merge_target_size = usize::MAX;
         if opt_level >= OPT_BLOCK_MERGE {
             // Construct CFG
             let (
@@ -1736,16 +1843,18 @@ impl<'ast> ZGen<'ast> {
                 exit_bls_fn,
                 _,
                 _
-            ) = self.construct_flow_graph(&bls, entry_bl);
+            ) = self.construct_flow_graph(&bls, entry_bl).map_err(&err)?;
             if VERBOSE && CFG_VERBOSE {
                 print_cfg(&successor, &predecessor, &exit_bls, &entry_bls_fn, &successor_fn, &predecessor_fn, &exit_bls_fn);
             }
             // Func Merge
-            bls = self.func_merge(bls, &predecessor, successor_fn, entry_bls_fn, exit_bls_fn);
+            bls = self.func_merge(bls, &predecessor, successor_fn, entry_bls_fn, exit_bls_fn).map_err(&err)?;
             if VERBOSE {
                 println!("\n\n--\nFunc Merge:");
                 print_bls(&bls, &entry_bl);
             }
+            self.record_pass_time("func_merge", &mut pass_start);
+            self.export_cfg(&bls, entry_bl, "1_func_merge", &mut pass_start).map_err(&err)?;
 
             // Reconstruct CFG
             let (
@@ -1758,15 +1867,16 @@ impl<'ast> ZGen<'ast> {
                 _,
                 _,
                 _
-            ) = self.construct_flow_graph(&bls, entry_bl);
+            ) = self.construct_flow_graph(&bls, entry_bl).map_err(&err)?;
             // Liveness
-            bls = self.liveness_analysis(bls, &successor, &predecessor,  &predecessor_fn, &exit_bls, no_ro_accesses);
+            bls = self.liveness_analysis(bls, &successor, &predecessor,  &predecessor_fn, &exit_bls, no_ro_accesses).map_err(&err)?;
             // DBE
-            (bls, entry_bl, _) = self.dead_block_elimination(bls, entry_bl, predecessor);
+            (bls, entry_bl, _) = self.dead_block_elimination(bls, entry_bl, predecessor).map_err(&err)?;
             if VERBOSE {
                 println!("\n\n--\nLiveness:");
                 print_bls(&bls, &entry_bl);
             }
+            self.record_pass_time("liveness", &mut pass_start);
 
             // Reconstruct CFG
             let (
@@ -1779,20 +1889,21 @@ impl<'ast> ZGen<'ast> {
                 exit_bls_fn,
                 _,
                 call_exit_entry_map
-            ) = self.construct_flow_graph(&bls, entry_bl);
+            ) = self.construct_flow_graph(&bls, entry_bl).map_err(&err)?;
             if VERBOSE && CFG_VERBOSE {
                 print_cfg(&successor, &predecessor, &exit_bls, &entry_bls_fn, &successor_fn, &predecessor_fn, &exit_bls_fn);
             }
 
             // Set Input Output
-            (bls, _) = self.set_input_output(bls, &successor, &predecessor, &predecessor_fn, &entry_bl, &exit_bls, &entry_bls_fn, &exit_bls_fn, &call_exit_entry_map, inputs.clone(), no_ro_accesses);
+            (bls, _) = self.set_input_output(bls, &successor, &predecessor, &predecessor_fn, &entry_bl, &exit_bls, &entry_bls_fn, &exit_bls_fn, &call_exit_entry_map, inputs.clone(), no_ro_accesses).map_err(&err)?;
             if VERBOSE {
                 println!("\n\n--\nSet Input Output before Spilling:");
                 print_bls(&bls, &entry_bl);
             }
+            self.record_pass_time("set_input_output", &mut pass_start);
 
             // Resolve block merge
-            bls = self.resolve_block_merge(bls, &successor, &successor_fn, &predecessor_fn, &exit_bls_fn, no_ro_accesses);
+            (bls, merge_target_size) = self.resolve_block_merge(bls, &successor, &successor_fn, &predecessor_fn, &exit_bls_fn, no_ro_accesses).map_err(&err)?;
             // Reconstruct CFG
             let (
                 successor, 
@@ -1804,16 +1915,18 @@ impl<'ast> ZGen<'ast> {
                 exit_bls_fn,
                 _,
                 _
-            ) = self.construct_flow_graph(&bls, entry_bl);
+            ) = self.construct_flow_graph(&bls, entry_bl).map_err(&err)?;
             if VERBOSE && CFG_VERBOSE {
                 print_cfg(&successor, &predecessor, &exit_bls, &entry_bls_fn, &successor_fn, &predecessor_fn, &exit_bls_fn);
             }
             // DBE
-            (bls, entry_bl, _) = self.dead_block_elimination(bls, entry_bl, predecessor);
+            (bls, entry_bl, _) = self.dead_block_elimination(bls, entry_bl, predecessor).map_err(&err)?;
             if VERBOSE {
                 println!("\n\n--\nBlock Merge:");
                 print_bls(&bls, &entry_bl);
             }
+            self.record_pass_time("block_merge", &mut pass_start);
+            self.export_cfg(&bls, entry_bl, "2_block_merge", &mut pass_start).map_err(&err)?;
 
             if opt_level >= OPT_SPILLING {
                 // Reconstruct CFG
@@ -1827,7 +1940,7 @@ impl<'ast> ZGen<'ast> {
                     exit_bls_fn,
                     _,
                     _
-                ) = self.construct_flow_graph(&bls, entry_bl);
+                ) = self.construct_flow_graph(&bls, entry_bl).map_err(&err)?;
                 if VERBOSE && CFG_VERBOSE {
                     print_cfg(&successor, &predecessor, &exit_bls, &entry_bls_fn, &successor_fn, &predecessor_fn, &exit_bls_fn);
                 }
@@ -1837,11 +1950,13 @@ impl<'ast> ZGen<'ast> {
                 // Note that this value is not the final io_size as it does not include any reserved registers
                 let tmp_io_size = self.get_max_io_size(&bls, &inputs);
                 // Perform spilling
-                bls = self.resolve_spilling(bls, tmp_io_size, &predecessor, &successor, entry_bl, &entry_bls_fn, &predecessor_fn, &successor_fn);
+                bls = self.resolve_spilling(bls, tmp_io_size, &predecessor, &successor, entry_bl, &entry_bls_fn, &predecessor_fn, &successor_fn).map_err(&err)?;
                 if VERBOSE {
                     println!("\n\n--\nSpilling:");
                     print_bls(&bls, &entry_bl);
                 }
+                self.record_pass_time("spilling", &mut pass_start);
+                self.export_cfg(&bls, entry_bl, "3_spilling", &mut pass_start).map_err(&err)?;
             }
         }
 
@@ -1856,21 +1971,22 @@ impl<'ast> ZGen<'ast> {
             exit_bls_fn,
             _,
             _
-        ) = self.construct_flow_graph(&bls, entry_bl);
+        ) = self.construct_flow_graph(&bls, entry_bl).map_err(&err)?;
         if VERBOSE && CFG_VERBOSE {
             print_cfg(&successor, &predecessor, &exit_bls, &entry_bls_fn, &successor_fn, &predecessor_fn, &exit_bls_fn);
         }
 
         // Liveness, mainly to remove %BP
-        bls = self.liveness_analysis(bls, &successor, &predecessor,  &predecessor_fn, &exit_bls, no_ro_accesses);
+        bls = self.liveness_analysis(bls, &successor, &predecessor,  &predecessor_fn, &exit_bls, no_ro_accesses).map_err(&err)?;
         // EBE
-        (_, predecessor, bls) = self.empty_block_elimination(bls, exit_bls, successor, predecessor, &entry_bls_fn, &exit_bls_fn);
+        (_, predecessor, bls) = self.empty_block_elimination(bls, exit_bls, successor, predecessor, &entry_bls_fn, &exit_bls_fn, merge_target_size).map_err(&err)?;
         // DBE
-        (bls, entry_bl, _) = self.dead_block_elimination(bls, entry_bl, predecessor);
+        (bls, entry_bl, _) = self.dead_block_elimination(bls, entry_bl, predecessor).map_err(&err)?;
         if VERBOSE {
             println!("\n\n--\nEBE:");
             print_bls(&bls, &entry_bl);
         }
+        self.record_pass_time("empty_block_elimination", &mut pass_start);
 
         // Construct CFG again after DBE
         let (
@@ -1883,19 +1999,150 @@ impl<'ast> ZGen<'ast> {
             exit_bls_fn,
             _,
             call_exit_entry_map
-        ) = self.construct_flow_graph(&bls, entry_bl);
+        ) = self.construct_flow_graph(&bls, entry_bl).map_err(&err)?;
         if VERBOSE && CFG_VERBOSE {
             print_cfg(&successor, &predecessor, &exit_bls, &entry_bls_fn, &successor_fn, &predecessor_fn, &exit_bls_fn);
         }
 
         // Set I/O again after optimizations
         let live_input_set: BTreeSet<String>;
-        (bls, live_input_set) = self.set_input_output(bls, &successor, &predecessor, &predecessor_fn, &entry_bl, &exit_bls, &entry_bls_fn, &exit_bls_fn, &call_exit_entry_map, inputs.clone(), no_ro_accesses);
+        (bls, live_input_set) = self.set_input_output(bls, &successor, &predecessor, &predecessor_fn, &entry_bl, &exit_bls, &entry_bls_fn, &exit_bls_fn, &call_exit_entry_map, inputs.clone(), no_ro_accesses).map_err(&err)?;
         if VERBOSE {
             println!("\n\n--\nSet Input Output after Spilling:");
             print_bls(&bls, &entry_bl);
         }
-        (bls, entry_bl, live_input_set)
+        self.record_pass_time("final_set_input_output", &mut pass_start);
+        self.export_cfg(&bls, entry_bl, "4_final", &mut pass_start).map_err(&err)?;
+        Ok((bls, entry_bl, live_input_set))
+    }
+
+    // Record the time elapsed since pass_start as the time of pass, and restart the timer
+    // CFG exports are excluded, since export_cfg restarts the timer once it is done
+    pub(super) fn record_pass_time(&self, pass: &str, pass_start: &mut Instant) {
+        self.pass_times.borrow_mut().push((pass.to_string(), pass_start.elapsed()));
+        *pass_start = Instant::now();
+    }
+
+    // Export the CFG of bls, and restart the timer of the next pass, so that the export is not counted in its time
+    fn export_cfg(
+        &self,
+        bls: &Vec<Block>,
+        entry_bl: usize,
+        stage: &str,
+        pass_start: &mut Instant,
+    ) -> Result<(), String> {
+        let r = self.export_cfg_impl_(bls, entry_bl, stage);
+        *pass_start = Instant::now();
+        r
+    }
+
+    // Export the CFG of bls as DOT or JSON to {cfg_export_dir}/cfg_{stage}.{dot, json}, according to the CirC options
+    // Every node records the metadata of a block, every edge is labelled as a call, a return, or a transition
+    fn export_cfg_impl_(
+        &self,
+        bls: &Vec<Block>,
+        entry_bl: usize,
+        stage: &str,
+    ) -> Result<(), String> {
+        let ext = match cfg().zsharp.cfg_export {
+            CfgExportFormat::None => { return Ok(()); }
+            CfgExportFormat::Dot => "dot",
+            CfgExportFormat::Json => "json",
+        };
+        let content = self.cfg_content_(bls, entry_bl, stage, ext == "dot")?;
+
+        let dir = &cfg().zsharp.cfg_export_dir;
+        let path = std::path::Path::new(if dir.is_empty() { "." } else { dir }).join(format!("cfg_{}.{}", stage, ext));
+        std::fs::write(&path, content).map_err(|e| format!("Failed to export CFG to {}: {}", path.display(), e))
+    }
+
+    // The exported CFG of bls, as DOT if dot is set, otherwise as JSON
+    fn cfg_content_(
+        &self,
+        bls: &Vec<Block>,
+        entry_bl: usize,
+        stage: &str,
+        dot: bool,
+    ) -> Result<String, String> {
+        let (
+            successor, 
+            _, 
+            _, 
+            _, 
+            successor_fn, 
+            _, 
+            _,
+            call_entry_exit_map,
+            _
+        ) = self.construct_flow_graph(bls, entry_bl)?;
+
+        // Only successors within the same function are transitions
+        // Otherwise the edge either jumps from a caller into the callee or from a callee exit to the return block
+        let mut edges: Vec<(usize, usize, &str)> = Vec::new();
+        for cur_bl in 0..bls.len() {
+            for next_bl in &successor[cur_bl] {
+                let kind = if successor_fn[cur_bl].contains(next_bl) {
+                    "transition"
+                } else if call_entry_exit_map.contains_key(&cur_bl) {
+                    "call"
+                } else {
+                    "return"
+                };
+                edges.push((cur_bl, *next_bl, kind));
+            }
+        }
+        // Only list live inputs and outputs
+        let live_vars = |vars: &Vec<(String, Option<Ty>)>| -> Vec<String> {
+            vars.iter().filter_map(|(name, ty)| ty.as_ref().map(|ty| format!("{}: {}", name, ty))).collect()
+        };
+
+        let content = if dot {
+            let mut content = format!("digraph cfg_{} {{\n    node [shape=box, fontname=\"monospace\"];\n", stage);
+            for b in bls {
+                let mut label = format!("Block {}{}\\lfn: {}, scope: {}\\lexec bound: {}, cons: {}\\lro ops: {}, vm ops: {}\\l",
+                    b.name,
+                    if b.name == entry_bl { " (entry)" } else { "" },
+                    b.fn_name, b.scope, b.fn_num_exec_bound, b.num_cons, b.num_ro_ops, b.num_vm_ops
+                );
+                label.push_str(&format!("in: {}\\l", live_vars(&b.inputs).join(", ")));
+                label.push_str(&format!("out: {}\\l", live_vars(&b.outputs).join(", ")));
+                content.push_str(&format!("    {} [label=\"{}\"];\n", b.name, label.replace("\"", "\\\"")));
+            }
+            for (cur_bl, next_bl, kind) in &edges {
+                let style = match *kind {
+                    "call" => ", style=dashed",
+                    "return" => ", style=dotted",
+                    _ => ""
+                };
+                content.push_str(&format!("    {} -> {} [label=\"{}\"{}];\n", cur_bl, next_bl, kind, style));
+            }
+            content.push_str("}\n");
+            content
+        } else {
+            let json = serde_json::json!({
+                "stage": stage,
+                "entry_bl": entry_bl,
+                "blocks": bls.iter().map(|b| serde_json::json!({
+                    "name": b.name,
+                    "fn_name": b.fn_name,
+                    "scope": b.scope,
+                    "fn_num_exec_bound": b.fn_num_exec_bound,
+                    "is_head_of_while_loop": b.is_head_of_while_loop,
+                    "num_cons": b.num_cons,
+                    "num_ro_ops": b.num_ro_ops,
+                    "num_vm_ops": b.num_vm_ops,
+                    "inputs": live_vars(&b.inputs),
+                    "outputs": live_vars(&b.outputs),
+                })).collect::<Vec<_>>(),
+                "edges": edges.iter().map(|(cur_bl, next_bl, kind)| serde_json::json!({
+                    "from": cur_bl,
+                    "to": next_bl,
+                    "kind": kind,
+                })).collect::<Vec<_>>(),
+            });
+            serde_json::to_string_pretty(&json).unwrap()
+        };
+        Ok(content)
     }
 
     // Return value: successor, rp_successor, successor_fn, visited, next_bls
@@ -1909,7 +2156,7 @@ impl<'ast> ZGen<'ast> {
         mut successor_fn: Vec<BTreeSet<usize>>,
         mut visited: Vec<bool>,
         mut next_bls: VecDeque<usize>
-    ) -> (Vec<BTreeSet<usize>>, Vec<BTreeSet<usize>>, Vec<BTreeSet<usize>>, Vec<bool>, VecDeque<usize>) {
+    ) -> Result<(Vec<BTreeSet<usize>>, Vec<BTreeSet<usize>>, Vec<BTreeSet<usize>>, Vec<bool>, VecDeque<usize>), String> {
 
         match next_bl {
             NextBlock::Label(tmp_bl) => {
@@ -1947,7 +2194,7 @@ impl<'ast> ZGen<'ast> {
             }
             NextBlock::Rp(_) => {
                 if rp_successor.len() == 0 {
-                    panic!("Control flow graph construction fails: reaching end of function point but rp@ not set!")
+                    return Err(format!("Control flow graph construction fails: reaching end of function point but rp@ not set!"));
                 }
                 // Add everything in rp_successor of cur_bl to successor of cur_bl
                 for i in rp_successor[cur_bl].iter() {
@@ -1956,7 +2203,7 @@ impl<'ast> ZGen<'ast> {
                 // Whatever that rp is should already be in next_bls
             }
         }
-        return (successor, rp_successor, successor_fn, visited, next_bls);
+        return Ok((successor, rp_successor, successor_fn, visited, next_bls));
     }
 
     // Construct a flow graph from a set of blocks
@@ -1965,7 +2212,7 @@ impl<'ast> ZGen<'ast> {
         &self,
         bls: &Vec<Block>,
         entry_bl: usize
-    ) -> (Vec<BTreeSet<usize>>, Vec<BTreeSet<usize>>, BTreeSet<usize>, BTreeSet<usize>, Vec<BTreeSet<usize>>, Vec<BTreeSet<usize>>, BTreeSet<usize>, BTreeMap<usize, usize>, BTreeMap<usize, usize>) {
+    ) -> Result<(Vec<BTreeSet<usize>>, Vec<BTreeSet<usize>>, BTreeSet<usize>, BTreeSet<usize>, Vec<BTreeSet<usize>>, Vec<BTreeSet<usize>>, BTreeSet<usize>, BTreeMap<usize, usize>, BTreeMap<usize, usize>), String> {
         let bl_size = bls.len();
         
         // list of all blocks that ends with ProgTerm
@@ -2008,7 +2255,7 @@ impl<'ast> ZGen<'ast> {
             let mut rp_slot = 0;
             
             for i in 0..bls[cur_bl].instructions.len() {
-                if let Some(tmp_bl) = rp_find_val(&bls[cur_bl].instructions[i]) {
+                if let Some(tmp_bl) = rp_find_val(&bls[cur_bl].instructions[i])? {
                     rp_slot = tmp_bl;
                 }
             }
@@ -2016,7 +2263,7 @@ impl<'ast> ZGen<'ast> {
             // Process RP block
             if rp_slot != 0 {
                 (successor, rp_successor, successor_fn, visited, next_bls) = 
-                    self.flow_graph_transition::<true>(cur_bl, &NextBlock::Label(rp_slot), rp_slot, successor, rp_successor, successor_fn, visited, next_bls);
+                    self.flow_graph_transition::<true>(cur_bl, &NextBlock::Label(rp_slot), rp_slot, successor, rp_successor, successor_fn, visited, next_bls)?;
                 call_entry_exit_map.insert(cur_bl, rp_slot);
                 call_exit_entry_map.insert(rp_slot, cur_bl);
             }
@@ -2025,10 +2272,10 @@ impl<'ast> ZGen<'ast> {
             // according to flow_graph_transition
             match bls[cur_bl].terminator.clone() {
                 BlockTerminator::Transition(e) => {
-                    let branches = bl_trans_find_val(&e);
+                    let branches = bl_trans_find_val(&e)?;
                     for b in &branches {
                         (successor, rp_successor, successor_fn, visited, next_bls) = 
-                            self.flow_graph_transition::<false>(cur_bl, b, rp_slot, successor, rp_successor, successor_fn, visited, next_bls);
+                            self.flow_graph_transition::<false>(cur_bl, b, rp_slot, successor, rp_successor, successor_fn, visited, next_bls)?;
                     }
                     // if rp@ is set, the next block must be a function entrance
                     if rp_slot != 0 {
@@ -2036,7 +2283,7 @@ impl<'ast> ZGen<'ast> {
                             if let NextBlock::Label(l) = b {
                                 entry_bl_fn.insert(*l);
                             } else {
-                                panic!("Blocks {} invokes function calls and cannot terminate to rp@ block.", cur_bl)
+                                return Err(format!("Blocks {} invokes function calls and cannot terminate to rp@ block.", cur_bl));
                             }
                         }
 
@@ -2048,7 +2295,7 @@ impl<'ast> ZGen<'ast> {
                         }
                     }
                 }
-                BlockTerminator::FuncCall(_) => { panic!("Blocks pending optimization should not have FuncCall as terminator.") }
+                BlockTerminator::FuncCall(_) => { return Err(format!("Blocks pending optimization should not have FuncCall as terminator.")); }
                 BlockTerminator::ProgTerm => { 
                     exit_bls.insert(cur_bl);
                     exit_bls_fn.insert(cur_bl);
@@ -2064,7 +2311,7 @@ impl<'ast> ZGen<'ast> {
                 predecessor_fn[*j].insert(i);
             }
         }
-        return (successor, predecessor, exit_bls, entry_bl_fn, successor_fn, predecessor_fn, exit_bls_fn, call_entry_exit_map, call_exit_entry_map);
+        return Ok((successor, predecessor, exit_bls, entry_bl_fn, successor_fn, predecessor_fn, exit_bls_fn, call_entry_exit_map, call_exit_entry_map));
     }
 
     // Standard Liveness Analysis
@@ -2090,7 +2337,7 @@ impl<'ast> ZGen<'ast> {
         predecessor_fn: &Vec<BTreeSet<usize>>,
         exit_bls: &BTreeSet<usize>,
         no_ro_accesses: bool,
-    ) -> Vec<Block<'ast>> {
+    ) -> Result<Vec<Block<'ast>>, String> {
         let mut visited: Vec<bool> = vec![false; bls.len()];
         // MEET is union, so IN and OUT are Empty Set
         let mut bl_in: Vec<BTreeSet<String>> = vec![BTreeSet::new(); bls.len()];
@@ -2098,7 +2345,7 @@ impl<'ast> ZGen<'ast> {
         
         // Can this ever happen?
         if exit_bls.is_empty() { 
-            panic!("The program has no exit block!");
+            return Err(format!("The program has no exit block!"));
         }
         
         // Start from exit block
@@ -2128,15 +2375,15 @@ impl<'ast> ZGen<'ast> {
 
                 // KILL and GEN within the terminator
                 match &bls[cur_bl].terminator {
-                    BlockTerminator::Transition(e) => { state.extend(expr_find_val(&e)); }
-                    BlockTerminator::FuncCall(_) => { panic!("Blocks pending optimization should not have FuncCall as terminator.") }
+                    BlockTerminator::Transition(e) => { state.extend(expr_find_val(&e).map_err(|e| self.bl_locate_spanned(e))?); }
+                    BlockTerminator::FuncCall(_) => { return Err(format!("Blocks pending optimization should not have FuncCall as terminator.")); }
                     BlockTerminator::ProgTerm => {}            
                 }
 
                 // KILL and GEN within the block
                 // We do not need to worry about state_per_trace in liveness analysis
                 // Only useful in set_input_output
-                (state, _, _, _, _) = la_inst::<false>(state, BTreeMap::new(), &bls[cur_bl].instructions, no_ro_accesses);
+                (state, _, _, _, _) = la_inst::<false>(state, BTreeMap::new(), &bls[cur_bl].instructions, no_ro_accesses).map_err(|e| self.bl_locate_spanned(e))?;
                 bl_in[cur_bl] = state;
 
                 // Block Transition
@@ -2173,14 +2420,14 @@ impl<'ast> ZGen<'ast> {
 
                 // KILL and GEN within the terminator
                 match &bls[cur_bl].terminator {
-                    BlockTerminator::Transition(e) => { state.extend(expr_find_val(&e)); }
-                    BlockTerminator::FuncCall(_) => { panic!("Blocks pending optimization should not have FuncCall as terminator.") }
+                    BlockTerminator::Transition(e) => { state.extend(expr_find_val(&e).map_err(|e| self.bl_locate_spanned(e))?); }
+                    BlockTerminator::FuncCall(_) => { return Err(format!("Blocks pending optimization should not have FuncCall as terminator.")); }
                     BlockTerminator::ProgTerm => {}            
                 }
 
                 let num_ro_ops: usize;
                 let num_vm_ops: usize;
-                (_, _, new_instructions, num_ro_ops, num_vm_ops) = la_inst::<true>(state, BTreeMap::new(), &bls[cur_bl].instructions, no_ro_accesses);
+                (_, _, new_instructions, num_ro_ops, num_vm_ops) = la_inst::<true>(state, BTreeMap::new(), &bls[cur_bl].instructions, no_ro_accesses).map_err(|e| self.bl_locate_spanned(e))?;
                 bls[cur_bl].instructions = new_instructions;
                 bls[cur_bl].num_ro_ops = num_ro_ops;
                 bls[cur_bl].num_vm_ops = num_vm_ops;
@@ -2197,7 +2444,7 @@ impl<'ast> ZGen<'ast> {
             }    
         }
 
-        return bls;
+        return Ok(bls);
     }
 
     // If a function is called only once, merge that function with its caller, which includes:
@@ -2212,7 +2459,7 @@ impl<'ast> ZGen<'ast> {
         mut successor_fn: Vec<BTreeSet<usize>>,
         mut entry_bls_fn: BTreeSet<usize>,
         mut exit_bls_fn: BTreeSet<usize>,
-    ) -> Vec<Block<'ast>> {
+    ) -> Result<Vec<Block<'ast>>, String> {
         // Repeatedly scan through all blocks until no function can be merged
         let mut changed = true;
         // Iterate through CFG, find all functions that are called once
@@ -2236,7 +2483,7 @@ impl<'ast> ZGen<'ast> {
                     
                     // Merge callee with caller
                     // First on caller (to deal with call parameters)
-                    bls[caller].instructions = fm_inst::<true>(&bls[caller].instructions, callee_fn, caller_fn, scope_diff);
+                    bls[caller].instructions = fm_inst::<true>(&bls[caller].instructions, callee_fn, caller_fn, scope_diff).map_err(|e| self.bl_locate_spanned(e))?;
                     // Then on callee
                     let mut visited: Vec<bool> = vec![false; bls.len()];
                     let mut next_bls: VecDeque<usize> = VecDeque::new();
@@ -2250,10 +2497,10 @@ impl<'ast> ZGen<'ast> {
                             bls[cur_bl].fn_name = caller_fn.clone();
                             bls[cur_bl].scope += scope_diff;
                             bls[cur_bl].fn_num_exec_bound *= num_exec_factor;
-                            bls[cur_bl].instructions = fm_inst::<false>(&bls[cur_bl].instructions, callee_fn, caller_fn, scope_diff);
+                            bls[cur_bl].instructions = fm_inst::<false>(&bls[cur_bl].instructions, callee_fn, caller_fn, scope_diff).map_err(|e| self.bl_locate_spanned(e))?;
                             // Update terminator
                             if let BlockTerminator::Transition(e) = &bls[cur_bl].terminator {
-                                bls[cur_bl].terminator = BlockTerminator::Transition(expr_replace_fn(e, callee_fn, caller_fn, scope_diff));
+                                bls[cur_bl].terminator = BlockTerminator::Transition(expr_replace_fn(e, callee_fn, caller_fn, scope_diff).map_err(|e| self.bl_locate_spanned(e))?);
                             } else {
                                 unreachable!();
                             }
@@ -2288,12 +2535,12 @@ impl<'ast> ZGen<'ast> {
                         }
                     }
                     // Finally on caller_exit
-                    bls[caller_exit].instructions = fm_inst::<false>(&bls[caller_exit].instructions, callee_fn, caller_fn, scope_diff);
+                    bls[caller_exit].instructions = fm_inst::<false>(&bls[caller_exit].instructions, callee_fn, caller_fn, scope_diff).map_err(|e| self.bl_locate_spanned(e))?;
                 }
             }
         }
 
-        return bls;
+        return Ok(bls);
     }
 
     // For each block, set its input to be variables that are alive & defined at the entry point of the block and their type
@@ -2312,7 +2559,7 @@ impl<'ast> ZGen<'ast> {
         call_exit_entry_map: &BTreeMap<usize, usize>,
         inputs: Vec<(String, Ty)>,
         no_ro_accesses: bool,
-    ) -> (Vec<Block<'ast>>, BTreeSet<String>) {
+    ) -> Result<(Vec<Block<'ast>>, BTreeSet<String>), String> {
         // Liveness
         let mut visited: Vec<bool> = vec![false; bls.len()];
         // MEET is union, so IN and OUT are Empty Set
@@ -2324,7 +2571,7 @@ impl<'ast> ZGen<'ast> {
         
         // Can this ever happen?
         if exit_bls.is_empty() { 
-            panic!("The program has no exit block!");
+            return Err(format!("The program has no exit block!"));
         }
         
         // Start from exit block
@@ -2397,17 +2644,18 @@ impl<'ast> ZGen<'ast> {
                 // KILL and GEN within the terminator
                 match &bls[cur_bl].terminator {
                     BlockTerminator::Transition(e) => { 
-                        state.extend(expr_find_val(&e)); 
+                        let gen = expr_find_val(&e).map_err(|e| self.bl_locate_spanned(e))?;
+                        state.extend(gen.clone());
                         for (_, s) in state_per_trace.iter_mut() {
-                            s.extend(expr_find_val(&e)); 
+                            s.extend(gen.clone());
                         }
                     }
-                    BlockTerminator::FuncCall(_) => { panic!("Blocks pending optimization should not have FuncCall as terminator.") }
+                    BlockTerminator::FuncCall(_) => { return Err(format!("Blocks pending optimization should not have FuncCall as terminator.")); }
                     BlockTerminator::ProgTerm => {}            
                 }
 
                 // KILL and GEN within the block
-                (state, state_per_trace, _, _, _) = la_inst::<false>(state, state_per_trace, &bls[cur_bl].instructions, no_ro_accesses);
+                (state, state_per_trace, _, _, _) = la_inst::<false>(state, state_per_trace, &bls[cur_bl].instructions, no_ro_accesses).map_err(|e| self.bl_locate_spanned(e))?;
 
                 bl_in[cur_bl] = state;
                 bl_in_per_call_trace[cur_bl] = state_per_trace.clone();
@@ -2450,7 +2698,7 @@ impl<'ast> ZGen<'ast> {
                 for (name, ty) in &bl_out[*s] {
                     if let Some(k) = state.get(name) {
                         if *ty != *k {
-                            panic!("Type analysis failed: mismatched types on variable {} of block {}, expected {:?} but obtained {:?}", name, cur_bl, ty, k)
+                            return Err(format!("Type analysis failed: mismatched types on variable {} of block {}, expected {:?} but obtained {:?}", name, cur_bl, ty, k));
                         }
                     }
                     if state.get(name) == None {
@@ -2473,7 +2721,7 @@ impl<'ast> ZGen<'ast> {
 
                 // No KILL, GEN if we meet a typed definition
                 // The only case we need to process is Typed Definition
-                state = ty_inst(state, &bls[cur_bl].instructions);
+                state = ty_inst(state, &bls[cur_bl].instructions).map_err(|e| self.bl_locate_spanned(e))?;
                 bl_out[cur_bl] = state;
 
                 // Terminator is just an expression so we don't need to worry about it
@@ -2518,18 +2766,20 @@ impl<'ast> ZGen<'ast> {
             }
         }
 
-        return (bls, live_input_set);
+        return Ok((bls, live_input_set));
     }
 
     // Count number of constraints for a block
-    fn bl_count_num_cons(
+    // In estimation mode, outputs and the terminator are checked at the end of the block (see bl_to_circ)
+    // Otherwise, the block should have been processed by process_block::<0>, and is counted as is
+    pub fn bl_count_num_cons<const ESTIMATE: bool>(
         &self,
         bl: &Block<'ast>,
         no_ro_accesses: bool,
-    ) -> usize {
+    ) -> Result<usize, String> {
         let block_name = &format!("Pseudo_Block_{}", bl.name);
         self.circ_init_block(block_name);
-        self.bl_to_circ::<true>(bl, block_name, no_ro_accesses);
+        self.bl_to_circ::<ESTIMATE>(bl, block_name, no_ro_accesses);
 
         let mut cs = Computations::new();
         cs.comps = self.circ.borrow().cir_ctx().cs.borrow_mut().clone();
@@ -2540,9 +2790,9 @@ impl<'ast> ZGen<'ast> {
             // Remove the last constraint because it is about the return value
             r1cs.constraints.pop();
 
-            return r1cs.constraints.len();
+            return Ok(r1cs.constraints.len());
         } else {
-            panic!("Count num cons failed: block {} does not exist!", bl.name);
+            return Err(format!("Count num cons failed: block {} does not exist!", bl.name));
         }
     }
 
@@ -2555,11 +2805,11 @@ impl<'ast> ZGen<'ast> {
         predecessor_fn: &Vec<BTreeSet<usize>>,
         exit_bls_fn: &BTreeSet<usize>,
         no_ro_accesses: bool,
-    ) -> Vec<Block<'ast>> {
+    ) -> Result<(Vec<Block<'ast>>, usize), String> {
         // STEP 1: Obtain number of constraints for all blocks
         let mut bl_num_cons = Vec::new();
         for b in &bls {
-            bl_num_cons.push(self.bl_count_num_cons(b, no_ro_accesses));
+            bl_num_cons.push(self.bl_count_num_cons::<true>(b, no_ro_accesses)?);
         }
         // Reset self.circ
         self.circ.borrow_mut().reset(ZSharp::new());
@@ -2567,8 +2817,6 @@ impl<'ast> ZGen<'ast> {
         for i in 0..bls.len() {
             bls[i].num_cons = bl_num_cons[i];
         }
-        // Maximum # of constraints is max(MAX_BLOCK_SIZE, bl_num_cons.max().next_power_of_two())
-        let max_num_cons = max(MAX_BLOCK_SIZE, bl_num_cons.iter().max().unwrap().next_power_of_two());
 
         // STEP 2: Backward analysis within each function
         // For each block, if there exists a potential merge component, record the size of constraints of that component
@@ -2671,17 +2919,57 @@ impl<'ast> ZGen<'ast> {
             }
         }
 
-        // STEP 3: Iterate through the blocks to perform merge
+        // STEP 3: Pick the target instance size using the Spartan cost model
+        // Candidates are powers of two starting from the largest block. Perform the merge under each candidate
+        // and keep the one with the lowest estimated prover time. Once a merge leaves no component behind,
+        // a larger target cannot change the outcome, so stop there.
+        let mut target_size = bl_num_cons.iter().max().unwrap().next_power_of_two();
+        let mut candidates = Vec::new();
+        let mut outcomes = Vec::new();
+        loop {
+            let (merged_bls, merged_num_cons, absorbed, remaining) = self.merge_components(
+                bls.clone(), bl_num_cons.clone(), count_list.clone(), scope_list.clone(), &scope_head_list, predecessor_fn, target_size
+            )?;
+            outcomes.push((0..merged_bls.len()).filter(|i| !absorbed[*i]).map(|i| (merged_num_cons[i], merged_bls[i].fn_num_exec_bound)).collect());
+            candidates.push((target_size, merged_bls));
+            if !remaining {
+                break;
+            }
+            target_size *= 2;
+        }
+        let (best, cost) = cheapest_merge(&outcomes);
+        let (target_size, bls) = candidates.swap_remove(best);
+        if VERBOSE {
+            println!("Block merge target size: {}, estimated prover cost: {}", target_size, cost);
+        }
+
+        Ok((bls, target_size))
+    }
+
+    // Merge every component whose size is below target_size, lowest label first
+    // Returns the new blocks, their number of constraints, whether each block has been absorbed into a component,
+    // and whether any component was left unmerged
+    fn merge_components(
+        &self,
+        mut bls: Vec<Block<'ast>>,
+        mut bl_num_cons: Vec<usize>,
+        mut count_list: Vec<usize>,
+        mut scope_list: Vec<Vec<usize>>,
+        scope_head_list: &Vec<usize>,
+        predecessor_fn: &Vec<BTreeSet<usize>>,
+        target_size: usize,
+    ) -> Result<(Vec<Block<'ast>>, Vec<usize>, Vec<bool>, bool), String> {
         // This is a partial backward analysis on the component we want to merge
         // We want to merge the largest component possible, which means it will start at the block with the lowest label
         // Repeat the merge process until there is nothing left to be merged
+        let mut absorbed = vec![false; bls.len()];
         let mut changed = true;
         while changed {
             changed = false;
             // DO NOT MERGE BLOCK 0!!!
             // This will be handled later by EBE
             for i in 1..bls.len() {
-                if count_list[i] > 0 && count_list[i] < max_num_cons {
+                if count_list[i] > 0 && count_list[i] < target_size {
                     // Process a merge
                     let comp_head = i;
                     let comp_scope = bls[comp_head].scope;
@@ -2715,7 +3003,7 @@ impl<'ast> ZGen<'ast> {
                         if cur_bl != comp_tail {                  
                             if let BlockTerminator::Transition(t) = &bls[cur_bl].terminator {
                                 let (merged_instr, merged_cons_count, merged_ro_count, merged_vm_count, _) = 
-                                    term_to_instr(&bls, t, &instr_list, &cons_count_list, &ro_count_list, &vm_count_list, cur_bl);
+                                    term_to_instr(&bls, t, &instr_list, &cons_count_list, &ro_count_list, &vm_count_list, cur_bl)?;
                                 instr_state.extend(merged_instr);
                                 cons_count_state += merged_cons_count;
                                 ro_count_state += merged_ro_count;
@@ -2749,6 +3037,9 @@ impl<'ast> ZGen<'ast> {
                         if cur_bl != comp_head && cur_bl != comp_tail {
                             count_list[cur_bl] = 0;
                         }
+                        if cur_bl != comp_head {
+                            absorbed[cur_bl] = true;
+                        }
                     }
 
                     // Recompute num_cons, scope, & count for comp_head & comp_tail
@@ -2770,7 +3061,8 @@ impl<'ast> ZGen<'ast> {
             }
         }
 
-        bls
+        let remaining = count_list.iter().skip(1).any(|c| *c > 0);
+        Ok((bls, bl_num_cons, absorbed, remaining))
     }
 
     // Obtain io_size = maximum # of variables in any transition state that are in scope
@@ -2804,7 +3096,7 @@ impl<'ast> ZGen<'ast> {
         entry_bls_fn: &BTreeSet<usize>,
         predecessor_fn: &Vec<BTreeSet<usize>>,
         successor_fn: &Vec<BTreeSet<usize>>,
-    ) -> Vec<Block<'ast>> {
+    ) -> Result<Vec<Block<'ast>>, String> {
         // Number of spills required for every block
         let mut spill_size = vec![0];
         for i in 1..bls.len() {
@@ -2893,7 +3185,7 @@ impl<'ast> ZGen<'ast> {
                     match i {
                         BlockContent::Stmt(stmt) => {
                             // GEN describes all newly defined variables
-                            let (gen, _) = stmt_find_val(stmt);
+                            let (gen, _) = stmt_find_val(stmt).map_err(|e| self.bl_locate_spanned(e))?;
                             for shadower in &gen {
                                 let shadower_alive = bls[cur_bl].outputs.iter().fold(false, |a, b| a || &b.0 == shadower);
                                 // Proceed if the shadower lives till the end of the block
@@ -3148,7 +3440,7 @@ impl<'ast> ZGen<'ast> {
                     match i {
                         BlockContent::Stmt(stmt) => {
                             // GEN describes all newly defined variables
-                            let (gen, _) = stmt_find_val(stmt);
+                            let (gen, _) = stmt_find_val(stmt).map_err(|e| self.bl_locate_spanned(e))?;
                             for shadower in &gen {
                                 let shadower_alive = bls[cur_bl].outputs.iter().fold(false, |a, b| a || &b.0 == shadower);
                                 // Proceed if the shadower live till the end of the block
@@ -3291,14 +3583,15 @@ impl<'ast> ZGen<'ast> {
                 }
             }
         }
-        bls
+        Ok(bls)
     }
 
     // EBE: Backward analysis
     // If a block is empty and its terminator is a coda (to another block or rp@)
     // replace all the reference to it in its predecessors with that terminator
     // If a block terminates with a branching and both branches to the same block, eliminate the branching
-    // If a block only has one successor and that successor only has one predecessor, merge the two blocks
+    // If a block only has one successor and that successor only has one predecessor, merge the two blocks,
+    // unless the merged block exceeds max_num_cons, the target size chosen by block merge
     //
     // We assume that something would happen after the function call, so we do not change the value of any rp@
     // This would not affect correctness. Worst case it might make DBE later inefficient.
@@ -3311,8 +3604,9 @@ impl<'ast> ZGen<'ast> {
         mut successor: Vec<BTreeSet<usize>>,
         mut predecessor: Vec<BTreeSet<usize>>,
         entry_bls_fn: &BTreeSet<usize>,
-        exit_bls_fn: &BTreeSet<usize>
-    ) -> (Vec<BTreeSet<usize>>, Vec<BTreeSet<usize>>, Vec<Block<'ast>>) {
+        exit_bls_fn: &BTreeSet<usize>,
+        max_num_cons: usize,
+    ) -> Result<(Vec<BTreeSet<usize>>, Vec<BTreeSet<usize>>, Vec<Block<'ast>>), String> {
 
         let mut visited: Vec<bool> = Vec::new();
         for _ in 0..bls.len() {
@@ -3321,7 +3615,7 @@ impl<'ast> ZGen<'ast> {
         
         // Can this ever happen?
         if exit_bls.is_empty() {
-            panic!("The program has no exit block!");
+            return Err(format!("The program has no exit block!"));
         }
         
         // Start from exit block
@@ -3336,17 +3630,15 @@ impl<'ast> ZGen<'ast> {
 
             // If the block only has one successor and the successor only has one predecessor
             // AND the transition does not involve function calls / returns, 
-            // AND the merged block size (num cons) does not exceed MAX_BLOCK_SIZE,
+            // AND the merged block size (num cons) does not exceed max_num_cons,
             // merge the two blocks
+            // Both blocks have the same number of executions, so a merge only saves an instance,
+            // while a block above the target size raises the padded size of every instance
             if !exit_bls_fn.contains(&cur_bl) && successor[cur_bl].len() == 1 {
                 let s = Vec::from_iter(successor[cur_bl].clone())[0];
                 if !entry_bls_fn.contains(&s) && 
-                    predecessor[s].len() == 1// &&
-                    // (
-                        // bls[cur_bl].num_cons + bls[s].num_cons > MAX_BLOCK_SIZE || // Limit the size of the merged block
-                        // bls[cur_bl].num_cons * 4 < bls[s].num_cons || // But also allow merges between a large block and a small one
-                        // bls[s].num_cons * 4 < bls[cur_bl].num_cons
-                    // )
+                    predecessor[s].len() == 1 &&
+                    bls[cur_bl].num_cons.saturating_add(bls[s].num_cons) <= max_num_cons
                 {
                     // Append s to cur_bl
                     let s_inst = bls[s].clone();
@@ -3371,7 +3663,7 @@ impl<'ast> ZGen<'ast> {
                         if let BlockTerminator::Transition(cur_e) = &bls[cur_bl].terminator {
                             if let BlockTerminator::Transition(e) = &bls[tmp_bl].terminator {
                                 // Replace terminator of the predecessors
-                                let new_e = bl_trans_replace(e, cur_bl, cur_e);
+                                let new_e = bl_trans_replace(e, cur_bl, cur_e)?;
                                 bls[tmp_bl].terminator = BlockTerminator::Transition(new_e);
 
                                 // Update CFG
@@ -3388,7 +3680,7 @@ impl<'ast> ZGen<'ast> {
                 }
             }
         }
-        return (successor, predecessor, bls);
+        return Ok((successor, predecessor, bls));
     }
 
     // DBE: Remove all the dead blocks in the list
@@ -3399,7 +3691,7 @@ impl<'ast> ZGen<'ast> {
         bls: Vec<Block<'ast>>,
         entry_bl: usize,
         predecessor: Vec<BTreeSet<usize>>
-    ) -> (Vec<Block<'ast>>, usize, BTreeMap<usize, usize>) {      
+    ) -> Result<(Vec<Block<'ast>>, usize, BTreeMap<usize, usize>), String> {      
         let old_size = bls.len();
         
         // Initialize map from old label of blocks to new labels
@@ -3434,10 +3726,68 @@ impl<'ast> ZGen<'ast> {
             
             // Update the terminator of each blocks using label_map
             if let BlockTerminator::Transition(e) = &new_bls[cur_bl].terminator {
-                new_bls[cur_bl].terminator = BlockTerminator::Transition(bl_trans_map(e, &label_map))
+                new_bls[cur_bl].terminator = BlockTerminator::Transition(bl_trans_map(e, &label_map)?)
+            }
+        }
+        return Ok((new_bls, new_entry_bl, label_map));
+    }
+
+    // --
+    // READ-ONLY ARRAY INFERENCE
+    // --
+
+    // Promote every array that is never STOREd to after initialization to a read-only array
+    // Pointers are tracked through definitions, which include function parameters and return values
+    // An array is NOT promoted if any pointer that might alias with it:
+    //   1. is the target of a non-initializing STORE,
+    //   2. is itself STOREd into memory (we lose track of it afterwards),
+    //   3. is a program input or a witness (its content is not initialized by the program)
+    // Must be performed before any block optimization, while variables still carry their full names
+    // Returns the new blocks and the names of all promoted arrays
+    fn infer_read_only_arrays(
+        &self,
+        mut bls: Vec<Block<'ast>>,
+        inputs: &Vec<(String, Ty)>,
+    ) -> (Vec<Block<'ast>>, Vec<String>) {
+        let mut alias_map: BTreeMap<String, String> = BTreeMap::new();
+        let mut alloc_list: Vec<(String, bool)> = Vec::new();
+        let mut store_set: BTreeSet<String> = BTreeSet::new();
+        let mut escape_set: BTreeSet<String> = BTreeSet::new();
+        let mut external_set: BTreeSet<String> = BTreeSet::new();
+        for (name, ty) in inputs {
+            if let Ty::Array(..) = ty {
+                external_set.insert(name.clone());
             }
         }
-        return (new_bls, new_entry_bl, label_map);
+        for b in &bls {
+            roi_inst(&b.instructions, &mut alias_map, &mut alloc_list, &mut store_set, &mut escape_set, &mut external_set);
+        }
+
+        // Every alias class that contains a disqualifying pointer
+        let disqualified: BTreeSet<String> = store_set.iter()
+            .chain(escape_set.iter())
+            .chain(external_set.iter())
+            .map(|p| ptr_find(&alias_map, p))
+            .collect();
+        let mut ro_set: BTreeSet<String> = BTreeSet::new();
+        let mut promoted = Vec::new();
+        for (arr, ro) in &alloc_list {
+            let root = ptr_find(&alias_map, arr);
+            if !ro && !disqualified.contains(&root) {
+                ro_set.insert(root);
+                promoted.push(arr.clone());
+            }
+        }
+
+        if !ro_set.is_empty() {
+            for b in bls.iter_mut() {
+                let (new_instructions, num_converted) = roi_mark_inst(&b.instructions, &alias_map, &ro_set);
+                b.instructions = new_instructions;
+                b.num_ro_ops += num_converted;
+                b.num_vm_ops -= num_converted;
+            }
+        }
+        (bls, promoted)
     }
 
     // --
@@ -3455,8 +3805,10 @@ impl<'ast> ZGen<'ast> {
         opt_level: usize,
         VERBOSE: bool,
         // inputs: Vec<(String, Ty)>,
-    ) -> (Vec<Block<'ast>>, usize, usize, usize, Vec<(Vec<usize>, Vec<usize>)>, Vec<(usize, usize)>, Vec<Vec<usize>>) { //, Vec<usize>) {
+    ) -> Result<(Vec<Block<'ast>>, usize, usize, usize, Vec<(Vec<usize>, Vec<usize>)>, Vec<(usize, usize)>, Vec<Vec<usize>>), BlockError> { //, Vec<usize>) {
         println!("\n\n--\nPost-Processing:");
+        let mut pass_start = Instant::now();
+        let err = |e: String| self.bl_error(BlockStage::Optimization, e, None);
         // Construct a new CFG for the program
         // Note that this is the CFG after DBE, and might be different from the previous CFG
         let (
@@ -3469,7 +3821,7 @@ impl<'ast> ZGen<'ast> {
             exit_bls_fn,
             _,
             _
-        ) = self.construct_flow_graph(&bls, entry_bl);
+        ) = self.construct_flow_graph(&bls, entry_bl).map_err(&err)?;
         if VERBOSE && CFG_VERBOSE {
             print_cfg(&successor, &predecessor, &exit_bls, &entry_bls_fn, &successor_fn, &predecessor_fn, &exit_bls_fn);
         }
@@ -3487,7 +3839,7 @@ impl<'ast> ZGen<'ast> {
         */
 
         // VtR
-        let (bls, transition_map_list, io_size, witness_map, witness_size, live_io) = self.var_to_reg::<MODE>(bls, &predecessor, &successor, entry_bl);
+        let (bls, transition_map_list, io_size, witness_map, witness_size, live_io) = self.var_to_reg::<MODE>(bls, &predecessor, &successor, entry_bl).map_err(&err)?;
         if VERBOSE {
             println!("\n\n--\nVar -> Reg:");
             println!("Var -> IO map:");
@@ -3500,20 +3852,23 @@ impl<'ast> ZGen<'ast> {
                 println!("  BLOCK {}: {:?}", i, live_io[i])
             }
         }
+        self.record_pass_time("var_to_reg", &mut pass_start);
         // let input_indices = inputs.
 
         // Convert Typed Defs back to Assignees
-        let bls = self.tydef_to_assignee::<MODE>(bls);
+        let bls = self.tydef_to_assignee::<MODE>(bls).map_err(&err)?;
         if VERBOSE {
             println!("\n\n--\nTydef -> Assignee:");
             print_bls(&bls, &entry_bl);
         }
+        self.record_pass_time("tydef_to_assignee", &mut pass_start);
 
         // Obtain # of scoping memory accesses per block
         let (num_mem_accesses, live_vm) = self.get_blocks_memory_info(&bls, opt_level < OPT_RO_ARRAYS);
+        self.record_pass_time("memory_info", &mut pass_start);
 
         print_bls(&bls, &entry_bl);
-        (bls, entry_bl, io_size, witness_size, live_io, num_mem_accesses, live_vm)
+        Ok((bls, entry_bl, io_size, witness_size, live_io, num_mem_accesses, live_vm))
     }
 
     // Convert all mentionings of variables to registers
@@ -3548,7 +3903,7 @@ impl<'ast> ZGen<'ast> {
         predecessor: &Vec<BTreeSet<usize>>,
         successor: &Vec<BTreeSet<usize>>,
         entry_bl: usize
-    ) -> (Vec<Block<'ast>>, Vec<BTreeMap<String, usize>>, usize, BTreeMap<String, usize>, usize, Vec<(Vec<usize>, Vec<usize>)>) {    
+    ) -> Result<(Vec<Block<'ast>>, Vec<BTreeMap<String, usize>>, usize, BTreeMap<String, usize>, usize, Vec<(Vec<usize>, Vec<usize>)>), String> {    
         // reg_map is consisted of two Var -> Reg Maps: TRANSITION_MAP_LIST & WITNESS_MAP
         // TRANSITION_MAP_LIST is a list of maps corresponding to each transition state
         // Reserve registers 0 - 7 for %V, %BN, %RET, %TS, %AS, %RP, %SP, and %BP
@@ -3567,6 +3922,7 @@ impl<'ast> ZGen<'ast> {
             for (v, _) in &bls[entry_bl].inputs {
                 (_, io_map, _) = var_name_to_reg_id_expr::<1>(v.to_string(), io_map);
             }
+            self.bl_entry_input_regs.replace(io_map.clone());
             io_map
         };
         bl_in[entry_bl] = Some(0);
@@ -3719,7 +4075,7 @@ impl<'ast> ZGen<'ast> {
             bls[i].inputs = new_inputs;
 
             // Map the instructions
-            (witness_map, new_instr) = vtr_inst(witness_map, &bls[i].instructions, new_instr);
+            (witness_map, new_instr) = vtr_inst(witness_map, &bls[i].instructions, new_instr).map_err(|e| self.bl_locate_spanned(e))?;
 
             // Map the outputs
             // If in MODE 0, assert the %o variables
@@ -3787,7 +4143,7 @@ impl<'ast> ZGen<'ast> {
             let new_expr = {
                 let new_expr: Expression;
                 if let BlockTerminator::Transition(e) = &bls[i].terminator {
-                    (new_expr, witness_map) = var_to_reg_expr(&e, witness_map);
+                    (new_expr, witness_map) = var_to_reg_expr(&e, witness_map).map_err(|e| self.bl_locate_spanned(e))?;
                     bls[i].terminator = BlockTerminator::Transition(new_expr.clone());
                 } else {
                     // If it is the end of the program, assign %BN to be bls.len()
@@ -3846,10 +4202,14 @@ impl<'ast> ZGen<'ast> {
             live_io[i].1.sort();
         }
         if max_io_size > 999999 {
-            panic!("Register assignment failed: number of i/o variables cannot exceed 999999!")
+            return Err(format!("Register assignment failed: number of i/o variables cannot exceed 999999!"));
         }
         let witness_size = witness_map.len();
-        (bls, transition_map_list, max_io_size, witness_map, witness_size, live_io)
+        // Record the variable held by each register, only needed by the debugger
+        if self.debugger.borrow().is_some() {
+            *self.reg_name_map.borrow_mut() = RegNameMap::new(&transition_map_list, &bl_in, &bl_out, &witness_map);
+        }
+        Ok((bls, transition_map_list, max_io_size, witness_map, witness_size, live_io))
     }
 
     // Revert typed identifiers back to assignees to avoid scoping confusion when translating to IR
@@ -3862,7 +4222,7 @@ impl<'ast> ZGen<'ast> {
     fn tydef_to_assignee<const MODE: usize>(
         &self,
         mut bls: Vec<Block<'ast>>,
-    ) -> Vec<Block<'ast>> {
+    ) -> Result<Vec<Block<'ast>>, String> {
         for i in 0..bls.len() {
             // gen_set - all defined variables
             let mut gen_set = BTreeSet::new();
@@ -3877,10 +4237,10 @@ impl<'ast> ZGen<'ast> {
                 }
             }
             // Process instructions
-            let (_, _, new_instr) = tta_inst::<false>(gen_set, &bls[i].instructions);
+            let (_, _, new_instr) = tta_inst::<false>(gen_set, &bls[i].instructions).map_err(|e| self.bl_locate_spanned(e))?;
             bls[i].instructions = new_instr;
         }
-        bls
+        Ok(bls)
     }
 
     // Construct a view of memory from the blocks.
@@ -4018,4 +4378,215 @@ impl<'ast> ZGen<'ast> {
 
         (total_num_proofs_bound, total_num_mem_accesses_bound)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::cfg::cfg_or_default;
+    use crate::front::zsharp::parser;
+    use crate::front::Mode;
+    use std::collections::HashMap;
+
+    // Generate the blocks of a Z# program and return the base names of all arrays promoted to read-only
+    fn promoted_arrays(name: &str, src: &str) -> Vec<String> {
+        let path = std::env::temp_dir().join(format!("zsharp_roi_{}_{}.zok", name, std::process::id()));
+        std::fs::write(&path, src).unwrap();
+        let loader = parser::ZLoad::new();
+        let asts = loader.load(&path);
+        // The source is kept by the loader, so the file is not needed past this point
+        std::fs::remove_file(&path).unwrap();
+        let mut g = ZGen::new(asts, Mode::Proof, loader.stdlib(), cfg_or_default().zsharp.isolate_asserts);
+        g.visit_files();
+        g.file_stack_push(path);
+        g.generics_stack_push(HashMap::new());
+
+        let (bls, _, mut inputs) = g.bl_gen_entry_fn("main").unwrap();
+        inputs.insert(0, ("%AS".to_string(), Ty::Field));
+        inputs.insert(0, ("%SP".to_string(), Ty::Field));
+        let (_, promoted) = g.infer_read_only_arrays(bls, &inputs);
+        promoted.iter().map(|p| p.split('.').next().unwrap().to_string()).collect()
+    }
+
+    #[test]
+    fn promote_initialized_array() {
+        let promoted = promoted_arrays("init", "
+def main(u32 x) -> field:
+    field[4] a = [1, 2, 3, 4]
+    return a[x]
+");
+        assert_eq!(promoted, vec!["a".to_string()]);
+    }
+
+    #[test]
+    fn store_disqualifies() {
+        let promoted = promoted_arrays("store", "
+def main(u32 x) -> field:
+    field[4] a = [1, 2, 3, 4]
+    field[4] b = [5, 6, 7, 8]
+    b[x] = 0
+    return a[x] + b[x]
+");
+        assert_eq!(promoted, vec!["a".to_string()]);
+    }
+
+    #[test]
+    fn store_through_parameter_disqualifies() {
+        let promoted = promoted_arrays("param", "
+def get(field[4] arr, u32 i) -> field:
+    return arr[i]
+
+def set(field[4] arr, u32 i) -> field[4]:
+    arr[i] = 0
+    return arr
+
+def main(u32 x) -> field:
+    field[4] a = [1, 2, 3, 4]
+    field[4] b = [5, 6, 7, 8]
+    field[4] c = set(b, x)
+    return get(a, x) + c[x]
+");
+        assert_eq!(promoted, vec!["a".to_string()]);
+    }
+
+    #[test]
+    fn escape_disqualifies() {
+        let promoted = promoted_arrays("escape", "
+struct S {
+    field[2] v
+}
+
+def main(u32 x) -> field:
+    field[2] a = [1, 2]
+    field[2] b = [3, 4]
+    S[1] s = [S { v: a }]
+    return s[0].v[x] + b[x]
+");
+        assert!(!promoted.contains(&"a".to_string()));
+        assert!(promoted.contains(&"b".to_string()));
+    }
+
+    #[test]
+    fn alias_of_input_disqualifies() {
+        // arr aliases both the program input and b, so b may be written by the prover
+        let promoted = promoted_arrays("external", "
+def get(field[4] arr, u32 i) -> field:
+    return arr[i]
+
+def main(field[4] a, u32 x) -> field:
+    field[4] b = [5, 6, 7, 8]
+    field[4] c = [1, 2, 3, 4]
+    return get(a, x) + get(b, x) + c[x]
+");
+        assert_eq!(promoted, vec!["c".to_string()]);
+    }
+
+    #[test]
+    fn merge_target_follows_execution_counts() {
+        // A loop head of 500 constraints with a body of 100, either kept apart or unrolled into the head
+        let outcomes = |k: usize| vec![vec![(500, 1), (100, k)], vec![(500 + 100 * k, 1)]];
+        // A few iterations are cheaper unrolled into one instance
+        assert_eq!(cheapest_merge(&outcomes(4)), (1, 10240));
+        // Many iterations would double the padded size of the unrolled block
+        assert_eq!(cheapest_merge(&outcomes(8)), (0, 17408));
+    }
+
+    // Check that both exports of the CFG of bls parse and list exactly the blocks and edges of its flow graph
+    fn check_cfg_export(g: &ZGen, bls: &Vec<Block>, entry_bl: usize, stage: &str) {
+        let successor = g.construct_flow_graph(bls, entry_bl).unwrap().0;
+        let blocks: BTreeSet<usize> = (0..bls.len()).collect();
+        let edges: BTreeSet<(usize, usize)> = successor.iter().enumerate()
+            .flat_map(|(cur_bl, next_bls)| next_bls.iter().map(move |next_bl| (cur_bl, *next_bl)))
+            .collect();
+
+        let json: serde_json::Value = serde_json::from_str(&g.cfg_content_(bls, entry_bl, stage, false).unwrap()).unwrap();
+        assert_eq!(json["stage"], stage);
+        assert_eq!(json["entry_bl"], entry_bl);
+        let json_blocks: BTreeSet<usize> = json["blocks"].as_array().unwrap().iter()
+            .map(|b| b["name"].as_u64().unwrap() as usize)
+            .collect();
+        assert_eq!(json_blocks, blocks);
+        let json_edges: Vec<(usize, usize)> = json["edges"].as_array().unwrap().iter()
+            .map(|e| {
+                assert!(["transition", "call", "return"].contains(&e["kind"].as_str().unwrap()));
+                (e["from"].as_u64().unwrap() as usize, e["to"].as_u64().unwrap() as usize)
+            })
+            .collect();
+        assert_eq!(json_edges.len(), edges.len());
+        assert_eq!(json_edges.into_iter().collect::<BTreeSet<_>>(), edges);
+
+        let dot = g.cfg_content_(bls, entry_bl, stage, true).unwrap();
+        let mut lines: Vec<&str> = dot.lines().collect();
+        assert_eq!(lines.remove(0), format!("digraph cfg_{} {{", stage));
+        assert_eq!(lines.pop(), Some("}"));
+        let mut dot_blocks = BTreeSet::new();
+        let mut dot_edges = BTreeSet::new();
+        for line in &lines[1..] {
+            let (stmt, attrs) = line.trim().split_once(" [label=\"").unwrap();
+            assert!(attrs.ends_with("];"));
+            match stmt.split_once(" -> ") {
+                Some((cur_bl, next_bl)) => assert!(dot_edges.insert((cur_bl.parse().unwrap(), next_bl.parse().unwrap()))),
+                None => assert!(dot_blocks.insert(stmt.parse::<usize>().unwrap())),
+            }
+        }
+        assert_eq!(dot_blocks, blocks);
+        assert_eq!(dot_edges, edges);
+    }
+
+    #[test]
+    fn cfg_export_matches_flow_graph() {
+        let path = std::env::temp_dir().join(format!("zsharp_cfg_export_{}.zok", std::process::id()));
+        std::fs::write(&path, "
+def inc(field x) -> field:
+    return x + 1
+
+def main(field x) -> field:
+    field y = x
+    for u32 i in 0..4 do
+        y = inc(y)
+    endfor
+    return y
+").unwrap();
+        let loader = parser::ZLoad::new();
+        let asts = loader.load(&path);
+        std::fs::remove_file(&path).unwrap();
+        let mut g = ZGen::new(asts, Mode::Proof, loader.stdlib(), cfg_or_default().zsharp.isolate_asserts);
+        g.visit_files();
+        g.file_stack_push(path);
+        g.generics_stack_push(HashMap::new());
+
+        let (bls, entry_bl, inputs) = g.bl_gen_entry_fn("main").unwrap();
+        // The loop and the call each split the program into blocks
+        assert!(bls.len() > 2);
+        check_cfg_export(&g, &bls, entry_bl, "0_generation");
+        let (bls, entry_bl, _) = g.optimize_block(bls, entry_bl, inputs, OPT_RO_ARRAYS, false).unwrap();
+        check_cfg_export(&g, &bls, entry_bl, "optimized");
+    }
+
+    #[test]
+    fn challenge_without_ro_arrays_is_error() {
+        let path = std::env::temp_dir().join(format!("zsharp_opt_err_{}.zok", std::process::id()));
+        std::fs::write(&path, "
+from \"EMBED\" import sample_challenge
+
+def main(private field x, private field y) -> field:
+    field a = sample_challenge([x, y])
+    assert(a * x == a * y)
+    return x
+").unwrap();
+        let loader = parser::ZLoad::new();
+        let asts = loader.load(&path);
+        std::fs::remove_file(&path).unwrap();
+        let mut g = ZGen::new(asts, Mode::Proof, loader.stdlib(), cfg_or_default().zsharp.isolate_asserts);
+        g.visit_files();
+        g.file_stack_push(path);
+        g.generics_stack_push(HashMap::new());
+
+        let (bls, entry_bl, inputs) = g.bl_gen_entry_fn("main").unwrap();
+        // The error is returned, not raised as a panic
+        let e = g.optimize_block(bls, entry_bl, inputs, NO_OPT, false).unwrap_err();
+        assert_eq!(e.stage, BlockStage::Optimization);
+        assert!(e.msg.contains("sample_challenge"));
+    }
+}
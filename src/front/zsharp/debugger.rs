@@ -0,0 +1,420 @@
+// Interactive debugger for block execution
+// The debugger is invoked by bl_eval_entry_fn before every block execution, and stops when
+// 1. the user is stepping through blocks,
+// 2. the next block is a breakpoint, or is the first block of a function with a breakpoint,
+// 3. the previous block accessed a watched memory address, or
+// 4. the block execution failed
+// When stopped, commands are read from the input (stdin by default) until the user resumes execution
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{BufRead, BufReader, Write};
+use crate::front::zsharp::blocks::Block;
+use crate::front::zsharp::pretty::pretty_name;
+use crate::front::zsharp::prover::{MemOp, STORE};
+use crate::front::zsharp::term::to_const_value;
+use crate::front::zsharp::T;
+
+use rug::Integer;
+
+const HELP: &str = "\
+Commands:
+  s, step [N]          execute the next N (default 1) blocks
+  c, continue          execute until the next breakpoint or watchpoint
+  b, break LABEL|FN    break before block LABEL, or when entering function FN
+  d, delete LABEL|FN   delete a breakpoint
+  w, watch phy|vir A   stop after any block that accesses memory address A
+  u, unwatch phy|vir A delete a watchpoint
+  p, print VAR|REG     print variable VAR (by its Z# name) or register REG (%iN, %oN, %wN)
+  r, regs              print the input registers of the next block
+  m, mem phy|vir A [N] print N (default 1) memory cells starting at address A
+  l, list              print the next block
+  i, info              print all breakpoints and watchpoints
+  q, quit              abort execution
+  h, help              print this message";
+
+/// Names of the variables held by each register, recorded by var_to_reg
+#[derive(Default)]
+pub struct RegNameMap {
+    // bl_in_names[X][K]: variable held by %iK at the entrance of block X
+    bl_in_names: Vec<BTreeMap<usize, String>>,
+    // bl_out_names[X][K]: variable held by %oK at the exit of block X
+    bl_out_names: Vec<BTreeMap<usize, String>>,
+    // witness_names[K]: variable held by %wK
+    witness_names: BTreeMap<usize, String>,
+}
+
+impl RegNameMap {
+    pub fn new(
+        transition_map_list: &Vec<BTreeMap<String, usize>>,
+        bl_in: &Vec<Option<usize>>,
+        bl_out: &Vec<Option<usize>>,
+        witness_map: &BTreeMap<String, usize>,
+    ) -> RegNameMap {
+        let rev_map = |m: &BTreeMap<String, usize>| m.iter().map(|(v, r)| (*r, v.clone())).collect::<BTreeMap<usize, String>>();
+        let transition_names: Vec<BTreeMap<usize, String>> = transition_map_list.iter().map(rev_map).collect();
+        RegNameMap {
+            bl_in_names: bl_in.iter().map(|t| transition_names[t.unwrap()].clone()).collect(),
+            bl_out_names: bl_out.iter().map(|t| transition_names[t.unwrap()].clone()).collect(),
+            witness_names: rev_map(witness_map),
+        }
+    }
+}
+
+// Variables are named as <name>.<fn>.<depth>.<version>, strip everything after the name
+fn source_name(var: &str) -> &str {
+    var.split(".").next().unwrap_or(var)
+}
+
+fn t_to_string(t: &T) -> String {
+    let mut buf = Vec::new();
+    t.pretty(&mut buf).expect("error pretty-printing value");
+    String::from_utf8(buf).unwrap()
+}
+
+fn t_to_integer(t: &T) -> Option<Integer> {
+    to_const_value(t.clone()).ok()?.as_integer()
+}
+
+fn reg_to_string(regs: &Vec<Option<T>>, index: usize) -> String {
+    match regs.get(index) {
+        Some(Some(t)) => t_to_string(t),
+        _ => "<uninitialized>".to_string(),
+    }
+}
+
+/// Interactive block-level debugger for bl_eval_entry_fn
+pub struct BlockDebugger {
+    // Number of blocks to execute before stopping, None if running until a breakpoint
+    steps_left: Option<usize>,
+    bl_breakpoints: BTreeSet<usize>,
+    fn_breakpoints: BTreeSet<String>,
+    phy_watchpoints: BTreeSet<usize>,
+    vir_watchpoints: BTreeSet<usize>,
+    // Did the previous block access a watched address?
+    watch_hit: bool,
+    // Function of the previous block, to detect function entries
+    last_fn_name: Option<String>,
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+}
+
+// Write a line to the output of the debugger
+macro_rules! out {
+    ($d:expr, $($arg:tt)*) => {
+        writeln!($d.output, $($arg)*).expect("failed to write debugger output")
+    };
+}
+
+impl BlockDebugger {
+    /// Create a debugger that stops before the first block
+    pub fn new() -> BlockDebugger {
+        BlockDebugger::with_io(Box::new(BufReader::new(std::io::stdin())), Box::new(std::io::stdout()))
+    }
+
+    /// Same as [BlockDebugger::new], but commands are read from input and everything else is written to output
+    pub fn with_io(input: Box<dyn BufRead>, output: Box<dyn Write>) -> BlockDebugger {
+        BlockDebugger {
+            steps_left: Some(0),
+            bl_breakpoints: BTreeSet::new(),
+            fn_breakpoints: BTreeSet::new(),
+            phy_watchpoints: BTreeSet::new(),
+            vir_watchpoints: BTreeSet::new(),
+            watch_hit: false,
+            last_fn_name: None,
+            input,
+            output,
+        }
+    }
+
+    /// Set a breakpoint on a block label or a function name
+    pub fn add_breakpoint(&mut self, bp: &str) {
+        match bp.parse::<usize>() {
+            Ok(label) => { self.bl_breakpoints.insert(label); }
+            Err(_) => { self.fn_breakpoints.insert(bp.to_string()); }
+        }
+    }
+
+    /// Watch a physical (is_phy) or virtual memory address
+    pub fn add_watchpoint(&mut self, is_phy: bool, addr: usize) {
+        if is_phy { self.phy_watchpoints.insert(addr); } else { self.vir_watchpoints.insert(addr); }
+    }
+
+    /// Run until the first breakpoint instead of stopping before the first block
+    pub fn run_to_breakpoint(&mut self) {
+        self.steps_left = None;
+    }
+
+    // Called before block bl is executed as the exec_num-th block execution
+    pub fn before_block(
+        &mut self,
+        exec_num: usize,
+        bl: &Block,
+        io_regs: &Vec<Option<T>>,
+        wit_regs: &Vec<Option<T>>,
+        phy_mem: &Vec<Option<T>>,
+        vir_mem: &Vec<Option<T>>,
+        reg_names: &RegNameMap,
+    ) -> Result<(), String> {
+        let fn_entry = self.last_fn_name.as_ref() != Some(&bl.fn_name);
+        self.last_fn_name = Some(bl.fn_name.clone());
+        let mut stop = false;
+        if let Some(n) = self.steps_left {
+            if n == 0 { stop = true; } else { self.steps_left = Some(n - 1); }
+        }
+        if self.bl_breakpoints.contains(&bl.name) {
+            out!(self, "Breakpoint: block {}", bl.name);
+            stop = true;
+        }
+        if fn_entry && self.fn_breakpoints.contains(&bl.fn_name) {
+            out!(self, "Breakpoint: function {}", bl.fn_name);
+            stop = true;
+        }
+        if self.watch_hit {
+            self.watch_hit = false;
+            stop = true;
+        }
+        if stop {
+            out!(self, "EXEC {}: stopped before block {} (function {})", exec_num, bl.name, bl.fn_name);
+            self.prompt(bl, io_regs, wit_regs, phy_mem, vir_mem, reg_names, true)?;
+        }
+        Ok(())
+    }
+
+    // Called after block bl is executed, checks whether any watched address is accessed
+    pub fn after_block(&mut self, exec_num: usize, bl: &Block, phy_mem_op: &Vec<MemOp>, vir_mem_op: &Vec<MemOp>) {
+        for m in phy_mem_op {
+            if self.phy_watchpoints.contains(&m.addr) {
+                out!(self, "Watchpoint: EXEC {} (block {}) accessed PHY[{}] = {}", exec_num, bl.name, m.addr, t_to_string(&m.data_t));
+                self.watch_hit = true;
+            }
+        }
+        for m in vir_mem_op {
+            if self.vir_watchpoints.contains(&m.addr) {
+                let ls = if m.ls_t.as_ref().and_then(t_to_integer) == Some(Integer::from(STORE)) { "stored" } else { "loaded" };
+                out!(self, "Watchpoint: EXEC {} (block {}) {} VIR[{}] = {}", exec_num, bl.name, ls, m.addr, t_to_string(&m.data_t));
+                self.watch_hit = true;
+            }
+        }
+    }
+
+    // Called when the execution of block bl fails
+    // Registers and memory reflect the state at the point of failure
+    pub fn on_error(
+        &mut self,
+        err: &str,
+        exec_num: usize,
+        bl: &Block,
+        io_regs: &Vec<Option<T>>,
+        wit_regs: &Vec<Option<T>>,
+        phy_mem: &Vec<Option<T>>,
+        vir_mem: &Vec<Option<T>>,
+        reg_names: &RegNameMap,
+    ) -> Result<(), String> {
+        out!(self, "EXEC {}: block {} (function {}) failed: {}", exec_num, bl.name, bl.fn_name, err);
+        self.prompt(bl, io_regs, wit_regs, phy_mem, vir_mem, reg_names, false)
+    }
+
+    // Read and process commands until execution is resumed
+    fn prompt(
+        &mut self,
+        bl: &Block,
+        io_regs: &Vec<Option<T>>,
+        wit_regs: &Vec<Option<T>>,
+        phy_mem: &Vec<Option<T>>,
+        vir_mem: &Vec<Option<T>>,
+        reg_names: &RegNameMap,
+        can_resume: bool,
+    ) -> Result<(), String> {
+        loop {
+            write!(self.output, "(zdb) ").expect("failed to write debugger output");
+            self.output.flush().expect("failed to write debugger output");
+            let mut line = String::new();
+            if self.input.read_line(&mut line).unwrap_or(0) == 0 {
+                // Treat EOF as quit
+                return Err("Execution aborted by debugger".to_string());
+            }
+            let args: Vec<&str> = line.split_whitespace().collect();
+            if args.len() == 0 {
+                continue;
+            }
+            match args[0] {
+                "s" | "step" | "c" | "continue" if !can_resume => {
+                    out!(self, "Execution failed and cannot be resumed, use q to quit");
+                }
+                "s" | "step" => {
+                    let n = args.get(1).and_then(|n| n.parse::<usize>().ok()).unwrap_or(1);
+                    self.steps_left = Some(n.max(1) - 1);
+                    return Ok(());
+                }
+                "c" | "continue" => {
+                    self.steps_left = None;
+                    return Ok(());
+                }
+                "b" | "break" if args.len() == 2 => self.add_breakpoint(args[1]),
+                "d" | "delete" if args.len() == 2 => {
+                    match args[1].parse::<usize>() {
+                        Ok(label) => { self.bl_breakpoints.remove(&label); }
+                        Err(_) => { self.fn_breakpoints.remove(args[1]); }
+                    }
+                }
+                "w" | "watch" | "u" | "unwatch" if args.len() == 3 && (args[1] == "phy" || args[1] == "vir") => {
+                    match args[2].parse::<usize>() {
+                        Ok(addr) => {
+                            let is_phy = args[1] == "phy";
+                            if args[0].starts_with("w") {
+                                self.add_watchpoint(is_phy, addr);
+                            } else if is_phy {
+                                self.phy_watchpoints.remove(&addr);
+                            } else {
+                                self.vir_watchpoints.remove(&addr);
+                            }
+                        }
+                        Err(_) => out!(self, "Invalid address: {}", args[2]),
+                    }
+                }
+                "p" | "print" if args.len() == 2 => self.print_var(args[1], bl, io_regs, wit_regs, reg_names),
+                "r" | "regs" => {
+                    for (name, _) in &bl.inputs {
+                        let index = name[2..].parse::<usize>().unwrap();
+                        let var = reg_names.bl_in_names.get(bl.name).and_then(|m| m.get(&index));
+                        let var = if let Some(var) = var { format!("{} ({})", source_name(var), var) } else { pretty_name(name) };
+                        out!(self, "  {}: {} = {}", pretty_name(name), var, reg_to_string(io_regs, index));
+                    }
+                }
+                "m" | "mem" if args.len() >= 3 && (args[1] == "phy" || args[1] == "vir") => {
+                    let mem = if args[1] == "phy" { phy_mem } else { vir_mem };
+                    let start = args[2].parse::<usize>();
+                    let len = args.get(3).map(|n| n.parse::<usize>()).unwrap_or(Ok(1));
+                    match (start, len) {
+                        (Ok(start), Ok(len)) => {
+                            for addr in start..start + len {
+                                out!(self, "  {}[{}] = {}", args[1].to_uppercase(), addr, reg_to_string(mem, addr));
+                            }
+                        }
+                        _ => out!(self, "Invalid address range"),
+                    }
+                }
+                "l" | "list" => bl.pretty(),
+                "i" | "info" => {
+                    out!(self, "Block breakpoints: {:?}", self.bl_breakpoints);
+                    out!(self, "Function breakpoints: {:?}", self.fn_breakpoints);
+                    out!(self, "PHY watchpoints: {:?}", self.phy_watchpoints);
+                    out!(self, "VIR watchpoints: {:?}", self.vir_watchpoints);
+                }
+                "q" | "quit" => return Err("Execution aborted by debugger".to_string()),
+                "h" | "help" => out!(self, "{}", HELP),
+                _ => out!(self, "Unknown command: {}, use h for help", line.trim()),
+            }
+        }
+    }
+
+    // Print a variable by its Z# name, or a register by its name
+    // A variable is printed both as the input of the block (if it is one) and by its last assigned witness
+    fn print_var(&mut self, name: &str, bl: &Block, io_regs: &Vec<Option<T>>, wit_regs: &Vec<Option<T>>, reg_names: &RegNameMap) {
+        if name.starts_with("%i") || name.starts_with("%o") || name.starts_with("%w") {
+            match name[2..].parse::<usize>() {
+                Ok(index) => {
+                    let regs = if name.starts_with("%w") { wit_regs } else { io_regs };
+                    out!(self, "  {} = {}", pretty_name(name), reg_to_string(regs, index));
+                }
+                Err(_) => out!(self, "Invalid register: {}", name),
+            }
+            return;
+        }
+        let mut found = false;
+        if let Some(in_names) = reg_names.bl_in_names.get(bl.name) {
+            for (index, var) in in_names {
+                if source_name(var) == name || var == name {
+                    let reg = format!("%i{:06}", index);
+                    // Only registers that are inputs of the block hold a valid value
+                    if bl.inputs.iter().any(|(n, _)| n == &reg) {
+                        out!(self, "  {} (input {}) = {}", var, pretty_name(&reg), reg_to_string(io_regs, *index));
+                        found = true;
+                    }
+                }
+            }
+        }
+        for (index, var) in &reg_names.witness_names {
+            if source_name(var) == name || var == name {
+                if let Some(Some(t)) = wit_regs.get(*index) {
+                    out!(self, "  {} (%w{}) = {}", var, index, t_to_string(t));
+                    found = true;
+                }
+            }
+        }
+        if !found {
+            out!(self, "No value found for {}", name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::cfg::cfg_or_default;
+    use crate::front::zsharp::{zx, Inputs, ZSharpFE, NO_OPT};
+    use crate::front::Mode;
+    use std::cell::RefCell;
+    use std::io::Cursor;
+    use std::rc::Rc;
+
+    // Output of the debugger, kept by the test after the debugger is dropped
+    #[derive(Clone, Default)]
+    struct SharedBuf(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn break_on_function_and_print_registers() {
+        cfg_or_default();
+        let path = std::env::temp_dir().join(format!("zsharp_debugger_{}.zok", std::process::id()));
+        let input_path = std::env::temp_dir().join(format!("zsharp_debugger_{}.input", std::process::id()));
+        std::fs::write(&path, "
+def add(field a, field b) -> field:
+    return a + b
+
+def main(field x) -> field:
+    field y = x * 3
+    return add(y, x)
+").unwrap();
+        std::fs::write(&input_path, "x 12345\nEND\n").unwrap();
+        let (mut entry_regs, entry_stacks, entry_arrays, _, _, _, _, _) = zx::read_inputs(&input_path, 0);
+        std::fs::remove_file(&input_path).unwrap();
+
+        // Print x before the first block, run to the entry of add, print its parameters and registers, then run to the end
+        let commands = "p x\nb add\nc\np a\np b\nr\nc\n";
+        let output = SharedBuf::default();
+        let debugger = BlockDebugger::with_io(Box::new(Cursor::new(commands)), Box::new(output.clone()));
+        let inputs = Inputs { file: path.clone(), mode: Mode::Proof, opt_level: NO_OPT, verbose_opt: false };
+        let (ret, _, _, _) = ZSharpFE::debug(inputs, &mut entry_regs, &entry_stacks, &entry_arrays, &Vec::new(), debugger, &mut |_| {}).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(to_const_value(ret).unwrap().as_integer(), Some(Integer::from(12345 * 4)));
+
+        let output = String::from_utf8(output.0.borrow().clone()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        // The debugger stops before the first block and at the breakpoint only
+        let stops: Vec<usize> = (0..lines.len()).filter(|i| lines[*i].contains("stopped before block")).collect();
+        assert_eq!(stops.len(), 2);
+        assert!(lines[stops[0]].starts_with("EXEC 0:") && lines[stops[0]].ends_with("(function main)"));
+        assert!(lines[stops[1] - 1].ends_with("Breakpoint: function add"));
+        assert!(lines[stops[1]].ends_with("(function add)"));
+
+        // x is an input of the entry block, with the value of the program input
+        let main_lines = &lines[stops[0]..stops[1]];
+        assert!(main_lines.iter().any(|l| l.contains(" x.") && l.contains("(input %i") && l.ends_with("= 12345f")));
+        // The parameters of add hold the arguments, both by their names and in the registers of the block
+        let add_lines = &lines[stops[1]..];
+        assert!(add_lines.iter().any(|l| l.contains(" a.") && l.contains("(input %i") && l.ends_with(&format!("= {}f", 12345 * 3))));
+        assert!(add_lines.iter().any(|l| l.contains(" b.") && l.contains("(input %i") && l.ends_with("= 12345f")));
+        assert!(add_lines.iter().any(|l| l.contains(": a (a.") && l.ends_with(&format!("= {}f", 12345 * 3))));
+        assert!(add_lines.iter().any(|l| l.contains(": b (b.") && l.ends_with("= 12345f")));
+    }
+}
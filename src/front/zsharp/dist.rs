@@ -0,0 +1,681 @@
+//! Distributions over Z# programs (useful for fuzz testing the block front-end)
+//!
+//! Programs only use field arithmetic, bounded loops, branches, calls to previously defined
+//! functions and fixed-size local arrays, so every program is well-typed, terminates, and never
+//! accesses an array out of bounds.
+//! Every program carries its own reference semantics ([ZProgram::eval]), which the tests compare
+//! against the constant evaluator of ZGen and against the block interpreter at every opt level.
+
+use rand::{distributions::Distribution, Rng};
+use rug::Integer;
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+
+// Length of every array
+const ARR_LEN: usize = 4;
+// Upper bound of every loop and of the u32 input n
+// Every u32 variable is at most MAX_BOUND < ARR_LEN, so it is always a valid array index
+const MAX_BOUND: usize = 3;
+// Maximum nesting of branches and loops
+const MAX_DEPTH: usize = 2;
+// Number of field inputs of main, named x0, x1, ...
+pub(crate) const NUM_FIELD_INPUTS: usize = 2;
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum ZBinOp {
+    Add,
+    Sub,
+    Mul,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum ZIndex {
+    Const(usize),
+    // u32 variable
+    Var(String),
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum ZExpr {
+    Const(usize),
+    Var(String),
+    Bin(ZBinOp, Box<ZExpr>, Box<ZExpr>),
+    Load(String, ZIndex),
+    Ite(Box<ZCond>, Box<ZExpr>, Box<ZExpr>),
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum ZCond {
+    Eq(ZExpr, ZExpr),
+    Ne(ZExpr, ZExpr),
+    // u32 variable < constant
+    Lt(String, usize),
+    And(Box<ZCond>, Box<ZCond>),
+    Or(Box<ZCond>, Box<ZCond>),
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum ZBound {
+    Const(usize),
+    // The u32 input n of main
+    Input,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum ZStmt {
+    Decl(String, ZExpr),
+    Assign(String, ZExpr),
+    // Array name, initial value of every entry
+    ArrDecl(String, usize),
+    Store(String, ZIndex, ZExpr),
+    // Declared variable, index of the callee, name of the callee, arguments
+    Call(String, usize, String, Vec<ZExpr>),
+    If(ZCond, Vec<ZStmt>, Vec<ZStmt>),
+    // Loop variable, bound, body
+    For(String, usize, Vec<ZStmt>),
+    // Counter, bound, body
+    While(String, ZBound, Vec<ZStmt>),
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct ZFn {
+    pub name: String,
+    // Field parameters
+    pub params: Vec<String>,
+    // Whether the function also takes the u32 input n (only main does)
+    pub has_n: bool,
+    pub body: Vec<ZStmt>,
+    pub ret: ZExpr,
+}
+
+/// A random Z# program, main is the last function
+#[derive(Clone)]
+pub(crate) struct ZProgram {
+    pub fns: Vec<ZFn>,
+}
+
+// A distribution of programs with roughly this many statements.
+pub(crate) struct ZProgramDist(pub usize);
+
+impl Distribution<ZProgram> for ZProgramDist {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> ZProgram {
+        let num_fns = rng.gen_range(0..=2);
+        let mut gen = ProgGen {
+            rng,
+            budget: 0,
+            next_var: 0,
+            field_vars: Vec::new(),
+            u32_vars: Vec::new(),
+            arrays: Vec::new(),
+            fns: Vec::new(),
+        };
+        let mut fns = Vec::new();
+        for i in 0..num_fns {
+            let num_params = gen.rng.gen_range(1..=2);
+            let params = (0..num_params).map(|j| format!("p{}", j)).collect();
+            let f = gen.gen_fn(format!("f{}", i), params, false, self.0 / 2 + 1);
+            gen.fns.push((f.name.clone(), f.params.len()));
+            fns.push(f);
+        }
+        let params = (0..NUM_FIELD_INPUTS).map(|j| format!("x{}", j)).collect();
+        fns.push(gen.gen_fn("main".to_string(), params, true, self.0 + 1));
+        ZProgram { fns }
+    }
+}
+
+struct ProgGen<'a, R: Rng + ?Sized> {
+    rng: &'a mut R,
+    // Number of statements left in the current function
+    budget: usize,
+    next_var: usize,
+    // Variables in scope, innermost scope last
+    field_vars: Vec<Vec<String>>,
+    u32_vars: Vec<Vec<String>>,
+    // Arrays are only declared at the top of a function
+    arrays: Vec<String>,
+    // (name, number of parameters) of every function that can be called
+    fns: Vec<(String, usize)>,
+}
+
+impl<'a, R: Rng + ?Sized> ProgGen<'a, R> {
+    fn fresh(&mut self, prefix: &str) -> String {
+        self.next_var += 1;
+        format!("{}{}", prefix, self.next_var)
+    }
+
+    // There is always at least one field variable in scope: the parameters
+    fn choose_field_var(&mut self) -> String {
+        let vars: Vec<&String> = self.field_vars.iter().flatten().collect();
+        vars[self.rng.gen_range(0..vars.len())].clone()
+    }
+
+    fn choose_u32_var(&mut self) -> Option<String> {
+        let vars: Vec<&String> = self.u32_vars.iter().flatten().collect();
+        if vars.is_empty() {
+            None
+        } else {
+            Some(vars[self.rng.gen_range(0..vars.len())].clone())
+        }
+    }
+
+    fn gen_fn(&mut self, name: String, params: Vec<String>, has_n: bool, budget: usize) -> ZFn {
+        self.budget = budget;
+        self.field_vars = vec![params.clone()];
+        self.u32_vars = vec![if has_n { vec!["n".to_string()] } else { Vec::new() }];
+        self.arrays = Vec::new();
+        let mut body = Vec::new();
+        for _ in 0..self.rng.gen_range(0..=2) {
+            let arr = self.fresh("arr");
+            body.push(ZStmt::ArrDecl(arr.clone(), self.rng.gen_range(0..10)));
+            self.arrays.push(arr);
+        }
+        while self.budget > 0 {
+            body.push(self.gen_stmt(0, has_n));
+        }
+        let ret = self.gen_expr(2);
+        ZFn { name, params, has_n, body, ret }
+    }
+
+    fn gen_block(&mut self, depth: usize, in_main: bool) -> Vec<ZStmt> {
+        self.field_vars.push(Vec::new());
+        self.u32_vars.push(Vec::new());
+        let mut stmts = Vec::new();
+        for _ in 0..self.rng.gen_range(1..=3) {
+            if self.budget == 0 {
+                break;
+            }
+            stmts.push(self.gen_stmt(depth, in_main));
+        }
+        self.field_vars.pop();
+        self.u32_vars.pop();
+        stmts
+    }
+
+    fn gen_stmt(&mut self, depth: usize, in_main: bool) -> ZStmt {
+        self.budget -= 1;
+        loop {
+            match self.rng.gen_range(0..7) {
+                0 => {
+                    let e = self.gen_expr(2);
+                    let v = self.fresh("v");
+                    self.field_vars.last_mut().unwrap().push(v.clone());
+                    return ZStmt::Decl(v, e);
+                }
+                1 => {
+                    let v = self.choose_field_var();
+                    return ZStmt::Assign(v, self.gen_expr(2));
+                }
+                2 if !self.arrays.is_empty() => {
+                    let arr = self.arrays[self.rng.gen_range(0..self.arrays.len())].clone();
+                    let idx = self.gen_index();
+                    return ZStmt::Store(arr, idx, self.gen_expr(2));
+                }
+                3 if !self.fns.is_empty() => {
+                    let callee = self.rng.gen_range(0..self.fns.len());
+                    let (name, num_params) = self.fns[callee].clone();
+                    let args = (0..num_params).map(|_| self.gen_expr(1)).collect();
+                    let v = self.fresh("v");
+                    self.field_vars.last_mut().unwrap().push(v.clone());
+                    return ZStmt::Call(v, callee, name, args);
+                }
+                4 if depth < MAX_DEPTH => {
+                    let c = self.gen_cond(1);
+                    let t = self.gen_block(depth + 1, in_main);
+                    let f = if self.rng.gen_bool(0.5) { self.gen_block(depth + 1, in_main) } else { Vec::new() };
+                    return ZStmt::If(c, t, f);
+                }
+                5 if depth < MAX_DEPTH => {
+                    let i = self.fresh("i");
+                    let bound = self.rng.gen_range(1..=MAX_BOUND);
+                    // The loop variable lives in the scope of the body
+                    self.u32_vars.push(vec![i.clone()]);
+                    let body = self.gen_block(depth + 1, in_main);
+                    self.u32_vars.pop();
+                    return ZStmt::For(i, bound, body);
+                }
+                6 if depth < MAX_DEPTH => {
+                    let w = self.fresh("w");
+                    let bound = if in_main && self.rng.gen_bool(0.5) {
+                        ZBound::Input
+                    } else {
+                        ZBound::Const(self.rng.gen_range(1..=MAX_BOUND))
+                    };
+                    let body = self.gen_block(depth + 1, in_main);
+                    // The counter is declared before the loop
+                    self.u32_vars.last_mut().unwrap().push(w.clone());
+                    return ZStmt::While(w, bound, body);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn gen_index(&mut self) -> ZIndex {
+        match self.choose_u32_var() {
+            Some(v) if self.rng.gen_bool(0.5) => ZIndex::Var(v),
+            _ => ZIndex::Const(self.rng.gen_range(0..ARR_LEN)),
+        }
+    }
+
+    fn gen_expr(&mut self, depth: usize) -> ZExpr {
+        if depth == 0 || self.rng.gen_bool(0.3) {
+            match self.rng.gen_range(0..3) {
+                0 => ZExpr::Const(self.rng.gen_range(0..10)),
+                1 if !self.arrays.is_empty() => {
+                    let arr = self.arrays[self.rng.gen_range(0..self.arrays.len())].clone();
+                    ZExpr::Load(arr, self.gen_index())
+                }
+                _ => ZExpr::Var(self.choose_field_var()),
+            }
+        } else {
+            match self.rng.gen_range(0..4) {
+                0 => ZExpr::Bin(ZBinOp::Add, Box::new(self.gen_expr(depth - 1)), Box::new(self.gen_expr(depth - 1))),
+                1 => ZExpr::Bin(ZBinOp::Sub, Box::new(self.gen_expr(depth - 1)), Box::new(self.gen_expr(depth - 1))),
+                2 => ZExpr::Bin(ZBinOp::Mul, Box::new(self.gen_expr(depth - 1)), Box::new(self.gen_expr(depth - 1))),
+                _ => ZExpr::Ite(Box::new(self.gen_cond(depth - 1)), Box::new(self.gen_expr(depth - 1)), Box::new(self.gen_expr(depth - 1))),
+            }
+        }
+    }
+
+    fn gen_cond(&mut self, depth: usize) -> ZCond {
+        match self.rng.gen_range(0..5) {
+            0 => ZCond::Eq(self.gen_expr(depth), self.gen_expr(depth)),
+            1 => ZCond::Ne(self.gen_expr(depth), self.gen_expr(depth)),
+            2 if depth > 0 => ZCond::And(Box::new(self.gen_cond(depth - 1)), Box::new(self.gen_cond(depth - 1))),
+            3 if depth > 0 => ZCond::Or(Box::new(self.gen_cond(depth - 1)), Box::new(self.gen_cond(depth - 1))),
+            _ => match self.choose_u32_var() {
+                Some(v) => ZCond::Lt(v, self.rng.gen_range(0..=MAX_BOUND)),
+                None => ZCond::Eq(self.gen_expr(depth), self.gen_expr(depth)),
+            },
+        }
+    }
+}
+
+// --
+// Printing
+// --
+
+impl Display for ZIndex {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            ZIndex::Const(c) => write!(f, "{}", c),
+            ZIndex::Var(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+impl Display for ZExpr {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            ZExpr::Const(c) => write!(f, "{}", c),
+            ZExpr::Var(v) => write!(f, "{}", v),
+            ZExpr::Bin(op, a, b) => {
+                let op = match op {
+                    ZBinOp::Add => "+",
+                    ZBinOp::Sub => "-",
+                    ZBinOp::Mul => "*",
+                };
+                write!(f, "({} {} {})", a, op, b)
+            }
+            ZExpr::Load(arr, idx) => write!(f, "{}[{}]", arr, idx),
+            ZExpr::Ite(c, a, b) => write!(f, "(if {} then {} else {} fi)", c, a, b),
+        }
+    }
+}
+
+impl Display for ZCond {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            ZCond::Eq(a, b) => write!(f, "({} == {})", a, b),
+            ZCond::Ne(a, b) => write!(f, "({} != {})", a, b),
+            ZCond::Lt(v, c) => write!(f, "({} < {})", v, c),
+            ZCond::And(a, b) => write!(f, "({} && {})", a, b),
+            ZCond::Or(a, b) => write!(f, "({} || {})", a, b),
+        }
+    }
+}
+
+fn fmt_block(f: &mut Formatter, stmts: &Vec<ZStmt>, indent: usize) -> fmt::Result {
+    for s in stmts {
+        fmt_stmt(f, s, indent)?;
+    }
+    Ok(())
+}
+
+fn fmt_stmt(f: &mut Formatter, s: &ZStmt, indent: usize) -> fmt::Result {
+    let pad = "    ".repeat(indent);
+    match s {
+        ZStmt::Decl(v, e) => writeln!(f, "{}field {} = {}", pad, v, e),
+        ZStmt::Assign(v, e) => writeln!(f, "{}{} = {}", pad, v, e),
+        ZStmt::ArrDecl(arr, init) => writeln!(f, "{}field[{}] {} = [{}; {}]", pad, ARR_LEN, arr, init, ARR_LEN),
+        ZStmt::Store(arr, idx, e) => writeln!(f, "{}{}[{}] = {}", pad, arr, idx, e),
+        ZStmt::Call(v, _, callee, args) => {
+            let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+            writeln!(f, "{}field {} = {}({})", pad, v, callee, args.join(", "))
+        }
+        ZStmt::If(c, t, e) => {
+            writeln!(f, "{}if {} then", pad, c)?;
+            fmt_block(f, t, indent + 1)?;
+            if !e.is_empty() {
+                writeln!(f, "{}else", pad)?;
+                fmt_block(f, e, indent + 1)?;
+            }
+            writeln!(f, "{}endif", pad)
+        }
+        ZStmt::For(i, bound, body) => {
+            writeln!(f, "{}for u32 {} in 0..{} do", pad, i, bound)?;
+            fmt_block(f, body, indent + 1)?;
+            writeln!(f, "{}endfor", pad)
+        }
+        ZStmt::While(w, bound, body) => {
+            let bound = match bound {
+                ZBound::Const(c) => c.to_string(),
+                ZBound::Input => "n".to_string(),
+            };
+            writeln!(f, "{}u32 {} = 0", pad, w)?;
+            writeln!(f, "{}while {} < {} do", pad, w, bound)?;
+            fmt_block(f, body, indent + 1)?;
+            writeln!(f, "{}    {} = {} + 1", pad, w, w)?;
+            writeln!(f, "{}endwhile", pad)
+        }
+    }
+}
+
+impl Display for ZProgram {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for func in &self.fns {
+            let mut params: Vec<String> = func.params.iter().map(|p| format!("field {}", p)).collect();
+            if func.has_n {
+                params.push("u32 n".to_string());
+            }
+            writeln!(f, "def {}({}) -> field:", func.name, params.join(", "))?;
+            fmt_block(f, &func.body, 1)?;
+            writeln!(f, "    return {}", func.ret)?;
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for ZProgram {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+// --
+// Reference semantics
+// --
+
+#[derive(Clone)]
+enum ZVal {
+    Field(Integer),
+    U32(usize),
+    Array(Vec<Integer>),
+}
+
+struct Env<'a> {
+    modulus: &'a Integer,
+    // Innermost scope last
+    scopes: Vec<HashMap<String, ZVal>>,
+}
+
+impl<'a> Env<'a> {
+    fn declare(&mut self, name: &str, val: ZVal) {
+        self.scopes.last_mut().unwrap().insert(name.to_string(), val);
+    }
+
+    fn get(&self, name: &str) -> &ZVal {
+        self.scopes.iter().rev().find_map(|s| s.get(name)).unwrap_or_else(|| panic!("Undeclared variable {}", name))
+    }
+
+    fn get_mut(&mut self, name: &str) -> &mut ZVal {
+        self.scopes.iter_mut().rev().find_map(|s| s.get_mut(name)).unwrap_or_else(|| panic!("Undeclared variable {}", name))
+    }
+
+    fn field(&self, name: &str) -> Integer {
+        match self.get(name) {
+            ZVal::Field(v) => v.clone(),
+            _ => panic!("{} is not a field", name),
+        }
+    }
+
+    fn u32(&self, name: &str) -> usize {
+        match self.get(name) {
+            ZVal::U32(v) => *v,
+            _ => panic!("{} is not a u32", name),
+        }
+    }
+
+    fn reduce(&self, v: Integer) -> Integer {
+        let mut v = v % self.modulus;
+        if v < 0 {
+            v += self.modulus;
+        }
+        v
+    }
+}
+
+impl ZProgram {
+    /// Evaluate main on field inputs x0, x1, ... and the u32 input n, over the field of the given modulus
+    pub fn eval(&self, field_inputs: &Vec<Integer>, n: usize, modulus: &Integer) -> Integer {
+        let main = self.fns.last().unwrap();
+        let mut args: Vec<ZVal> = field_inputs.iter().map(|x| ZVal::Field(x.clone())).collect();
+        args.push(ZVal::U32(n));
+        self.eval_fn(main, args, modulus)
+    }
+
+    fn eval_fn(&self, func: &ZFn, args: Vec<ZVal>, modulus: &Integer) -> Integer {
+        let mut names = func.params.clone();
+        if func.has_n {
+            names.push("n".to_string());
+        }
+        let mut env = Env { modulus, scopes: vec![names.into_iter().zip(args).collect()] };
+        for s in &func.body {
+            self.exec_stmt(s, &mut env);
+        }
+        self.eval_expr(&func.ret, &env)
+    }
+
+    fn exec_block(&self, stmts: &Vec<ZStmt>, env: &mut Env) {
+        env.scopes.push(HashMap::new());
+        for s in stmts {
+            self.exec_stmt(s, env);
+        }
+        env.scopes.pop();
+    }
+
+    fn exec_stmt(&self, s: &ZStmt, env: &mut Env) {
+        match s {
+            ZStmt::Decl(v, e) => {
+                let val = self.eval_expr(e, env);
+                env.declare(v, ZVal::Field(val));
+            }
+            ZStmt::Assign(v, e) => {
+                let val = self.eval_expr(e, env);
+                *env.get_mut(v) = ZVal::Field(val);
+            }
+            ZStmt::ArrDecl(arr, init) => env.declare(arr, ZVal::Array(vec![Integer::from(*init); ARR_LEN])),
+            ZStmt::Store(arr, idx, e) => {
+                let idx = self.eval_index(idx, env);
+                let val = self.eval_expr(e, env);
+                match env.get_mut(arr) {
+                    ZVal::Array(entries) => entries[idx] = val,
+                    _ => panic!("{} is not an array", arr),
+                }
+            }
+            ZStmt::Call(v, callee, _, args) => {
+                let args = args.iter().map(|a| ZVal::Field(self.eval_expr(a, env))).collect();
+                let val = self.eval_fn(&self.fns[*callee], args, env.modulus);
+                env.declare(v, ZVal::Field(val));
+            }
+            ZStmt::If(c, t, e) => {
+                if self.eval_cond(c, env) {
+                    self.exec_block(t, env);
+                } else {
+                    self.exec_block(e, env);
+                }
+            }
+            ZStmt::For(i, bound, body) => {
+                for iter in 0..*bound {
+                    env.scopes.push(HashMap::new());
+                    env.declare(i, ZVal::U32(iter));
+                    self.exec_block(body, env);
+                    env.scopes.pop();
+                }
+            }
+            ZStmt::While(w, bound, body) => {
+                env.declare(w, ZVal::U32(0));
+                let bound = match bound {
+                    ZBound::Const(c) => *c,
+                    ZBound::Input => env.u32("n"),
+                };
+                while env.u32(w) < bound {
+                    self.exec_block(body, env);
+                    let next = env.u32(w) + 1;
+                    *env.get_mut(w) = ZVal::U32(next);
+                }
+            }
+        }
+    }
+
+    fn eval_index(&self, idx: &ZIndex, env: &Env) -> usize {
+        match idx {
+            ZIndex::Const(c) => *c,
+            ZIndex::Var(v) => env.u32(v),
+        }
+    }
+
+    fn eval_expr(&self, e: &ZExpr, env: &Env) -> Integer {
+        match e {
+            ZExpr::Const(c) => Integer::from(*c),
+            ZExpr::Var(v) => env.field(v),
+            ZExpr::Bin(op, a, b) => {
+                let a = self.eval_expr(a, env);
+                let b = self.eval_expr(b, env);
+                env.reduce(match op {
+                    ZBinOp::Add => a + b,
+                    ZBinOp::Sub => a - b,
+                    ZBinOp::Mul => a * b,
+                })
+            }
+            ZExpr::Load(arr, idx) => match env.get(arr) {
+                ZVal::Array(entries) => entries[self.eval_index(idx, env)].clone(),
+                _ => panic!("{} is not an array", arr),
+            },
+            ZExpr::Ite(c, a, b) => {
+                if self.eval_cond(c, env) {
+                    self.eval_expr(a, env)
+                } else {
+                    self.eval_expr(b, env)
+                }
+            }
+        }
+    }
+
+    fn eval_cond(&self, c: &ZCond, env: &Env) -> bool {
+        match c {
+            ZCond::Eq(a, b) => self.eval_expr(a, env) == self.eval_expr(b, env),
+            ZCond::Ne(a, b) => self.eval_expr(a, env) != self.eval_expr(b, env),
+            ZCond::Lt(v, c) => env.u32(v) < *c,
+            ZCond::And(a, b) => self.eval_cond(a, env) && self.eval_cond(b, env),
+            ZCond::Or(a, b) => self.eval_cond(a, env) || self.eval_cond(b, env),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::cfg::cfg_or_default;
+    use crate::front::zsharp::{const_int, parser, zx, BlockExecution, Inputs, ZGen, ZSharpFE, OPT_RO_ARRAYS, T};
+    use crate::front::Mode;
+    use crate::ir::term::{Op, Value};
+    use quickcheck::{Arbitrary, Gen, QuickCheck};
+    use rand::SeedableRng;
+    use std::collections::BTreeMap;
+    use std::path::{Path, PathBuf};
+
+    // A program written to the temp dir, removed once the test is done with it, even if the test fails
+    struct TempZok(PathBuf);
+
+    impl Drop for TempZok {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[derive(Clone, Debug)]
+    struct ZProgramWithInputs(ZProgram, Vec<Integer>, usize);
+
+    impl Arbitrary for ZProgramWithInputs {
+        fn arbitrary(g: &mut Gen) -> Self {
+            let mut rng = rand::rngs::StdRng::seed_from_u64(u64::arbitrary(g));
+            let p = ZProgramDist(g.size()).sample(&mut rng);
+            let field_inputs = (0..NUM_FIELD_INPUTS).map(|_| Integer::from(rng.gen_range(0..100))).collect();
+            let n = rng.gen_range(0..=MAX_BOUND);
+            ZProgramWithInputs(p, field_inputs, n)
+        }
+    }
+
+    // Evaluate main with the constant evaluator of ZGen, without generating blocks
+    fn const_eval(path: &Path, field_inputs: &Vec<Integer>, n: usize) -> Integer {
+        let loader = parser::ZLoad::new();
+        let asts = loader.load(&path.to_path_buf());
+        let mut g = ZGen::new(asts, Mode::Proof, loader.stdlib(), cfg_or_default().zsharp.isolate_asserts);
+        g.visit_files();
+        g.file_stack_push(path.to_path_buf());
+        g.generics_stack_push(HashMap::new());
+        let mut args: Vec<T> = field_inputs.iter().map(|x| T::new_field(x.clone())).collect();
+        args.push(T::new_u32(n));
+        const_int(g.const_entry_fn("main", args).unwrap()).unwrap()
+    }
+
+    // Interpret the program at every opt level, compare the result against the reference semantics
+    // and the constant evaluator of ZGen, and check every block execution against the constraints of its block
+    fn check_program(ZProgramWithInputs(p, field_inputs, n): ZProgramWithInputs) {
+        let expected = p.eval(&field_inputs, n, cfg_or_default().field().modulus());
+        let file = TempZok(std::env::temp_dir().join(format!("zsharp_dist_{}.zok", std::process::id())));
+        let path = file.0.clone();
+        std::fs::write(&path, p.to_string()).unwrap();
+        assert_eq!(const_eval(&path, &field_inputs, n), expected, "return value differs in the constant evaluator");
+
+        let mut entry_regs: BTreeMap<String, Integer> = BTreeMap::new();
+        for (i, x) in field_inputs.iter().enumerate() {
+            entry_regs.insert(format!("x{}", i), x.clone());
+        }
+        entry_regs.insert("n".to_string(), Integer::from(n));
+        entry_regs.insert("%AS".to_string(), Integer::from(0));
+        entry_regs.insert("%SP".to_string(), Integer::from(0));
+        for opt_level in 0..=OPT_RO_ARRAYS {
+            let inputs = || Inputs {
+                file: path.clone(),
+                mode: Mode::Proof,
+                opt_level,
+                verbose_opt: false,
+            };
+            let prover_data_list = zx::gen_block_prover_data(inputs()).unwrap();
+            let (ret, _, _, _) = ZSharpFE::interpret_stream(
+                inputs(),
+                &mut entry_regs.clone(),
+                &BTreeMap::new(),
+                &BTreeMap::new(),
+                &Vec::new(),
+                &mut |exec: BlockExecution| prover_data_list[exec.blk_id].check_all(&exec.io_map),
+            ).unwrap();
+            match ret.term.op() {
+                Op::Const(Value::Field(v)) => assert_eq!(v.i(), expected, "return value differs at opt level {}", opt_level),
+                _ => panic!("return value {} is not a field constant", ret),
+            }
+        }
+    }
+
+    #[test]
+    fn random_programs() {
+        QuickCheck::new()
+            .tests(20)
+            .gen(Gen::new(8))
+            .quickcheck(check_program as fn(ZProgramWithInputs));
+    }
+}
@@ -0,0 +1,170 @@
+// Source-level errors of the block pipeline
+// Errors inside block generation, optimization and interpretation are still Strings,
+// but whenever one passes through a statement or an expression that comes from the source,
+// ZGen remembers the span of the innermost one. At the end of each stage, the String is
+// turned into a BlockError at that span, which renders as a rustc-style diagnostic.
+
+use std::fmt::{self, Display, Formatter};
+use std::path::PathBuf;
+use zokrates_pest_ast::Span;
+
+use crate::front::zsharp::ZGen;
+
+/// Stage of the block pipeline an error is raised in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockStage {
+    /// Block generation
+    Generation,
+    /// Block optimization
+    Optimization,
+    /// Block interpretation
+    Interpretation,
+}
+
+impl Display for BlockStage {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            BlockStage::Generation => write!(f, "generation"),
+            BlockStage::Optimization => write!(f, "optimization"),
+            BlockStage::Interpretation => write!(f, "interpretation"),
+        }
+    }
+}
+
+/// A location in a Z# source file
+#[derive(Clone, Debug)]
+pub struct SourceLoc {
+    /// File containing the location
+    pub path: PathBuf,
+    /// Line of the start of the span, starting from 1
+    pub line: usize,
+    /// Column of the start of the span, starting from 1
+    pub col: usize,
+    /// Source lines covered by the span
+    pub lines: Vec<String>,
+    /// Column of the end of the span on its last line
+    pub end_col: usize,
+}
+
+impl SourceLoc {
+    fn new(path: PathBuf, span: &Span) -> SourceLoc {
+        let (line, col) = span.start_pos().line_col();
+        let (_, end_col) = span.end_pos().line_col();
+        let lines = span.lines().map(|l| l.trim_end_matches(&['\n', '\r'][..]).to_string()).collect();
+        SourceLoc { path, line, col, lines, end_col }
+    }
+}
+
+/// An error of the block pipeline, with the source location it was raised at if known
+#[derive(Clone, Debug)]
+pub struct BlockError {
+    /// Stage of the pipeline
+    pub stage: BlockStage,
+    /// Error message
+    pub msg: String,
+    /// Location of the error
+    pub loc: Option<SourceLoc>,
+}
+
+impl Display for BlockError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(f, "error: {}", self.msg)?;
+        if let Some(loc) = &self.loc {
+            let width = (loc.line + loc.lines.len()).to_string().len();
+            let pad = " ".repeat(width);
+            writeln!(f, "{}--> {}:{}:{}", pad, loc.path.display(), loc.line, loc.col)?;
+            writeln!(f, "{} |", pad)?;
+            for (i, l) in loc.lines.iter().enumerate() {
+                writeln!(f, "{:>width$} | {}", loc.line + i, l, width = width)?;
+                // Underline the span, from its start on the first line to its end on the last line
+                let start = if i == 0 { loc.col - 1 } else { l.len() - l.trim_start().len() };
+                let end = if i == loc.lines.len() - 1 { loc.end_col - 1 } else { l.len() };
+                if end > start {
+                    writeln!(f, "{} | {}{}", pad, " ".repeat(start), "^".repeat(end - start))?;
+                }
+            }
+        }
+        write!(f, "  = note: raised during block {}", self.stage)
+    }
+}
+
+impl std::error::Error for BlockError {}
+
+/// An error of the free helpers of block optimization, which cannot reach ZGen to remember a location,
+/// with the span of the innermost source statement or expression it was raised in
+#[derive(Clone, Debug)]
+pub(super) struct SpannedError<'ast> {
+    pub(super) msg: String,
+    pub(super) span: Option<Span<'ast>>,
+}
+
+impl<'ast> From<String> for SpannedError<'ast> {
+    fn from(msg: String) -> Self {
+        SpannedError { msg, span: None }
+    }
+}
+
+// Pass r through, attaching span to the error if it has no span yet
+// Spans of statements and expressions synthesized by block generation are empty, and are skipped
+pub(super) fn sp_locate<'ast, R>(r: Result<R, SpannedError<'ast>>, span: &Span<'ast>) -> Result<R, SpannedError<'ast>> {
+    r.map_err(|mut e| {
+        if e.span.is_none() && !span.as_str().is_empty() {
+            e.span = Some(span.clone());
+        }
+        e
+    })
+}
+
+// An error raised at span
+pub(super) fn sp_error<'ast, R>(msg: String, span: &Span<'ast>) -> Result<R, SpannedError<'ast>> {
+    sp_locate(Err(msg.into()), span)
+}
+
+impl<'ast> ZGen<'ast> {
+    // Pass r through, remembering span as the location of the error if r is an error that has no location yet
+    // Spans of statements and expressions synthesized by block generation are empty, and are skipped
+    pub(super) fn bl_locate<R>(&self, r: Result<R, String>, span: &Span) -> Result<R, String> {
+        if r.is_err() && self.bl_err_loc.borrow().is_none() && !span.as_str().is_empty() {
+            *self.bl_err_loc.borrow_mut() = Some((span.get_input().as_ptr() as usize, span.start(), span.end()));
+        }
+        r
+    }
+
+    // Remember the span of e as the location of the error, and return its message
+    pub(super) fn bl_locate_spanned(&self, e: SpannedError<'ast>) -> String {
+        match &e.span {
+            Some(span) => self.bl_locate::<()>(Err(e.msg), span).unwrap_err(),
+            None => e.msg,
+        }
+    }
+
+    // Convert an error into a BlockError, located at span if provided, or at the remembered location otherwise
+    pub(super) fn bl_error<E: Display>(&self, stage: BlockStage, e: E, span: Option<&Span>) -> BlockError {
+        let loc = match span {
+            Some(span) if !span.as_str().is_empty() => Some((span.get_input().as_ptr() as usize, span.start(), span.end())),
+            _ => self.bl_err_loc.take(),
+        };
+        self.bl_err_loc.replace(None);
+        let loc = loc.and_then(|(input, start, end)| {
+            self.bl_source_file(input)
+                .and_then(|(path, src)| Span::new(src, start, end).map(|span| SourceLoc::new(path.clone(), &span)))
+        });
+        BlockError { stage, msg: e.to_string(), loc }
+    }
+
+    // Find the path and the source of the file whose source starts at input
+    fn bl_source_file(&self, input: usize) -> Option<(&PathBuf, &'ast str)> {
+        self.asts.iter()
+            .find(|(_, f)| f.span.get_input().as_ptr() as usize == input)
+            .map(|(path, f)| (path, f.span.get_input()))
+    }
+
+    // Find the file and the line a span of the source program starts at
+    pub(super) fn bl_span_line(&self, span: &Span) -> Option<(PathBuf, usize)> {
+        if span.as_str().is_empty() {
+            return None;
+        }
+        self.bl_source_file(span.get_input().as_ptr() as usize)
+            .map(|(path, _)| (path.clone(), span.start_pos().line_col().0))
+    }
+}
@@ -9,6 +9,12 @@ mod pretty;
 pub mod zvisit;
 
 mod prover;
+mod debugger;
+mod error;
+mod profile;
+pub mod zx;
+#[cfg(test)]
+mod dist;
 
 use super::{FrontEnd, Mode};
 use crate::cfg::cfg;
@@ -16,16 +22,24 @@ use crate::circify::{CircError, Circify, Loc, Val};
 use crate::front::proof::PROVER_ID;
 use crate::ir::proof::ConstraintMetadata;
 use crate::ir::term::*;
-use crate::front::zsharp::prover::MemOp;
+use crate::front::zsharp::prover::{MemOp, ExecState};
+use crate::front::zsharp::debugger::RegNameMap;
+use crate::target::r1cs::LookupTable;
+pub use crate::front::zsharp::blocks::challenge_hash;
+pub use crate::front::zsharp::debugger::BlockDebugger;
+pub use crate::front::zsharp::error::{BlockError, BlockStage, SourceLoc};
+pub use crate::front::zsharp::profile::{CostProfile, FnCost, LineCost};
 
 use log::{debug,trace, warn};
 use rug::Integer;
 use std::cell::{Cell, RefCell};
-use std::collections::{HashMap, BTreeMap};
+use std::collections::{HashMap, BTreeMap, BTreeSet};
 use std::fmt::Display;
 use std::path::PathBuf;
 use std::str::FromStr;
 use std::hash::BuildHasherDefault;
+use std::time::{Duration, Instant};
+use serde::Serialize;
 use zokrates_pest_ast as ast;
 
 use term::*;
@@ -62,6 +76,25 @@ pub const OPT_RO_ARRAYS: usize = 3;
 impl FrontEnd for ZSharpFE {
     type Inputs<'ast> = Inputs;
     fn gen(i: Inputs) -> (Computations, usize, usize, Vec<(Vec<usize>, Vec<usize>)>, Vec<(usize, usize)>, Vec<Vec<usize>>, Vec<bool>) {
+        // FrontEnd::gen cannot return an error; binaries that should exit use gen_with_pass_times
+        Self::gen_with_pass_times(i)
+            .unwrap_or_else(|e| panic!("{}", e))
+            .0
+    }
+}
+
+impl ZSharpFE {
+    /// Same as [FrontEnd::gen], but also returns the wall-clock time of every pass of the block pipeline, in order,
+    /// the number of calls to `sample_challenge` in the blocks, whose seeds the prover provides as the read-only input `%CH`,
+    /// and the input register of the entry block that holds every input of main (in the order of the input liveness),
+    /// or 0 if the input is dead
+    /// Fails if the program cannot be converted into blocks
+    pub fn gen_with_pass_times(i: Inputs) -> Result<(
+        (Computations, usize, usize, Vec<(Vec<usize>, Vec<usize>)>, Vec<(usize, usize)>, Vec<Vec<usize>>, Vec<bool>),
+        Vec<(String, Duration)>,
+        usize,
+        Vec<usize>,
+    ), BlockError> {
         debug!(
             "Starting Z# front-end, field: {}",
             Sort::Field(cfg().field().clone())
@@ -73,30 +106,43 @@ impl FrontEnd for ZSharpFE {
         g.file_stack_push(i.file);
         g.generics_stack_push(HashMap::new());
         
-        let (blks, entry_bl, inputs) = g.bl_gen_entry_fn("main");
+        let mut pass_start = Instant::now();
+        let (blks, entry_bl, inputs) = g.bl_gen_entry_fn("main")?;
+        g.record_pass_time("generation", &mut pass_start);
         println!("Entry block: {entry_bl}");
         for b in &blks {
             b.pretty();
             println!("");
         }
-        let (blks, entry_bl, live_input_set) = g.optimize_block(blks, entry_bl, inputs.clone(), i.opt_level, i.verbose_opt);
-        let input_liveness = [vec![("%SP".to_string(), Ty::Field), ("%AS".to_string(), Ty::Field)], inputs].concat().iter().map(|(n, _)| live_input_set.contains(n)).collect();
+        let (blks, entry_bl, live_input_set) = g.optimize_block(blks, entry_bl, inputs.clone(), i.opt_level, i.verbose_opt)?;
+        // Private inputs are not checked against the instance, as if they were dead
+        let private_inputs = g.bl_private_inputs.borrow().clone();
+        let inputs = [vec![("%SP".to_string(), Ty::Field), ("%AS".to_string(), Ty::Field)], inputs].concat();
+        let input_liveness = inputs.iter()
+            .map(|(n, _)| live_input_set.contains(n) && !private_inputs.contains(n)).collect();
         let (blks, _, io_size, _, live_io_list, num_mem_accesses, live_vm_list) = 
-            g.process_block::<0>(blks, entry_bl, i.opt_level, i.verbose_opt);
+            g.process_block::<0>(blks, entry_bl, i.opt_level, i.verbose_opt)?;
+        // Private inputs still take a register, so registers cannot be derived from the input liveness
+        let input_regs = {
+            let entry_input_regs = g.bl_entry_input_regs.borrow();
+            inputs.iter().map(|(n, _)| entry_input_regs.get(n).cloned().unwrap_or(0)).collect()
+        };
         // NOTE: The input of block 0 includes %BN, which should be removed when reasoning about function input
         let func_input_width = blks[0].get_num_inputs() - 1;
         println!("\n\n--\nCirc IR:");
+        let mut pass_start = Instant::now();
         g.bls_to_circ(&blks, i.opt_level < OPT_RO_ARRAYS);
+        g.record_pass_time("to_circ", &mut pass_start);
 
         g.generics_stack_pop();
         g.file_stack_pop();
+        let pass_times = g.pass_times.take();
+        let num_challenges = g.bl_num_challenges.get();
         let mut cs = Computations::new();
         cs.comps = g.into_circify().cir_ctx().cs.borrow_mut().clone();
-        (cs, func_input_width, io_size, live_io_list, num_mem_accesses, live_vm_list, input_liveness)
+        Ok(((cs, func_input_width, io_size, live_io_list, num_mem_accesses, live_vm_list, input_liveness), pass_times, num_challenges, input_regs))
     }
-}
 
-impl ZSharpFE {
     /// Execute the Z# front-end interpreter on the supplied file with the supplied inputs
     pub fn interpret(
         i: Inputs, 
@@ -104,7 +150,7 @@ impl ZSharpFE {
         entry_stacks: &BTreeMap<String, Vec<Integer>>, 
         entry_arrays: &BTreeMap<String, Vec<Integer>>,
         entry_witnesses: &Vec<Integer>,
-    ) -> (
+    ) -> Result<(
         T, // Return Value
         Vec<usize>, // Block IDs
         Vec<Vec<Option<Value>>>, // Prog Input | Block Outputs
@@ -114,29 +160,110 @@ impl ZSharpFE {
         Vec<[Value; 4]>, // Initial virtual memory accesses, sorted by execution & address (same ordering)
         Vec<(Value, Value)>, // Physical memory accesses, sorted by address
         Vec<[Value; 4]>, // Virtual memory accesses, sorted by address
-    ) {
+    ), BlockError> {
+        // A vector of all the blocks executed
+        let mut block_id_list = Vec::new();
+        let mut block_outputs_list = Vec::new();
+        let mut block_mems_list = Vec::new();
+        let mut block_io_map_list = Vec::new();
+        let mut phy_mem_list = Vec::new();
+        let mut vir_mem_list = Vec::new();
+        let (ret, prog_reg_in, init_phy_mem_list, init_vir_mem_list) = Self::interpret_stream(
+            i,
+            entry_regs,
+            entry_stacks,
+            entry_arrays,
+            entry_witnesses,
+            &mut |exec: BlockExecution| {
+                block_id_list.push(exec.blk_id);
+                block_outputs_list.push(exec.reg_out);
+                block_mems_list.push(exec.mems);
+                block_io_map_list.push(exec.io_map);
+                phy_mem_list.extend(exec.phy_mem_op);
+                vir_mem_list.extend(exec.vir_mem_op);
+            }
+        )?;
+        let block_outputs_list = [vec![prog_reg_in], block_outputs_list].concat();
+        // Sort memory accesses by address, then timestamp
+        // Sorting is stable, so accesses with the same (addr, ts) stay in execution order
+        let phy_mem_list = [init_phy_mem_list.iter().map(|m| (m[0].clone(), m[1].clone())).collect(), phy_mem_list].concat();
+        let phy_mem_list = sort_phy_mem_list(phy_mem_list);
+        let vir_mem_list = sort_vir_mem_list([init_vir_mem_list.clone(), vir_mem_list].concat());
+        Ok((ret, block_id_list, block_outputs_list, block_mems_list, block_io_map_list, init_phy_mem_list, init_vir_mem_list, phy_mem_list, vir_mem_list))
+    }
+
+    /// Execute the Z# front-end interpreter on the supplied file with the supplied inputs,
+    /// and pass every block execution to exec_sink as soon as it completes.
+    /// Memory accesses are NOT sorted.
+    pub fn interpret_stream(
+        i: Inputs, 
+        entry_regs: &mut BTreeMap<String, Integer>, 
+        entry_stacks: &BTreeMap<String, Vec<Integer>>, 
+        entry_arrays: &BTreeMap<String, Vec<Integer>>,
+        entry_witnesses: &Vec<Integer>,
+        exec_sink: &mut dyn FnMut(BlockExecution),
+    ) -> Result<(
+        T, // Return Value
+        Vec<Option<Value>>, // Prog Input
+        Vec<[Value; 2]>, // Initial physical (read-only) memory accesses, sorted by execution & address (same ordering)
+        Vec<[Value; 4]>, // Initial virtual memory accesses, sorted by execution & address (same ordering)
+    ), BlockError> {
+        Self::interpret_stream_impl(i, entry_regs, entry_stacks, entry_arrays, entry_witnesses, None, exec_sink)
+    }
+
+    /// Same as [ZSharpFE::interpret_stream], but the execution is controlled by an interactive debugger
+    pub fn debug(
+        i: Inputs, 
+        entry_regs: &mut BTreeMap<String, Integer>, 
+        entry_stacks: &BTreeMap<String, Vec<Integer>>, 
+        entry_arrays: &BTreeMap<String, Vec<Integer>>,
+        entry_witnesses: &Vec<Integer>,
+        debugger: BlockDebugger,
+        exec_sink: &mut dyn FnMut(BlockExecution),
+    ) -> Result<(
+        T, // Return Value
+        Vec<Option<Value>>, // Prog Input
+        Vec<[Value; 2]>, // Initial physical (read-only) memory accesses
+        Vec<[Value; 4]>, // Initial virtual memory accesses
+    ), BlockError> {
+        Self::interpret_stream_impl(i, entry_regs, entry_stacks, entry_arrays, entry_witnesses, Some(debugger), exec_sink)
+    }
+
+    fn interpret_stream_impl(
+        i: Inputs, 
+        entry_regs: &mut BTreeMap<String, Integer>, 
+        entry_stacks: &BTreeMap<String, Vec<Integer>>, 
+        entry_arrays: &BTreeMap<String, Vec<Integer>>,
+        entry_witnesses: &Vec<Integer>,
+        debugger: Option<BlockDebugger>,
+        exec_sink: &mut dyn FnMut(BlockExecution),
+    ) -> Result<(
+        T,
+        Vec<Option<Value>>,
+        Vec<[Value; 2]>,
+        Vec<[Value; 4]>,
+    ), BlockError> {
         let loader = parser::ZLoad::new();
         let asts = loader.load(&i.file);
         let mut g = ZGen::new(asts, i.mode, loader.stdlib(), cfg().zsharp.isolate_asserts);
         g.visit_files();
         g.file_stack_push(i.file);
         g.generics_stack_push(HashMap::new());
+        // The names of the registers are only recorded for the debugger
+        g.debugger.replace(debugger);
         
-        let (blks, entry_bl, inputs) = g.bl_gen_entry_fn("main");
-        let (blks, entry_bl, mut live_input_set) = g.optimize_block(blks, entry_bl, inputs.clone(), i.opt_level, INTERPRET_VERBOSE);
-        let (blks, entry_bl, io_size, _, _, _, _) = g.process_block::<1>(blks, entry_bl, i.opt_level, INTERPRET_VERBOSE);
+        let (blks, entry_bl, inputs) = g.bl_gen_entry_fn("main")?;
+        let (blks, entry_bl, mut live_input_set) = g.optimize_block(blks, entry_bl, inputs.clone(), i.opt_level, INTERPRET_VERBOSE)?;
+        let (blks, entry_bl, io_size, _, _, _, _) = g.process_block::<1>(blks, entry_bl, i.opt_level, INTERPRET_VERBOSE)?;
 
         println!("\n\n--\nInterpretation:");
         let (
             ret, 
             _, 
             prog_reg_in, 
-            bl_exec_state, 
             init_phy_mem_list,
             init_vir_mem_list,
-            phy_mem_list, 
-            vir_mem_list
-        ) = g.bl_eval_entry_fn::<INTERPRET_VERBOSE>(
+        ) = g.bl_eval_entry_fn_stream::<INTERPRET_VERBOSE>(
             entry_bl, 
             &inputs, 
             &mut live_input_set, 
@@ -147,96 +274,10 @@ impl ZSharpFE {
             &blks, 
             io_size,
             i.opt_level < OPT_RO_ARRAYS,
+            &mut |reg_in, state| { exec_sink(exec_state_to_block_execution(reg_in, &state)); Ok(()) },
         )
-            .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e));
-        // prover::print_state_list(&bl_exec_state);
-        // let _ = prover::sort_by_block(&bl_exec_state);
-        // let _ = prover::sort_by_mem(&bl_exec_state);
+            .map_err(|e| g.bl_error(BlockStage::Interpretation, e, None))?;
 
-        // A vector of all the blocks executed
-        let mut block_id_list = Vec::new();
-        // Convert bl_exec_state to list of String -> Value hashmaps
-        // Variables should be named Block_X_fX_lex0_%XX_v0
-        // Memory accesses should be named Block_X_fX_lex0_%pmXv / %pmXa
-        let mut block_io_map_list = Vec::new();
-        let suffix = format!("_v0");
-        for i in 0..bl_exec_state.len() {
-            let state = &bl_exec_state[i];
-            let prefix = format!("Block_{}_f{}_lex0_", state.blk_id, state.blk_id);
-            block_id_list.push(state.blk_id);
-            let mut inputs = HashMap::<String, Value, BuildHasherDefault<fxhash::FxHasher>>::default();
-            // Process reg_ins
-            if i == 0 {
-                for j in 0..prog_reg_in.len() {
-                    // Only insert if state.reg_in[i] != None
-                    // Convert T to Value
-                    if !prog_reg_in[j].is_none() {
-                        let value = to_const_value(prog_reg_in[j].clone().unwrap())
-                            .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e));
-                        inputs.insert(format!("{}%i{:06}{}", prefix, j, suffix), value);
-                    }
-                }
-            } else {
-                let last_state = &bl_exec_state[i - 1];
-                for j in 0..last_state.reg_out.len() {
-                    // Only insert if state.reg_in[i] != None
-                    // Convert T to Value
-                    if !last_state.reg_out[j].is_none() {
-                        let value = to_const_value(last_state.reg_out[j].clone().unwrap())
-                            .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e));
-                        inputs.insert(format!("{}%i{:06}{}", prefix, j, suffix), value);
-                    }
-                }
-            }
-            // Process reg_outs
-            for j in 0..state.reg_out.len() {
-                // Only insert if state.reg_out[i] != None
-                // Convert T to Value
-                if !state.reg_out[j].is_none() {
-                    let value = to_const_value(state.reg_out[j].clone().unwrap())
-                        .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e));
-                    inputs.insert(format!("{}%o{:06}{}", prefix, j, suffix), value);
-                }
-            }
-            // Process physical mems
-            for j in 0..state.phy_mem_op.len() {
-                // addr
-                let addr = to_const_value(state.phy_mem_op[j].addr_t.clone())
-                .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e));
-                inputs.insert(format!("{}%pm{:06}a{}", prefix, j, suffix), addr);
-                // data
-                let data = to_const_value(state.phy_mem_op[j].data_t.clone())
-                .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e));
-                inputs.insert(format!("{}%pm{:06}v{}", prefix, j, suffix), data);
-            }
-            // Process virtual mems
-            for j in 0..state.vir_mem_op.len() {
-                // addr
-                let addr = to_const_value(state.vir_mem_op[j].addr_t.clone())
-                .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e));
-                inputs.insert(format!("{}%vm{:06}a{}", prefix, j, suffix), addr);
-                // data
-                let data = to_const_value(state.vir_mem_op[j].data_t.clone())
-                .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e));
-                inputs.insert(format!("{}%vm{:06}d{}", prefix, j, suffix), data);
-                // ls
-                let ls = to_const_value(state.vir_mem_op[j].ls_t.clone().unwrap())
-                .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e));
-                inputs.insert(format!("{}%vm{:06}l{}", prefix, j, suffix), ls);
-                // ts
-                let ts = to_const_value(state.vir_mem_op[j].ts_t.clone().unwrap())
-                .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e));
-                inputs.insert(format!("{}%vm{:06}t{}", prefix, j, suffix), ts);
-            }
-            // Process witnesses
-            for j in 0..state.wit_op.len() {
-                // witness
-                let wit = to_const_value(state.wit_op[j].clone())
-                .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e));
-                inputs.insert(format!("{}%wt{:06}{}", prefix, j, suffix), wit);
-            }
-            block_io_map_list.push(inputs);
-        }
         let init_phy_mem_list = init_phy_mem_list.iter().map(|i|
             [
                 to_const_value(i.addr_t.clone())
@@ -257,70 +298,352 @@ impl ZSharpFE {
                 .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e)),
             ]
         ).collect();
-        let phy_mem_list = phy_mem_list.iter().map(|i|
-            (
-                to_const_value(i.addr_t.clone())
-                .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e)),
-                to_const_value(i.data_t.clone())
-                .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e)),
-            )
+        let prog_reg_in = prog_reg_in.iter().map(|j|
+            if let Some(k) = j {
+                Some(to_const_value(k.clone())
+                    .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e)))
+            } else { None }
         ).collect();
-        let vir_mem_list = vir_mem_list.iter().map(|i|
-            [
-                to_const_value(i.addr_t.clone())
-                .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e)),
-                to_const_value(i.data_t.clone())
-                .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e)),
-                to_const_value(i.ls_t.clone().unwrap())
-                .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e)),
-                to_const_value(i.ts_t.clone().unwrap())
-                .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e)),
-            ]
-        ).collect();
-        let block_outputs_list = [
-            vec![prog_reg_in.iter().map(|j|
-                if let Some(k) = j {
-                    Some(to_const_value(k.clone())
-                        .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e)))
-                } else { None }
-            ).collect()],
-            bl_exec_state.iter().map(|i| i.reg_out.iter().map(|j|
-                if let Some(k) = j {
-                    Some(to_const_value(k.clone())
-                        .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e)))
-                } else { None }
-            ).collect()).collect()
-        ].concat();
-        let block_mems_list = bl_exec_state.iter().map(|i| [
-            i.phy_mem_op.iter().flat_map(|pm: &MemOp| [
-                // addr
-                Some(to_const_value(pm.addr_t.clone())
-                    .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e))),
-                // data
-                Some(to_const_value(pm.data_t.clone())
-                    .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e))),
-            ]).collect::<Vec<Option<Value>>>(),
-            i.vir_mem_op.iter().flat_map(|vm: &MemOp| [
-                // addr
-                Some(to_const_value(vm.addr_t.clone())
-                    .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e))),
-                // data
-                Some(to_const_value(vm.data_t.clone())
-                    .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e))),
-                // ls
-                if let Some(ls) = &vm.ls_t {
-                    Some(to_const_value(ls.clone())
-                        .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e)))
-                } else { None },
-                // ts
-                if let Some(ts) = &vm.ts_t {
-                    Some(to_const_value(ts.clone())
-                        .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e)))
-                } else { None },
-            ]).collect::<Vec<Option<Value>>>(),
-        ].concat()).collect();
-        return (ret, block_id_list, block_outputs_list, block_mems_list, block_io_map_list, init_phy_mem_list, init_vir_mem_list, phy_mem_list, vir_mem_list);
+        Ok((ret, prog_reg_in, init_phy_mem_list, init_vir_mem_list))
     }
+
+    /// Execute the Z# front-end interpreter and record every memory access and array allocation,
+    /// in execution order
+    pub fn mem_trace(
+        i: Inputs, 
+        entry_regs: &mut BTreeMap<String, Integer>, 
+        entry_stacks: &BTreeMap<String, Vec<Integer>>, 
+        entry_arrays: &BTreeMap<String, Vec<Integer>>,
+        entry_witnesses: &Vec<Integer>,
+    ) -> Result<(Vec<MemTraceEntry>, Vec<MemTraceAlloc>), BlockError> {
+        let loader = parser::ZLoad::new();
+        let asts = loader.load(&i.file);
+        let mut g = ZGen::new(asts, i.mode, loader.stdlib(), cfg().zsharp.isolate_asserts);
+        g.visit_files();
+        g.file_stack_push(i.file);
+        g.generics_stack_push(HashMap::new());
+        
+        let (blks, entry_bl, inputs) = g.bl_gen_entry_fn("main")?;
+        let (blks, entry_bl, mut live_input_set) = g.optimize_block(blks, entry_bl, inputs.clone(), i.opt_level, INTERPRET_VERBOSE)?;
+        let (blks, entry_bl, io_size, _, _, _, _) = g.process_block::<1>(blks, entry_bl, i.opt_level, INTERPRET_VERBOSE)?;
+        let no_ro_accesses = i.opt_level < OPT_RO_ARRAYS;
+
+        println!("\n\n--\nMemory Trace:");
+        let (_, _, _, bl_exec_state, init_phy_mem_list, init_vir_mem_list, _, _) = g.bl_eval_entry_fn::<INTERPRET_VERBOSE>(
+            entry_bl, 
+            &inputs, 
+            &mut live_input_set, 
+            entry_regs, 
+            entry_stacks, 
+            entry_arrays,
+            entry_witnesses,
+            &blks, 
+            io_size,
+            no_ro_accesses,
+        )
+            .map_err(|e| g.bl_error(BlockStage::Interpretation, e, None))?;
+
+        let t_to_integer = |t: &T| to_const_value(t.clone())
+            .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e))
+            .as_integer()
+            .unwrap();
+
+        // Array inputs are allocated in order of declaration, following bl_eval_entry_fn,
+        // read-only ones after the lookup tables and the challenges
+        let mut alloc_list = Vec::new();
+        let mut stack_addr_count = LookupTable::memory_size(cfg().r1cs.lookup_width) + g.bl_num_challenges.get();
+        let mut mem_addr_count = 0;
+        for (name, ty) in &inputs {
+            if let Ty::Array(read_only, _, _) = ty {
+                let var_name = name.split(".").next().unwrap().split("@").next().unwrap().to_string();
+                let (addr, len) = if *read_only {
+                    let len = entry_stacks.get(&var_name).map_or(0, |s| s.len());
+                    stack_addr_count += len;
+                    (stack_addr_count - len, len)
+                } else {
+                    let len = entry_arrays.get(&var_name).map_or(0, |a| a.len());
+                    mem_addr_count += len;
+                    (mem_addr_count - len, len)
+                };
+                alloc_list.push(MemTraceAlloc { name: var_name, exec_num: None, blk_id: None, phy: *read_only, addr, len });
+            }
+        }
+        // Initial memory states are recorded as STOREs before the first block execution
+        let mut trace = Vec::new();
+        for m in &init_phy_mem_list {
+            trace.push(MemTraceEntry { exec_num: None, blk_id: None, phy: true, addr: m.addr, data: t_to_integer(&m.data_t), store: None, ts: None });
+        }
+        for m in &init_vir_mem_list {
+            trace.push(MemTraceEntry { exec_num: None, blk_id: None, phy: false, addr: m.addr, data: t_to_integer(&m.data_t), store: Some(true), ts: m.ts });
+        }
+        for (exec_num, state) in bl_exec_state.iter().enumerate() {
+            for a in &state.alloc_op {
+                alloc_list.push(MemTraceAlloc { 
+                    name: pretty::pretty_name(&a.arr), 
+                    exec_num: Some(exec_num), 
+                    blk_id: Some(state.blk_id), 
+                    phy: a.read_only, 
+                    addr: a.addr, 
+                    len: a.len 
+                });
+            }
+            for m in &state.phy_mem_op {
+                trace.push(MemTraceEntry { exec_num: Some(exec_num), blk_id: Some(state.blk_id), phy: true, addr: m.addr, data: t_to_integer(&m.data_t), store: None, ts: None });
+            }
+            for m in &state.vir_mem_op {
+                let store = t_to_integer(m.ls_t.as_ref().unwrap()) == prover::STORE;
+                trace.push(MemTraceEntry { exec_num: Some(exec_num), blk_id: Some(state.blk_id), phy: false, addr: m.addr, data: t_to_integer(&m.data_t), store: Some(store), ts: m.ts });
+            }
+        }
+        Ok((trace, alloc_list))
+    }
+
+    /// Execute the Z# front-end interpreter and attribute the constraints and memory operations of every block execution
+    /// to the source lines and functions they come from
+    /// Constraints are counted before IR optimizations, in the same way as the estimates used by block merging
+    pub fn profile(
+        i: Inputs, 
+        entry_regs: &mut BTreeMap<String, Integer>, 
+        entry_stacks: &BTreeMap<String, Vec<Integer>>, 
+        entry_arrays: &BTreeMap<String, Vec<Integer>>,
+        entry_witnesses: &Vec<Integer>,
+    ) -> Result<CostProfile, BlockError> {
+        let loader = parser::ZLoad::new();
+        let asts = loader.load(&i.file);
+        let mut g = ZGen::new(asts, i.mode, loader.stdlib(), cfg().zsharp.isolate_asserts);
+        g.visit_files();
+        g.file_stack_push(i.file);
+        g.generics_stack_push(HashMap::new());
+        
+        let (blks, entry_bl, inputs) = g.bl_gen_entry_fn("main")?;
+        let (blks, entry_bl, mut live_input_set) = g.optimize_block(blks, entry_bl, inputs.clone(), i.opt_level, INTERPRET_VERBOSE)?;
+        let no_ro_accesses = i.opt_level < OPT_RO_ARRAYS;
+        // Constraints are counted on the blocks of the circuit, and executions on the blocks of the interpreter
+        // Both keep the labels of the optimized blocks
+        let (cons_blks, _, _, _, _, _, _) = g.process_block::<0>(blks.clone(), entry_bl, i.opt_level, INTERPRET_VERBOSE)?;
+        let (blks, entry_bl, io_size, _, _, _, _) = g.process_block::<1>(blks, entry_bl, i.opt_level, INTERPRET_VERBOSE)?;
+
+        println!("\n\n--\nProfile:");
+        let (_, bl_exec_count, _, _, _) = g.bl_eval_entry_fn_stream::<INTERPRET_VERBOSE>(
+            entry_bl, 
+            &inputs, 
+            &mut live_input_set, 
+            entry_regs, 
+            entry_stacks, 
+            entry_arrays,
+            entry_witnesses,
+            &blks, 
+            io_size,
+            no_ro_accesses,
+            &mut |_, _| Ok(()),
+        )
+            .map_err(|e| g.bl_error(BlockStage::Interpretation, e, None))?;
+        g.bl_profile(&cons_blks, &bl_exec_count, no_ro_accesses)
+            .map_err(|e| g.bl_error(BlockStage::Optimization, e, None))
+    }
+}
+
+/// A single block execution produced by [ZSharpFE::interpret_stream]
+pub struct BlockExecution {
+    /// ID of the executed block
+    pub blk_id: usize,
+    /// Input state of the block
+    pub reg_in: Vec<Option<Value>>,
+    /// Output state of the block
+    pub reg_out: Vec<Option<Value>>,
+    /// (PM Vars + VM Vars) of the block
+    pub mems: Vec<Option<Value>>,
+    /// Map of IO name -> IO value, for witness generation
+    pub io_map: HashMap<String, Value, BuildHasherDefault<fxhash::FxHasher>>,
+    /// Physical memory accesses of the block, as (addr, data)
+    pub phy_mem_op: Vec<(Value, Value)>,
+    /// Virtual memory accesses of the block, as [addr, data, ls, ts]
+    pub vir_mem_op: Vec<[Value; 4]>,
+}
+
+fn value_to_usize(v: &Value) -> usize {
+    v.as_integer().unwrap().to_usize().unwrap()
+}
+
+/// Sort physical memory accesses by address, accesses to the same address stay in execution order
+pub fn sort_phy_mem_list(mut phy_mem_list: Vec<(Value, Value)>) -> Vec<(Value, Value)> {
+    phy_mem_list.sort_by_cached_key(|m| value_to_usize(&m.0));
+    phy_mem_list
+}
+
+/// Sort virtual memory accesses by address and timestamp, ties stay in execution order
+pub fn sort_vir_mem_list(mut vir_mem_list: Vec<[Value; 4]>) -> Vec<[Value; 4]> {
+    vir_mem_list.sort_by_cached_key(|m| (value_to_usize(&m[0]), value_to_usize(&m[3])));
+    vir_mem_list
+}
+
+// Convert an ExecState into values
+// reg_in is the program input for the first block execution, and the output of the previous block otherwise
+fn exec_state_to_block_execution(reg_in: &Vec<Option<T>>, state: &ExecState) -> BlockExecution {
+    // Variables should be named Block_X_fX_lex0_%XX_v0
+    // Memory accesses should be named Block_X_fX_lex0_%pmXv / %pmXa
+    let suffix = format!("_v0");
+    let prefix = format!("Block_{}_f{}_lex0_", state.blk_id, state.blk_id);
+    let mut inputs = HashMap::<String, Value, BuildHasherDefault<fxhash::FxHasher>>::default();
+    // Process reg_ins
+    let reg_in: Vec<Option<Value>> = reg_in.iter().map(|j|
+        if let Some(k) = j {
+            Some(to_const_value(k.clone())
+                .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e)))
+        } else { None }
+    ).collect();
+    for j in 0..reg_in.len() {
+        // Only insert if reg_in[j] != None
+        if let Some(value) = &reg_in[j] {
+            inputs.insert(format!("{}%i{:06}{}", prefix, j, suffix), value.clone());
+        }
+    }
+    // Process reg_outs
+    for j in 0..state.reg_out.len() {
+        // Only insert if state.reg_out[i] != None
+        // Convert T to Value
+        if !state.reg_out[j].is_none() {
+            let value = to_const_value(state.reg_out[j].clone().unwrap())
+                .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e));
+            inputs.insert(format!("{}%o{:06}{}", prefix, j, suffix), value);
+        }
+    }
+    // Process physical mems
+    for j in 0..state.phy_mem_op.len() {
+        // addr
+        let addr = to_const_value(state.phy_mem_op[j].addr_t.clone())
+        .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e));
+        inputs.insert(format!("{}%pm{:06}a{}", prefix, j, suffix), addr);
+        // data
+        let data = to_const_value(state.phy_mem_op[j].data_t.clone())
+        .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e));
+        inputs.insert(format!("{}%pm{:06}v{}", prefix, j, suffix), data);
+    }
+    // Process virtual mems
+    for j in 0..state.vir_mem_op.len() {
+        // addr
+        let addr = to_const_value(state.vir_mem_op[j].addr_t.clone())
+        .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e));
+        inputs.insert(format!("{}%vm{:06}a{}", prefix, j, suffix), addr);
+        // data
+        let data = to_const_value(state.vir_mem_op[j].data_t.clone())
+        .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e));
+        inputs.insert(format!("{}%vm{:06}d{}", prefix, j, suffix), data);
+        // ls
+        let ls = to_const_value(state.vir_mem_op[j].ls_t.clone().unwrap())
+        .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e));
+        inputs.insert(format!("{}%vm{:06}l{}", prefix, j, suffix), ls);
+        // ts
+        let ts = to_const_value(state.vir_mem_op[j].ts_t.clone().unwrap())
+        .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e));
+        inputs.insert(format!("{}%vm{:06}t{}", prefix, j, suffix), ts);
+    }
+    // Process witnesses
+    for j in 0..state.wit_op.len() {
+        // witness
+        let wit = to_const_value(state.wit_op[j].clone())
+        .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e));
+        inputs.insert(format!("{}%wt{:06}{}", prefix, j, suffix), wit);
+    }
+    let reg_out = state.reg_out.iter().map(|j|
+        if let Some(k) = j {
+            Some(to_const_value(k.clone())
+                .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e)))
+        } else { None }
+    ).collect();
+    let mems = [
+        state.phy_mem_op.iter().flat_map(|pm: &MemOp| [
+            // addr
+            Some(to_const_value(pm.addr_t.clone())
+                .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e))),
+            // data
+            Some(to_const_value(pm.data_t.clone())
+                .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e))),
+        ]).collect::<Vec<Option<Value>>>(),
+        state.vir_mem_op.iter().flat_map(|vm: &MemOp| [
+            // addr
+            Some(to_const_value(vm.addr_t.clone())
+                .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e))),
+            // data
+            Some(to_const_value(vm.data_t.clone())
+                .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e))),
+            // ls
+            if let Some(ls) = &vm.ls_t {
+                Some(to_const_value(ls.clone())
+                    .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e)))
+            } else { None },
+            // ts
+            if let Some(ts) = &vm.ts_t {
+                Some(to_const_value(ts.clone())
+                    .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e)))
+            } else { None },
+        ]).collect::<Vec<Option<Value>>>(),
+    ].concat();
+    let phy_mem_op = state.phy_mem_op.iter().map(|i|
+        (
+            to_const_value(i.addr_t.clone())
+            .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e)),
+            to_const_value(i.data_t.clone())
+            .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e)),
+        )
+    ).collect();
+    let vir_mem_op = state.vir_mem_op.iter().map(|i|
+        [
+            to_const_value(i.addr_t.clone())
+            .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e)),
+            to_const_value(i.data_t.clone())
+            .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e)),
+            to_const_value(i.ls_t.clone().unwrap())
+            .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e)),
+            to_const_value(i.ts_t.clone().unwrap())
+            .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e)),
+        ]
+    ).collect();
+    BlockExecution {
+        blk_id: state.blk_id,
+        reg_in,
+        reg_out,
+        mems,
+        io_map: inputs,
+        phy_mem_op,
+        vir_mem_op,
+    }
+}
+
+/// A single memory access recorded by [ZSharpFE::mem_trace]
+#[derive(Debug, Clone, Serialize)]
+pub struct MemTraceEntry {
+    /// Index of the block execution that performed the access, None for program inputs
+    pub exec_num: Option<usize>,
+    /// ID of the block that performed the access, None for program inputs
+    pub blk_id: Option<usize>,
+    /// Physical memory (read-only arrays and stack) or virtual memory
+    pub phy: bool,
+    /// Address of the access
+    pub addr: usize,
+    /// Value loaded or stored
+    pub data: Integer,
+    /// STORE or LOAD, None for physical memory
+    pub store: Option<bool>,
+    /// Timestamp of the access, None for physical memory
+    pub ts: Option<usize>,
+}
+
+/// An array allocation recorded by [ZSharpFE::mem_trace]
+#[derive(Debug, Clone, Serialize)]
+pub struct MemTraceAlloc {
+    /// Name of the program input, or the register holding the array pointer
+    pub name: String,
+    /// Index of the block execution that allocated the array, None for program inputs
+    pub exec_num: Option<usize>,
+    /// ID of the block that allocated the array, None for program inputs
+    pub blk_id: Option<usize>,
+    /// Allocated on physical memory or virtual memory
+    pub phy: bool,
+    /// Starting address of the array
+    pub addr: usize,
+    /// Number of entries allocated
+    pub len: usize,
 }
 
 struct ZGen<'ast> {
@@ -347,6 +670,21 @@ struct ZGen<'ast> {
     challenge_count: Cell<usize>,
     isolate_asserts: bool,
     in_witness_gen: Cell<bool>,
+    // Variable held by each register, recorded by var_to_reg for the debugger
+    reg_name_map: RefCell<RegNameMap>,
+    // Debugger invoked by bl_eval_entry_fn, if any
+    debugger: RefCell<Option<BlockDebugger>>,
+    // Location of the innermost source statement or expression an error of the block pipeline passed through,
+    // as (input pointer, start, end) of its span
+    bl_err_loc: RefCell<Option<(usize, usize, usize)>>,
+    // Wall-clock time of every pass of the block pipeline, in order
+    pass_times: RefCell<Vec<(String, Duration)>>,
+    // Flattened inputs of the entry function declared private, recorded by bl_gen_entry_fn
+    bl_private_inputs: RefCell<BTreeSet<String>>,
+    // Input register of every live input of the entry block, recorded by var_to_reg
+    bl_entry_input_regs: RefCell<BTreeMap<String, usize>>,
+    // Number of calls to sample_challenge in the blocks, recorded by bl_gen_entry_fn
+    bl_num_challenges: Cell<usize>,
 }
 
 impl<'ast> Drop for ZGen<'ast> {
@@ -424,6 +762,13 @@ impl<'ast> ZGen<'ast> {
             challenge_count: Cell::new(0),
             isolate_asserts,
             in_witness_gen: Cell::new(false),
+            reg_name_map: Default::default(),
+            debugger: Default::default(),
+            bl_err_loc: Default::default(),
+            pass_times: Default::default(),
+            bl_private_inputs: Default::default(),
+            bl_entry_input_regs: Default::default(),
+            bl_num_challenges: Cell::new(0),
         };
         /*
         this.circ
@@ -969,44 +1314,24 @@ impl<'ast> ZGen<'ast> {
         }
     }
 
-    /*
-    fn const_entry_fn(&self, n: &str) -> T {
+    /// Evaluate the entry function on constant arguments with the constant evaluator of ZGen,
+    /// independently of the block pipeline
+    #[cfg(test)]
+    fn const_entry_fn(&self, n: &str, args: Vec<T>) -> Result<T, String> {
         debug!("Const entry: {}", n);
         let (f_file, f_name) = self.deref_import(n);
-        if let Some(f) = self.functions.get(&f_file).and_then(|m| m.get(&f_name)) {
-            if !f.generics.is_empty() {
-                panic!("const_entry_fn cannot be called on a generic function")
-            }
-
-            let mut args = Vec::new();
-            for p in &f.parameters {
-                let name = &p.id.value;
-                let ty = self.type_(&p.ty);
-                let value = interp::extract(name, &ty, &mut input_scalar_values)
-                    .unwrap_or_else(|e| self.err(format!("Error: {e}"), &p.span));
-                args.push(value);
-            }
-
-            if !input_scalar_values.is_empty() {
-                let unused_input_list = input_scalar_values
-                    .keys()
-                    .map(|s| s.as_str())
-                    .collect::<Vec<_>>()
-                    .as_slice()
-                    .join(", ");
-                self.err(format!("Ununused inputs {unused_input_list}"), &f.span);
-            }
-
-            self.function_call_impl_::<true>(args, &[][..], None, f_file, f_name)
-                .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e))
-        } else {
-            panic!(
-                "No function '{:?}//{}' attempting const_entry_fn",
-                &f_file, &f_name
-            )
+        let f = self
+            .functions
+            .get(&f_file)
+            .and_then(|m| m.get(&f_name))
+            .ok_or_else(|| format!("No function '{:?}//{}' attempting const_entry_fn", &f_file, &f_name))?;
+        if !f.generics.is_empty() {
+            return Err("const_entry_fn cannot be called on a generic function".to_string());
         }
+        self.function_call_impl_::<true>(args, &[][..], None, f_file, f_name)
     }
 
+    /*
     fn entry_fn(&self, n: &str) {
         debug!("Entry: {}", n);
         // find the entry function
@@ -1574,6 +1899,18 @@ impl<'ast> ZGen<'ast> {
                 self.exit_scope_impl_::<IS_CNST>();
                 Ok(())
             }
+            ast::Statement::WhileLoop(w) if IS_CNST => {
+                while const_bool(self.expr_impl_::<true>(&w.condition)?)
+                    .ok_or_else(|| "While loop condition not constant bool".to_string())?
+                {
+                    self.enter_scope_impl_::<true>();
+                    for s in &w.statements {
+                        self.stmt_impl_::<true>(s)?;
+                    }
+                    self.exit_scope_impl_::<true>();
+                }
+                Ok(())
+            }
             ast::Statement::WhileLoop(_) => {
                 panic!("Direct processing of while loops are unsupported. Convert them to blocks first.")
             }
@@ -2418,4 +2755,63 @@ fn type_span<'ast, 'a>(ty: &'a ast::Type<'ast>) -> &'a ast::Span<'ast> {
             U64(u) => &u.span,
         },
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::cfg::cfg_or_default;
+
+    #[test]
+    fn mem_trace_of_physical_and_virtual_arrays() {
+        cfg_or_default();
+        let path = std::env::temp_dir().join(format!("zsharp_mem_trace_{}.zok", std::process::id()));
+        let input_path = std::env::temp_dir().join(format!("zsharp_mem_trace_{}.input", std::process::id()));
+        // a is promoted to read-only memory, while the store to b keeps it in virtual memory
+        std::fs::write(&path, "
+def main(u32 x) -> field:
+    field[4] a = [1, 2, 3, 4]
+    field[4] b = [5, 6, 7, 8]
+    b[x] = 9
+    return a[x] + b[x]
+").unwrap();
+        std::fs::write(&input_path, "x 2\nEND\n").unwrap();
+        let (mut entry_regs, entry_stacks, entry_arrays, _, _, _, _, _) = zx::read_inputs(&input_path, 0);
+        std::fs::remove_file(&input_path).unwrap();
+        let inputs = Inputs { file: path.clone(), mode: Mode::Proof, opt_level: OPT_RO_ARRAYS, verbose_opt: false };
+        let (trace, alloc_list) = ZSharpFE::mem_trace(inputs, &mut entry_regs, &entry_stacks, &entry_arrays, &Vec::new()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let alloc = |phy: bool| {
+            let allocs: Vec<_> = alloc_list.iter().filter(|a| a.phy == phy && a.len == 4).collect();
+            assert_eq!(allocs.len(), 1);
+            assert!(allocs[0].exec_num.is_some());
+            allocs[0].clone()
+        };
+        let accesses = |phy: bool, addr: usize| -> Vec<&MemTraceEntry> {
+            trace.iter().filter(|m| m.phy == phy && m.addr == addr).collect()
+        };
+
+        // Read-only arrays start after the lookup tables, and every access to an entry sees its initial value
+        let a = alloc(true);
+        assert!(a.addr >= LookupTable::memory_size(cfg().r1cs.lookup_width));
+        for (i, v) in [1, 2, 3, 4].into_iter().enumerate() {
+            let entries = accesses(true, a.addr + i);
+            assert!(!entries.is_empty());
+            assert!(entries.iter().all(|m| m.data == v && m.store.is_none() && m.ts.is_none()));
+        }
+        // The entry at x is initialized, then loaded
+        assert!(accesses(true, a.addr + 2).len() >= 2);
+
+        // Virtual accesses to b[x] follow the store: initial value, then the stored value, then the load of it
+        let b = alloc(false);
+        for (i, v) in [5, 6, 7, 8].into_iter().enumerate() {
+            assert_eq!(accesses(false, b.addr + i)[0].data, v);
+            assert_eq!(accesses(false, b.addr + i)[0].store, Some(true));
+        }
+        let b_x: Vec<_> = accesses(false, b.addr + 2).iter().map(|m| (m.data.to_usize().unwrap(), m.store.unwrap())).collect();
+        assert_eq!(&b_x[b_x.len() - 2..], &[(9, true), (9, false)]);
+        let ts: Vec<_> = accesses(false, b.addr + 2).iter().map(|m| m.ts).collect();
+        assert!(ts.windows(2).all(|w| w[0] <= w[1]));
+    }
+}
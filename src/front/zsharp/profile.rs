@@ -0,0 +1,239 @@
+// Constraint-cost profiler for block-compiled programs
+// Every instruction of a block is attributed to the source line of its first expression that comes from the source.
+// The number of constraints of an instruction is the number of constraints of the assertions it emits, as counted by
+// count_constraints_by_assertion on the constraints of the whole block. A term shared by several instructions is
+// counted in the first one, so that the costs of the instructions of a block sum up to its number of constraints.
+// Branches produced by block merging are counted as a single instruction, attributed to the line of their condition,
+// since their two sides must perform the same number of memory operations.
+// Both are weighted by the number of times the block is executed, as recorded by the interpreter.
+// Instructions without a source location (register transfers, scoping, block transitions) are reported as generated.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use serde::Serialize;
+use zokrates_pest_ast::*;
+
+use crate::front::zsharp::blocks::{Block, BlockContent};
+use crate::front::zsharp::{cfg, ZGen, ZSharp};
+use crate::target::r1cs::trans::count_constraints_by_assertion;
+
+/// Cost of a single source line over the whole execution
+#[derive(Debug, Clone, Serialize)]
+pub struct LineCost {
+    /// Source file, None for instructions generated by the compiler
+    pub path: Option<PathBuf>,
+    /// Line number, starting from 1, 0 for instructions generated by the compiler
+    pub line: usize,
+    /// Text of the line
+    pub source: String,
+    /// Number of times an instruction of the line is executed
+    pub num_execs: usize,
+    /// Number of constraints
+    pub num_cons: usize,
+    /// Number of memory operations
+    pub num_mem_ops: usize,
+}
+
+/// Cost of a single function over the whole execution
+#[derive(Debug, Clone, Serialize)]
+pub struct FnCost {
+    /// Name of the function
+    pub name: String,
+    /// Number of blocks of the function executed
+    pub num_execs: usize,
+    /// Number of constraints
+    pub num_cons: usize,
+    /// Number of memory operations
+    pub num_mem_ops: usize,
+}
+
+/// Constraint cost of an execution, produced by [crate::front::zsharp::ZSharpFE::profile]
+#[derive(Debug, Clone, Serialize)]
+pub struct CostProfile {
+    /// Number of blocks executed
+    pub num_execs: usize,
+    /// Number of constraints over all block executions
+    pub num_cons: usize,
+    /// Number of memory operations over all block executions
+    pub num_mem_ops: usize,
+    /// Cost of every source line, sorted by number of constraints
+    pub lines: Vec<LineCost>,
+    /// Cost of every function, sorted by number of constraints
+    pub functions: Vec<FnCost>,
+}
+
+// Find the first span within e that comes from the source
+fn expr_src_span<'a, 'ast>(e: &'a Expression<'ast>) -> Option<&'a Span<'ast>> {
+    if !e.span().as_str().is_empty() {
+        return Some(e.span());
+    }
+    match e {
+        Expression::Ternary(t) => expr_src_span(&t.first)
+            .or_else(|| expr_src_span(&t.second))
+            .or_else(|| expr_src_span(&t.third)),
+        Expression::Binary(b) => expr_src_span(&b.left).or_else(|| expr_src_span(&b.right)),
+        Expression::Unary(u) => expr_src_span(&u.expression),
+        Expression::Postfix(p) => p.accesses.iter().find_map(|aa| match aa {
+            Access::Select(ArrayAccess { expression: RangeOrExpression::Expression(e), .. }) => expr_src_span(e),
+            _ => None
+        }),
+        _ => None
+    }
+}
+
+// Find the span of the source program an instruction comes from
+fn inst_src_span<'a, 'ast>(bc: &'a BlockContent<'ast>) -> Option<&'a Span<'ast>> {
+    match bc {
+        BlockContent::Witness(_) | BlockContent::MemPush(_) | BlockContent::MemPop(_) | BlockContent::DummyLoad(_) => None,
+        BlockContent::ArrayInit((_, _, size_expr, _)) => expr_src_span(size_expr),
+        BlockContent::Store((val_expr, _, _, id_expr, _, _)) => expr_src_span(val_expr).or_else(|| expr_src_span(id_expr)),
+        BlockContent::Load((_, _, _, id_expr, _)) => expr_src_span(id_expr),
+        BlockContent::Branch((cond, _, _)) => expr_src_span(cond),
+        BlockContent::Stmt(s) => {
+            if !s.span().as_str().is_empty() {
+                return Some(s.span());
+            }
+            match s {
+                Statement::Definition(d) => expr_src_span(&d.expression),
+                Statement::Assertion(a) => expr_src_span(&a.expression),
+                _ => None
+            }
+        }
+    }
+}
+
+// Number of memory operations performed by an instruction
+// The two sides of a branch share the same memory operations
+fn inst_num_mem_ops(bc: &BlockContent) -> usize {
+    match bc {
+        BlockContent::MemPush(_) | BlockContent::MemPop(_) | BlockContent::Store(_) | BlockContent::Load(_) | BlockContent::DummyLoad(_) => 1,
+        BlockContent::Branch((_, if_insts, _)) => if_insts.iter().map(inst_num_mem_ops).sum(),
+        _ => 0
+    }
+}
+
+impl<'ast> ZGen<'ast> {
+    // Compute the cost profile of an execution
+    // bls should have been processed by process_block::<0>, bl_exec_count records the number of executions of each block
+    pub(super) fn bl_profile(
+        &self,
+        bls: &Vec<Block<'ast>>,
+        bl_exec_count: &Vec<usize>,
+        no_ro_accesses: bool,
+    ) -> Result<CostProfile, String> {
+        // (path, line) -> (source, num_execs, num_cons, num_mem_ops), None for generated instructions
+        let mut line_map: BTreeMap<Option<(PathBuf, usize)>, (String, usize, usize, usize)> = BTreeMap::new();
+        // fn_name -> (num_execs, num_cons, num_mem_ops)
+        let mut fn_map: BTreeMap<String, (usize, usize, usize)> = BTreeMap::new();
+
+        for b in bls {
+            let count = bl_exec_count[b.name];
+            if count == 0 {
+                continue;
+            }
+            let fn_entry = fn_map.entry(b.fn_name.clone()).or_insert((0, 0, 0));
+            fn_entry.0 += count;
+
+            // Number of constraints of every assertion of the block, and the assertions emitted by each instruction
+            let block_name = &format!("Pseudo_Block_{}", b.name);
+            self.circ_init_block(block_name);
+            let inst_num_asserts = self.bl_to_circ::<false>(b, block_name, no_ro_accesses);
+            let (num_input_cons, assertion_num_cons) = self.circ.borrow().cir_ctx().cs.borrow().get(block_name)
+                .map(|c| count_constraints_by_assertion(c, cfg()))
+                .ok_or(format!("Profile failed: block {} does not exist!", b.name))?;
+            // The assertions after the last instruction check the return value, whose last constraint is removed
+            // in the same way as in bl_count_num_cons
+            let num_insts_asserts = inst_num_asserts.last().copied().unwrap_or(0);
+            let gen_num_cons = num_input_cons + assertion_num_cons[num_insts_asserts..].iter().sum::<usize>() - 1;
+            line_map.entry(None).or_insert((String::new(), 0, 0, 0)).2 += gen_num_cons * count;
+            fn_entry.1 += gen_num_cons * count;
+            let mut last_num_asserts = 0;
+            for (bc, num_asserts) in b.instructions.iter().zip(inst_num_asserts) {
+                let inst_num_cons: usize = assertion_num_cons[last_num_asserts..num_asserts].iter().sum();
+                last_num_asserts = num_asserts;
+                let inst_num_mem_ops = inst_num_mem_ops(bc);
+
+                let span = inst_src_span(bc);
+                let loc = span.and_then(|s| self.bl_span_line(s));
+                let line_entry = line_map.entry(loc.clone()).or_insert_with(|| {
+                    let source = if loc.is_some() { span.unwrap().start_pos().line_of().trim().to_string() } else { String::new() };
+                    (source, 0, 0, 0)
+                });
+                line_entry.1 += count;
+                line_entry.2 += inst_num_cons * count;
+                line_entry.3 += inst_num_mem_ops * count;
+                fn_entry.1 += inst_num_cons * count;
+                fn_entry.2 += inst_num_mem_ops * count;
+            }
+        }
+        // Reset self.circ
+        self.circ.borrow_mut().reset(ZSharp::new());
+
+        let mut lines: Vec<LineCost> = line_map.into_iter().map(|(loc, (source, num_execs, num_cons, num_mem_ops))| {
+            let (path, line) = match loc {
+                Some((path, line)) => (Some(path), line),
+                None => (None, 0)
+            };
+            LineCost { path, line, source, num_execs, num_cons, num_mem_ops }
+        }).collect();
+        // Sort from high -> low, ties stay in source order
+        lines.sort_by(|a, b| (b.num_cons, b.num_mem_ops).cmp(&(a.num_cons, a.num_mem_ops)));
+        let mut functions: Vec<FnCost> = fn_map.into_iter().map(|(name, (num_execs, num_cons, num_mem_ops))|
+            FnCost { name, num_execs, num_cons, num_mem_ops }
+        ).collect();
+        functions.sort_by(|a, b| (b.num_cons, b.num_mem_ops).cmp(&(a.num_cons, a.num_mem_ops)));
+
+        Ok(CostProfile {
+            num_execs: bl_exec_count.iter().sum(),
+            num_cons: functions.iter().map(|f| f.num_cons).sum(),
+            num_mem_ops: functions.iter().map(|f| f.num_mem_ops).sum(),
+            lines,
+            functions,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cfg::cfg_or_default;
+    use crate::front::zsharp::{parser, ZGen, OPT_RO_ARRAYS};
+    use crate::front::Mode;
+    use std::collections::HashMap;
+
+    #[test]
+    fn profile_matches_block_counts() {
+        let path = std::env::temp_dir().join(format!("zsharp_profile_{}.zok", std::process::id()));
+        std::fs::write(&path, "
+def add(u32 a, u32 b) -> u32:
+    return a + b
+
+def main(u32 n, field x) -> u32:
+    u32 s = 0
+    for u32 i in 0..n do
+        s = add(s, i * n)
+        assert(x * x != 0)
+    endfor
+    return if s < n then s else n fi
+").unwrap();
+        let loader = parser::ZLoad::new();
+        let asts = loader.load(&path);
+        std::fs::remove_file(&path).unwrap();
+        let mut g = ZGen::new(asts, Mode::Proof, loader.stdlib(), cfg_or_default().zsharp.isolate_asserts);
+        g.visit_files();
+        g.file_stack_push(path);
+        g.generics_stack_push(HashMap::new());
+
+        let (bls, entry_bl, inputs) = g.bl_gen_entry_fn("main").unwrap();
+        let (bls, entry_bl, _) = g.optimize_block(bls, entry_bl, inputs, OPT_RO_ARRAYS, false).unwrap();
+        let (bls, _, _, _, _, _, _) = g.process_block::<0>(bls, entry_bl, OPT_RO_ARRAYS, false).unwrap();
+        // Any execution count works, as the profile only weights the blocks by it
+        let bl_exec_count: Vec<usize> = (0..bls.len()).map(|i| i % 3).collect();
+        let profile = g.bl_profile(&bls, &bl_exec_count, false).unwrap();
+
+        let num_cons: usize = bls.iter()
+            .map(|b| g.bl_count_num_cons::<false>(b, false).unwrap() * bl_exec_count[b.name])
+            .sum();
+        assert_eq!(profile.num_cons, num_cons);
+        assert_eq!(profile.lines.iter().map(|l| l.num_cons).sum::<usize>(), num_cons);
+    }
+}
@@ -8,7 +8,6 @@ use crate::front::zsharp::Ty;
 use crate::front::zsharp::Value;
 use crate::front::zsharp::const_bool;
 use crate::front::zsharp::const_val;
-use crate::front::zsharp::span_to_string;
 use crate::front::zsharp::Op;
 use std::collections::BTreeMap;
 use crate::front::zsharp::blocks::*;
@@ -18,10 +17,12 @@ use log::warn;
 use log::debug;
 use std::cmp::Ordering;
 use crate::ir::term::*;
+use crate::cfg::cfg_or_default as cfg;
+use crate::target::r1cs::LookupTable;
 
 use rug::Integer;
 
-const STORE: usize = 0;
+pub const STORE: usize = 0;
 const LOAD: usize = 1;
 
 const O_RET: &str = "%o000002";
@@ -88,6 +89,18 @@ impl PartialEq for MemOp {
 }
 impl Eq for MemOp {}
 
+// An array allocation performed by ARRAY_INIT
+#[derive(Debug, Clone)]
+pub struct MemAlloc {
+    // Register that holds the pointer to the array
+    pub arr: String,
+    // Allocated on physical (read-only) or virtual memory?
+    pub read_only: bool,
+    // Starting address and length of the allocation
+    pub addr: usize,
+    pub len: usize,
+}
+
 // We reserve indices for reg_in and reg_out to:
 // reg  0   1   2   3   4   5   6   7   8
 //      V  BN  RET TS  AS  SP  BP  i7  i8
@@ -99,6 +112,7 @@ pub struct ExecState {
     pub phy_mem_op: Vec<MemOp>,  // List of physical memory operations within the block
     pub vir_mem_op: Vec<MemOp>,  // List of virtual memory operations within the block
     pub wit_op: Vec<T>, // List of witnesses in the block
+    pub alloc_op: Vec<MemAlloc>, // List of array allocations within the block
 }
 
 impl ExecState {
@@ -110,6 +124,7 @@ impl ExecState {
             phy_mem_op: Vec::new(),
             vir_mem_op: Vec::new(),
             wit_op: Vec::new(),
+            alloc_op: Vec::new(),
         };
         input
     }
@@ -186,7 +201,7 @@ impl<'ast> ZGen<'ast> {
                     Ty::Uint(8) => DecimalSuffix::U8(U8Suffix {
                         span: Span::new("", 0, 0).unwrap()
                     }),
-                    _ => panic!("Unsupported input type: {:?}!", ty)
+                    _ => return Err(format!("Unsupported input type: {:?}!", ty))
                 }),
                 span: Span::new("", 0, 0).unwrap()
             }
@@ -251,6 +266,48 @@ impl<'ast> ZGen<'ast> {
         Vec<MemOp>, // Input Virtual Memory operations
         Vec<MemOp>, // Physical Memory operations
         Vec<MemOp> // Virtual Memory operations
+    ), String> {
+        let mut bl_exec_state: Vec<ExecState> = Vec::new();
+        let (ret, bl_exec_count, prog_reg_in, init_phy_mem_list, init_vir_mem_list) = self.bl_eval_entry_fn_stream::<VERBOSE>(
+            entry_bl, 
+            prog_inputs, 
+            live_input_set, 
+            entry_regs, 
+            entry_stacks, 
+            entry_arrays, 
+            entry_witnesses, 
+            bls, 
+            io_size, 
+            no_ro_accesses,
+            &mut |_, state| { bl_exec_state.push(state); Ok(()) },
+        )?;
+        let (phy_mem_list, vir_mem_list) = sort_by_mem(&init_phy_mem_list, &init_vir_mem_list, &bl_exec_state);
+        Ok((ret, bl_exec_count, prog_reg_in, bl_exec_state, init_phy_mem_list, init_vir_mem_list, phy_mem_list, vir_mem_list))
+    }
+
+    // Same as bl_eval_entry_fn, but instead of collecting all block executions,
+    // pass every ExecState to exec_sink as soon as its output state is finalized,
+    // together with its input state (program input for the first block, output of the previous block otherwise)
+    // This allows the caller to process arbitrarily long executions without keeping them in memory
+    pub fn bl_eval_entry_fn_stream<const VERBOSE: bool>(
+        &self,
+        entry_bl: usize,
+        prog_inputs: &Vec<(String, Ty)>,
+        live_input_set: &mut BTreeSet<String>,
+        entry_regs: &mut BTreeMap<String, Integer>, // Entry regs should match the input of the entry block
+        entry_stacks: &BTreeMap<String, Vec<Integer>>,
+        entry_arrays: &BTreeMap<String, Vec<Integer>>,
+        entry_witnesses: &Vec<Integer>,
+        bls: &Vec<Block<'ast>>,
+        io_size: usize,
+        no_ro_accesses: bool,
+        exec_sink: &mut dyn FnMut(&Vec<Option<T>>, ExecState) -> Result<(), String>,
+    ) -> Result<(
+        T, // Return value
+        Vec<usize>, // Block ID
+        Vec<Option<T>>, // Program input state
+        Vec<MemOp>, // Input Physical Memory operations
+        Vec<MemOp>, // Input Virtual Memory operations
     ), String> {
         if bls.len() < entry_bl {
             return Err(format!("Invalid entry_bl: entry_bl exceeds block size."));
@@ -263,8 +320,9 @@ impl<'ast> ZGen<'ast> {
         let mut tr_size = 0;
         // bl_exec_count[i]: how many times have block i been executed?
         let mut bl_exec_count: Vec<usize> = vec![0; bls.len()];
-        // bl_exec_state[i]: execution state of each block-execution
-        let mut bl_exec_state: Vec<ExecState> = Vec::new();
+        // Execution state of the previous block-execution, whose output state is finalized when the next block starts
+        let mut last_state: Option<ExecState> = None;
+        let mut last_reg_in: Vec<Option<T>> = Vec::new();
 
         let mut nb = entry_bl;
         let mut io_regs: Vec<Option<T>> = Vec::new();
@@ -286,6 +344,31 @@ impl<'ast> ZGen<'ast> {
         // The next stack / address to allocate
         let mut stack_addr_count = 0;
         let mut mem_addr_count = 0;
+        // Lookup tables occupy the lowest stack addresses, before any read-only input
+        for entry in LookupTable::memory(cfg().r1cs.lookup_width) {
+            let addr_t = self.int_to_t(&Integer::from(stack_addr_count), &Ty::Field)?;
+            let data_t = self.int_to_t(&Integer::from(entry), &Ty::Field)?;
+            phy_mem.push(Some(data_t.clone()));
+            init_phy_mem_list.push(MemOp::new_phy(stack_addr_count, addr_t, data_t));
+            stack_addr_count += 1;
+        }
+        // Seeds of the challenges follow the lookup tables, they are provided as the read-only input %CH
+        // They are drawn from the transcript of the instance, which only zxc computes
+        let num_chall = self.bl_num_challenges.get();
+        let challenges = entry_stacks.get("%CH").map_or(&[][..], |c| &c[..]);
+        if challenges.len() != num_chall {
+            return Err(format!(
+                "The program calls sample_challenge {} times, but {} seeds are provided: programs that sample challenges can only be run by zxc",
+                num_chall, challenges.len()
+            ));
+        }
+        for entry in challenges {
+            let addr_t = self.int_to_t(&Integer::from(stack_addr_count), &Ty::Field)?;
+            let data_t = self.int_to_t(entry, &Ty::Field)?;
+            phy_mem.push(Some(data_t.clone()));
+            init_phy_mem_list.push(MemOp::new_phy(stack_addr_count, addr_t, data_t));
+            stack_addr_count += 1;
+        }
         // The index in prog_inputs
         let mut i = 0;
         // The corresponding index in bls[entry_bl].input
@@ -316,7 +399,7 @@ impl<'ast> ZGen<'ast> {
                             flatten_var("", &*entry_ty, &mut flattened_var);
                             flattened_var.into_iter().map(|i| i.1).collect()
                         }
-                        _ => { panic!("Mut Array input type not supported!") }
+                        _ => { return Err(format!("Mut Array input type not supported!")); }
                     };
                     if alive {
                         // Find register name
@@ -384,12 +467,13 @@ impl<'ast> ZGen<'ast> {
             bl_exec_count[nb] += 1;
 
             // Push-in new block state
-            bl_exec_state.push(ExecState::new(nb, io_size));
+            let mut cur_state = ExecState::new(nb, io_size);
             // If it is the first block, add input to prog_reg_in
             if tr_size == 0 {
                 for i in 1..io_size {
                     prog_reg_in[i] = self.bl_eval_identifier_impl_(&io_regs, &wit_regs, &format!("%i{:06}", i)).ok();
                 }
+                last_reg_in = prog_reg_in.clone();
             }
             // Block transition is largely free, unless an input is not defined in the previous output, 
             // then set it to 0 / false
@@ -439,9 +523,17 @@ impl<'ast> ZGen<'ast> {
                     }
                 }
                 // Record the last transition state as the union of reg_in and reg_out
+                let mut state = last_state.take().unwrap();
                 for i in 1..io_size {
-                    bl_exec_state[tr_size - 1].reg_out[i] = self.bl_eval_identifier_impl_(&io_regs, &wit_regs, &format!("%o{:06}", i)).ok();
+                    state.reg_out[i] = self.bl_eval_identifier_impl_(&io_regs, &wit_regs, &format!("%o{:06}", i)).ok();
                 }
+                let reg_out = state.reg_out.clone();
+                exec_sink(&last_reg_in, state)?;
+                last_reg_in = reg_out;
+            }
+
+            if let Some(d) = self.debugger.borrow_mut().as_mut() {
+                d.before_block(tr_size, &bls[nb], &io_regs, &wit_regs, &phy_mem, &vir_mem, &self.reg_name_map.borrow())?;
             }
 
             if VERBOSE {
@@ -475,12 +567,19 @@ impl<'ast> ZGen<'ast> {
             let phy_mem_op: Vec<MemOp>;
             let vir_mem_op: Vec<MemOp>;
             let wit_op: Vec<T>;
-            (nb, terminated, phy_mem_op, vir_mem_op, wit_op, witness_count) = 
-                self.bl_eval_impl_(&bls[nb], &mut io_regs, &mut wit_regs, &mut phy_mem, &mut vir_mem, entry_witnesses, witness_count, no_ro_accesses)?;
+            let alloc_op: Vec<MemAlloc>;
+            let res = self.bl_eval_impl_(&bls[nb], &mut io_regs, &mut wit_regs, &mut phy_mem, &mut vir_mem, entry_witnesses, witness_count, no_ro_accesses);
+            if let Some(d) = self.debugger.borrow_mut().as_mut() {
+                match &res {
+                    Ok((_, _, phy_mem_op, vir_mem_op, ..)) => d.after_block(tr_size, &bls[nb], phy_mem_op, vir_mem_op),
+                    Err(e) => d.on_error(e, tr_size, &bls[nb], &io_regs, &wit_regs, &phy_mem, &vir_mem, &self.reg_name_map.borrow())?,
+                }
+            }
+            (nb, terminated, phy_mem_op, vir_mem_op, wit_op, alloc_op, witness_count) = res?;
             // Remove undeclared %o registers
             let mut live_o_regs = vec![false; io_regs.len()];
-            if bl_exec_state.len() > 1 {
-                for (name, _) in &bls[bl_exec_state[tr_size].blk_id].outputs {
+            if tr_size > 0 {
+                for (name, _) in &bls[cur_state.blk_id].outputs {
                     let reg_index = self.reg_name_to_index(name)?;
                     live_o_regs[reg_index] = true;
                 }
@@ -491,26 +590,29 @@ impl<'ast> ZGen<'ast> {
                 }
             }
             // Update successor block ID
-            bl_exec_state[tr_size].succ_id = nb;
+            cur_state.succ_id = nb;
             // Update Memory Op
-            bl_exec_state[tr_size].phy_mem_op = phy_mem_op;
-            bl_exec_state[tr_size].vir_mem_op = vir_mem_op;
-            bl_exec_state[tr_size].wit_op = wit_op;
+            cur_state.phy_mem_op = phy_mem_op;
+            cur_state.vir_mem_op = vir_mem_op;
+            cur_state.wit_op = wit_op;
+            cur_state.alloc_op = alloc_op;
+            last_state = Some(cur_state);
             tr_size += 1;
         }
         
         // Record the final transition state
+        let mut state = last_state.take().unwrap();
         for i in 1..io_size {
-            bl_exec_state[tr_size - 1].reg_out[i] = self.bl_eval_identifier_impl_(&io_regs, &wit_regs, &format!("%o{:06}", i)).ok();
+            state.reg_out[i] = self.bl_eval_identifier_impl_(&io_regs, &wit_regs, &format!("%o{:06}", i)).ok();
         }
+        exec_sink(&last_reg_in, state)?;
         // Return value is just the value of the variable called "%RET"
         // Type of return value is checked during assignment
         let ret = self.bl_eval_identifier_impl_(&io_regs, &wit_regs, O_RET).or(
             Err(format!("Missing return value for one or more functions."))
         );
 
-        let (phy_mem_list, vir_mem_list) = sort_by_mem(&init_phy_mem_list, &init_vir_mem_list, &bl_exec_state);
-        Ok((ret?, bl_exec_count, prog_reg_in, bl_exec_state, init_phy_mem_list, init_vir_mem_list, phy_mem_list, vir_mem_list))
+        Ok((ret?, bl_exec_count, prog_reg_in, init_phy_mem_list, init_vir_mem_list))
     }
 
     // Return type:
@@ -520,6 +622,7 @@ impl<'ast> ZGen<'ast> {
     // ret[3]: Has the program terminated?
     // ret[4]: Pairs of [addr, data] for all physical (scoping) memory operations in the block
     // ret[5]: Quadruples of [addr, data, ls, ts] for all virtual memory operations in the block
+    // ret[6]: All array allocations in the block
     fn bl_eval_impl_(
         &self, 
         bl: &Block<'ast>,
@@ -530,7 +633,7 @@ impl<'ast> ZGen<'ast> {
         entry_witnesses: &Vec<Integer>,
         mut witness_count: usize,
         no_ro_accesses: bool,
-    ) -> Result<(usize, bool, Vec<MemOp>, Vec<MemOp>, Vec<T>, usize), String> {
+    ) -> Result<(usize, bool, Vec<MemOp>, Vec<MemOp>, Vec<T>, Vec<MemAlloc>, usize), String> {
         debug!("Block eval impl: {}", bl.name);
 
         // Record all RO mem ops before any PHY mem ops
@@ -538,20 +641,21 @@ impl<'ast> ZGen<'ast> {
         let mut ro_mem_op: Vec<MemOp> = Vec::new();
         let mut vir_mem_op: Vec<MemOp> = Vec::new();
         let mut wit_op: Vec<T> = Vec::new();
+        let mut alloc_op: Vec<MemAlloc> = Vec::new();
 
-        witness_count = self.bl_eval_inst_impl_(io_regs, wit_regs, &bl.instructions, phy_mem, vir_mem, &mut phy_mem_op, &mut ro_mem_op, &mut vir_mem_op, &mut wit_op, entry_witnesses, witness_count, no_ro_accesses)?;
+        witness_count = self.bl_eval_inst_impl_(io_regs, wit_regs, &bl.instructions, phy_mem, vir_mem, &mut phy_mem_op, &mut ro_mem_op, &mut vir_mem_op, &mut wit_op, &mut alloc_op, entry_witnesses, witness_count, no_ro_accesses)?;
         ro_mem_op.extend(phy_mem_op);
         let phy_mem_op = ro_mem_op;
 
         match &bl.terminator {
             BlockTerminator::Transition(e) => {
                 match self.t_to_usize(self.bl_eval_expr_impl_(io_regs, wit_regs, &e)?) {
-                    Ok(nb) => { return Ok((nb, false, phy_mem_op, vir_mem_op, wit_op, witness_count)); }, 
+                    Ok(nb) => { return Ok((nb, false, phy_mem_op, vir_mem_op, wit_op, alloc_op, witness_count)); }, 
                     _ => { return Err("Evaluation failed: block transition evaluated to an invalid block label".to_string()); }
                 }
             }
             BlockTerminator::FuncCall(fc) => Err(format!("Evaluation failed: function call to {} needs to be converted to block label.", fc)),
-            BlockTerminator::ProgTerm => Ok((0, true, phy_mem_op, vir_mem_op, wit_op, witness_count))
+            BlockTerminator::ProgTerm => Ok((0, true, phy_mem_op, vir_mem_op, wit_op, alloc_op, witness_count))
         }
     }
 
@@ -566,6 +670,7 @@ impl<'ast> ZGen<'ast> {
         ro_mem_op: &mut Vec<MemOp>,
         vir_mem_op: &mut Vec<MemOp>,
         wit_op: &mut Vec<T>, 
+        alloc_op: &mut Vec<MemAlloc>,
         entry_witnesses: &Vec<Integer>,
         mut witness_count: usize,
         no_ro_accesses: bool,
@@ -628,13 +733,19 @@ impl<'ast> ZGen<'ast> {
                     if len_t.type_() != &Ty::Field {
                         len_t = uint_to_field(len_t).unwrap();
                     }
-                    let new_pointer_t = add(pointer_t, len_t.clone()).unwrap();
+                    let new_pointer_t = add(pointer_t.clone(), len_t.clone()).unwrap();
                     self.bl_eval_assign_impl_(io_regs, wit_regs, if *read_only { W_SP } else { W_AS }, new_pointer_t)?;
+                    let len = self.t_to_usize(len_t)?;
                     // If RO array, allocate space on PHY_MEM
                     if *read_only {
-                        let len = self.t_to_usize(len_t)?;
                         phy_mem.extend(vec![None; len]);
                     }
+                    alloc_op.push(MemAlloc {
+                        arr: arr.to_string(),
+                        read_only: *read_only,
+                        addr: self.t_to_usize(pointer_t)?,
+                        len,
+                    });
                 }
                 BlockContent::Store((val_expr, ty, arr, id_expr, init, read_only)) => {
                     let read_only = if no_ro_accesses { &false } else { &read_only };
@@ -837,20 +948,19 @@ impl<'ast> ZGen<'ast> {
                             .ok_or_else(|| "interpreting expr as const bool failed".to_string())
                     }) {
                         Ok(true) => {
-                            witness_count = self.bl_eval_inst_impl_(io_regs, wit_regs, if_inst, phy_mem, vir_mem, phy_mem_op, ro_mem_op, vir_mem_op, wit_op, entry_witnesses, witness_count, no_ro_accesses)?;
+                            witness_count = self.bl_eval_inst_impl_(io_regs, wit_regs, if_inst, phy_mem, vir_mem, phy_mem_op, ro_mem_op, vir_mem_op, wit_op, alloc_op, entry_witnesses, witness_count, no_ro_accesses)?;
                         },
                         Ok(false) => {
-                            witness_count = self.bl_eval_inst_impl_(io_regs, wit_regs, else_inst, phy_mem, vir_mem, phy_mem_op, ro_mem_op, vir_mem_op, wit_op, entry_witnesses, witness_count, no_ro_accesses)?;
+                            witness_count = self.bl_eval_inst_impl_(io_regs, wit_regs, else_inst, phy_mem, vir_mem, phy_mem_op, ro_mem_op, vir_mem_op, wit_op, alloc_op, entry_witnesses, witness_count, no_ro_accesses)?;
                         },
-                        Err(err) => return Err(format!(
-                            "Const conditional expression eval failed: {} at\n{}",
+                        Err(err) => return self.bl_locate(Err(format!(
+                            "Const conditional expression eval failed: {}",
                             err,
-                            span_to_string(cond.span()),
-                        ))
+                        )), cond.span())
                     }
                 }
                 BlockContent::Stmt(s) => {
-                    self.bl_eval_stmt_impl_(io_regs, wit_regs, s)?;
+                    self.bl_locate(self.bl_eval_stmt_impl_(io_regs, wit_regs, s), s.span())?;
                 }
             }
         };
@@ -874,19 +984,17 @@ impl<'ast> ZGen<'ast> {
                         .ok_or_else(|| "interpreting expr as const bool failed".to_string())
                 }) {
                     Ok(true) => {},
-                    Ok(false) => return Err(format!(
-                        "Const assert failed: {} at\n{}",
+                    Ok(false) => return self.bl_locate(Err(format!(
+                        "Const assert failed: {}",
                         a.message
                             .as_ref()
                             .map(|m| m.value.as_ref())
                             .unwrap_or("(no error message given)"),
-                        span_to_string(a.expression.span()),
-                    )),
-                    Err(err) => return Err(format!(
-                        "Const assert expression eval failed: {} at\n{}",
+                    )), a.expression.span()),
+                    Err(err) => return self.bl_locate(Err(format!(
+                        "Const assert expression eval failed: {}",
                         err,
-                        span_to_string(a.expression.span()),
-                    ))
+                    )), a.expression.span())
                 }
             }
             Statement::Iteration(_) => {
@@ -938,9 +1046,11 @@ impl<'ast> ZGen<'ast> {
         wit_regs: &Vec<Option<T>>,
         e: &Expression
     ) -> Result<T, String> {
-        match e {
+        let r = match e {
             ast::Expression::Ternary(u) => {
-                match self.bl_eval_expr_impl_(io_regs, wit_regs, &u.first).ok().and_then(const_bool) {
+                // Errors of the condition are propagated, so that they keep their own location
+                let cond = self.bl_eval_expr_impl_(io_regs, wit_regs, &u.first)?;
+                match const_bool(cond) {
                     Some(true) => self.bl_eval_expr_impl_(io_regs, wit_regs, &u.second),
                     Some(false) => self.bl_eval_expr_impl_(io_regs, wit_regs, &u.third),
                     None => Err("ternary condition not const bool".to_string()),
@@ -974,8 +1084,8 @@ impl<'ast> ZGen<'ast> {
                 Err(format!("Blocks should not contain inline structs!"))
             }
         }
-        .and_then(|res| const_val(res))
-        .map_err(|err| format!("{}; context:\n{}", err, span_to_string(e.span())))
+        .and_then(|res| const_val(res));
+        self.bl_locate(r, e.span())
     }
 
     fn bl_eval_assign_impl_(
@@ -0,0 +1,160 @@
+//! Input files and the block IR pipeline shared by the zx tools (zxc, zxi and zxd)
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use rug::Integer;
+
+use crate::cfg::cfg;
+use crate::front::zsharp::{BlockError, Inputs, ZSharpFE};
+use crate::front::Computations;
+use crate::ir::opt::{opt, Opt};
+use crate::target::r1cs::trans::to_r1cs;
+use crate::target::r1cs::{LookupTable, ProverData};
+
+/// Inputs of the entry function, as read by [read_inputs]
+/// (entry_regs, entry_stacks, entry_arrays, entry_regs_concat, entry_stacks_concat, entry_arrays_concat, %SP, %AS)
+pub type EntryInputs = (
+    BTreeMap<String, Integer>,
+    BTreeMap<String, Vec<Integer>>,
+    BTreeMap<String, Vec<Integer>>,
+    Vec<Integer>,
+    Vec<Integer>,
+    Vec<Integer>,
+    usize,
+    usize,
+);
+
+/// Parse an input file of the entry function.
+/// Every line is either VAR VAL, VAR [ ENTRY_0 ENTRY_1 ... ], or VAR [ro ENTRY_0 ENTRY_1 ... ]; the file ends with END.
+/// A missing file means that main takes no inputs.
+///
+/// Read-only memory starts with the lookup tables, followed by num_challenges entries reserved for
+/// the seeds of the challenges (the read-only input %CH), and then the read-only inputs. The seeds are left as 0.
+/// %SP and %AS are inserted in front of entry_regs_concat.
+pub fn read_inputs(input_file_name: &Path, num_challenges: usize) -> EntryInputs {
+    // Keep track of %SP and %AS and record initial memory state
+    let mut stack_alloc_counter = 0;
+    let mut mem_alloc_counter = 0;
+    // Inputs as register, stack, or array
+    // Note that stacks and arrays cans be passed as pointers (registers)
+    let mut entry_regs: BTreeMap<String, Integer> = BTreeMap::new();
+    let mut entry_stacks: BTreeMap<String, Vec<Integer>> = BTreeMap::new(); // for read-only
+    let mut entry_arrays: BTreeMap<String, Vec<Integer>> = BTreeMap::new(); // for others
+    // Initial memory setup
+    let mut entry_regs_concat: Vec<Integer> = Vec::new();
+    let mut entry_stacks_concat: Vec<Integer> = Vec::new();
+    let mut entry_arrays_concat: Vec<Integer> = Vec::new();
+    // Lookup tables are public read-only memory below all read-only inputs
+    let lookup_memory = LookupTable::memory(cfg().r1cs.lookup_width);
+    stack_alloc_counter += lookup_memory.len();
+    entry_stacks_concat.extend(lookup_memory.into_iter().map(Integer::from));
+    stack_alloc_counter += num_challenges;
+    entry_stacks_concat.extend(vec![Integer::from(0); num_challenges]);
+
+    if let Ok(f) = File::open(input_file_name) {
+        let mut reader = BufReader::new(f);
+        let mut buffer = String::new();
+        reader.read_line(&mut buffer).unwrap();
+        while buffer.trim() != "END" {
+            let split: Vec<String> = buffer.split(' ').map(|i| i.trim().to_string()).collect();
+            let var_name = split[0].split(':').next().unwrap().trim();
+            // split is either of form [VAR, VAL] or [VAR, "[", ENTRY_0, ENTRY_1, ..., "]"]
+            if let Ok(val) = Integer::from_str_radix(&split[1], 10) {
+                entry_regs.insert(var_name.to_string(), val.clone());
+                entry_regs_concat.push(val);
+            } else if split[1] == "[ro" {
+                assert_eq!(split[split.len() - 1], "]");
+                entry_regs.insert(var_name.to_string(), Integer::from(stack_alloc_counter));
+                entry_regs_concat.push(Integer::from(stack_alloc_counter));
+                // Parse the entries
+                let stack_entries: Vec<Integer> = split[2..split.len() - 1].iter().map(|entry| Integer::from_str_radix(entry, 10).unwrap()).collect();
+                entry_stacks.insert(var_name.to_string(), stack_entries.clone());
+                entry_stacks_concat.extend(stack_entries);
+                stack_alloc_counter += split.len() - 3; // var, "[", and "]"
+            } else {
+                assert_eq!(split[1], "[");
+                assert_eq!(split[split.len() - 1], "]");
+                entry_regs.insert(var_name.to_string(), Integer::from(mem_alloc_counter));
+                entry_regs_concat.push(Integer::from(mem_alloc_counter));
+                // Parse the entries
+                let array_entries: Vec<Integer> = split[2..split.len() - 1].iter().map(|entry| Integer::from_str_radix(entry, 10).unwrap()).collect();
+                entry_arrays.insert(var_name.to_string(), array_entries.clone());
+                entry_arrays_concat.extend(array_entries);
+                mem_alloc_counter += split.len() - 3; // var, "[", and "]"
+            }
+            buffer.clear();
+            reader.read_line(&mut buffer).unwrap();
+        }
+    }
+    // Insert [%SP, %AS] to the front of entry_reg
+    entry_regs.insert("%AS".to_string(), Integer::from(mem_alloc_counter));
+    entry_regs_concat.insert(0, Integer::from(mem_alloc_counter));
+    entry_regs.insert("%SP".to_string(), Integer::from(stack_alloc_counter));
+    entry_regs_concat.insert(0, Integer::from(stack_alloc_counter));
+
+    (entry_regs, entry_stacks, entry_arrays, entry_regs_concat, entry_stacks_concat, entry_arrays_concat, stack_alloc_counter, mem_alloc_counter)
+}
+
+/// Parse a witness file, a list of whitespace-separated values that ends with END.
+/// A missing file means that the program takes no witnesses.
+pub fn read_witnesses(witness_file_name: &Path) -> Vec<Integer> {
+    let mut entry_witnesses: Vec<Integer> = Vec::new();
+    if let Ok(f) = File::open(witness_file_name) {
+        let mut reader = BufReader::new(f);
+        let mut buffer = String::new();
+        reader.read_line(&mut buffer).unwrap();
+        while buffer.trim() != "END" {
+            let split: Vec<String> = buffer.split(' ').map(|i| i.trim().to_string()).collect();
+            entry_witnesses.extend(split.iter().map(|entry| Integer::from_str_radix(entry, 10).unwrap()));
+            buffer.clear();
+            reader.read_line(&mut buffer).unwrap();
+        }
+    }
+    entry_witnesses
+}
+
+/// The IR optimizations applied to the circuits of the blocks before they are lowered to R1CS
+pub fn block_ir_opts() -> Vec<Opt> {
+    vec![
+        Opt::ScalarizeVars,
+        Opt::Flatten,
+        Opt::Sha,
+        Opt::ConstantFold(Box::new([])),
+        Opt::Flatten,
+        Opt::Inline,
+        // Tuples must be eliminated before oblivious array elim
+        Opt::Tuple,
+        Opt::ConstantFold(Box::new([])),
+        Opt::Obliv,
+        // The obliv elim pass produces more tuples, that must be eliminated
+        Opt::Tuple,
+        Opt::LinearScan,
+        // The linear scan pass produces more tuples, that must be eliminated
+        Opt::Tuple,
+        Opt::Flatten,
+        Opt::ConstantFold(Box::new([])),
+        Opt::Inline,
+        Opt::SkolemizeChallenges,
+    ]
+}
+
+/// The prover data of every block circuit Block_0, Block_1, ... of cs
+pub fn block_prover_data(cs: &Computations) -> Vec<ProverData> {
+    let mut prover_data_list = Vec::new();
+    while let Some(c) = cs.comps.get(&format!("Block_{}", prover_data_list.len())) {
+        let mut r1cs = to_r1cs(c, cfg());
+        // Remove the last constraint because it is about the return value
+        r1cs.constraints.pop();
+        prover_data_list.push(r1cs.finalize(c).0);
+    }
+    prover_data_list
+}
+
+/// Compile a program to blocks and generate the prover data of every block, without linearity reduction
+pub fn gen_block_prover_data(i: Inputs) -> Result<Vec<ProverData>, BlockError> {
+    let ((cs, _, _, _, _, _, _), _, _, _) = ZSharpFE::gen_with_pass_times(i)?;
+    Ok(block_prover_data(&opt(cs, block_ir_opts())))
+}
@@ -15,6 +15,7 @@ mod visit;
 use super::term::*;
 
 use log::{debug, trace};
+use std::time::{Duration, Instant};
 
 #[derive(Clone, Debug)]
 /// An optimization pass
@@ -57,12 +58,24 @@ pub enum Opt {
 }
 
 /// Run optimizations on `cs`, in this order, returning the new constraint system.
-pub fn opt<I: IntoIterator<Item = Opt>>(mut cs: Computations, optimizations: I) -> Computations {
+pub fn opt<I: IntoIterator<Item = Opt>>(cs: Computations, optimizations: I) -> Computations {
+    opt_with_times(cs, optimizations).0
+}
+
+/// Run optimizations on `cs`, in this order, returning the new constraint system
+/// and the wall-clock time of every optimization.
+pub fn opt_with_times<I: IntoIterator<Item = Opt>>(
+    mut cs: Computations,
+    optimizations: I,
+) -> (Computations, Vec<(Opt, Duration)>) {
+    let mut times = Vec::new();
     for i in optimizations {
         debug!("Applying: {:?}", i);
+        let start = Instant::now();
 
         if let Opt::Link = i {
             link::link_all_function_calls(&mut cs);
+            times.push((i, start.elapsed()));
             continue;
         }
 
@@ -155,9 +168,10 @@ pub fn opt<I: IntoIterator<Item = Opt>>(mut cs: Computations, optimizations: I)
         if crate::cfg::cfg().ir.frequent_gc {
             garbage_collect();
         }
+        times.push((i, start.elapsed()));
     }
     if !crate::cfg::cfg().ir.frequent_gc {
         garbage_collect();
     }
-    cs
+    (cs, times)
 }
@@ -0,0 +1,4 @@
+//! ABY
+pub mod assignment;
+pub mod trans;
+pub mod utils;
@@ -0,0 +1,455 @@
+//! Folding over blocks (non-succinct demo)
+//!
+//! Every block execution is a step of a dispatcher circuit, which contains the vars of every block,
+//! and selects the block of the step by its valid bit: exactly one block is valid, with input %BN its id,
+//! and all I/O and memory vars of the other blocks are zero, so they satisfy their constraints.
+//! The public input of a step is its I/O and the memory accesses of the selected block.
+//! Steps are folded into a single relaxed R1CS instance, with Pedersen commitments over BLS12-381.
+//! There is no augmented circuit that verifies the folding of the previous steps, so the verifier folds
+//! the instances itself, checks the transitions and memory on the public inputs of the steps, and checks the folded
+//! instance against its opened witness. Proofs and verification are linear in the number of executions.
+//! Keys do not depend on the execution shape. Requires --field-builtin bls12381.
+
+use bls12_381::{G1Affine, G1Projective, Scalar};
+use ff::{Field, PrimeField};
+use group::{Curve, Group};
+use merlin::Transcript;
+use rug::Integer;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::convert::TryInto;
+
+use super::groth16::{check_field, to_integer, to_scalar};
+use super::knowledge::*;
+use super::proof::*;
+use super::{VARS_PER_ST_ACCESS, VARS_PER_VM_ACCESS};
+
+type Row = Vec<(usize, Scalar)>;
+
+// Wire 0 is the relaxation u, followed by the public input x and the witness W
+struct Dispatcher {
+    num_x: usize,
+    num_w: usize,
+    // Wire of var 0 of every block
+    block_offsets: Vec<usize>,
+    max_phy_ops: usize,
+    max_vir_ops: usize,
+    cons: Vec<(Row, Row, Row)>,
+}
+
+// x: I/O, then (valid, addr, data) of every physical memory slot, then (valid, addr, data, ls, ts) of every virtual memory slot
+const PHY_SLOT_WIDTH: usize = 1 + VARS_PER_ST_ACCESS;
+const VIR_SLOT_WIDTH: usize = 1 + VARS_PER_VM_ACCESS;
+
+// lc = 0, as lc * u = 0
+fn linear(lc: Row) -> (Row, Row, Row) {
+    (lc, vec![(0, Scalar::one())], Vec::new())
+}
+
+impl Dispatcher {
+    fn new(ctk: &CompileTimeKnowledge) -> Dispatcher {
+        let io_width = 2 * ctk.num_inputs_unpadded;
+        let max_phy_ops = ctk.block_num_phy_ops.iter().cloned().max().unwrap_or(0);
+        let max_vir_ops = ctk.block_num_vir_ops.iter().cloned().max().unwrap_or(0);
+        let num_x = io_width + PHY_SLOT_WIDTH * max_phy_ops + VIR_SLOT_WIDTH * max_vir_ops;
+        let mut block_offsets = Vec::new();
+        let mut next_offset = 1 + num_x;
+        for b in 0..ctk.block_num_instances {
+            block_offsets.push(next_offset);
+            next_offset += ctk.num_vars_per_block[b];
+        }
+        let num_w = next_offset - 1 - num_x;
+        let x = |j: usize| 1 + j;
+        let one = Scalar::one();
+
+        let mut cons = Vec::new();
+        // Constraints of every block, the valid bit is the constant of the block
+        for b in 0..ctk.block_num_instances {
+            let shift = |row: &Vec<(usize, [u8; 32])>| -> Row {
+                row.iter().map(|(var, coeff)| (block_offsets[b] + var, to_scalar(coeff))).collect()
+            };
+            for (a, b_, c) in &ctk.args[b] {
+                cons.push((shift(a), shift(b_), shift(c)));
+            }
+        }
+        // Exactly one block is valid
+        let mut num_valid = vec![(0, -one)];
+        for b in 0..ctk.block_num_instances {
+            let v = block_offsets[b];
+            num_valid.push((v, one));
+            cons.push((vec![(v, one)], vec![(v, one), (0, -one)], Vec::new()));
+            // Input %BN is the block id
+            cons.push(linear(vec![(v + 2, one), (v, -Scalar::from(b as u64))]));
+            // The I/O and memory vars of other blocks are zero
+            let num_mem_vars = VARS_PER_ST_ACCESS * ctk.block_num_phy_ops[b] + VARS_PER_VM_ACCESS * ctk.block_num_vir_ops[b];
+            for j in 1..io_width + num_mem_vars {
+                cons.push((vec![(0, one), (v, -one)], vec![(v + j, one)], Vec::new()));
+            }
+        }
+        cons.push(linear(num_valid));
+        // The public input is the sum over all blocks
+        for j in 0..io_width {
+            let mut lc = vec![(x(j), -one)];
+            lc.extend(block_offsets.iter().map(|v| (v + j, one)));
+            cons.push(linear(lc));
+        }
+        for k in 0..max_phy_ops {
+            let base = io_width + PHY_SLOT_WIDTH * k;
+            let mut lcs: Vec<Row> = (0..PHY_SLOT_WIDTH).map(|c| vec![(x(base + c), -one)]).collect();
+            for b in (0..ctk.block_num_instances).filter(|b| ctk.block_num_phy_ops[*b] > k) {
+                let v = block_offsets[b];
+                lcs[0].push((v, one));
+                for c in 0..VARS_PER_ST_ACCESS {
+                    lcs[1 + c].push((v + io_width + VARS_PER_ST_ACCESS * k + c, one));
+                }
+            }
+            cons.extend(lcs.into_iter().map(linear));
+        }
+        for k in 0..max_vir_ops {
+            let base = io_width + PHY_SLOT_WIDTH * max_phy_ops + VIR_SLOT_WIDTH * k;
+            let mut lcs: Vec<Row> = (0..VIR_SLOT_WIDTH).map(|c| vec![(x(base + c), -one)]).collect();
+            for b in (0..ctk.block_num_instances).filter(|b| ctk.block_num_vir_ops[*b] > k) {
+                let v = block_offsets[b];
+                let vm_base = io_width + VARS_PER_ST_ACCESS * ctk.block_num_phy_ops[b];
+                lcs[0].push((v, one));
+                for c in 0..VARS_PER_VM_ACCESS {
+                    lcs[1 + c].push((v + vm_base + VARS_PER_VM_ACCESS * k + c, one));
+                }
+            }
+            cons.extend(lcs.into_iter().map(linear));
+        }
+        Dispatcher { num_x, num_w, block_offsets, max_phy_ops, max_vir_ops, cons }
+    }
+
+    fn num_gens(&self) -> usize {
+        self.num_w.max(self.cons.len())
+    }
+
+    // Public input and witness of a step executing block b with vars z
+    fn step(&self, ctk: &CompileTimeKnowledge, b: usize, z: &Vec<Scalar>) -> (Vec<Scalar>, Vec<Scalar>) {
+        let io_width = 2 * ctk.num_inputs_unpadded;
+        let mut x = z[..io_width].to_vec();
+        for k in 0..self.max_phy_ops {
+            if k < ctk.block_num_phy_ops[b] {
+                x.push(Scalar::one());
+                let base = io_width + VARS_PER_ST_ACCESS * k;
+                x.extend_from_slice(&z[base..base + VARS_PER_ST_ACCESS]);
+            } else {
+                x.extend(vec![Scalar::zero(); PHY_SLOT_WIDTH]);
+            }
+        }
+        for k in 0..self.max_vir_ops {
+            if k < ctk.block_num_vir_ops[b] {
+                x.push(Scalar::one());
+                let base = io_width + VARS_PER_ST_ACCESS * ctk.block_num_phy_ops[b] + VARS_PER_VM_ACCESS * k;
+                x.extend_from_slice(&z[base..base + VARS_PER_VM_ACCESS]);
+            } else {
+                x.extend(vec![Scalar::zero(); VIR_SLOT_WIDTH]);
+            }
+        }
+        let mut w = vec![Scalar::zero(); self.num_w];
+        let offset = self.block_offsets[b] - 1 - self.num_x;
+        w[offset..offset + z.len()].copy_from_slice(z);
+        (x, w)
+    }
+
+    // (Az, Bz, Cz) for z = (u, x, W)
+    fn eval(&self, u: &Scalar, x: &Vec<Scalar>, w: &Vec<Scalar>) -> (Vec<Scalar>, Vec<Scalar>, Vec<Scalar>) {
+        let wire = |i: usize| -> Scalar {
+            if i == 0 { *u } else if i <= self.num_x { x[i - 1] } else { w[i - 1 - self.num_x] }
+        };
+        let eval_row = |row: &Row| -> Scalar { row.iter().fold(Scalar::zero(), |acc, (i, coeff)| acc + *coeff * wire(*i)) };
+        let az = self.cons.iter().map(|(a, _, _)| eval_row(a)).collect();
+        let bz = self.cons.iter().map(|(_, b, _)| eval_row(b)).collect();
+        let cz = self.cons.iter().map(|(_, _, c)| eval_row(c)).collect();
+        (az, bz, cz)
+    }
+}
+
+// Pedersen generators with unknown discrete logs, by hashing into G1
+fn derive_gens(n: usize) -> Vec<G1Projective> {
+    let mut transcript = Transcript::new(b"circ_blocks folding generators");
+    (0..n).map(|_| loop {
+        let mut bytes = [0; 48];
+        transcript.challenge_bytes(b"point", &mut bytes);
+        // Compressed encoding of a random x-coordinate and sign
+        bytes[0] = (bytes[0] & 0x3f) | 0x80;
+        if let Some(p) = Option::<G1Affine>::from(G1Affine::from_compressed_unchecked(&bytes)) {
+            let g = G1Projective::from(p).clear_cofactor();
+            if !bool::from(g.is_identity()) {
+                break g;
+            }
+        }
+    }).collect()
+}
+
+fn commit(gens: &Vec<G1Projective>, v: &Vec<Scalar>) -> G1Projective {
+    v.iter().zip(gens).filter(|(s, _)| !bool::from(s.is_zero())).fold(G1Projective::identity(), |acc, (s, g)| acc + g * s)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Point(G1Projective);
+
+impl Serialize for Point {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_bytes(&self.0.to_affine().to_compressed())
+    }
+}
+
+impl<'de> Deserialize<'de> for Point {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Point, D::Error> {
+        let bytes: serde_bytes::ByteBuf = Deserialize::deserialize(d)?;
+        let bytes: [u8; 48] = bytes.as_ref().try_into().map_err(|_| serde::de::Error::custom("G1 points are 48 bytes"))?;
+        Option::<G1Affine>::from(G1Affine::from_compressed(&bytes))
+            .map(|p| Point(p.into()))
+            .ok_or_else(|| serde::de::Error::custom("Invalid G1 point"))
+    }
+}
+
+fn to_bytes(v: &Vec<Scalar>) -> Vec<[u8; 32]> {
+    v.iter().map(|s| s.to_repr()).collect()
+}
+
+fn from_bytes(v: &Vec<[u8; 32]>) -> Option<Vec<Scalar>> {
+    v.iter().map(|b| Option::from(Scalar::from_repr(*b))).collect()
+}
+
+// A relaxed R1CS instance: Az * Bz = u * Cz + E for z = (u, x, W), with W and E committed
+struct RelaxedInstance {
+    com_w: G1Projective,
+    com_e: G1Projective,
+    u: Scalar,
+    x: Vec<Scalar>,
+}
+
+impl RelaxedInstance {
+    fn fresh(com_w: G1Projective, x: Vec<Scalar>) -> RelaxedInstance {
+        RelaxedInstance { com_w, com_e: G1Projective::identity(), u: Scalar::one(), x }
+    }
+
+    // Fiat-Shamir challenge of folding a fresh instance with cross term T into self
+    fn challenge(&self, transcript: &mut Transcript, com_w: &G1Projective, x: &Vec<Scalar>, com_t: &G1Projective) -> Scalar {
+        transcript.append_message(b"running com_w", &self.com_w.to_affine().to_compressed());
+        transcript.append_message(b"running com_e", &self.com_e.to_affine().to_compressed());
+        transcript.append_message(b"running u", &self.u.to_repr());
+        for s in &self.x {
+            transcript.append_message(b"running x", &s.to_repr());
+        }
+        transcript.append_message(b"com_w", &com_w.to_affine().to_compressed());
+        for s in x {
+            transcript.append_message(b"x", &s.to_repr());
+        }
+        transcript.append_message(b"com_t", &com_t.to_affine().to_compressed());
+        let mut bytes = [0; 64];
+        transcript.challenge_bytes(b"r", &mut bytes);
+        Scalar::from_bytes_wide(&bytes)
+    }
+
+    fn fold(self, com_w: &G1Projective, x: &Vec<Scalar>, com_t: &G1Projective, r: &Scalar) -> RelaxedInstance {
+        RelaxedInstance {
+            com_w: self.com_w + com_w * r,
+            com_e: self.com_e + com_t * r,
+            u: self.u + r,
+            x: self.x.iter().zip(x).map(|(a, b)| *a + *r * b).collect(),
+        }
+    }
+}
+
+fn new_transcript(ctk: &CompileTimeKnowledge) -> Transcript {
+    let mut transcript = Transcript::new(b"circ_blocks folding");
+    transcript.append_message(b"ctk", &bincode::serialize(ctk).unwrap());
+    transcript
+}
+
+/// Folding of block executions over BLS12-381
+pub struct BlockFolding;
+
+/// The proving and verifying key of [BlockFolding]
+#[derive(Serialize, Deserialize)]
+pub struct BlockFoldingKey {
+    ctk: CompileTimeKnowledge,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FoldingStep {
+    com_w: Point,
+    x: Vec<[u8; 32]>,
+    // Commitment to the cross term, except for the first step
+    com_t: Option<Point>,
+}
+
+/// A proof of [BlockFolding]: every folded step, and the witness of the folded instance
+#[derive(Serialize, Deserialize)]
+pub struct BlockFoldingProof {
+    steps: Vec<FoldingStep>,
+    // Witness of the folded instance
+    w: Vec<[u8; 32]>,
+    e: Vec<[u8; 32]>,
+}
+
+// The transitions between steps and the memory accesses, checked on the public inputs of the steps
+fn check_steps(ctk: &CompileTimeKnowledge, disp: &Dispatcher, io: &BlockPublicIo, xs: &Vec<Vec<Scalar>>) -> bool {
+    let n = ctk.num_inputs_unpadded;
+    let io_width = 2 * n;
+    let int = |i: usize| Scalar::from(i as u64);
+    let first = &xs[0];
+    let last = &xs[xs.len() - 1];
+    let input_regs = live_input_regs(&ctk.input_liveness, &ctk.input_regs);
+    if first[2] != int(ctk.input_block_num)
+        || input_regs.iter().any(|(i, reg)| io.input.get(*i).map(|x| to_scalar(x)) != Some(first[reg + 1]))
+        || last[n + 1] != int(ctk.output_block_num)
+        || last[n + ctk.output_offset] != to_scalar(&io.output)
+    {
+        return false;
+    }
+    // Input reg j is at z[j + 1], output reg j is at z[n + j]
+    if (1..xs.len()).any(|i| (1..n - 1).any(|j| xs[i][j + 1] != xs[i - 1][n + j])) {
+        return false;
+    }
+
+    // Physical memory: (addr, data), entry i of the input stack is at address i
+    let mut phy_mems: Vec<(Integer, Scalar)> = io.input_stack.iter().enumerate().map(|(i, x)| (Integer::from(i), to_scalar(x))).collect();
+    for x in xs {
+        for k in 0..disp.max_phy_ops {
+            let base = io_width + PHY_SLOT_WIDTH * k;
+            if x[base] == Scalar::one() {
+                phy_mems.push((to_integer(&x[base + 1]), x[base + 2]));
+            }
+        }
+    }
+    phy_mems.sort_by(|a, b| a.0.cmp(&b.0));
+    for k in 1..phy_mems.len() {
+        let (last, next) = (&phy_mems[k - 1], &phy_mems[k]);
+        let same = next.0 == last.0;
+        if (same && next.1 != last.1) || (!same && next.0 != last.0.clone() + 1) {
+            return false;
+        }
+    }
+
+    // Virtual memory: (addr, data, ls, ts), entry i of the input arrays is stored at address i, at timestamp 0
+    let mut vir_mems: Vec<(Integer, Scalar, Scalar, Integer)> = io.input_mem.iter().enumerate()
+        .map(|(i, x)| (Integer::from(i), to_scalar(x), Scalar::zero(), Integer::from(0)))
+        .collect();
+    for x in xs {
+        for k in 0..disp.max_vir_ops {
+            let base = io_width + PHY_SLOT_WIDTH * disp.max_phy_ops + VIR_SLOT_WIDTH * k;
+            if x[base] == Scalar::one() {
+                vir_mems.push((to_integer(&x[base + 1]), x[base + 2], x[base + 3], to_integer(&x[base + 4])));
+            }
+        }
+    }
+    vir_mems.sort_by(|a, b| (&a.0, &a.3).cmp(&(&b.0, &b.3)));
+    // A LOAD (= 1) reads the data of the previous access to the same address
+    (1..vir_mems.len()).all(|k| {
+        let (last, next) = (&vir_mems[k - 1], &vir_mems[k]);
+        next.0 != last.0 || next.2 != Scalar::one() || next.1 == last.1
+    })
+}
+
+impl BlockProofSystem for BlockFolding {
+    type Assignment = RawAssignment;
+    type ProvingKey = BlockFoldingKey;
+    type VerifyingKey = BlockFoldingKey;
+    type Proof = BlockFoldingProof;
+    // The verifier reads the I/O and memory accesses of every step, and opens the folded witness
+    const ZERO_KNOWLEDGE: bool = false;
+
+    fn setup(ctk: &CompileTimeKnowledge, _shape: &ExecShape) -> Result<(BlockFoldingKey, BlockFoldingKey), String> {
+        check_field()?;
+        Ok((BlockFoldingKey { ctk: ctk.clone() }, BlockFoldingKey { ctk: ctk.clone() }))
+    }
+
+    fn prove(pk: BlockFoldingKey, rtk: RunTimeKnowledge<RawAssignment>) -> Result<BlockFoldingProof, String> {
+        check_field()?;
+        let ctk = &pk.ctk;
+        let disp = Dispatcher::new(ctk);
+        println!("Deriving {} generators...", disp.num_gens());
+        let gens = derive_gens(disp.num_gens());
+
+        println!("Folding {} steps...", rtk.exec_inputs.len());
+        // exec_inputs are in execution order, with input %BN in z[2]
+        let slots = live_blocks_by_slot(&rtk.block_num_proofs);
+        let mut next_exec = vec![0; ctk.block_num_instances];
+        let mut transcript = new_transcript(ctk);
+        let mut steps = Vec::new();
+        let mut running: Option<(RelaxedInstance, Vec<Scalar>, Vec<Scalar>)> = None;
+        for io in &rtk.exec_inputs {
+            let b = bytes_to_integer(&io.assignment[2]).to_usize().unwrap();
+            let slot = slots.iter().position(|s| *s == b).unwrap();
+            let z: Vec<Scalar> = rtk.block_vars_matrix[slot][next_exec[b]].assignment.iter().map(to_scalar).collect();
+            next_exec[b] += 1;
+            let (x, w) = disp.step(ctk, b, &z);
+            let com_w = commit(&gens, &w);
+            running = Some(match running {
+                None => {
+                    steps.push(FoldingStep { com_w: Point(com_w), x: to_bytes(&x), com_t: None });
+                    (RelaxedInstance::fresh(com_w, x), w, vec![Scalar::zero(); disp.cons.len()])
+                }
+                Some((inst, run_w, run_e)) => {
+                    // T = Az1 * Bz2 + Az2 * Bz1 - u1 * Cz2 - u2 * Cz1, with u2 = 1
+                    let (az1, bz1, cz1) = disp.eval(&inst.u, &inst.x, &run_w);
+                    let (az2, bz2, cz2) = disp.eval(&Scalar::one(), &x, &w);
+                    let t: Vec<Scalar> = (0..disp.cons.len()).map(|i| az1[i] * bz2[i] + az2[i] * bz1[i] - inst.u * cz2[i] - cz1[i]).collect();
+                    let com_t = commit(&gens, &t);
+                    let r = inst.challenge(&mut transcript, &com_w, &x, &com_t);
+                    let run_w = run_w.iter().zip(&w).map(|(a, b)| *a + r * b).collect();
+                    let run_e = run_e.iter().zip(&t).map(|(a, b)| *a + r * b).collect();
+                    let inst = inst.fold(&com_w, &x, &com_t, &r);
+                    steps.push(FoldingStep { com_w: Point(com_w), x: to_bytes(&x), com_t: Some(Point(com_t)) });
+                    (inst, run_w, run_e)
+                }
+            });
+        }
+        let (_, w, e) = running.ok_or("The RTK has no block executions to fold")?;
+        Ok(BlockFoldingProof { steps, w: to_bytes(&w), e: to_bytes(&e) })
+    }
+
+    // Every execution is a step, so the key proves executions of any shape
+    fn shape(_pk: &BlockFoldingKey) -> Option<&ExecShape> {
+        None
+    }
+
+    fn public_io(pk: &BlockFoldingKey, rtk: &RunTimeKnowledge<RawAssignment>) -> BlockPublicIo {
+        BlockPublicIo::of(rtk, &pk.ctk.input_liveness)
+    }
+
+    fn verify(vk: &BlockFoldingKey, io: &BlockPublicIo, pf: &BlockFoldingProof) -> bool {
+        let ctk = &vk.ctk;
+        let disp = Dispatcher::new(ctk);
+        let xs: Option<Vec<Vec<Scalar>>> = pf.steps.iter().map(|s| from_bytes(&s.x)).collect();
+        let (xs, w, e) = match (xs, from_bytes(&pf.w), from_bytes(&pf.e)) {
+            (Some(xs), Some(w), Some(e)) => (xs, w, e),
+            _ => return false,
+        };
+        // The output is the output of the last step
+        if xs.is_empty()
+            || io.output_exec_num + 1 != xs.len()
+            || xs.iter().any(|x| x.len() != disp.num_x)
+            || w.len() != disp.num_w
+            || e.len() != disp.cons.len()
+            || pf.steps[0].com_t.is_some()
+            || pf.steps[1..].iter().any(|s| s.com_t.is_none())
+            || ReservedMemory::of(ctk).check(io).is_none()
+        {
+            return false;
+        }
+        println!("Verifying {} steps...", xs.len());
+        if !check_steps(ctk, &disp, io, &xs) {
+            return false;
+        }
+
+        let mut transcript = new_transcript(ctk);
+        let mut inst = RelaxedInstance::fresh(pf.steps[0].com_w.0, xs[0].clone());
+        for (s, x) in pf.steps.iter().zip(&xs).skip(1) {
+            let com_t = s.com_t.unwrap().0;
+            let r = inst.challenge(&mut transcript, &s.com_w.0, x, &com_t);
+            inst = inst.fold(&s.com_w.0, x, &com_t, &r);
+        }
+
+        let gens = derive_gens(disp.num_gens());
+        if commit(&gens, &w) != inst.com_w || commit(&gens, &e) != inst.com_e {
+            return false;
+        }
+        let (az, bz, cz) = disp.eval(&inst.u, &inst.x, &w);
+        (0..disp.cons.len()).all(|i| az[i] * bz[i] == inst.u * cz[i] + e[i])
+    }
+}
@@ -0,0 +1,516 @@
+//! Groth16 over blocks
+//!
+//! The whole execution is unrolled into a single circuit over BLS12-381, so keys are bound to an execution shape:
+//! * every execution of every block, grouped by block, satisfies the constraints of the block,
+//!   with input %BN the id of the block if it is valid, and all vars zero otherwise;
+//! * a Waksman network routes the inputs and outputs of the executions into execution order, where valid executions
+//!   come first, every valid execution takes the outputs of the previous one as inputs, and the last one is the output;
+//! * Waksman networks route the initial memory and the memory accesses of the executions into (address, timestamp) order,
+//!   where physical memory is read-only, and a LOAD of virtual memory reads the data of the previous access.
+//!   Accesses of invalid executions are LOADs of address 0 at timestamp 0, with the data of address 0.
+//!   With lookup tables, the timestamps are checked in limbs, with reads of the range table routed with physical memory.
+//!
+//! Invalid executions are the padding of [pad_run_time_knowledge].
+//! The switches of the networks are witnesses, so the permutation checks need no challenge.
+//! The public inputs of the circuit are the live inputs of main, the initial memory and the output.
+
+use bellman::groth16::{self, Parameters, Proof, VerifyingKey};
+use bellman::{Circuit, ConstraintSystem, LinearCombination, SynthesisError, Variable};
+use bls12_381::{Bls12, Scalar};
+use circ_waksman::{n_switches, symbolic_apply, Config};
+use ff::{Field, PrimeField};
+use rug::integer::Order;
+use rug::Integer;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::cfg::cfg;
+use crate::target::r1cs::bellman::{get_modulus, int_to_ff, serde_pf, serde_pk, serde_vk};
+use crate::target::r1cs::LookupTable;
+
+use super::knowledge::*;
+use super::proof::*;
+use super::{VARS_PER_ST_ACCESS, VARS_PER_VM_ACCESS};
+
+/// A little-endian field element of BLS12-381
+pub fn to_scalar(bytes: &[u8; 32]) -> Scalar {
+    int_to_ff(bytes_to_integer(bytes))
+}
+
+/// The integer of a field element of BLS12-381
+pub fn to_integer(s: &Scalar) -> Integer {
+    Integer::from_digits(s.to_repr().as_ref(), Order::Lsf)
+}
+
+// A linear combination and its value, which is only known when proving
+#[derive(Clone)]
+struct Wire {
+    lc: LinearCombination<Scalar>,
+    val: Option<Scalar>,
+}
+
+impl Wire {
+    fn var(v: Variable, val: Option<Scalar>) -> Wire {
+        Wire { lc: LinearCombination::zero() + v, val }
+    }
+
+    fn constant<CS: ConstraintSystem<Scalar>>(c: Scalar) -> Wire {
+        Wire { lc: LinearCombination::zero() + (c, CS::one()), val: Some(c) }
+    }
+
+    fn add(&self, other: &Wire) -> Wire {
+        Wire { lc: self.lc.clone() + &other.lc, val: self.val.zip(other.val).map(|(a, b)| a + b) }
+    }
+
+    fn sub(&self, other: &Wire) -> Wire {
+        Wire { lc: self.lc.clone() - &other.lc, val: self.val.zip(other.val).map(|(a, b)| a - b) }
+    }
+
+    fn scale(&self, c: Scalar) -> Wire {
+        Wire { lc: LinearCombination::zero() + (c, &self.lc), val: self.val.map(|v| v * c) }
+    }
+}
+
+fn alloc<CS: ConstraintSystem<Scalar>>(cs: &mut CS, name: impl FnOnce() -> String, val: Option<Scalar>) -> Result<Wire, SynthesisError> {
+    let v = cs.alloc(name, || val.ok_or(SynthesisError::AssignmentMissing))?;
+    Ok(Wire::var(v, val))
+}
+
+fn alloc_input<CS: ConstraintSystem<Scalar>>(cs: &mut CS, name: impl FnOnce() -> String, val: Option<Scalar>) -> Result<Wire, SynthesisError> {
+    let v = cs.alloc_input(name, || val.ok_or(SynthesisError::AssignmentMissing))?;
+    Ok(Wire::var(v, val))
+}
+
+// a * b = c
+fn enforce<CS: ConstraintSystem<Scalar>>(cs: &mut CS, name: impl FnOnce() -> String, a: &Wire, b: &Wire, c: &Wire) {
+    cs.enforce(name, |lc| lc + &a.lc, |lc| lc + &b.lc, |lc| lc + &c.lc);
+}
+
+fn enforce_eq<CS: ConstraintSystem<Scalar>>(cs: &mut CS, name: impl FnOnce() -> String, a: &Wire, b: &Wire) {
+    cs.enforce(name, |lc| lc + &a.lc - &b.lc, |lc| lc + CS::one(), |lc| lc);
+}
+
+fn enforce_bool<CS: ConstraintSystem<Scalar>>(cs: &mut CS, name: impl FnOnce() -> String, a: &Wire) {
+    cs.enforce(name, |lc| lc + &a.lc, |lc| lc + CS::one() - &a.lc, |lc| lc);
+}
+
+fn mul<CS: ConstraintSystem<Scalar>>(cs: &mut CS, name: &str, a: &Wire, b: &Wire) -> Result<Wire, SynthesisError> {
+    let c = alloc(cs, || name.to_string(), a.val.zip(b.val).map(|(a, b)| a * b))?;
+    enforce(cs, || format!("{}_mul", name), a, b, &c);
+    Ok(c)
+}
+
+// Bits of a, which must be smaller than 2^width
+fn enforce_range<CS: ConstraintSystem<Scalar>>(cs: &mut CS, name: &str, a: &Wire, width: usize) -> Result<(), SynthesisError> {
+    let val = a.val.map(|v| to_integer(&v));
+    let mut sum = LinearCombination::zero();
+    let mut coeff = Scalar::one();
+    for i in 0..width {
+        let bit = val.as_ref().map(|v| if v.get_bit(i as u32) { Scalar::one() } else { Scalar::zero() });
+        let b = alloc(cs, || format!("{}_b{}", name, i), bit)?;
+        enforce_bool(cs, || format!("{}_b{}_bool", name, i), &b);
+        sum = sum + (coeff, &b.lc);
+        coeff = coeff.double();
+    }
+    cs.enforce(|| format!("{}_bits", name), |lc| lc + &sum - &a.lc, |lc| lc + CS::one(), |lc| lc);
+    Ok(())
+}
+
+// Reads of the w-bit range table, as (addr, data) physical memory accesses, that check that a is smaller than 2^width
+// a is split into w-bit limbs, and a narrower last limb is also looked up once shifted to the top of the table
+fn range_lookups<CS: ConstraintSystem<Scalar>>(cs: &mut CS, name: &str, a: &Wire, width: usize, w: usize) -> Result<Vec<Vec<Wire>>, SynthesisError> {
+    let table = LookupTable::Range(w);
+    let base = Wire::constant::<CS>(int_to_ff(table.base(w)));
+    let tag = Wire::constant::<CS>(Scalar::from(table.tag(w) as u64));
+    let val = a.val.map(|v| to_integer(&v));
+    let mut sum = LinearCombination::zero();
+    let mut reads = Vec::new();
+    for (i, lo) in (0..width).step_by(w).enumerate() {
+        let limb_w = std::cmp::min(w, width - lo);
+        let limb_val = val.as_ref().map(|v| int_to_ff(Integer::from(v >> lo as u32).keep_bits(limb_w as u32)));
+        let limb = alloc(cs, || format!("{}_l{}", name, i), limb_val)?;
+        sum = sum + (int_to_ff(Integer::from(1) << lo as u32), &limb.lc);
+        reads.push(vec![limb.add(&base), limb.add(&tag)]);
+        if limb_w < w {
+            let shifted = limb.scale(int_to_ff(Integer::from(1) << (w - limb_w) as u32));
+            reads.push(vec![shifted.add(&base), shifted.add(&tag)]);
+        }
+    }
+    cs.enforce(|| format!("{}_limbs", name), |lc| lc + &sum - &a.lc, |lc| lc + CS::one(), |lc| lc);
+    Ok(reads)
+}
+
+// Destination of every entry when sorting keys, ties stay in order
+fn sorting_dest<K: Ord>(keys: &Vec<K>) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..keys.len()).collect();
+    order.sort_by(|a, b| keys[*a].cmp(&keys[*b]));
+    let mut dest = vec![0; keys.len()];
+    for (j, i) in order.into_iter().enumerate() {
+        dest[i] = j;
+    }
+    dest
+}
+
+// Route tuples through a Waksman network, entry i ends up at dest[i]
+fn permute<CS: ConstraintSystem<Scalar>>(cs: &mut CS, name: &str, tuples: Vec<Vec<Wire>>, dest: Option<Vec<usize>>) -> Result<Vec<Vec<Wire>>, SynthesisError> {
+    let n = tuples.len();
+    if n <= 1 {
+        return Ok(tuples);
+    }
+    let switch_vals: Vec<Option<bool>> = match dest {
+        Some(dest) => Config::for_sorting(dest).switches().into_iter().map(Some).collect(),
+        None => vec![None; n_switches(n)],
+    };
+    let mut switches = VecDeque::new();
+    for (i, val) in switch_vals.into_iter().enumerate() {
+        let s = alloc(cs, || format!("{}_sw{}", name, i), val.map(|b| if b { Scalar::one() } else { Scalar::zero() }))?;
+        enforce_bool(cs, || format!("{}_sw{}_bool", name, i), &s);
+        switches.push_back(s);
+    }
+    // symbolic_apply cannot fail, so the first error is kept aside
+    let mut res = Ok(());
+    let mut num_crossings = 0;
+    let sorted = symbolic_apply(tuples, &mut switches, &mut |top: &Vec<Wire>, bot: &Vec<Wire>, s: Wire| {
+        // d = s * (bot - top), top' = top + d, bot' = bot - d
+        let mut new_top = Vec::new();
+        let mut new_bot = Vec::new();
+        for (t, b) in top.iter().zip(bot) {
+            let d = mul(cs, &format!("{}_x{}", name, num_crossings), &s, &b.sub(t));
+            num_crossings += 1;
+            let d = match d {
+                Ok(d) => d,
+                Err(e) => {
+                    if res.is_ok() {
+                        res = Err(e);
+                    }
+                    return (top.clone(), bot.clone());
+                }
+            };
+            new_top.push(t.add(&d));
+            new_bot.push(b.sub(&d));
+        }
+        (new_top, new_bot)
+    });
+    assert!(switches.is_empty());
+    res.map(|_| sorted)
+}
+
+// The circuit of an execution of some shape
+struct BlockTraceCircuit<'a> {
+    ctk: &'a CompileTimeKnowledge,
+    shape: &'a ExecShape,
+    // Only known when proving
+    rtk: Option<&'a RunTimeKnowledge<RawAssignment>>,
+}
+
+impl<'a> Circuit<Scalar> for BlockTraceCircuit<'a> {
+    fn synthesize<CS: ConstraintSystem<Scalar>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let ctk = self.ctk;
+        let shape = self.shape;
+        let rtk = self.rtk;
+        let n = ctk.num_inputs_unpadded;
+        let io_width = 2 * n;
+        let zero = Wire::constant::<CS>(Scalar::zero());
+        let one = Wire::constant::<CS>(Scalar::one());
+
+        // --
+        // Public inputs
+        // --
+        let input_regs = live_input_regs(&ctk.input_liveness, &ctk.input_regs);
+        let mut input = Vec::new();
+        for (i, _) in &input_regs {
+            input.push(alloc_input(cs, || format!("input{}", i), rtk.map(|r| to_scalar(&r.input[*i])))?);
+        }
+        let mut input_stack = Vec::new();
+        for i in 0..shape.total_num_init_phy_mem_accesses {
+            input_stack.push(alloc_input(cs, || format!("input_stack{}", i), rtk.map(|r| to_scalar(&r.input_stack[i])))?);
+        }
+        let mut input_mem = Vec::new();
+        for i in 0..shape.total_num_init_vir_mem_accesses {
+            input_mem.push(alloc_input(cs, || format!("input_mem{}", i), rtk.map(|r| to_scalar(&r.input_mem[i])))?);
+        }
+        let output = alloc_input(cs, || "output".to_string(), rtk.map(|r| to_scalar(&r.output)))?;
+
+        // --
+        // Block executions, grouped by block
+        // --
+        let slots = live_blocks_by_slot(&shape.block_num_proofs);
+        let mut exec_vars: Vec<Vec<Wire>> = Vec::new();
+        // (block, execution of the block) of every entry of exec_vars
+        let mut exec_ids: Vec<(usize, usize)> = Vec::new();
+        for b in 0..ctk.block_num_instances {
+            if shape.block_num_proofs[b] == 0 {
+                continue;
+            }
+            let slot = slots.iter().position(|s| *s == b).unwrap();
+            let to_row = |row: &Vec<(usize, [u8; 32])>| -> Vec<(usize, Scalar)> {
+                row.iter().map(|(var, coeff)| (*var, to_scalar(coeff))).collect()
+            };
+            let args: Vec<_> = ctk.args[b].iter().map(|(a, b_, c)| (to_row(a), to_row(b_), to_row(c))).collect();
+            for e in 0..shape.block_num_proofs[b] {
+                let vals = rtk.map(|r| &r.block_vars_matrix[slot][e].assignment);
+                let mut z = Vec::new();
+                for v in 0..ctk.num_vars_per_block[b] {
+                    z.push(alloc(cs, || format!("b{}_e{}_z{}", b, e, v), vals.map(|vals| to_scalar(&vals[v])))?);
+                }
+                enforce_bool(cs, || format!("b{}_e{}_valid", b, e), &z[0]);
+                enforce(cs, || format!("b{}_e{}_bn", b, e), &z[0], &Wire::constant::<CS>(Scalar::from(b as u64)), &z[2]);
+                let eval_row = |row: &Vec<(usize, Scalar)>| -> LinearCombination<Scalar> {
+                    row.iter().fold(LinearCombination::zero(), |lc, (var, coeff)| lc + (*coeff, &z[*var].lc))
+                };
+                for (i, (a, b_, c)) in args.iter().enumerate() {
+                    let (a, b_, c) = (eval_row(a), eval_row(b_), eval_row(c));
+                    cs.enforce(|| format!("b{}_e{}_con{}", b, e, i), |lc| lc + &a, |lc| lc + &b_, |lc| lc + &c);
+                }
+                exec_vars.push(z);
+                exec_ids.push((b, e));
+            }
+        }
+
+        // --
+        // Transitions, in execution order
+        // --
+        // exec_inputs are in execution order, with input %BN in z[2], followed by invalid executions
+        let exec_dest = rtk.map(|r| {
+            let mut next_exec = vec![0; ctk.block_num_instances];
+            let mut exec_order = BTreeMap::new();
+            for (i, io) in r.exec_inputs.iter().enumerate() {
+                if to_scalar(&io.assignment[0]) == Scalar::zero() {
+                    continue;
+                }
+                let b = bytes_to_integer(&io.assignment[2]).to_usize().unwrap();
+                exec_order.insert((b, next_exec[b]), i);
+                next_exec[b] += 1;
+            }
+            // Invalid executions of every block follow its valid ones in block_vars_matrix
+            let mut next_invalid = exec_order.len();
+            exec_ids.iter().map(|id| exec_order.get(id).cloned().unwrap_or_else(|| {
+                next_invalid += 1;
+                next_invalid - 1
+            })).collect::<Vec<usize>>()
+        });
+        let ios = exec_vars.iter().map(|z| z[..io_width].to_vec()).collect();
+        let ios = permute(cs, "exec", ios, exec_dest)?;
+        let first = &ios[0];
+        enforce_eq(cs, || "input_valid".to_string(), &first[0], &one);
+        enforce_eq(cs, || "input_bn".to_string(), &first[2], &Wire::constant::<CS>(Scalar::from(ctk.input_block_num as u64)));
+        for ((i, reg), x) in input_regs.iter().zip(&input) {
+            enforce_eq(cs, || format!("input{}_reg", i), &first[reg + 1], x);
+        }
+        for i in 1..ios.len() {
+            // Only a valid execution follows a valid one
+            enforce(cs, || format!("exec{}_valid", i), &ios[i][0], &one.sub(&ios[i - 1][0]), &zero);
+            for j in 1..n - 1 {
+                enforce(cs, || format!("exec{}_reg{}", i, j), &ios[i][0], &ios[i][j + 1].sub(&ios[i - 1][n + j]), &zero);
+            }
+        }
+        // The output is that of the last valid execution
+        let output_bn = Wire::constant::<CS>(Scalar::from(ctk.output_block_num as u64));
+        for i in 0..ios.len() {
+            let is_last = if i + 1 < ios.len() { ios[i][0].sub(&ios[i + 1][0]) } else { ios[i][0].clone() };
+            enforce(cs, || format!("exec{}_output_bn", i), &is_last, &ios[i][n + 1].sub(&output_bn), &zero);
+            enforce(cs, || format!("exec{}_output_reg", i), &is_last, &ios[i][n + ctk.output_offset].sub(&output), &zero);
+        }
+
+        // --
+        // Physical memory: (addr, data)
+        // --
+        // Entry i of the input stack is at address i
+        let mut phy_mems: Vec<Vec<Wire>> = input_stack.iter().enumerate()
+            .map(|(i, x)| vec![Wire::constant::<CS>(Scalar::from(i as u64)), x.clone()])
+            .collect();
+        // Data at address 0, read by the accesses of invalid executions
+        let pad_phy_data = alloc(cs, || "pad_phy_data".to_string(), rtk.map(|r| match r.input_stack.first() {
+            Some(x) => to_scalar(x),
+            None => exec_ids.iter().zip(&exec_vars)
+                .flat_map(|((b, _), z)| (0..ctk.block_num_phy_ops[*b]).map(move |k| (z, io_width + VARS_PER_ST_ACCESS * k)))
+                .find(|(z, base)| z[0].val == Some(Scalar::one()) && z[*base].val == Some(Scalar::zero()))
+                .map_or(Scalar::zero(), |(z, base)| z[base + 1].val.unwrap()),
+        }))?;
+        for ((b, e), z) in exec_ids.iter().zip(&exec_vars) {
+            for k in 0..ctk.block_num_phy_ops[*b] {
+                let base = io_width + VARS_PER_ST_ACCESS * k;
+                let name = format!("b{}_e{}_phy{}", b, e, k);
+                let addr = mul(cs, &format!("{}_addr", name), &z[0], &z[base])?;
+                let data = mul(cs, &format!("{}_data", name), &z[0], &z[base + 1].sub(&pad_phy_data))?.add(&pad_phy_data);
+                phy_mems.push(vec![addr, data]);
+            }
+        }
+        // Physical memory is checked after virtual memory, which adds the reads of the range table
+
+        // --
+        // Virtual memory: (addr, data, ls, ts)
+        // --
+        // Entry i of the input arrays is stored at address i, at timestamp 0
+        let mut vir_mems: Vec<Vec<Wire>> = input_mem.iter().enumerate()
+            .map(|(i, x)| vec![Wire::constant::<CS>(Scalar::from(i as u64)), x.clone(), zero.clone(), zero.clone()])
+            .collect();
+        // Data at address 0 and timestamp 0, read by the accesses of invalid executions
+        let pad_vir_data = alloc(cs, || "pad_vir_data".to_string(), rtk.map(|r| r.input_mem.first().map_or(Scalar::zero(), to_scalar)))?;
+        // Accesses at the same address and timestamp are sorted as initial memory, invalid, then valid accesses
+        let mut vir_order: Vec<usize> = vec![0; input_mem.len()];
+        for ((b, e), z) in exec_ids.iter().zip(&exec_vars) {
+            let vm_base = io_width + VARS_PER_ST_ACCESS * ctk.block_num_phy_ops[*b];
+            for k in 0..ctk.block_num_vir_ops[*b] {
+                let base = vm_base + VARS_PER_VM_ACCESS * k;
+                let name = format!("b{}_e{}_vir{}", b, e, k);
+                let addr = mul(cs, &format!("{}_addr", name), &z[0], &z[base])?;
+                let data = mul(cs, &format!("{}_data", name), &z[0], &z[base + 1].sub(&pad_vir_data))?.add(&pad_vir_data);
+                // LOAD = 1
+                let ls = mul(cs, &format!("{}_ls", name), &z[0], &z[base + 2].sub(&one))?.add(&one);
+                let ts = mul(cs, &format!("{}_ts", name), &z[0], &z[base + 3])?;
+                vir_mems.push(vec![addr, data, ls, ts]);
+                vir_order.push(if z[0].val == Some(Scalar::one()) { 2 } else { 1 });
+            }
+        }
+        let vir_dest = rtk.map(|_| sorting_dest(&vir_mems.iter().zip(&vir_order)
+            .map(|(m, o)| (to_integer(&m[0].val.unwrap()), to_integer(&m[3].val.unwrap()), *o))
+            .collect()));
+        let vir_mems = permute(cs, "vir", vir_mems, vir_dest)?;
+        for k in 0..vir_mems.len() {
+            // LOAD = 1, STORE = 0
+            enforce_bool(cs, || format!("vir{}_ls", k), &vir_mems[k][2]);
+        }
+        for k in 1..vir_mems.len() {
+            let (last, next) = (&vir_mems[k - 1], &vir_mems[k]);
+            // Addresses are consecutive
+            let diff = next[0].sub(&last[0]);
+            enforce(cs, || format!("vir{}_addr", k), &diff, &diff.sub(&one), &zero);
+            // Accesses to the same address are sorted by timestamp
+            let same = one.sub(&diff);
+            let ts_diff = mul(cs, &format!("vir{}_ts", k), &same, &next[3].sub(&last[3]))?;
+            if ctk.lookup_width > 0 {
+                phy_mems.extend(range_lookups(cs, &format!("vir{}_ts", k), &ts_diff, ctk.max_ts_width, ctk.lookup_width)?);
+            } else {
+                enforce_range(cs, &format!("vir{}_ts", k), &ts_diff, ctk.max_ts_width)?;
+            }
+            // A LOAD reads the data of the previous access to the same address
+            let load = mul(cs, &format!("vir{}_load", k), &same, &next[2])?;
+            enforce(cs, || format!("vir{}_data", k), &load, &next[1].sub(&last[1]), &zero);
+        }
+
+        let phy_dest = rtk.map(|_| sorting_dest(&phy_mems.iter().map(|m| to_integer(&m[0].val.unwrap())).collect()));
+        let phy_mems = permute(cs, "phy", phy_mems, phy_dest)?;
+        for k in 1..phy_mems.len() {
+            let (last, next) = (&phy_mems[k - 1], &phy_mems[k]);
+            // Addresses are consecutive, and accesses to the same address have the same data
+            let diff = next[0].sub(&last[0]);
+            enforce(cs, || format!("phy{}_addr", k), &diff, &diff.sub(&one), &zero);
+            enforce(cs, || format!("phy{}_data", k), &one.sub(&diff), &next[1].sub(&last[1]), &zero);
+        }
+        Ok(())
+    }
+}
+
+/// Backends over BLS12-381 require the program to be compiled over its field
+pub fn check_field() -> Result<(), String> {
+    if cfg().field().modulus() != &get_modulus::<Scalar>() {
+        return Err("Backends over BLS12-381 require the program to be compiled with --field-builtin bls12381".to_string());
+    }
+    Ok(())
+}
+
+/// Groth16 over BLS12-381, with the whole execution in one circuit
+pub struct BlockGroth16;
+
+/// The proving key of [BlockGroth16], bound to an execution shape
+#[derive(Serialize, Deserialize)]
+pub struct BlockGroth16ProvingKey {
+    ctk: CompileTimeKnowledge,
+    shape: ExecShape,
+    #[serde(with = "serde_pk")]
+    params: Parameters<Bls12>,
+}
+
+/// The verifying key of [BlockGroth16]
+#[derive(Serialize, Deserialize)]
+pub struct BlockGroth16VerifyingKey {
+    input_liveness: Vec<bool>,
+    input_regs: Vec<usize>,
+    reserved: ReservedMemory,
+    shape: ExecShape,
+    #[serde(with = "serde_vk")]
+    vk: VerifyingKey<Bls12>,
+}
+
+/// A proof of [BlockGroth16]
+#[derive(Serialize, Deserialize)]
+pub struct BlockGroth16Proof(#[serde(with = "serde_pf")] Proof<Bls12>);
+
+impl BlockProofSystem for BlockGroth16 {
+    type Assignment = RawAssignment;
+    type ProvingKey = BlockGroth16ProvingKey;
+    type VerifyingKey = BlockGroth16VerifyingKey;
+    type Proof = BlockGroth16Proof;
+    // create_random_proof blinds every proof with fresh randomness, and the instance does not depend on the
+    // number of valid executions, see public_io
+    const ZERO_KNOWLEDGE: bool = true;
+
+    fn setup(ctk: &CompileTimeKnowledge, shape: &ExecShape) -> Result<(BlockGroth16ProvingKey, BlockGroth16VerifyingKey), String> {
+        check_field()?;
+        println!("Generating Groth16 parameters...");
+        let circuit = BlockTraceCircuit { ctk, shape, rtk: None };
+        let params = groth16::generate_random_parameters::<Bls12, _, _>(circuit, &mut rand::thread_rng())
+            .map_err(|e| format!("Groth16 setup failed: {}", e))?;
+        let vk = BlockGroth16VerifyingKey {
+            input_liveness: ctk.input_liveness.clone(),
+            input_regs: ctk.input_regs.clone(),
+            reserved: ReservedMemory::of(ctk),
+            shape: shape.clone(),
+            vk: params.vk.clone(),
+        };
+        let pk = BlockGroth16ProvingKey {
+            ctk: ctk.clone(),
+            shape: shape.clone(),
+            params,
+        };
+        Ok((pk, vk))
+    }
+
+    fn prove(pk: BlockGroth16ProvingKey, rtk: RunTimeKnowledge<RawAssignment>) -> Result<BlockGroth16Proof, String> {
+        check_field()?;
+        if ExecShape::of(&rtk) != pk.shape {
+            return Err(format!("The proving key was generated for executions of shape {:?}, but the RTK has shape {:?}", pk.shape, ExecShape::of(&rtk)));
+        }
+        println!("Running the proof...");
+        let circuit = BlockTraceCircuit { ctk: &pk.ctk, shape: &pk.shape, rtk: Some(&rtk) };
+        let pf = groth16::create_random_proof(circuit, &pk.params, &mut rand::thread_rng())
+            .map_err(|e| format!("Groth16 proof failed: {}", e))?;
+        Ok(BlockGroth16Proof(pf))
+    }
+
+    fn shape(pk: &BlockGroth16ProvingKey) -> Option<&ExecShape> {
+        Some(&pk.shape)
+    }
+
+    // The circuit finds the output at the last valid execution, so the instance always names the last execution
+    // of the shape, which does not reveal how many executions are padding
+    fn public_io(pk: &BlockGroth16ProvingKey, rtk: &RunTimeKnowledge<RawAssignment>) -> BlockPublicIo {
+        BlockPublicIo {
+            output_exec_num: pk.shape.consis_num_proofs - 1,
+            ..BlockPublicIo::of(rtk, &pk.ctk.input_liveness)
+        }
+    }
+
+    fn verify(vk: &BlockGroth16VerifyingKey, io: &BlockPublicIo, pf: &BlockGroth16Proof) -> bool {
+        if Some(io.output_exec_num) != vk.shape.consis_num_proofs.checked_sub(1)
+            || io.input_stack.len() != vk.shape.total_num_init_phy_mem_accesses
+            || io.input_mem.len() != vk.shape.total_num_init_vir_mem_accesses
+            || vk.reserved.check(io).is_none()
+        {
+            return false;
+        }
+        let input_regs = live_input_regs(&vk.input_liveness, &vk.input_regs);
+        if input_regs.iter().any(|(i, _)| *i >= io.input.len()) {
+            return false;
+        }
+        println!("Verifying the proof...");
+        let inst: Vec<Scalar> = input_regs.into_iter().map(|(i, _)| to_scalar(&io.input[i]))
+            .chain(io.input_stack.iter().map(to_scalar))
+            .chain(io.input_mem.iter().map(to_scalar))
+            .chain(std::iter::once(to_scalar(&io.output)))
+            .collect();
+        let pvk = groth16::prepare_verifying_key(&vk.vk);
+        groth16::verify_proof(&pvk, &pf.0, &inst).is_ok()
+    }
+}
@@ -0,0 +1,636 @@
+//! Compile-time and run-time knowledge of a block program, and their files
+//!
+//! The format of the versioned files is described in README_zsharp.md, under "CTK and RTK files".
+//! Every file starts with a header line "<KIND> <VERSION> <ENCODING>", and headerless files are the bincode files
+//! read by Spartan.
+
+use crate::cfg::{
+    cfg,
+    clap::{self, ValueEnum},
+};
+use bincode::Options as _;
+use libspartan::Assignment;
+use rug::Integer;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::PathBuf;
+
+use super::proof::ExecShape;
+
+/// Convert an integer into a little-endian byte array
+pub fn integer_to_bytes(mut raw: Integer) -> [u8; 32] {
+    let mut res = [0; 32];
+    let width = Integer::from(256);
+    let field = cfg().field().modulus().clone();
+    // Cast negative number to the other side of the field
+    if raw < 0 {
+        raw += field;
+    }
+    let mut i = 0;
+    while raw != 0 {
+        if i >= 32 {
+            panic!("Failed to convert integer to byte array: integer is too large! Remainder is: {:?}", raw)
+        }
+        res[i] = (raw.clone() % width.clone()).to_u8().unwrap();
+        raw /= width.clone();
+        i += 1;
+    }
+    res
+}
+
+/// Convert a little-endian byte array to integer
+pub fn bytes_to_integer(bytes: &[u8; 32]) -> Integer {
+    let mut i = Integer::from(0);
+    let mut factor = Integer::from(1);
+    for b in bytes {
+        i += Integer::from(*b as usize) * factor.clone();
+        factor *= 256;
+    }
+    i
+}
+
+// --
+// Structures to match Spartan
+// --
+/// The blocks of a program, and the layout of its inputs
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CompileTimeKnowledge {
+    /// Number of blocks
+    pub block_num_instances: usize,
+    /// Number of vars of the widest block, a power of two
+    pub num_vars: usize,
+    /// Number of registers of the inputs of a block, the outputs have as many
+    pub num_inputs_unpadded: usize,
+    /// Number of vars of every block
+    pub num_vars_per_block: Vec<usize>,
+    /// Number of physical memory accesses of every block
+    pub block_num_phy_ops: Vec<usize>,
+    /// Number of virtual memory accesses of every block
+    pub block_num_vir_ops: Vec<usize>,
+    /// Width of the timestamps of virtual memory
+    pub max_ts_width: usize,
+
+    /// (A, B, C) of every constraint of every block, as (var, coeff) pairs
+    pub args: Vec<Vec<(Vec<(usize, [u8; 32])>, Vec<(usize, [u8; 32])>, Vec<(usize, [u8; 32])>)>>,
+
+    /// Whether every input of main is live and public
+    pub input_liveness: Vec<bool>,
+    /// Number of inputs of main
+    pub func_input_width: usize,
+    /// Number of reserved registers in front of the inputs of main
+    pub input_offset: usize,
+    /// The entry block
+    pub input_block_num: usize,
+    /// Index of %RET in the outputs of a block, including the valid bit
+    pub output_offset: usize,
+    /// The block after the last one, which the last execution jumps to
+    pub output_block_num: usize,
+
+    /// Width of the lookup tables at the start of the input stack, see [super::proof::ReservedMemory]
+    pub lookup_width: usize,
+    /// Number of challenges after the lookup tables, see [super::proof::ReservedMemory]
+    pub num_challenges: usize,
+    /// Input register of the entry block that holds every input of main, 0 if the input is dead
+    ///
+    /// Private inputs are not live in input_liveness, but still take a register
+    pub input_regs: Vec<usize>,
+}
+
+/// Field elements of a block execution or a memory access in an RTK
+///
+/// libspartan::Assignment only holds elements of Spartan's field, so backends over other fields use [RawAssignment]
+pub trait RtkAssignment: Serialize + DeserializeOwned + Clone + Send + Sync {
+    /// Parse little-endian field elements
+    fn from_bytes(vals: &[[u8; 32]]) -> Result<Self, String>;
+    /// Little-endian field elements
+    fn to_bytes(&self) -> Vec<[u8; 32]>;
+}
+
+impl RtkAssignment for Assignment {
+    fn from_bytes(vals: &[[u8; 32]]) -> Result<Assignment, String> {
+        Assignment::new(vals).map_err(|e| format!("{:?}", e))
+    }
+
+    fn to_bytes(&self) -> Vec<[u8; 32]> {
+        self.assignment.iter().map(|s| s.to_bytes()).collect()
+    }
+}
+
+/// Little-endian field elements, in any field that fits in 32 bytes
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RawAssignment {
+    /// The field elements
+    pub assignment: Vec<[u8; 32]>,
+}
+
+impl RtkAssignment for RawAssignment {
+    fn from_bytes(vals: &[[u8; 32]]) -> Result<RawAssignment, String> {
+        Ok(RawAssignment { assignment: vals.to_vec() })
+    }
+
+    fn to_bytes(&self) -> Vec<[u8; 32]> {
+        self.assignment.clone()
+    }
+}
+
+/// The witnesses of an execution of a program, and its inputs and output
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "A: RtkAssignment")]
+pub struct RunTimeKnowledge<A: RtkAssignment = Assignment> {
+    /// Maximum of block_num_proofs
+    pub block_max_num_proofs: usize,
+    /// Number of executions of every block
+    pub block_num_proofs: Vec<usize>,
+    /// Number of block executions
+    pub consis_num_proofs: usize,
+    /// Size of the input stack
+    pub total_num_init_phy_mem_accesses: usize,
+    /// Size of the input arrays
+    pub total_num_init_vir_mem_accesses: usize,
+    /// Number of physical memory accesses, including the input stack
+    pub total_num_phy_mem_accesses: usize,
+    /// Number of virtual memory accesses, including the input arrays
+    pub total_num_vir_mem_accesses: usize,
+
+    /// Vars of every execution of every live block, see [live_blocks_by_slot]
+    pub block_vars_matrix: Vec<Vec<A>>,
+    /// Inputs and outputs of every execution, in execution order
+    pub exec_inputs: Vec<A>,
+    /// Initial physical memory state, in (addr, val, ls = STORE, ts = 0) pair, sorted by appearance in program input
+    /// (the same as address order)
+    pub init_phy_mems_list: Vec<A>,
+    /// Initial virtual memory state, as init_phy_mems_list
+    pub init_vir_mems_list: Vec<A>,
+    /// Physical memory accesses, sorted by address
+    pub addr_phy_mems_list: Vec<A>,
+    /// Virtual memory accesses, sorted by address and timestamp
+    pub addr_vir_mems_list: Vec<A>,
+    /// Bits of the timestamps of addr_vir_mems_list
+    pub addr_ts_bits_list: Vec<A>,
+
+    /// Inputs of main
+    pub input: Vec<[u8; 32]>,
+    /// Initial physical memory
+    pub input_stack: Vec<[u8; 32]>,
+    /// Initial virtual memory
+    pub input_mem: Vec<[u8; 32]>,
+    /// Return value of main
+    pub output: [u8; 32],
+    /// The last valid execution, which holds the output
+    pub output_exec_num: usize,
+}
+
+// --
+// Versioned CTK / RTK files
+// --
+/// Version in the header of every versioned file
+pub const KNOWLEDGE_FORMAT_VERSION: usize = 3;
+
+/// Encoding of a versioned file
+#[derive(PartialEq, Eq, Debug, Clone, ValueEnum)]
+pub enum KnowledgeEncoding {
+    /// One field per line, with field elements in decimal
+    Text,
+    /// bincode
+    Bin,
+}
+
+impl KnowledgeEncoding {
+    /// ENCODING of the header
+    pub fn name(&self) -> &'static str {
+        match self {
+            KnowledgeEncoding::Text => "text",
+            KnowledgeEncoding::Bin => "bin",
+        }
+    }
+
+    /// Extension of versioned files, the headerless files read by Spartan end with _bin.ctk / _bin.rtk
+    pub fn extension(&self, kind: &str) -> String {
+        match self {
+            KnowledgeEncoding::Text => kind.to_string(),
+            KnowledgeEncoding::Bin => format!("{}b", kind),
+        }
+    }
+}
+
+/// Writes fields of the text encoding
+pub fn write_field<T: std::fmt::Display>(f: &mut impl Write, name: &str, vals: impl IntoIterator<Item = T>) -> std::io::Result<()> {
+    write!(f, "{}", name)?;
+    for v in vals {
+        write!(f, " {}", v)?;
+    }
+    writeln!(f)
+}
+
+fn write_assignments<A: RtkAssignment>(f: &mut impl Write, name: &str, list: &Vec<A>) -> std::io::Result<()> {
+    write_field(f, name, [list.len()])?;
+    for a in list {
+        write_field(f, "-", a.to_bytes().iter().map(bytes_to_integer))?;
+    }
+    Ok(())
+}
+
+/// Reads fields of the text encoding, checking their names
+pub struct TextReader<R: BufRead> {
+    lines: std::io::Lines<R>,
+    line_num: usize,
+}
+
+impl<R: BufRead> TextReader<R> {
+    fn new(r: R, line_num: usize) -> TextReader<R> {
+        TextReader { lines: r.lines(), line_num }
+    }
+
+    // Values of the next field, which must be called name
+    fn field(&mut self, name: &str) -> Result<Vec<String>, String> {
+        let line = loop {
+            self.line_num += 1;
+            match self.lines.next() {
+                Some(Ok(line)) if line.trim().is_empty() => continue,
+                Some(Ok(line)) => break line,
+                Some(Err(e)) => return Err(format!("line {}: {}", self.line_num, e)),
+                None => return Err(format!("line {}: expected field {}, found end of file", self.line_num, name)),
+            }
+        };
+        let mut tokens = line.split_whitespace().map(|t| t.to_string());
+        let found = tokens.next().unwrap();
+        if found != name {
+            return Err(format!("line {}: expected field {}, found {}", self.line_num, name, found));
+        }
+        Ok(tokens.collect())
+    }
+
+    fn parse<T: std::str::FromStr>(&self, name: &str, tok: &str) -> Result<T, String> {
+        tok.parse().map_err(|_| format!("line {}: invalid value {} of field {}", self.line_num, tok, name))
+    }
+
+    /// The single value of the next field
+    pub fn usize(&mut self, name: &str) -> Result<usize, String> {
+        let vals = self.field(name)?;
+        if vals.len() != 1 {
+            return Err(format!("line {}: field {} should have 1 value, found {}", self.line_num, name, vals.len()));
+        }
+        self.parse(name, &vals[0])
+    }
+
+    /// The values of the next field
+    pub fn usizes(&mut self, name: &str) -> Result<Vec<usize>, String> {
+        self.field(name)?.iter().map(|t| self.parse(name, t)).collect()
+    }
+
+    /// The field elements of the next field
+    pub fn bytes_list(&mut self, name: &str) -> Result<Vec<[u8; 32]>, String> {
+        self.field(name)?.iter().map(|t| self.parse::<Integer>(name, t).map(integer_to_bytes)).collect()
+    }
+
+    /// The single field element of the next field
+    pub fn bytes(&mut self, name: &str) -> Result<[u8; 32], String> {
+        let vals = self.bytes_list(name)?;
+        if vals.len() != 1 {
+            return Err(format!("line {}: field {} should have 1 value, found {}", self.line_num, name, vals.len()));
+        }
+        Ok(vals[0])
+    }
+
+    // (var, coeff) pairs of a row of a constraint
+    fn row(&mut self, name: &str) -> Result<Vec<(usize, [u8; 32])>, String> {
+        let vals = self.field(name)?;
+        if vals.len() % 2 != 0 {
+            return Err(format!("line {}: field {} should have (var, coeff) pairs", self.line_num, name));
+        }
+        vals.chunks(2).map(|p| Ok((self.parse(name, &p[0])?, integer_to_bytes(self.parse(name, &p[1])?)))).collect()
+    }
+
+    fn assignments<A: RtkAssignment>(&mut self, name: &str) -> Result<Vec<A>, String> {
+        let len = self.usize(name)?;
+        (0..len).map(|_| {
+            let vals = self.bytes_list("-")?;
+            A::from_bytes(&vals).map_err(|e| format!("line {}: invalid assignment: {}", self.line_num, e))
+        }).collect()
+    }
+}
+
+/// Contents of a versioned file
+pub trait Knowledge: Serialize + DeserializeOwned {
+    /// KIND of the header
+    const KIND: &'static str;
+    /// Write the text encoding
+    fn write_text(&self, f: &mut impl Write) -> std::io::Result<()>;
+    /// Read the text encoding
+    fn read_text<R: BufRead>(r: &mut TextReader<R>) -> Result<Self, String>;
+    /// Check that the sizes of the fields agree with each other, every loaded file must pass it
+    fn validate(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Check the length of a field
+pub fn check_len(name: &str, found: usize, expected: usize) -> Result<(), String> {
+    if found != expected {
+        return Err(format!("{} has length {}, expected {}", name, found, expected));
+    }
+    Ok(())
+}
+
+impl Knowledge for CompileTimeKnowledge {
+    const KIND: &'static str = "CIRC_CTK";
+
+    fn write_text(&self, f: &mut impl Write) -> std::io::Result<()> {
+        write_field(f, "block_num_instances", [self.block_num_instances])?;
+        write_field(f, "num_vars", [self.num_vars])?;
+        write_field(f, "num_inputs_unpadded", [self.num_inputs_unpadded])?;
+        write_field(f, "num_vars_per_block", &self.num_vars_per_block)?;
+        write_field(f, "block_num_phy_ops", &self.block_num_phy_ops)?;
+        write_field(f, "block_num_vir_ops", &self.block_num_vir_ops)?;
+        write_field(f, "max_ts_width", [self.max_ts_width])?;
+        write_field(f, "args", [self.args.len()])?;
+        for inst in &self.args {
+            write_field(f, "block", [inst.len()])?;
+            for (a, b, c) in inst {
+                for (name, row) in [("A", a), ("B", b), ("C", c)] {
+                    write_field(f, name, row.iter().map(|(var, val)| format!("{} {}", var, bytes_to_integer(val))))?;
+                }
+            }
+        }
+        write_field(f, "input_liveness", self.input_liveness.iter().map(|b| if *b { 1 } else { 0 }))?;
+        write_field(f, "func_input_width", [self.func_input_width])?;
+        write_field(f, "input_offset", [self.input_offset])?;
+        write_field(f, "input_block_num", [self.input_block_num])?;
+        write_field(f, "output_offset", [self.output_offset])?;
+        write_field(f, "output_block_num", [self.output_block_num])?;
+        write_field(f, "lookup_width", [self.lookup_width])?;
+        write_field(f, "num_challenges", [self.num_challenges])?;
+        write_field(f, "input_regs", &self.input_regs)
+    }
+
+    fn read_text<R: BufRead>(r: &mut TextReader<R>) -> Result<Self, String> {
+        let block_num_instances = r.usize("block_num_instances")?;
+        let num_vars = r.usize("num_vars")?;
+        let num_inputs_unpadded = r.usize("num_inputs_unpadded")?;
+        let num_vars_per_block = r.usizes("num_vars_per_block")?;
+        let block_num_phy_ops = r.usizes("block_num_phy_ops")?;
+        let block_num_vir_ops = r.usizes("block_num_vir_ops")?;
+        let max_ts_width = r.usize("max_ts_width")?;
+        let num_insts = r.usize("args")?;
+        let mut args = Vec::new();
+        for _ in 0..num_insts {
+            let num_cons = r.usize("block")?;
+            let mut inst = Vec::new();
+            for _ in 0..num_cons {
+                inst.push((r.row("A")?, r.row("B")?, r.row("C")?));
+            }
+            args.push(inst);
+        }
+        let input_liveness = r.usizes("input_liveness")?.into_iter().map(|b| b != 0).collect();
+        Ok(CompileTimeKnowledge {
+            block_num_instances,
+            num_vars,
+            num_inputs_unpadded,
+            num_vars_per_block,
+            block_num_phy_ops,
+            block_num_vir_ops,
+            max_ts_width,
+            args,
+            input_liveness,
+            func_input_width: r.usize("func_input_width")?,
+            input_offset: r.usize("input_offset")?,
+            input_block_num: r.usize("input_block_num")?,
+            output_offset: r.usize("output_offset")?,
+            output_block_num: r.usize("output_block_num")?,
+            lookup_width: r.usize("lookup_width")?,
+            num_challenges: r.usize("num_challenges")?,
+            input_regs: r.usizes("input_regs")?,
+        })
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let num_blocks = self.block_num_instances;
+        check_len("CTK num_vars_per_block", self.num_vars_per_block.len(), num_blocks)?;
+        check_len("CTK block_num_phy_ops", self.block_num_phy_ops.len(), num_blocks)?;
+        check_len("CTK block_num_vir_ops", self.block_num_vir_ops.len(), num_blocks)?;
+        check_len("CTK args", self.args.len(), num_blocks)?;
+        check_len("CTK input_regs", self.input_regs.len(), self.input_liveness.len())?;
+        if let Some(i) = (0..self.input_regs.len()).find(|i| self.input_liveness[*i] && self.input_regs[*i] == 0) {
+            return Err(format!("input {} of main is live, but has no register", i));
+        }
+        for b in 0..num_blocks {
+            if self.num_vars_per_block[b] > self.num_vars {
+                return Err(format!("block {} has {} vars, more than num_vars {}", b, self.num_vars_per_block[b], self.num_vars));
+            }
+            for (i, (a, b_, c)) in self.args[b].iter().enumerate() {
+                if let Some((var, _)) = a.iter().chain(b_).chain(c).find(|(var, _)| *var >= self.num_vars_per_block[b]) {
+                    return Err(format!("constraint {} of block {} refers to var {}, but the block only has {} vars", i, b, var, self.num_vars_per_block[b]));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<A: RtkAssignment> Knowledge for RunTimeKnowledge<A> {
+    const KIND: &'static str = "CIRC_RTK";
+
+    fn write_text(&self, f: &mut impl Write) -> std::io::Result<()> {
+        ExecShape::of(self).write_text(f)?;
+        write_field(f, "block_vars_matrix", [self.block_vars_matrix.len()])?;
+        for block in &self.block_vars_matrix {
+            write_assignments(f, "block", block)?;
+        }
+        write_assignments(f, "exec_inputs", &self.exec_inputs)?;
+        write_assignments(f, "init_phy_mems_list", &self.init_phy_mems_list)?;
+        write_assignments(f, "init_vir_mems_list", &self.init_vir_mems_list)?;
+        write_assignments(f, "addr_phy_mems_list", &self.addr_phy_mems_list)?;
+        write_assignments(f, "addr_vir_mems_list", &self.addr_vir_mems_list)?;
+        write_assignments(f, "addr_ts_bits_list", &self.addr_ts_bits_list)?;
+        write_field(f, "input", self.input.iter().map(bytes_to_integer))?;
+        write_field(f, "input_stack", self.input_stack.iter().map(bytes_to_integer))?;
+        write_field(f, "input_mem", self.input_mem.iter().map(bytes_to_integer))?;
+        write_field(f, "output", [bytes_to_integer(&self.output)])?;
+        write_field(f, "output_exec_num", [self.output_exec_num])
+    }
+
+    fn read_text<R: BufRead>(r: &mut TextReader<R>) -> Result<Self, String> {
+        let shape = ExecShape::read_text(r)?;
+        let num_blocks_live = r.usize("block_vars_matrix")?;
+        let block_vars_matrix = (0..num_blocks_live).map(|_| r.assignments("block")).collect::<Result<_, _>>()?;
+        Ok(RunTimeKnowledge {
+            block_max_num_proofs: shape.block_max_num_proofs,
+            block_num_proofs: shape.block_num_proofs,
+            consis_num_proofs: shape.consis_num_proofs,
+            total_num_init_phy_mem_accesses: shape.total_num_init_phy_mem_accesses,
+            total_num_init_vir_mem_accesses: shape.total_num_init_vir_mem_accesses,
+            total_num_phy_mem_accesses: shape.total_num_phy_mem_accesses,
+            total_num_vir_mem_accesses: shape.total_num_vir_mem_accesses,
+            block_vars_matrix,
+            exec_inputs: r.assignments("exec_inputs")?,
+            init_phy_mems_list: r.assignments("init_phy_mems_list")?,
+            init_vir_mems_list: r.assignments("init_vir_mems_list")?,
+            addr_phy_mems_list: r.assignments("addr_phy_mems_list")?,
+            addr_vir_mems_list: r.assignments("addr_vir_mems_list")?,
+            addr_ts_bits_list: r.assignments("addr_ts_bits_list")?,
+            input: r.bytes_list("input")?,
+            input_stack: r.bytes_list("input_stack")?,
+            input_mem: r.bytes_list("input_mem")?,
+            output: r.bytes("output")?,
+            output_exec_num: r.usize("output_exec_num")?,
+        })
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        check_len("RTK exec_inputs", self.exec_inputs.len(), self.consis_num_proofs)?;
+        ExecShape::of(self).validate()?;
+        check_len("RTK init_phy_mems_list", self.init_phy_mems_list.len(), self.total_num_init_phy_mem_accesses)?;
+        check_len("RTK init_vir_mems_list", self.init_vir_mems_list.len(), self.total_num_init_vir_mem_accesses)?;
+        check_len("RTK addr_phy_mems_list", self.addr_phy_mems_list.len(), self.total_num_phy_mem_accesses)?;
+        check_len("RTK addr_vir_mems_list", self.addr_vir_mems_list.len(), self.total_num_vir_mem_accesses)?;
+        check_len("RTK addr_ts_bits_list", self.addr_ts_bits_list.len(), self.total_num_vir_mem_accesses)?;
+        if self.output_exec_num >= self.consis_num_proofs {
+            return Err(format!("output_exec_num {} is out of the {} block executions", self.output_exec_num, self.consis_num_proofs));
+        }
+        let num_blocks_live = self.block_num_proofs.iter().filter(|n| **n > 0).count();
+        check_len("RTK block_vars_matrix", self.block_vars_matrix.len(), num_blocks_live)?;
+        for (slot, b) in live_blocks_by_slot(&self.block_num_proofs).into_iter().enumerate() {
+            check_len(&format!("RTK executions of block {}", b), self.block_vars_matrix[slot].len(), self.block_num_proofs[b])?;
+        }
+        Ok(())
+    }
+}
+
+fn knowledge_header(kind: &str, encoding: &KnowledgeEncoding) -> String {
+    format!("{} {} {}\n", kind, KNOWLEDGE_FORMAT_VERSION, encoding.name())
+}
+
+/// Write a versioned file
+pub fn save_knowledge<K: Knowledge>(k: &K, path: &PathBuf, encoding: &KnowledgeEncoding) -> std::io::Result<()> {
+    let mut f = BufWriter::new(File::create(path)?);
+    f.write_all(knowledge_header(K::KIND, encoding).as_bytes())?;
+    match encoding {
+        KnowledgeEncoding::Text => k.write_text(&mut f)?,
+        KnowledgeEncoding::Bin => bincode::serialize_into(&mut f, k).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?,
+    }
+    f.flush()
+}
+
+/// Prepend the header to a headerless file read by Spartan, without loading it
+pub fn copy_knowledge_as_bin(kind: &str, from: &PathBuf, to: &PathBuf) -> std::io::Result<()> {
+    let mut f = BufWriter::new(File::create(to)?);
+    f.write_all(knowledge_header(kind, &KnowledgeEncoding::Bin).as_bytes())?;
+    std::io::copy(&mut BufReader::new(File::open(from)?), &mut f)?;
+    f.flush()
+}
+
+// Deserialize the bincode encoding of a CTK or an RTK, which must take up the rest of r
+// Lengths are bounded by the size of the file, so that a corrupted length cannot exhaust the memory
+fn deserialize_knowledge<K: Knowledge>(mut r: impl BufRead) -> Result<K, String> {
+    let mut bytes = Vec::new();
+    r.read_to_end(&mut bytes).map_err(|e| e.to_string())?;
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .with_limit(bytes.len() as u64)
+        .reject_trailing_bytes()
+        .deserialize(&bytes)
+        .map_err(|e| e.to_string())
+}
+
+/// Load a versioned file of either encoding, or a headerless file read by Spartan, and validate it
+pub fn load_knowledge<K: Knowledge>(path: &PathBuf) -> Result<K, String> {
+    let err = |e: &dyn std::fmt::Display| format!("{}: {}", path.display(), e);
+    let mut r = BufReader::new(File::open(path).map_err(|e| err(&e))?);
+    let versioned = r.fill_buf().map_err(|e| err(&e))?.starts_with(b"CIRC_");
+    let k: K = if versioned {
+        load_versioned_knowledge(r).map_err(|e| err(&e))?
+    } else {
+        deserialize_knowledge(r).map_err(|e| err(&format!("not a {} file, nor a headerless file read by Spartan: {}", K::KIND, e)))?
+    };
+    k.validate().map_err(|e| err(&e))?;
+    Ok(k)
+}
+
+// Load a file that starts with a header
+fn load_versioned_knowledge<K: Knowledge>(mut r: BufReader<File>) -> Result<K, String> {
+    let mut header = String::new();
+    r.read_line(&mut header).map_err(|e| e.to_string())?;
+    let header: Vec<&str> = header.split_whitespace().collect();
+    if header.len() != 3 || header[0] != K::KIND {
+        return Err(format!("invalid header {:?}, expected {} <version> <encoding>", header.join(" "), K::KIND));
+    }
+    if header[1] != KNOWLEDGE_FORMAT_VERSION.to_string() {
+        return Err(format!("unsupported format version {}, expected {}", header[1], KNOWLEDGE_FORMAT_VERSION));
+    }
+    match header[2] {
+        "text" => K::read_text(&mut TextReader::new(r, 1)),
+        "bin" => deserialize_knowledge(r),
+        e => Err(format!("unknown encoding {}", e)),
+    }
+}
+
+/// Sort block_num_proofs and record where each entry is
+pub struct InstanceSortHelper {
+    /// Number of executions of the block
+    pub num_exec: usize,
+    /// The block
+    pub index: usize,
+}
+
+impl InstanceSortHelper {
+    /// An entry of block_num_proofs
+    pub fn new(num_exec: usize, index: usize) -> InstanceSortHelper {
+        InstanceSortHelper { num_exec, index }
+    }
+}
+
+// Ordering of InstanceSortHelper solely by num_exec
+impl Ord for InstanceSortHelper {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.num_exec.cmp(&other.num_exec)
+    }
+}
+
+impl PartialOrd for InstanceSortHelper {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for InstanceSortHelper {
+    fn eq(&self, other: &Self) -> bool {
+        self.num_exec == other.num_exec
+    }
+}
+
+impl Eq for InstanceSortHelper {}
+
+// --
+// Validate a CTK / RTK pair
+// --
+/// Checks that the sizes of the CTK and the RTK agree
+pub fn inspect_knowledge<A: RtkAssignment>(ctk: &CompileTimeKnowledge, rtk: &RunTimeKnowledge<A>) -> Result<(), String> {
+    ctk.validate()?;
+    rtk.validate()?;
+    check_len("RTK block_num_proofs", rtk.block_num_proofs.len(), ctk.block_num_instances)?;
+    for (slot, b) in live_blocks_by_slot(&rtk.block_num_proofs).into_iter().enumerate() {
+        for (e, exec) in rtk.block_vars_matrix[slot].iter().enumerate() {
+            check_len(&format!("execution {} of block {}", e, b), exec.to_bytes().len(), ctk.num_vars_per_block[b])?;
+        }
+    }
+    for (i, exec) in rtk.exec_inputs.iter().enumerate() {
+        check_len(&format!("exec_inputs {}", i), exec.to_bytes().len(), (2 * ctk.num_inputs_unpadded).next_power_of_two())?;
+    }
+    Ok(())
+}
+
+/// block_vars_matrix only contains live blocks, sorted by block_num_proofs from high to low, tie-breaked by block id
+///
+/// Returns the block id of every entry of block_vars_matrix
+pub fn live_blocks_by_slot(block_num_proofs: &Vec<usize>) -> Vec<usize> {
+    let mut inst_sorter: Vec<InstanceSortHelper> = (0..block_num_proofs.len()).map(|i| InstanceSortHelper::new(block_num_proofs[i], i)).collect();
+    inst_sorter.sort_by(|a, b| b.cmp(a));
+    inst_sorter.into_iter().filter(|i| i.num_exec > 0).map(|i| i.index).collect()
+}
+
+/// Field elements of an assignment
+pub fn integers_to_assignment<A: RtkAssignment>(vals: &Vec<Integer>) -> A {
+    A::from_bytes(&vals.iter().map(|i| integer_to_bytes(i.clone())).collect::<Vec<[u8; 32]>>()).unwrap()
+}
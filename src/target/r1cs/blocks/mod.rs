@@ -0,0 +1,18 @@
+//! Proof systems over the blocks of a Z# program, as compiled by examples/zxc.rs
+//!
+//! A program is described by its compile-time knowledge (CTK), the constraints of every block, and an execution
+//! by its run-time knowledge (RTK), the witnesses of every block execution and memory access.
+//! Every backend implements [proof::BlockProofSystem] over them.
+
+pub mod knowledge;
+pub mod proof;
+pub mod spartan;
+#[cfg(feature = "bellman")]
+pub mod groth16;
+#[cfg(feature = "bellman")]
+pub mod folding;
+
+/// Vars of a physical memory access in a block: addr, data
+pub const VARS_PER_ST_ACCESS: usize = 2;
+/// Vars of a virtual memory access in a block: addr, data, ls, ts
+pub const VARS_PER_VM_ACCESS: usize = 4;
@@ -0,0 +1,442 @@
+//! Setup, prove and verify block programs
+//!
+//! The block analogue of [crate::target::r1cs::proof], over CTK / RTK pairs instead of ProverData / VerifierData.
+
+use crate::cfg::{
+    cfg,
+    clap::{self, ValueEnum},
+};
+use crate::target::r1cs::LookupTable;
+use merlin::Transcript;
+use rug::Integer;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::time::Instant;
+
+use super::knowledge::*;
+
+// --
+// Block proof system
+// --
+/// The number of executions of every block and of memory accesses of an execution
+///
+/// The block, pairwise and permutation instances of Spartan depend on the number of executions of every block
+/// and on the number of memory accesses, so keys are generated for an execution shape: either the shape recorded
+/// in an RTK, or explicit bounds on the number of executions of every block, read from a CIRC_SHAPE file.
+/// An RTK within the bounds is padded to the shape of the key before it is proven, see [pad_run_time_knowledge].
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct ExecShape {
+    /// Maximum of block_num_proofs
+    pub block_max_num_proofs: usize,
+    /// Number of executions of every block
+    pub block_num_proofs: Vec<usize>,
+    /// Number of block executions
+    pub consis_num_proofs: usize,
+    /// Size of the input stack
+    pub total_num_init_phy_mem_accesses: usize,
+    /// Size of the input arrays
+    pub total_num_init_vir_mem_accesses: usize,
+    /// Number of physical memory accesses, including the input stack
+    pub total_num_phy_mem_accesses: usize,
+    /// Number of virtual memory accesses, including the input arrays
+    pub total_num_vir_mem_accesses: usize,
+}
+
+impl ExecShape {
+    /// The shape of an RTK
+    pub fn of<A: RtkAssignment>(rtk: &RunTimeKnowledge<A>) -> ExecShape {
+        ExecShape {
+            block_max_num_proofs: rtk.block_max_num_proofs,
+            block_num_proofs: rtk.block_num_proofs.clone(),
+            consis_num_proofs: rtk.consis_num_proofs,
+            total_num_init_phy_mem_accesses: rtk.total_num_init_phy_mem_accesses,
+            total_num_init_vir_mem_accesses: rtk.total_num_init_vir_mem_accesses,
+            total_num_phy_mem_accesses: rtk.total_num_phy_mem_accesses,
+            total_num_vir_mem_accesses: rtk.total_num_vir_mem_accesses,
+        }
+    }
+
+    /// Every block is executed block_num_proofs\[b\] times, and every memory access is either initial memory
+    /// or an operation of a block execution
+    pub fn bounded(ctk: &CompileTimeKnowledge, block_num_proofs: Vec<usize>, total_num_init_phy_mem_accesses: usize, total_num_init_vir_mem_accesses: usize) -> ExecShape {
+        let num_accesses = |ops: &Vec<usize>| -> usize { block_num_proofs.iter().zip(ops).map(|(e, o)| e * o).sum() };
+        ExecShape {
+            block_max_num_proofs: block_num_proofs.iter().max().cloned().unwrap_or(0),
+            consis_num_proofs: block_num_proofs.iter().sum(),
+            total_num_init_phy_mem_accesses,
+            total_num_init_vir_mem_accesses,
+            total_num_phy_mem_accesses: total_num_init_phy_mem_accesses + num_accesses(&ctk.block_num_phy_ops),
+            total_num_vir_mem_accesses: total_num_init_vir_mem_accesses + num_accesses(&ctk.block_num_vir_ops),
+            block_num_proofs,
+        }
+    }
+
+    /// Check the shape against the blocks of the CTK, every shape that keys are generated for must pass it
+    pub fn check(&self, ctk: &CompileTimeKnowledge) -> Result<(), String> {
+        check_len("block_num_proofs of the execution shape", self.block_num_proofs.len(), ctk.block_num_instances)?;
+        let expected = ExecShape::bounded(ctk, self.block_num_proofs.clone(), self.total_num_init_phy_mem_accesses, self.total_num_init_vir_mem_accesses);
+        if *self != expected {
+            return Err(format!("The memory accesses of the execution shape {:?} do not match the operations of the block executions, expected {:?}", self, expected));
+        }
+        Ok(())
+    }
+
+    /// Every block has the same number of executions, as after [pad_run_time_knowledge] in zk mode
+    pub fn is_padded(&self) -> bool {
+        self.block_num_proofs.iter().all(|n| *n == self.block_max_num_proofs)
+    }
+}
+
+// A CIRC_SHAPE file has the same fields as the start of a CIRC_RTK file
+impl Knowledge for ExecShape {
+    const KIND: &'static str = "CIRC_SHAPE";
+
+    fn write_text(&self, f: &mut impl Write) -> std::io::Result<()> {
+        write_field(f, "block_max_num_proofs", [self.block_max_num_proofs])?;
+        write_field(f, "block_num_proofs", &self.block_num_proofs)?;
+        write_field(f, "consis_num_proofs", [self.consis_num_proofs])?;
+        write_field(f, "total_num_init_phy_mem_accesses", [self.total_num_init_phy_mem_accesses])?;
+        write_field(f, "total_num_init_vir_mem_accesses", [self.total_num_init_vir_mem_accesses])?;
+        write_field(f, "total_num_phy_mem_accesses", [self.total_num_phy_mem_accesses])?;
+        write_field(f, "total_num_vir_mem_accesses", [self.total_num_vir_mem_accesses])
+    }
+
+    fn read_text<R: BufRead>(r: &mut TextReader<R>) -> Result<Self, String> {
+        Ok(ExecShape {
+            block_max_num_proofs: r.usize("block_max_num_proofs")?,
+            block_num_proofs: r.usizes("block_num_proofs")?,
+            consis_num_proofs: r.usize("consis_num_proofs")?,
+            total_num_init_phy_mem_accesses: r.usize("total_num_init_phy_mem_accesses")?,
+            total_num_init_vir_mem_accesses: r.usize("total_num_init_vir_mem_accesses")?,
+            total_num_phy_mem_accesses: r.usize("total_num_phy_mem_accesses")?,
+            total_num_vir_mem_accesses: r.usize("total_num_vir_mem_accesses")?,
+        })
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let max_num_proofs = self.block_num_proofs.iter().max().cloned().unwrap_or(0);
+        if max_num_proofs != self.block_max_num_proofs {
+            return Err(format!("block_max_num_proofs is {}, but the maximum of block_num_proofs is {}", self.block_max_num_proofs, max_num_proofs));
+        }
+        check_len("sum of block_num_proofs", self.block_num_proofs.iter().sum(), self.consis_num_proofs)
+    }
+}
+
+// --
+// Zero-knowledge mode
+// --
+/// Whether block proofs hide the witnesses and the control flow
+#[derive(PartialEq, Eq, Debug, Clone, ValueEnum)]
+pub enum ProofMode {
+    /// proofs may reveal the witnesses, and the number of executions of every block is part of the instance
+    NonZk,
+    /// proofs hide the witnesses, and every block is padded to --zk_exec_bound executions (requires the groth16 backend, the only zero-knowledge one)
+    Zk,
+}
+
+/// Pad every block of an RTK to the number of executions of a shape
+///
+/// The execution shape is part of the keys and the instance, and the number of executions of every block reveals
+/// which branches the private inputs took. In zk mode, every block is padded to the same public number of executions.
+/// A padded execution is invalid: all of its vars are zero, which satisfies the constraints of every block
+/// since constants are multiplied by the valid bit. Padded executions follow the valid ones in exec_inputs, and their
+/// memory accesses follow the valid ones in the sorted memory lists, as the padding of Spartan to powers of two.
+/// The output is still that of the last valid execution, so only backends that do not need output_exec_num in the
+/// instance, like Groth16, can hide the number of valid executions.
+/// The same padding fits an RTK to keys generated for explicit shape bounds, in which case blocks may have different bounds.
+pub fn pad_run_time_knowledge<A: RtkAssignment>(ctk: &CompileTimeKnowledge, mut rtk: RunTimeKnowledge<A>, shape: &ExecShape) -> Result<RunTimeKnowledge<A>, String> {
+    let num_blocks = ctk.block_num_instances;
+    shape.check(ctk)?;
+    check_len("RTK block_num_proofs", rtk.block_num_proofs.len(), num_blocks)?;
+    if let Some(b) = (0..num_blocks).find(|b| rtk.block_num_proofs[*b] > shape.block_num_proofs[*b]) {
+        return Err(format!("Block {} is executed {} times, more than its bound {}", b, rtk.block_num_proofs[b], shape.block_num_proofs[b]));
+    }
+    if (rtk.total_num_init_phy_mem_accesses, rtk.total_num_init_vir_mem_accesses) != (shape.total_num_init_phy_mem_accesses, shape.total_num_init_vir_mem_accesses) {
+        return Err(format!("The inputs have {} physical and {} virtual memory entries, but the execution shape expects {} and {}",
+            rtk.total_num_init_phy_mem_accesses, rtk.total_num_init_vir_mem_accesses, shape.total_num_init_phy_mem_accesses, shape.total_num_init_vir_mem_accesses));
+    }
+    let zeros = |width: usize| -> A { integers_to_assignment(&vec![Integer::from(0); width]) };
+
+    // block_vars_matrix is ordered by the number of executions, which changes
+    let mut block_vars: Vec<Vec<A>> = vec![Vec::new(); num_blocks];
+    for (slot, b) in live_blocks_by_slot(&rtk.block_num_proofs).into_iter().enumerate() {
+        block_vars[b] = std::mem::take(&mut rtk.block_vars_matrix[slot]);
+    }
+    let io_width = (2 * ctk.num_inputs_unpadded).next_power_of_two();
+    let mut num_padded_phy_accesses = 0;
+    let mut num_padded_vir_accesses = 0;
+    for b in 0..num_blocks {
+        let num_padded = shape.block_num_proofs[b] - rtk.block_num_proofs[b];
+        block_vars[b].extend((0..num_padded).map(|_| zeros(ctk.num_vars_per_block[b])));
+        rtk.exec_inputs.extend((0..num_padded).map(|_| zeros(io_width)));
+        num_padded_phy_accesses += num_padded * ctk.block_num_phy_ops[b];
+        num_padded_vir_accesses += num_padded * ctk.block_num_vir_ops[b];
+    }
+    rtk.block_num_proofs = shape.block_num_proofs.clone();
+    rtk.block_max_num_proofs = shape.block_max_num_proofs;
+    rtk.consis_num_proofs = shape.consis_num_proofs;
+    rtk.block_vars_matrix = live_blocks_by_slot(&rtk.block_num_proofs).into_iter().map(|b| std::mem::take(&mut block_vars[b])).collect();
+
+    // Physical Memory: valid, D, addr, data
+    rtk.addr_phy_mems_list.extend((0..num_padded_phy_accesses).map(|_| zeros(4)));
+    rtk.total_num_phy_mem_accesses += num_padded_phy_accesses;
+    // Virtual Memory: valid, D1, addr, data, ls, ts, _, _
+    rtk.addr_vir_mems_list.extend((0..num_padded_vir_accesses).map(|_| zeros(8)));
+    rtk.addr_ts_bits_list.extend((0..num_padded_vir_accesses).map(|_| zeros((ctk.max_ts_width + 2).next_power_of_two())));
+    rtk.total_num_vir_mem_accesses += num_padded_vir_accesses;
+    Ok(rtk)
+}
+
+/// Executions can only be proven in zk mode by a zero-knowledge backend, with keys for a padded shape
+pub fn check_proof_mode<P: BlockProofSystem>(mode: &ProofMode, shape: &ExecShape) -> Result<(), String> {
+    if *mode == ProofMode::NonZk {
+        return Ok(());
+    }
+    if !P::ZERO_KNOWLEDGE {
+        return Err("The block backend does not hide the witnesses, and cannot be used with --proof_mode zk".to_string());
+    }
+    if !shape.is_padded() {
+        return Err(format!("Blocks are executed {:?} times, which reveals the control flow, the RTK should be generated with --proof_mode zk", shape.block_num_proofs));
+    }
+    Ok(())
+}
+
+/// The instance provided by the verifier: the public inputs and the output of the program
+///
+/// Only the inputs of main that are live and public are checked, all other inputs are zero in the instance,
+/// so it reveals nothing about private inputs. The contents of input arrays are always public.
+/// Written as a versioned file with KIND CIRC_IO, so the verifier can write it by hand in the text encoding.
+#[derive(Serialize, Deserialize)]
+pub struct BlockPublicIo {
+    /// Inputs of main, see [public_input]
+    pub input: Vec<[u8; 32]>,
+    /// Initial physical memory
+    pub input_stack: Vec<[u8; 32]>,
+    /// Initial virtual memory
+    pub input_mem: Vec<[u8; 32]>,
+    /// Return value of main
+    pub output: [u8; 32],
+    /// The execution that holds the output
+    pub output_exec_num: usize,
+}
+
+/// Register of every live input of main, as (index in the input, register)
+///
+/// Input reg j of an execution is at z\[j + 1\], output reg j is at z\[n + j\]
+pub fn live_input_regs(input_liveness: &Vec<bool>, input_regs: &Vec<usize>) -> Vec<(usize, usize)> {
+    input_liveness.iter().zip(input_regs).enumerate().filter(|(_, (live, _))| **live).map(|(i, (_, reg))| (i, *reg)).collect()
+}
+
+/// Inputs of main that are not checked against the instance are zero
+pub fn public_input(input: &Vec<[u8; 32]>, input_liveness: &Vec<bool>) -> Vec<[u8; 32]> {
+    input.iter().enumerate().map(|(i, x)| if input_liveness.get(i) == Some(&true) { *x } else { [0; 32] }).collect()
+}
+
+impl BlockPublicIo {
+    /// The instance of an RTK
+    pub fn of<A: RtkAssignment>(rtk: &RunTimeKnowledge<A>, input_liveness: &Vec<bool>) -> BlockPublicIo {
+        BlockPublicIo {
+            input: public_input(&rtk.input, input_liveness),
+            input_stack: rtk.input_stack.clone(),
+            input_mem: rtk.input_mem.clone(),
+            output: rtk.output,
+            output_exec_num: rtk.output_exec_num,
+        }
+    }
+}
+
+impl Knowledge for BlockPublicIo {
+    const KIND: &'static str = "CIRC_IO";
+
+    fn write_text(&self, f: &mut impl Write) -> std::io::Result<()> {
+        write_field(f, "input", self.input.iter().map(bytes_to_integer))?;
+        write_field(f, "input_stack", self.input_stack.iter().map(bytes_to_integer))?;
+        write_field(f, "input_mem", self.input_mem.iter().map(bytes_to_integer))?;
+        write_field(f, "output", [bytes_to_integer(&self.output)])?;
+        write_field(f, "output_exec_num", [self.output_exec_num])
+    }
+
+    fn read_text<R: BufRead>(r: &mut TextReader<R>) -> Result<Self, String> {
+        Ok(BlockPublicIo {
+            input: r.bytes_list("input")?,
+            input_stack: r.bytes_list("input_stack")?,
+            input_mem: r.bytes_list("input_mem")?,
+            output: r.bytes("output")?,
+            output_exec_num: r.usize("output_exec_num")?,
+        })
+    }
+}
+
+// --
+// Reserved read-only memory
+// --
+/// The public entries at the start of the input stack
+///
+/// The input stack starts with the lookup tables, followed by the seed of every call to sample_challenge in the blocks.
+/// Seeds are drawn from a transcript of the public instance (except the output), in which the seeds are zero,
+/// and the proof continues the same transcript. A block hashes the argument of sample_challenge under its seed,
+/// so the challenge also binds the witnesses it is sampled for.
+/// The seeds are not drawn after the commitments to the witnesses and memory, which would need spartan_parallel
+/// to let the prover commit to its witnesses in several rounds.
+/// Both the tables and the seeds are public, so every backend checks them against the instance.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ReservedMemory {
+    lookup_width: usize,
+    num_challenges: usize,
+}
+
+impl ReservedMemory {
+    /// The reserved memory of a program
+    pub fn of(ctk: &CompileTimeKnowledge) -> ReservedMemory {
+        ReservedMemory {
+            lookup_width: ctk.lookup_width,
+            num_challenges: ctk.num_challenges,
+        }
+    }
+
+    /// Address of the first challenge
+    pub fn challenge_addr(&self) -> usize {
+        LookupTable::memory_size(self.lookup_width)
+    }
+
+    /// Number of reserved entries
+    pub fn size(&self) -> usize {
+        self.challenge_addr() + self.num_challenges
+    }
+
+    /// The transcript of the instance, and the challenges drawn from it
+    pub fn transcript(&self, input: &Vec<[u8; 32]>, input_stack: &Vec<[u8; 32]>, input_mem: &Vec<[u8; 32]>) -> (Transcript, Vec<Integer>) {
+        let mut transcript = Transcript::new(b"snark_example");
+        let chall_addrs = self.challenge_addr()..self.size();
+        let input_stack: Vec<[u8; 32]> = input_stack.iter().enumerate().map(|(i, x)| if chall_addrs.contains(&i) { [0; 32] } else { *x }).collect();
+        transcript.append_message(b"input", &bincode::serialize(input).unwrap());
+        transcript.append_message(b"input_stack", &bincode::serialize(&input_stack).unwrap());
+        transcript.append_message(b"input_mem", &bincode::serialize(input_mem).unwrap());
+        let field = cfg().field().modulus().clone();
+        let challenges = (0..self.num_challenges).map(|_| {
+            // Twice the size of the field, so the reduction is close to uniform
+            let mut bytes = [0; 64];
+            transcript.challenge_bytes(b"challenge", &mut bytes);
+            Integer::from_digits(&bytes, rug::integer::Order::Lsf) % &field
+        }).collect();
+        (transcript, challenges)
+    }
+
+    /// Check the tables and the challenges of the instance, and return its transcript
+    pub fn check(&self, io: &BlockPublicIo) -> Option<Transcript> {
+        if io.input_stack.len() < self.size() {
+            return None;
+        }
+        let tables = LookupTable::memory(self.lookup_width);
+        if io.input_stack.iter().zip(&tables).any(|(x, t)| bytes_to_integer(x) != *t) {
+            return None;
+        }
+        let (transcript, challenges) = self.transcript(&io.input, &io.input_stack, &io.input_mem);
+        if io.input_stack[self.challenge_addr()..].iter().zip(&challenges).any(|(x, c)| bytes_to_integer(x) != *c) {
+            return None;
+        }
+        Some(transcript)
+    }
+}
+
+/// Write data in bincode
+pub fn serialize_into_file<S: Serialize>(data: &S, path: &PathBuf) -> std::io::Result<()> {
+    let mut f = BufWriter::new(File::create(path)?);
+    bincode::serialize_into(&mut f, data).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+    f.flush()
+}
+
+fn deserialize_from_file<D: DeserializeOwned>(path: &PathBuf) -> std::io::Result<D> {
+    bincode::deserialize_from(BufReader::new(File::open(path)?)).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+}
+
+fn file_error(path: &PathBuf, e: std::io::Error) -> String {
+    format!("{}: {}", path.display(), e)
+}
+
+/// The block analogue of [crate::target::r1cs::proof::ProofSystem], over CTK / RTK pairs instead of ProverData / VerifierData
+pub trait BlockProofSystem {
+    /// Representation of the witnesses in the RTK, which depends on the field of the backend
+    type Assignment: RtkAssignment;
+    /// Proving key
+    type ProvingKey: Serialize + DeserializeOwned;
+    /// Verifying key
+    type VerifyingKey: Serialize + DeserializeOwned;
+    /// Proof
+    type Proof: Serialize + DeserializeOwned;
+    /// Whether proofs reveal nothing about the witnesses beyond the execution shape and the instance
+    const ZERO_KNOWLEDGE: bool;
+
+    /// Generate keys for a shape, which has passed [ExecShape::check] against the CTK
+    fn setup(ctk: &CompileTimeKnowledge, shape: &ExecShape) -> Result<(Self::ProvingKey, Self::VerifyingKey), String>;
+    /// Prove an RTK, consuming the key, since the backend might pad its instances in place
+    fn prove(pk: Self::ProvingKey, rtk: RunTimeKnowledge<Self::Assignment>) -> Result<Self::Proof, String>;
+    /// Verify a proof of an instance
+    fn verify(vk: &Self::VerifyingKey, io: &BlockPublicIo, pf: &Self::Proof) -> bool;
+    /// The execution shape pk proves, None if it proves executions of any shape
+    fn shape(pk: &Self::ProvingKey) -> Option<&ExecShape>;
+    /// The instance of an execution proven with pk
+    fn public_io(pk: &Self::ProvingKey, rtk: &RunTimeKnowledge<Self::Assignment>) -> BlockPublicIo;
+
+    /// Setup from a CTK and explicit shape bounds, or the shape of an RTK
+    fn setup_fs(mode: &ProofMode, ctk_path: &PathBuf, rtk_path: &PathBuf, shape_path: Option<&PathBuf>, pk_path: &PathBuf, vk_path: &PathBuf) -> Result<(), String> {
+        let ctk: CompileTimeKnowledge = load_knowledge(ctk_path)?;
+        let shape = match shape_path {
+            Some(shape_path) => load_knowledge(shape_path)?,
+            None => ExecShape::of(&load_knowledge::<RunTimeKnowledge<Self::Assignment>>(rtk_path)?),
+        };
+        shape.check(&ctk)?;
+        check_proof_mode::<Self>(mode, &shape)?;
+        let (pk, vk) = Self::setup(&ctk, &shape)?;
+        serialize_into_file(&pk, pk_path).map_err(|e| file_error(pk_path, e))?;
+        serialize_into_file(&vk, vk_path).map_err(|e| file_error(vk_path, e))
+    }
+    /// Prove from an RTK, writing the proof and the instance
+    fn prove_fs(mode: &ProofMode, ctk_path: &PathBuf, pk_path: &PathBuf, rtk_path: &PathBuf, pf_path: &PathBuf, io_path: &PathBuf) -> Result<(), String> {
+        let ctk: CompileTimeKnowledge = load_knowledge(ctk_path)?;
+        let pk: Self::ProvingKey = deserialize_from_file(pk_path).map_err(|e| file_error(pk_path, e))?;
+        let rtk: RunTimeKnowledge<Self::Assignment> = load_knowledge(rtk_path)?;
+        inspect_knowledge(&ctk, &rtk)?;
+        let rtk = fit_run_time_knowledge::<Self>(&ctk, &pk, rtk)?;
+        check_proof_mode::<Self>(mode, &ExecShape::of(&rtk))?;
+        save_knowledge(&Self::public_io(&pk, &rtk), io_path, &KnowledgeEncoding::Text).map_err(|e| file_error(io_path, e))?;
+        let pf = Self::prove(pk, rtk)?;
+        serialize_into_file(&pf, pf_path).map_err(|e| file_error(pf_path, e))
+    }
+    /// Verify a proof with the instance written by [BlockProofSystem::prove_fs], or by the verifier
+    fn verify_fs(vk_path: &PathBuf, io_path: &PathBuf, pf_path: &PathBuf) -> Result<bool, String> {
+        let vk: Self::VerifyingKey = deserialize_from_file(vk_path).map_err(|e| file_error(vk_path, e))?;
+        let io: BlockPublicIo = load_knowledge(io_path)?;
+        let pf: Self::Proof = deserialize_from_file(pf_path).map_err(|e| file_error(pf_path, e))?;
+        Ok(Self::verify(&vk, &io, &pf))
+    }
+}
+
+// Pad an RTK to the shape of the proving key, if the key is bound to a shape
+fn fit_run_time_knowledge<P: BlockProofSystem>(ctk: &CompileTimeKnowledge, pk: &P::ProvingKey, rtk: RunTimeKnowledge<P::Assignment>) -> Result<RunTimeKnowledge<P::Assignment>, String> {
+    match P::shape(pk) {
+        Some(shape) if *shape != ExecShape::of(&rtk) => pad_run_time_knowledge(ctk, rtk, shape),
+        _ => Ok(rtk),
+    }
+}
+
+/// Setup, prove and verify in memory
+pub fn run_block_proof<P: BlockProofSystem>(mode: &ProofMode, ctk: CompileTimeKnowledge, rtk: RunTimeKnowledge<P::Assignment>) -> Result<(), String> {
+    let shape = ExecShape::of(&rtk);
+    shape.check(&ctk)?;
+    check_proof_mode::<P>(mode, &shape)?;
+    let preprocess_start = Instant::now();
+    let (pk, vk) = P::setup(&ctk, &shape)?;
+    let preprocess_time = preprocess_start.elapsed();
+    println!("Preprocess time: {}ms", preprocess_time.as_millis());
+
+    let io = P::public_io(&pk, &rtk);
+    let proof = P::prove(pk, rtk)?;
+    if !P::verify(&vk, &io, &proof) {
+        return Err("proof verification failed!".to_string());
+    }
+    println!("proof verification successful!");
+    Ok(())
+}
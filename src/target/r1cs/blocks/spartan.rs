@@ -0,0 +1,401 @@
+//! Spartan over blocks, with one instance per block
+
+use crate::cfg::cfg;
+use libspartan::{instance::Instance, Assignment, ComputationCommitment, ComputationDecommitment, SNARKGens, SNARK};
+use rug::Integer;
+use serde::{Deserialize, Serialize};
+
+use super::knowledge::*;
+use super::proof::*;
+
+// Bound on the number of vars of all executions, which sizes the public parameters of the witnesses
+const TOTAL_NUM_VARS_BOUND: usize = 10000000000;
+// Order of the scalar field of Spartan
+const SPARTAN_MODULUS: &str = "7237005577332262213973186563042994240857116359379907606001950938285454250989";
+
+/// The backend of spartan_parallel
+pub struct BlockSpartan;
+
+fn check_spartan_field() -> Result<(), String> {
+    if cfg().field().modulus() != &Integer::from_str_radix(SPARTAN_MODULUS, 10).unwrap() {
+        return Err("Spartan requires the program to be compiled with --field-builtin curve25519".to_string());
+    }
+    Ok(())
+}
+
+/// The verifying key of [BlockSpartan]
+#[derive(Serialize, Deserialize)]
+pub struct BlockSpartanVerifyingKey {
+    // Program parameters, from the CTK
+    input_block_num: usize,
+    output_block_num: usize,
+    input_liveness: Vec<bool>,
+    func_input_width: usize,
+    input_offset: usize,
+    output_offset: usize,
+    num_vars: usize,
+    num_ios: usize,
+    num_inputs_unpadded: usize,
+    num_vars_per_block: Vec<usize>,
+    block_num_phy_ops: Vec<usize>,
+    block_num_vir_ops: Vec<usize>,
+    mem_addr_ts_bits_size: usize,
+    block_num_instances_bound: usize,
+    reserved: ReservedMemory,
+    shape: ExecShape,
+
+    // Sizes of the instances, the public parameters are regenerated from them
+    block_num_vars: usize,
+    block_num_cons: usize,
+    block_num_non_zero_entries: usize,
+    pairwise_check_num_vars: usize,
+    pairwise_check_num_cons: usize,
+    pairwise_check_num_non_zero_entries: usize,
+    perm_root_num_cons: usize,
+    perm_root_num_non_zero_entries: usize,
+
+    // Commitments to the instances
+    // Note that A, B, C are committed separately, so sparse_poly[3*i+2] corresponds to poly C of instance i
+    block_comm_map: Vec<Vec<usize>>,
+    block_comm_list: Vec<ComputationCommitment>,
+    pairwise_check_comm: ComputationCommitment,
+    perm_root_comm: ComputationCommitment,
+}
+
+/// The proving key of [BlockSpartan], with a copy of the verifying key
+#[derive(Serialize, Deserialize)]
+pub struct BlockSpartanProvingKey {
+    vk: BlockSpartanVerifyingKey,
+    block_inst: Instance,
+    block_decomm_list: Vec<ComputationDecommitment>,
+    pairwise_check_inst: Instance,
+    pairwise_check_decomm: ComputationDecommitment,
+    perm_root_inst: Instance,
+    perm_root_decomm: ComputationDecommitment,
+}
+
+struct BlockSpartanGens {
+    block_gens: SNARKGens,
+    pairwise_check_gens: SNARKGens,
+    perm_root_gens: SNARKGens,
+    // Only use one version of gens_r1cs_sat
+    vars_gens: SNARKGens,
+}
+
+impl BlockSpartanGens {
+    // Public parameters are deterministic in the sizes of the instances
+    fn new(
+        num_ios: usize,
+        block_num_instances_bound: usize,
+        (block_num_vars, block_num_cons, block_num_non_zero_entries): (usize, usize, usize),
+        (pairwise_check_num_vars, pairwise_check_num_cons, pairwise_check_num_non_zero_entries): (usize, usize, usize),
+        (perm_root_num_cons, perm_root_num_non_zero_entries): (usize, usize),
+    ) -> BlockSpartanGens {
+        BlockSpartanGens {
+            block_gens: SNARKGens::new(block_num_cons, block_num_vars, block_num_instances_bound, block_num_non_zero_entries),
+            pairwise_check_gens: SNARKGens::new(pairwise_check_num_cons, 4 * pairwise_check_num_vars, 3, pairwise_check_num_non_zero_entries),
+            perm_root_gens: SNARKGens::new(perm_root_num_cons, 8 * num_ios, 1, perm_root_num_non_zero_entries),
+            vars_gens: SNARKGens::new(block_num_cons, TOTAL_NUM_VARS_BOUND, block_num_instances_bound.next_power_of_two(), block_num_non_zero_entries),
+        }
+    }
+}
+
+impl BlockSpartanVerifyingKey {
+    fn gens(&self) -> BlockSpartanGens {
+        BlockSpartanGens::new(
+            self.num_ios,
+            self.block_num_instances_bound,
+            (self.block_num_vars, self.block_num_cons, self.block_num_non_zero_entries),
+            (self.pairwise_check_num_vars, self.pairwise_check_num_cons, self.pairwise_check_num_non_zero_entries),
+            (self.perm_root_num_cons, self.perm_root_num_non_zero_entries),
+        )
+    }
+}
+
+impl BlockProofSystem for BlockSpartan {
+    type Assignment = Assignment;
+    type ProvingKey = BlockSpartanProvingKey;
+    type VerifyingKey = BlockSpartanVerifyingKey;
+    type Proof = SNARK;
+    // The commitments of spartan_parallel to the witnesses and memory have no blinding factors, so they are not hiding
+    // Blinding them requires changes to spartan_parallel itself, so zk mode is limited to Groth16
+    const ZERO_KNOWLEDGE: bool = false;
+
+    fn setup(ctk: &CompileTimeKnowledge, shape: &ExecShape) -> Result<(BlockSpartanProvingKey, BlockSpartanVerifyingKey), String> {
+        // --
+        // INSTANCE PREPROCESSING
+        // --
+        println!("Preprocessing instances...");
+        let block_num_instances_bound = ctk.block_num_instances;
+        let num_vars = ctk.num_vars;
+        // num_inputs_unpadded is the actual size of the input
+        let num_inputs_unpadded = ctk.num_inputs_unpadded;
+        // num_ios is the width used by all input related computations
+        let num_ios = (num_inputs_unpadded * 2).next_power_of_two();
+        let mem_addr_ts_bits_size = (2 + ctk.max_ts_width).next_power_of_two();
+
+        if num_vars != num_vars.next_power_of_two() {
+            return Err(format!("Spartan requires num_vars to be a power of two, found {}", num_vars));
+        }
+        // If output_block_num < block_num_instances, the prover can cheat by executing the program multiple times
+        if ctk.output_block_num < block_num_instances_bound {
+            return Err(format!("output_block_num {} must be at least the number of blocks {}", ctk.output_block_num, block_num_instances_bound));
+        }
+        // Coefficients and witnesses are elements of the field of the compilation
+        check_spartan_field()?;
+        // Spartan reads the live inputs of main from consecutive registers after the reserved ones,
+        // which fails if a private or dead parameter precedes a live one
+        let live_regs = live_input_regs(&ctk.input_liveness, &ctk.input_regs);
+        if let Some((k, (i, reg))) = live_regs.iter().filter(|(i, _)| *i >= 2).enumerate()
+            .find(|(k, (_, reg))| *reg != ctk.input_offset + 1 + k)
+        {
+            return Err(format!(
+                "Spartan requires the live inputs of main in consecutive registers, found input {} in register {}; declare private parameters last",
+                i, reg
+            ));
+        }
+
+        println!("Generating Circuits...");
+        // --
+        // BLOCK INSTANCES
+        let (block_num_vars, block_num_cons, block_num_non_zero_entries, block_inst) = Instance::gen_block_inst::<true>(
+            block_num_instances_bound,
+            num_vars,
+            &ctk.args,
+            num_inputs_unpadded,
+            &ctk.block_num_phy_ops,
+            &ctk.block_num_vir_ops,
+            &ctk.num_vars_per_block,
+            &shape.block_num_proofs,
+        );
+        println!("Finished Block");
+
+        // Pairwise INSTANCES
+        // CONSIS_CHECK & PHY_MEM_COHERE
+        let (pairwise_check_num_vars, pairwise_check_num_cons, pairwise_check_num_non_zero_entries, pairwise_check_inst) = Instance::gen_pairwise_check_inst::<true>(
+            ctk.max_ts_width,
+            mem_addr_ts_bits_size,
+            shape.consis_num_proofs,
+            shape.total_num_phy_mem_accesses,
+            shape.total_num_vir_mem_accesses,
+        );
+        println!("Finished Pairwise");
+
+        // PERM INSTANCES
+        // PERM_ROOT
+        let (perm_root_num_cons, perm_root_num_non_zero_entries, perm_root_inst) = Instance::gen_perm_root_inst::<true>(
+            num_inputs_unpadded,
+            num_ios,
+            shape.consis_num_proofs,
+            shape.total_num_phy_mem_accesses,
+            shape.total_num_vir_mem_accesses,
+        );
+        println!("Finished Perm");
+
+        // --
+        // COMMITMENT PREPROCESSING
+        // --
+        println!("Producing Public Parameters...");
+        let gens = BlockSpartanGens::new(
+            num_ios,
+            block_num_instances_bound,
+            (block_num_vars, block_num_cons, block_num_non_zero_entries),
+            (pairwise_check_num_vars, pairwise_check_num_cons, pairwise_check_num_non_zero_entries),
+            (perm_root_num_cons, perm_root_num_non_zero_entries),
+        );
+
+        // create a commitment to the R1CS instance
+        println!("Comitting Circuits...");
+        let (block_comm_map, block_comm_list, block_decomm_list) = SNARK::multi_encode(&block_inst, &gens.block_gens);
+        println!("Finished Block");
+        let (pairwise_check_comm, pairwise_check_decomm) = SNARK::encode(&pairwise_check_inst, &gens.pairwise_check_gens);
+        println!("Finished Pairwise");
+        let (perm_root_comm, perm_root_decomm) = SNARK::encode(&perm_root_inst, &gens.perm_root_gens);
+        println!("Finished Perm");
+
+        let vk = BlockSpartanVerifyingKey {
+            input_block_num: ctk.input_block_num,
+            output_block_num: ctk.output_block_num,
+            input_liveness: ctk.input_liveness.clone(),
+            func_input_width: ctk.func_input_width,
+            input_offset: ctk.input_offset,
+            output_offset: ctk.output_offset,
+            num_vars,
+            num_ios,
+            num_inputs_unpadded,
+            num_vars_per_block: ctk.num_vars_per_block.clone(),
+            block_num_phy_ops: ctk.block_num_phy_ops.clone(),
+            block_num_vir_ops: ctk.block_num_vir_ops.clone(),
+            mem_addr_ts_bits_size,
+            block_num_instances_bound,
+            reserved: ReservedMemory::of(ctk),
+            shape: shape.clone(),
+            block_num_vars,
+            block_num_cons,
+            block_num_non_zero_entries,
+            pairwise_check_num_vars,
+            pairwise_check_num_cons,
+            pairwise_check_num_non_zero_entries,
+            perm_root_num_cons,
+            perm_root_num_non_zero_entries,
+            block_comm_map,
+            block_comm_list,
+            pairwise_check_comm,
+            perm_root_comm,
+        };
+        // The proving key keeps a copy of the verifying key
+        // Commitments are not Clone, so the copy goes through bincode
+        let pk = BlockSpartanProvingKey {
+            vk: bincode::deserialize(&bincode::serialize(&vk).unwrap()).unwrap(),
+            block_inst,
+            block_decomm_list,
+            pairwise_check_inst,
+            pairwise_check_decomm,
+            perm_root_inst,
+            perm_root_decomm,
+        };
+        Ok((pk, vk))
+    }
+
+    fn prove(mut pk: BlockSpartanProvingKey, rtk: RunTimeKnowledge) -> Result<SNARK, String> {
+        check_spartan_field()?;
+        let vk = &pk.vk;
+        if ExecShape::of(&rtk) != vk.shape {
+            return Err(format!("The proving key was generated for executions of shape {:?}, but the RTK has shape {:?}", vk.shape, ExecShape::of(&rtk)));
+        }
+        let gens = vk.gens();
+        let max_block_num_phy_ops = *vk.block_num_phy_ops.iter().max().unwrap();
+        let max_block_num_vir_ops = *vk.block_num_vir_ops.iter().max().unwrap();
+
+        println!("Running the proof...");
+        // produce a proof of satisfiability
+        // The prover is given the same instance as the verifier, in which private inputs are zero
+        let io = BlockPublicIo::of(&rtk, &vk.input_liveness);
+        let (mut prover_transcript, _) = vk.reserved.transcript(&io.input, &io.input_stack, &io.input_mem);
+        Ok(SNARK::prove(
+            vk.input_block_num,
+            vk.output_block_num,
+            &vk.input_liveness,
+            vk.func_input_width,
+            vk.input_offset,
+            vk.output_offset,
+            &io.input,
+            &io.output,
+            io.output_exec_num,
+
+            vk.num_vars,
+            vk.num_ios,
+            max_block_num_phy_ops,
+            &vk.block_num_phy_ops,
+            max_block_num_vir_ops,
+            &vk.block_num_vir_ops,
+            vk.mem_addr_ts_bits_size,
+            vk.num_inputs_unpadded,
+            &vk.num_vars_per_block,
+
+            vk.block_num_instances_bound,
+            rtk.block_max_num_proofs,
+            &rtk.block_num_proofs,
+            &mut pk.block_inst,
+            &vk.block_comm_map,
+            &vk.block_comm_list,
+            &pk.block_decomm_list,
+            &gens.block_gens,
+
+            rtk.consis_num_proofs,
+            rtk.total_num_init_phy_mem_accesses,
+            rtk.total_num_init_vir_mem_accesses,
+            rtk.total_num_phy_mem_accesses,
+            rtk.total_num_vir_mem_accesses,
+            &mut pk.pairwise_check_inst,
+            &vk.pairwise_check_comm,
+            &pk.pairwise_check_decomm,
+            &gens.pairwise_check_gens,
+
+            rtk.block_vars_matrix,
+            rtk.exec_inputs,
+            rtk.init_phy_mems_list,
+            rtk.init_vir_mems_list,
+            rtk.addr_phy_mems_list,
+            rtk.addr_vir_mems_list,
+            rtk.addr_ts_bits_list,
+
+            &pk.perm_root_inst,
+            &vk.perm_root_comm,
+            &pk.perm_root_decomm,
+            &gens.perm_root_gens,
+
+            &gens.vars_gens.gens_r1cs_sat,
+            &mut prover_transcript,
+        ))
+    }
+
+    fn shape(pk: &BlockSpartanProvingKey) -> Option<&ExecShape> {
+        Some(&pk.vk.shape)
+    }
+
+    fn public_io(pk: &BlockSpartanProvingKey, rtk: &RunTimeKnowledge) -> BlockPublicIo {
+        BlockPublicIo::of(rtk, &pk.vk.input_liveness)
+    }
+
+    fn verify(vk: &BlockSpartanVerifyingKey, io: &BlockPublicIo, pf: &SNARK) -> bool {
+        let gens = vk.gens();
+        let max_block_num_phy_ops = *vk.block_num_phy_ops.iter().max().unwrap();
+        let max_block_num_vir_ops = *vk.block_num_vir_ops.iter().max().unwrap();
+
+        let mut verifier_transcript = match vk.reserved.check(io) {
+            Some(transcript) => transcript,
+            None => return false,
+        };
+
+        println!("Verifying the proof...");
+        // verify the proof of satisfiability
+        pf.verify(
+            vk.input_block_num,
+            vk.output_block_num,
+            &vk.input_liveness,
+            vk.func_input_width,
+            vk.input_offset,
+            vk.output_offset,
+            &io.input,
+            &io.input_stack,
+            &io.input_mem,
+            &io.output,
+            io.output_exec_num,
+
+            vk.num_vars,
+            vk.num_ios,
+            max_block_num_phy_ops,
+            &vk.block_num_phy_ops,
+            max_block_num_vir_ops,
+            &vk.block_num_vir_ops,
+            vk.mem_addr_ts_bits_size,
+            vk.num_inputs_unpadded,
+            &vk.num_vars_per_block,
+
+            vk.block_num_instances_bound,
+            vk.shape.block_max_num_proofs,
+            &vk.shape.block_num_proofs,
+            vk.block_num_cons,
+            &vk.block_comm_map,
+            &vk.block_comm_list,
+            &gens.block_gens,
+
+            vk.shape.consis_num_proofs,
+            vk.shape.total_num_init_phy_mem_accesses,
+            vk.shape.total_num_init_vir_mem_accesses,
+            vk.shape.total_num_phy_mem_accesses,
+            vk.shape.total_num_vir_mem_accesses,
+            vk.pairwise_check_num_cons,
+            &vk.pairwise_check_comm,
+            &gens.pairwise_check_gens,
+
+            vk.perm_root_num_cons,
+            &vk.perm_root_comm,
+            &gens.perm_root_gens,
+
+            &gens.vars_gens.gens_r1cs_sat,
+            &mut verifier_transcript
+        ).is_ok()
+    }
+}
@@ -14,7 +14,7 @@ pub use ast::{
     BinaryOperator, BooleanLiteralExpression, BooleanType, CallAccess, ConditionalStatement, 
     CondStoreStatement, ConstantDefinition, ConstantGenericValue, Curve, DecimalLiteralExpression,
     DecimalNumber, DecimalSuffix, DefinitionStatement, DimRO, ExplicitGenerics, Expression, FieldSuffix,
-    FieldType, File, FromExpression, FromImportDirective, FuncInline, FunctionDefinition, HexLiteralExpression,
+    FieldType, File, FlatArray, FromExpression, FromImportDirective, FuncInline, FunctionDefinition, HexLiteralExpression,
     HexNumberExpression, IdentifierExpression, ImportDirective, ImportSymbol,
     InlineArrayExpression, InlineStructExpression, InlineStructMember, IterationStatement,
     LiteralExpression, MainImportDirective, MemberAccess, NegOperator, NotOperator, Parameter,
@@ -193,6 +193,7 @@ mod ast {
         pub id: IdentifierExpression<'ast>,
         pub generics: Vec<IdentifierExpression<'ast>>,
         pub parameters: Vec<Parameter<'ast>>,
+        pub returns_flat: Option<FlatArray<'ast>>,
         pub returns: Vec<Type<'ast>>,
         pub statements: Vec<Statement<'ast>>,
         #[pest_ast(outer())]
@@ -361,6 +362,7 @@ mod ast {
     pub struct Parameter<'ast> {
         pub array_metadata: Option<ArrayParamMetadata<'ast>>,
         pub visibility: Option<Visibility<'ast>>,
+        pub flat: Option<FlatArray<'ast>>,
         pub ty: Type<'ast>,
         pub id: IdentifierExpression<'ast>,
         #[pest_ast(outer())]
@@ -388,6 +390,13 @@ mod ast {
         pub span: Span<'ast>,
     }
 
+    #[derive(Debug, FromPest, PartialEq, Clone)]
+    #[pest_ast(rule(Rule::flat_array))]
+    pub struct FlatArray<'ast> {
+        #[pest_ast(outer())]
+        pub span: Span<'ast>,
+    }
+
     #[derive(Debug, FromPest, PartialEq, Clone)]
     #[pest_ast(rule(Rule::vis))]
     pub enum Visibility<'ast> {
@@ -1261,6 +1270,7 @@ mod tests {
                             span: Span::new(&source, 33, 37).unwrap()
                         },
                         parameters: vec![],
+                        returns_flat: None,
                         returns: vec![Type::Basic(BasicType::Field(FieldType {
                             span: Span::new(&source, 44, 49).unwrap()
                         }))],
@@ -1322,6 +1332,7 @@ mod tests {
                             span: Span::new(&source, 33, 37).unwrap()
                         },
                         parameters: vec![],
+                        returns_flat: None,
                         returns: vec![Type::Basic(BasicType::Field(FieldType {
                             span: Span::new(&source, 44, 49).unwrap()
                         }))],
@@ -1407,6 +1418,7 @@ mod tests {
                             span: Span::new(&source, 33, 37).unwrap()
                         },
                         parameters: vec![],
+                        returns_flat: None,
                         returns: vec![Type::Basic(BasicType::Field(FieldType {
                             span: Span::new(&source, 44, 49).unwrap()
                         }))],
@@ -1467,6 +1479,7 @@ mod tests {
                         span: Span::new(&source, 4, 8).unwrap()
                     },
                     parameters: vec![],
+                    returns_flat: None,
                     returns: vec![Type::Basic(BasicType::Field(FieldType {
                         span: Span::new(&source, 15, 20).unwrap()
                     }))],
@@ -1505,6 +1518,7 @@ mod tests {
                         span: Span::new(&source, 4, 8).unwrap()
                     },
                     parameters: vec![],
+                    returns_flat: None,
                     returns: vec![Type::Basic(BasicType::Field(FieldType {
                         span: Span::new(&source, 15, 20).unwrap()
                     }))],